@@ -13,7 +13,6 @@
 
 #define MAX_PILE_SIZE 52
 #define HAND_SIZE 8
-#define FLOOR_SIZE 13
 #define CLUBS "♣"
 #define DIAMONDS "♦"
 #define HEARTS "♥"
@@ -111,15 +110,38 @@ void print_card(uint8_t c) {
     }
 }
 
+void on_score(ScoreEvent e) {
+    char *who = e.player ? "Dealer" : "Opponent";
+    switch (e.kind) {
+    case Point:
+        printf("[+] %s takes ", who);
+        print_card(e.card);
+        printf(" (+%u)\n", e.points);
+        break;
+    case Suipi:
+        printf("[+] %s scores a suipi\n", who);
+        break;
+    default:
+        if (e.tied) {
+            printf("[+] %s is tied\n", e.kind == MostCards ? "Most Cards" : "Most Spades");
+        } else {
+            printf("[+] %s leads %s (+%u)\n", who,
+                   e.kind == MostCards ? "Most Cards" : "Most Spades", e.points);
+        }
+        break;
+    }
+}
+
 void print_floor(Game *g, Status *s) {
-    Pile piles[FLOOR_SIZE];
-    memcpy(&piles, read_floor(&g), FLOOR_SIZE * sizeof(Pile));
+    uintptr_t size = floor_size(&g);
+    Pile piles[size];
+    size = read_floor(&g, piles, size);
     printf("Floor: ");
-    for (int i = 0; i < FLOOR_SIZE; i++) {
+    for (uintptr_t i = 0; i < size; i++) {
         if (i != 0) {
             printf(", ");
         }
-        printf("%c=", 'A' + i);
+        printf("%c=", (char)('A' + i));
         Pile p = piles[i];
         bool single = p.cards[1] >= 52;
         char *wrap = "[]";
@@ -218,11 +240,16 @@ int main(int argc, char *argv[]) {
         seed = load_seed(argv[1]);
     }
     Game *g = new_game(seed);
+    if (g == NULL) {
+        printf("[!] Failed to set up the game\n");
+        return 1;
+    }
+    set_score_callback(&g, on_score);
     Status *s = status(&g);
     uint8_t gameIndex = s->game;
     uint8_t roundIndex = s->round;
     print_seed(s->seed);
-    while (s->game < 2) {
+    while (!s->over) {
         if (s->turn) {
             printf("\n[*] Dealer's turn:\n");
         } else {
@@ -234,13 +261,18 @@ int main(int argc, char *argv[]) {
         do {
             if (error != NULL) {
                 printf("[!] %s\n", error);
+                free_string(error);
             }
             char *m = get_move();
             error = (char *)apply_move(&g, m);
             free(m);
         } while (strcmp(error, "") != 0);
-        free(error);
-        next_turn(&g);
+        free_string(error);
+        error = (char *)next_turn(&g);
+        if (strcmp(error, "") != 0) {
+            printf("[!] %s\n", error);
+        }
+        free_string(error);
         s = status(&g);
         if (s->floor == 0) {
             printf("\n\n ===== SUIPI! =====\n\n");
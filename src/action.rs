@@ -1,3 +1,10 @@
+use crate::consts::{
+    ACTIONS_PER_CARD, ACTION_SPACE_SIZE, ACTIVE_FLAG, BUILD_SEPARATOR, FLOOR_ADDRESS_MAX,
+    FLOOR_ADDRESS_MIN, HAND_ADDRESS_MAX, HAND_ADDRESS_MIN, HAND_SIZE, MAX_ANNOTATION_LENGTH,
+    MAX_FLOOR_SIZE, MAX_MOVE_ACTIONS,
+};
+use crate::locale::Locale;
+use crate::state::{State, StateError};
 use std::collections::HashSet;
 use std::fmt;
 
@@ -9,58 +16,85 @@ pub enum ParsingError {
     InvalidOperationCharacter,
     InvalidAddressCharacter,
     BlankAnnotation,
+    NoShortcutMatch,
+    AmbiguousShortcut,
+    AnnotationTooLong,
+    TooManyActions,
+    NonCanonicalAnnotation,
 }
 
 impl fmt::Display for ParsingError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Parsing Error: {}",
-            match self {
-                ParsingError::InvalidByte => "Invalid byte",
-                ParsingError::InvalidAddress => "Invalid pile address",
-                ParsingError::InvalidOperationCharacter => "Invalid operation char",
-                ParsingError::InvalidAddressCharacter => "Invalid address char",
-                ParsingError::BlankAnnotation => "Annotation is blank",
-            }
-        )
+        f.write_str(&Locale::English.parsing_message(self))
     }
 }
 
+impl std::error::Error for ParsingError {}
+
 /// Move validation errors
 #[derive(Debug, Eq, PartialEq)]
 pub enum MoveError {
     DuplicateAddress,
     InvalidHandAddressCount,
     InvalidHandAddressPosition,
+    InvalidBuildSegment,
+    CaptureWithoutPair,
 }
 
 impl fmt::Display for MoveError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                MoveError::DuplicateAddress =>
-                    "You cannot use the same address multiple times in a single move",
-                MoveError::InvalidHandAddressCount =>
-                    "You must use exactly one hand address in your move",
-                MoveError::InvalidHandAddressPosition =>
-                    "The hand address must be the last address in your move",
-            }
-        )
+        f.write_str(Locale::English.code_message(self.into()))
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Reasons a move has no action id
+#[derive(Debug, Eq, PartialEq)]
+pub enum ActionIdError {
+    /// The move is not legal in the state
+    Illegal(StateError),
+    /// The move is legal, but the action space has no template for it
+    NoTemplate,
+}
+
+impl From<StateError> for ActionIdError {
+    fn from(value: StateError) -> ActionIdError {
+        ActionIdError::Illegal(value)
+    }
+}
+
+impl fmt::Display for ActionIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionIdError::Illegal(e) => e.fmt(f),
+            ActionIdError::NoTemplate => write!(f, "The move has no action id"),
+        }
     }
 }
 
+impl std::error::Error for ActionIdError {}
+
 /// A pile address
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Address {
     Hand(u8),  // Address of a pile in your hand
     Floor(u8), // Address of a pile on the floor
 }
 
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Hand(i) => write!(f, "{}", i + 1),
+            Address::Floor(j) => write!(f, "{}", (b'A' + j) as char),
+        }
+    }
+}
+
 /// The type of action
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operation {
     Passive, // Simple card movement between piles
     Active,  // Trigger a change in value or score
@@ -68,6 +102,7 @@ pub enum Operation {
 
 /// A single composable action
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Action {
     pub operation: Operation,
     pub address: Address,
@@ -89,14 +124,18 @@ impl Action {
             Err(ParsingError::InvalidByte)
         } else {
             Ok(Action::new(
-                if x > 0b00100000 {
+                if x > ACTIVE_FLAG {
                     Operation::Active
                 } else {
                     Operation::Passive
                 },
                 match x & 0b00011111 {
-                    1..=8 => Ok(Address::Hand((x & 0b00011111) - 1)),
-                    10..=23 => Ok(Address::Floor((x & 0b00011111) - 10)),
+                    HAND_ADDRESS_MIN..=HAND_ADDRESS_MAX => {
+                        Ok(Address::Hand((x & 0b00011111) - HAND_ADDRESS_MIN))
+                    }
+                    FLOOR_ADDRESS_MIN..=FLOOR_ADDRESS_MAX => {
+                        Ok(Address::Floor((x & 0b00011111) - FLOOR_ADDRESS_MIN))
+                    }
                     _ => Err(ParsingError::InvalidAddress),
                 }?,
             ))
@@ -105,32 +144,143 @@ impl Action {
 }
 
 /// A move comprised of sequential actions
-#[derive(Debug, PartialEq, Eq)]
+///
+/// Variants that allow several builds per turn keep the floor-only builds that happen before
+/// the main sequence in `builds`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Move {
     pub actions: Vec<Action>,
+    pub builds: Vec<Vec<Action>>,
 }
 
 impl Move {
     /// Get a move from a set of actions
     pub fn new(a: Vec<Action>) -> Move {
-        Move { actions: a }
+        Move {
+            actions: a,
+            builds: vec![],
+        }
+    }
+
+    /// Get a move from a set of floor builds followed by a set of actions
+    pub fn with_builds(b: Vec<Vec<Action>>, a: Vec<Action>) -> Move {
+        Move {
+            actions: a,
+            builds: b,
+        }
     }
 
     /// Get a move from a set of bytes
     pub fn from_bytes(xs: Vec<u8>) -> Result<Move, ParsingError> {
-        Ok(Move::new(
+        if xs.iter().filter(|x| **x != BUILD_SEPARATOR).count() > MAX_MOVE_ACTIONS {
+            return Err(ParsingError::TooManyActions);
+        }
+        let mut segments = xs
+            .split(|x| *x == BUILD_SEPARATOR)
+            .map(|s| {
+                s.iter()
+                    .map(|x| Action::from_byte(x.to_owned()))
+                    .collect::<Result<Vec<Action>, ParsingError>>()
+            })
+            .collect::<Result<Vec<Vec<Action>>, ParsingError>>()?;
+        let actions = segments.pop().unwrap_or_default();
+        Ok(Move::with_builds(segments, actions))
+    }
+
+    /// Get the canonical annotation for the move
+    ///
+    /// Leading passive operations are left implicit, except for single card discards.
+    pub fn to_annotation(&self) -> String {
+        let segment = |xs: &[Action]| {
             xs.iter()
-                .map(|x| Action::from_byte(x.to_owned()))
-                .collect::<Result<Vec<Action>, ParsingError>>()?,
-        ))
+                .enumerate()
+                .map(|(i, a)| {
+                    let op = match (i, a.operation) {
+                        (0, Operation::Active) => "*",
+                        (0, Operation::Passive) if xs.len() == 1 => "!",
+                        (0, Operation::Passive) => "",
+                        (_, Operation::Active) => "+",
+                        (_, Operation::Passive) => "&",
+                    };
+                    format!("{}{}", op, a.address)
+                })
+                .collect::<String>()
+        };
+        self.builds
+            .iter()
+            .map(|b| segment(b))
+            .chain([segment(&self.actions)])
+            .collect::<Vec<String>>()
+            .join(";")
+    }
+
+    /// Get the move template for an action id, legal or not
+    fn template(id: usize) -> Option<Move> {
+        if id >= ACTION_SPACE_SIZE {
+            return None;
+        }
+        let h = Address::Hand((id / ACTIONS_PER_CARD) as u8);
+        let t = id % ACTIONS_PER_CARD;
+        if t == 0 {
+            return Some(Move::new(vec![Action::new(Operation::Passive, h)]));
+        }
+        let f = Address::Floor(((t - 1) / 3) as u8);
+        let (x, y) = match (t - 1) % 3 {
+            0 => (Operation::Active, Operation::Passive),
+            1 => (Operation::Passive, Operation::Active),
+            _ => (Operation::Passive, Operation::Passive),
+        };
+        Some(Move::new(vec![Action::new(x, f), Action::new(y, h)]))
+    }
+
+    /// Get the move for an action id, if it is legal in the given state
+    ///
+    /// Each hand card has a block of `ACTIONS_PER_CARD` ids: a discard, then a pair, build, and
+    /// group onto each floor pile in order.
+    pub fn from_action_id(id: usize, state: &State) -> Option<Move> {
+        let m = Move::template(id)?;
+        state.clone().apply(m.clone()).ok()?;
+        Some(m)
+    }
+
+    /// Get the action id for the move, if it is legal in the given state and fits a template
+    ///
+    /// The action space only has a discard and one move onto each floor pile for every hand card,
+    /// so moves that combine more than one floor pile, or make extra floor builds, have no id.
+    /// Policies that need them must pick from `State::legal_moves` instead.
+    pub fn to_action_id(&self, state: &State) -> Result<usize, ActionIdError> {
+        state.clone().apply(self.clone())?;
+        let card = |a: &Action| match a.address {
+            Address::Hand(i) if (i as usize) < HAND_SIZE => Ok(i as usize * ACTIONS_PER_CARD),
+            _ => Err(ActionIdError::NoTemplate),
+        };
+        let pile = |a: &Action| match a.address {
+            Address::Floor(j) if (j as usize) < MAX_FLOOR_SIZE => Ok(1 + j as usize * 3),
+            _ => Err(ActionIdError::NoTemplate),
+        };
+        match (self.builds.is_empty(), self.actions.as_slice()) {
+            (true, [h]) if h.operation == Operation::Passive => card(h),
+            (true, [f, h]) => Ok(card(h)?
+                + pile(f)?
+                + match (f.operation, h.operation) {
+                    (Operation::Active, Operation::Passive) => 0,
+                    (Operation::Passive, Operation::Active) => 1,
+                    (Operation::Passive, Operation::Passive) => 2,
+                    _ => return Err(ActionIdError::NoTemplate),
+                }),
+            _ => Err(ActionIdError::NoTemplate),
+        }
     }
 
     /// Validate that the move is legal
     pub fn is_valid(&self) -> Result<(), MoveError> {
         let mut addresses = HashSet::new();
         if !self
-            .actions
+            .builds
             .iter()
+            .flatten()
+            .chain(self.actions.iter())
             .map(|a| a.address)
             .all(|a| addresses.insert(a))
         {
@@ -146,12 +296,28 @@ impl Move {
             != 1
         {
             Err(MoveError::InvalidHandAddressCount)
+        } else if !self.builds.iter().all(|b| {
+            b.len() > 1
+                && b.iter().all(|a| matches!(a.address, Address::Floor(_)))
+                && b.iter()
+                    .enumerate()
+                    .all(|(i, a)| (i == 0) == (a.operation == Operation::Passive))
+        }) {
+            Err(MoveError::InvalidBuildSegment)
         } else if !self.actions.is_empty()
             && self.actions.first().unwrap().operation == Operation::Active
         {
             match self.actions.last().unwrap().address {
-                Address::Hand(_) => Ok(()),
                 Address::Floor(_) => Err(MoveError::InvalidHandAddressPosition),
+                // Something has to be paired with, or the hand card is only built or played
+                _ if !self
+                    .actions
+                    .iter()
+                    .any(|a| a.operation == Operation::Passive) =>
+                {
+                    Err(MoveError::CaptureWithoutPair)
+                }
+                Address::Hand(_) => Ok(()),
             }
         } else {
             Ok(())
@@ -159,43 +325,131 @@ impl Move {
     }
 }
 
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_annotation())
+    }
+}
+
+/// How strictly an annotation is read
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Profile {
+    /// Any notation that describes a move, such as a discard without its `!`
+    #[default]
+    Standard,
+    /// Only the canonical notation for a move, without shortcuts, for validating untrusted input
+    Strict,
+    /// Also ignores whitespace and case, and takes `x` for `*` and `,` for `&`, for people typing
+    /// moves by hand
+    Lenient,
+}
+
+/// Something `Annotation::lint` found that could be written more clearly
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Lint {
+    /// The same move, written in canonical form
+    Rewrite(String),
+    /// The pile is already part of the move, so using it again does nothing
+    Redundant(Address),
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::Rewrite(x) => write!(f, "Could be written as {}", x),
+            Lint::Redundant(a) => write!(f, "{} is used more than once", a),
+        }
+    }
+}
+
+/// Order a move segment canonically, dropping any address already used in the move
+///
+/// Each passive action starts a chain of the active actions after it. Floor piles are sorted
+/// within their chain, and chains by their first pile, with the hand card last in both.
+fn tidy_segment(xs: &[Action], seen: &mut HashSet<Address>, lints: &mut Vec<Lint>) -> Vec<Action> {
+    let key = |a: &Address| match a {
+        Address::Floor(j) => (false, *j),
+        Address::Hand(i) => (true, *i),
+    };
+    let mut chains: Vec<Vec<Address>> = vec![];
+    for a in xs {
+        if !seen.insert(a.address) {
+            lints.push(Lint::Redundant(a.address));
+        } else if chains.is_empty() || a.operation == Operation::Passive {
+            chains.push(vec![a.address]);
+        } else {
+            chains.last_mut().unwrap().push(a.address);
+        }
+    }
+    for c in chains.iter_mut() {
+        c.sort_by_key(key);
+    }
+    chains.sort_by_key(|c| (c.iter().any(|a| matches!(a, Address::Hand(_))), key(&c[0])));
+    let first = xs.first().map_or(Operation::Passive, |a| a.operation);
+    chains
+        .iter()
+        .enumerate()
+        .flat_map(|(i, c)| {
+            c.iter().enumerate().map(move |(k, &a)| {
+                let op = match (i, k) {
+                    (0, 0) => first,
+                    (_, 0) => Operation::Passive,
+                    _ => Operation::Active,
+                };
+                Action::new(op, a)
+            })
+        })
+        .collect()
+}
+
 /// An annotation representing a move
 pub struct Annotation {
     pub value: String,
+    pub profile: Profile,
 }
 
 impl Annotation {
     /// Get an Annotation from a string
     pub fn new(v: String) -> Annotation {
-        Annotation { value: v }
+        Annotation::with_profile(v, Profile::default())
+    }
+
+    /// Get an Annotation from a string, to be read with the given profile
+    pub fn with_profile(v: String, profile: Profile) -> Annotation {
+        Annotation { value: v, profile }
+    }
+
+    /// Get the canonical annotation for a move, which parses back to the same move
+    pub fn from_move(m: &Move) -> Annotation {
+        Annotation::new(m.to_annotation())
     }
 
-    /// Get the value as a vector of bytes
-    fn bytes(&self) -> Vec<u8> {
-        if !self.value.is_empty() {
-            match self.value.as_bytes()[0] {
-                b'!' | b'*' => self.value.as_bytes().to_vec(),
-                _ => [[b'!'].as_slice(), self.value.as_bytes()].concat(),
+    /// Get a segment of the value as a vector of bytes
+    fn bytes(segment: &str) -> Vec<u8> {
+        if !segment.is_empty() {
+            match segment.as_bytes()[0] {
+                b'!' | b'*' => segment.as_bytes().to_vec(),
+                _ => [[b'!'].as_slice(), segment.as_bytes()].concat(),
             }
         } else {
             vec![]
         }
     }
 
-    /// Convert an annotation to action bytes
-    pub fn to_bytes(&self) -> Result<Vec<u8>, ParsingError> {
-        if !self.bytes().is_empty() {
-            self.bytes()
+    /// Convert an annotation segment to action bytes
+    fn segment_to_bytes(segment: &str) -> Result<Vec<u8>, ParsingError> {
+        if !segment.is_empty() {
+            Annotation::bytes(segment)
                 .windows(2)
                 .step_by(2)
                 .map(|x| {
                     Ok(match x[0] {
                         b'!' | b'&' => Ok(0),
-                        b'*' | b'+' => Ok(32),
+                        b'*' | b'+' => Ok(ACTIVE_FLAG),
                         _ => Err(ParsingError::InvalidOperationCharacter),
                     }? + match x[1] {
-                        b'1'..=b'8' => Ok(x[1] - b'0'),
-                        b'A'..=b'M' => Ok(x[1] - b'A' + 10),
+                        b'1'..=b'8' => Ok(x[1] - b'1' + HAND_ADDRESS_MIN),
+                        b'A'..=b'V' => Ok(x[1] - b'A' + FLOOR_ADDRESS_MIN),
                         _ => Err(ParsingError::InvalidAddressCharacter),
                     }?)
                 })
@@ -205,15 +459,135 @@ impl Annotation {
         }
     }
 
+    /// Convert an annotation to action bytes
+    ///
+    /// Additional floor builds are separated from the rest of the move with `;`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ParsingError> {
+        if self.value.len() > MAX_ANNOTATION_LENGTH {
+            return Err(ParsingError::AnnotationTooLong);
+        }
+        let value = match self.profile {
+            Profile::Lenient => Annotation::normalize(&self.value),
+            _ => self.value.clone(),
+        };
+        let bytes = value
+            .split(';')
+            .map(Annotation::segment_to_bytes)
+            .collect::<Result<Vec<Vec<u8>>, ParsingError>>()?
+            .join(&BUILD_SEPARATOR);
+        if self.profile == Profile::Strict
+            && Move::from_bytes(bytes.clone())?.to_annotation() != value
+        {
+            return Err(ParsingError::NonCanonicalAnnotation);
+        }
+        Ok(bytes)
+    }
+
+    /// Check whether the move could be written more clearly
+    ///
+    /// Suggests the canonical form if the annotation differs from it, such as when piles that
+    /// are grouped or added together are listed out of order, and flags every repeated address.
+    pub fn lint(&self) -> Result<Vec<Lint>, ParsingError> {
+        let profile = match self.profile {
+            Profile::Strict => Profile::Standard,
+            x => x,
+        };
+        let m = Annotation::with_profile(self.value.clone(), profile).to_move()?;
+        let mut seen = HashSet::new();
+        let mut lints = vec![];
+        let builds = m
+            .builds
+            .iter()
+            .map(|b| tidy_segment(b, &mut seen, &mut lints))
+            .collect();
+        let actions = tidy_segment(&m.actions, &mut seen, &mut lints);
+        let canonical = Move::with_builds(builds, actions).to_annotation();
+        if canonical != self.value {
+            lints.insert(0, Lint::Rewrite(canonical));
+        }
+        Ok(lints)
+    }
+
+    /// Rewrite loosely typed notation in the standard form
+    fn normalize(value: &str) -> String {
+        value
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c {
+                'x' | 'X' => '*',
+                ',' => '&',
+                _ => c.to_ascii_uppercase(),
+            })
+            .collect()
+    }
+
     /// Convert an annotation to a move
     pub fn to_move(&self) -> Result<Move, ParsingError> {
         Move::from_bytes(self.to_bytes()?)
     }
+
+    /// Expand a shortcut into a full annotation, leaving other annotations as they are
+    ///
+    /// `t3` trails hand card 3, and `cB` captures floor pile B with the one hand card that can.
+    /// Shortcuts are not canonical, so a strict annotation may not use them.
+    pub fn expand(&self, state: &State) -> Result<Annotation, ParsingError> {
+        if self.value.len() > MAX_ANNOTATION_LENGTH {
+            return Err(ParsingError::AnnotationTooLong);
+        }
+        let x = self.value.trim().as_bytes();
+        let shortcut = matches!(x, [b't', b'1'..=b'8'] | [b'c', b'A'..=b'V' | b'a'..=b'v']);
+        if shortcut && self.profile == Profile::Strict {
+            return Err(ParsingError::NonCanonicalAnnotation);
+        }
+        let value = match x {
+            [b't', h @ b'1'..=b'8'] => format!("!{}", *h as char),
+            [b'c', f @ (b'A'..=b'V' | b'a'..=b'v')] => {
+                let f = f.to_ascii_uppercase() as char;
+                let moves = state
+                    .legal_moves()
+                    .into_iter()
+                    .filter(|m| m.to_annotation().starts_with(&format!("*{}&", f)))
+                    .collect::<Vec<Move>>();
+                // Captures that take other piles too only count when every capture has to
+                let fewest = moves.iter().map(|m| m.actions.len()).min();
+                let moves = moves
+                    .into_iter()
+                    .filter(|m| Some(m.actions.len()) == fewest)
+                    .collect::<Vec<Move>>();
+                match moves.len() {
+                    0 => return Err(ParsingError::NoShortcutMatch),
+                    1 => moves[0].to_annotation(),
+                    _ => return Err(ParsingError::AmbiguousShortcut),
+                }
+            }
+            _ => self.value.clone(),
+        };
+        Ok(Annotation::with_profile(value, self.profile))
+    }
+
+    /// Get every legal move that starts with a partially typed annotation
+    ///
+    /// A prefix with an explicit leading `!` is completed in the same style.
+    pub fn complete(prefix: &str, state: &State) -> Vec<String> {
+        let mut moves = vec![];
+        for x in state.legal_moves().iter().map(|m| m.to_annotation()) {
+            let x = match x.as_bytes()[0] {
+                b'!' | b'*' => x,
+                _ if prefix.starts_with('!') => format!("!{}", x),
+                _ => x,
+            };
+            if x.starts_with(prefix) && !moves.contains(&x) {
+                moves.push(x);
+            }
+        }
+        moves
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pile::PileError;
 
     const A: u8 = 32;
     const P: u8 = 0;
@@ -260,6 +634,11 @@ mod tests {
             Move::from_bytes(vec![100, 101, 102]),
             Err(ParsingError::InvalidByte)
         );
+
+        assert_eq!(
+            Move::from_bytes(vec![P + 10; MAX_MOVE_ACTIONS + 1]),
+            Err(ParsingError::TooManyActions)
+        );
     }
 
     #[test]
@@ -301,6 +680,11 @@ mod tests {
             Annotation::new(String::from("")).to_bytes(),
             Err(ParsingError::BlankAnnotation),
         );
+
+        assert_eq!(
+            Annotation::new("&A".repeat(MAX_ANNOTATION_LENGTH)).to_bytes(),
+            Err(ParsingError::AnnotationTooLong),
+        );
     }
 
     #[test]
@@ -334,6 +718,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_annotation_with_builds() {
+        assert_eq!(
+            Annotation::new(String::from("A+B;C+1")).to_bytes(),
+            Ok(vec![P + 10, A + 11, 9, P + 12, A + 1])
+        );
+
+        assert_eq!(
+            Annotation::new(String::from("A+B;C+1")).to_move(),
+            Ok(Move::with_builds(
+                vec![vec![
+                    Action::new(Operation::Passive, Address::Floor(0)),
+                    Action::new(Operation::Active, Address::Floor(1)),
+                ]],
+                vec![
+                    Action::new(Operation::Passive, Address::Floor(2)),
+                    Action::new(Operation::Active, Address::Hand(0)),
+                ]
+            ))
+        );
+
+        assert_eq!(
+            Annotation::new(String::from("A+B;")).to_bytes(),
+            Err(ParsingError::BlankAnnotation)
+        );
+    }
+
+    #[test]
+    fn test_move_to_annotation() {
+        for x in ["!1", "*C&3", "A+B+C&D+E&1", "A+B;C+1", "C+8"] {
+            let m = Annotation::new(String::from(x)).to_move().unwrap();
+            assert_eq!(m.to_annotation(), x);
+        }
+        let m = Annotation::new(String::from("!A+B&1")).to_move().unwrap();
+        assert_eq!(m.to_annotation(), "A+B&1");
+        assert_eq!(m.to_string(), "A+B&1");
+        let a = Annotation::from_move(&m);
+        assert_eq!(a.value, "A+B&1");
+        assert_eq!(a.to_move().unwrap(), m);
+    }
+
+    #[test]
+    fn test_address_to_string() {
+        assert_eq!(Address::Hand(0).to_string(), "1");
+        assert_eq!(Address::Hand(7).to_string(), "8");
+        assert_eq!(Address::Floor(0).to_string(), "A");
+        assert_eq!(Address::Floor(12).to_string(), "M");
+    }
+
     #[test]
     fn test_move_validation() {
         assert!(Move::new(vec![
@@ -370,5 +803,228 @@ mod tests {
             .is_valid(),
             Err(MoveError::InvalidHandAddressPosition)
         );
+
+        // A capture has to pair the hand card, rather than build with it or play it alone
+        assert_eq!(
+            Move::new(vec![
+                Action::new(Operation::Active, Address::Floor(0)),
+                Action::new(Operation::Active, Address::Hand(0)),
+            ])
+            .is_valid(),
+            Err(MoveError::CaptureWithoutPair)
+        );
+        assert_eq!(
+            Move::new(vec![Action::new(Operation::Active, Address::Hand(0))]).is_valid(),
+            Err(MoveError::CaptureWithoutPair)
+        );
+
+        assert_eq!(
+            Move::with_builds(
+                vec![vec![
+                    Action::new(Operation::Passive, Address::Floor(0)),
+                    Action::new(Operation::Active, Address::Floor(1)),
+                ]],
+                vec![
+                    Action::new(Operation::Passive, Address::Floor(1)),
+                    Action::new(Operation::Active, Address::Hand(0)),
+                ]
+            )
+            .is_valid(),
+            Err(MoveError::DuplicateAddress)
+        );
+
+        assert_eq!(
+            Move::with_builds(
+                vec![vec![
+                    Action::new(Operation::Passive, Address::Floor(0)),
+                    Action::new(Operation::Passive, Address::Floor(1)),
+                ]],
+                vec![Action::new(Operation::Passive, Address::Hand(0))]
+            )
+            .is_valid(),
+            Err(MoveError::InvalidBuildSegment)
+        );
+    }
+
+    #[test]
+    fn test_action_ids() {
+        let mut rng = crate::rng::Rng::from_seed([0; 32]);
+        let mut s = State::default();
+        s.init_deck();
+        s.shuffle_deck(rng.rng_borrow_mut());
+        s.deal_hands();
+        s.deal_floor();
+        let m = |x: &str| Annotation::new(String::from(x)).to_move().unwrap();
+
+        assert_eq!(m("!1").to_action_id(&s), Ok(0));
+        assert_eq!(m("*B&5").to_action_id(&s), Ok(4 * ACTIONS_PER_CARD + 1 + 3));
+        assert_eq!(m("C+1").to_action_id(&s), Ok(1 + 2 * 3 + 1));
+        // Illegal moves and moves using several floor piles have no id
+        assert_eq!(
+            m("*A&1").to_action_id(&s),
+            Err(ActionIdError::Illegal(StateError::InvalidPile(
+                PileError::PairDifferentValues
+            )))
+        );
+        let multi = s.legal_moves().into_iter().find(|x| x.actions.len() > 2);
+        assert_eq!(
+            multi.unwrap().to_action_id(&s),
+            Err(ActionIdError::NoTemplate)
+        );
+
+        let mask = s.action_mask();
+        assert_eq!(mask.len(), ACTION_SPACE_SIZE);
+        for (id, legal) in mask.into_iter().enumerate() {
+            match Move::from_action_id(id, &s) {
+                Some(x) => {
+                    assert!(legal);
+                    assert_eq!(x.to_action_id(&s), Ok(id));
+                }
+                None => assert!(!legal),
+            }
+        }
+        assert_eq!(Move::from_action_id(ACTION_SPACE_SIZE, &s), None);
+
+        // Floor piles past the default floor size have ids too
+        let mut s = crate::scenario::Scenario::parse("floor: 3D\nhand: 3H")
+            .unwrap()
+            .state;
+        s.rules.floor_size = 20;
+        s.floor.resize(20, crate::pile::Pile::empty());
+        s.floor.swap(0, 15);
+        let id = m("*P&1").to_action_id(&s);
+        assert_eq!(id, Ok(1 + 15 * 3));
+        assert_eq!(Move::from_action_id(id.unwrap(), &s), Some(m("*P&1")));
+    }
+
+    #[test]
+    fn test_expand() {
+        let mut rng = crate::rng::Rng::from_seed([0; 32]);
+        let mut s = State::default();
+        s.init_deck();
+        s.shuffle_deck(rng.rng_borrow_mut());
+        s.deal_hands();
+        s.deal_floor();
+        let expand =
+            |x: &str, s: &State| Annotation::new(String::from(x)).expand(s).map(|a| a.value);
+
+        assert_eq!(expand("t3", &s), Ok(String::from("!3")));
+        assert_eq!(expand("cB", &s), Ok(String::from("*B&5")));
+        assert_eq!(expand("cc", &s), Ok(String::from("*C&3")));
+        assert_eq!(expand("cA", &s), Err(ParsingError::NoShortcutMatch));
+        assert_eq!(expand("B+5", &s), Ok(String::from("B+5")));
+        assert_eq!(expand("t9", &s), Ok(String::from("t9")));
+
+        // Either ace could take an ace on the floor
+        s.floor[4] = crate::pile::Pile::card(1, 3);
+        assert_eq!(expand("cE", &s), Err(ParsingError::AmbiguousShortcut));
+    }
+
+    #[test]
+    fn test_lint() {
+        let lint = |x: &str| Annotation::new(String::from(x)).lint();
+        assert_eq!(lint("*C&3"), Ok(vec![]));
+        assert_eq!(lint("A+B&C&1"), Ok(vec![]));
+        assert_eq!(lint("3"), Ok(vec![Lint::Rewrite(String::from("!3"))]));
+        assert_eq!(lint("1&A"), Ok(vec![Lint::Rewrite(String::from("A&1"))]));
+        assert_eq!(
+            lint("*C&B&A&1"),
+            Ok(vec![Lint::Rewrite(String::from("*A&B&C&1"))])
+        );
+        assert_eq!(
+            lint("D&B+A&1"),
+            Ok(vec![Lint::Rewrite(String::from("A+B&D&1"))])
+        );
+        assert_eq!(
+            lint("C+A;B+D;E&1"),
+            Ok(vec![Lint::Rewrite(String::from("A+C;B+D;E&1"))])
+        );
+        assert_eq!(
+            lint("*A&B&A&1"),
+            Ok(vec![
+                Lint::Rewrite(String::from("*A&B&1")),
+                Lint::Redundant(Address::Floor(0))
+            ])
+        );
+        assert_eq!(lint("!Z"), Err(ParsingError::InvalidAddressCharacter));
+
+        // Strict annotations are linted rather than rejected
+        let a = Annotation::with_profile(String::from("1&A"), Profile::Strict);
+        assert_eq!(a.lint(), Ok(vec![Lint::Rewrite(String::from("A&1"))]));
+        assert_eq!(
+            Lint::Redundant(Address::Floor(0)).to_string(),
+            "A is used more than once"
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let parse = |x: &str, p| Annotation::with_profile(String::from(x), p).to_move();
+        let capture = Annotation::new(String::from("*C&3")).to_move();
+        let discard = Annotation::new(String::from("!3")).to_move();
+        assert!(capture.is_ok());
+
+        assert_eq!(parse("*C&3", Profile::Strict), capture);
+        assert_eq!(parse("!3", Profile::Strict), discard);
+        assert_eq!(parse("3", Profile::Standard), discard);
+        assert_eq!(
+            parse("3", Profile::Strict),
+            Err(ParsingError::NonCanonicalAnnotation)
+        );
+        assert_eq!(
+            parse("!A&1", Profile::Strict),
+            Err(ParsingError::NonCanonicalAnnotation)
+        );
+        assert!(parse("A&1", Profile::Strict).is_ok());
+        assert_eq!(
+            parse("!Z", Profile::Strict),
+            Err(ParsingError::InvalidAddressCharacter)
+        );
+
+        assert_eq!(parse(" *c & 3 ", Profile::Lenient), capture);
+        assert_eq!(parse("xc,3", Profile::Lenient), capture);
+        assert_eq!(parse("xc,3", Profile::Standard).ok(), None);
+
+        // Shortcuts keep the profile they were typed with, except that strict refuses them
+        let expand =
+            |x: &str, p| Annotation::with_profile(String::from(x), p).expand(&State::default());
+        assert_eq!(
+            expand("t3", Profile::Lenient).unwrap().profile,
+            Profile::Lenient
+        );
+        assert_eq!(expand("t3", Profile::Lenient).unwrap().value, "!3");
+        assert_eq!(
+            expand("t3", Profile::Strict).err(),
+            Some(ParsingError::NonCanonicalAnnotation)
+        );
+        assert_eq!(
+            expand("cB", Profile::Strict).err(),
+            Some(ParsingError::NonCanonicalAnnotation)
+        );
+        assert_eq!(expand("!3", Profile::Strict).unwrap().value, "!3");
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut rng = crate::rng::Rng::from_seed([0; 32]);
+        let mut s = State::default();
+        s.init_deck();
+        s.shuffle_deck(rng.rng_borrow_mut());
+        s.deal_hands();
+        s.deal_floor();
+        let complete = |x| Annotation::complete(x, &s);
+
+        // Only the seven of clubs can take the seven of diamonds
+        assert_eq!(complete("*B&"), vec!["*B&5"]);
+        assert_eq!(complete("*B"), vec!["*B&5"]);
+        assert_eq!(complete("*A"), Vec::<String>::new());
+        assert_eq!(complete("C+"), vec!["C+1", "C+4"]);
+        assert_eq!(complete("!C+"), vec!["!C+1", "!C+4"]);
+        let all = complete("!");
+        assert!(all.contains(&String::from("!1")) && all.contains(&String::from("!C+1")));
+        // Captures can't be written with a leading passive operation
+        assert!(all.iter().all(|x| x.starts_with('!')));
+        assert!(complete("").len() > all.len());
+        assert_eq!(complete("*B&5"), vec!["*B&5"]);
     }
 }
@@ -1,28 +1,42 @@
-use crate::action::Annotation;
+use crate::bot;
 use crate::card::Card;
-use crate::game::Game;
+use crate::consts::{
+    DECK_SIZE, HANDS_SIZE, HAND_SIZE, MAX_PILE_CARDS, MIRROR_FLOOR_OFFSET, MIRROR_HANDS_OFFSET,
+    MIRROR_PILE_SIZE, MIRROR_SIZE, OBSERVATION_SIZE, VALUE_SLOTS,
+};
+use crate::error::ErrorCode;
+use crate::game::{panic_message, Deal, Event, Game};
+use crate::locale::Locale;
+use crate::observe;
 use crate::pile::{Mark, Pile as BasePile};
 use crate::rng::Seed;
-use crate::score::Score;
+use crate::score::{Category, Score};
+use crate::state::{PlayerId, StateError};
+use rand::random;
 use std::ffi::{c_char, CStr, CString};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
 
 /// API level card pile data
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct Pile {
-    pub cards: [u8; 20],
+    pub cards: [u8; MAX_PILE_CARDS],
     pub value: u8,
     pub build: bool,
-    pub owner: bool,
+    pub owner: PlayerId,
+    pub id: u16,
 }
 
 impl Default for Pile {
     fn default() -> Self {
         Pile {
-            cards: [u8::from(Card::invalid()); 20],
+            cards: [u8::from(Card::invalid()); MAX_PILE_CARDS],
             value: 0,
             build: false,
-            owner: false,
+            owner: PlayerId::default(),
+            id: 0,
         }
     }
 }
@@ -32,41 +46,84 @@ impl From<Pile> for BasePile {
         let cards = pile
             .cards
             .iter()
-            .filter(|&&x| x < 52)
+            .filter(|&&x| (x as usize) < DECK_SIZE)
             .map(|&x| Card::from(x))
             .collect::<Vec<Card>>();
-        BasePile::new(
-            cards.clone(),
-            pile.value,
-            match cards.len() {
-                0 => Mark::Empty,
-                1 => Mark::Single,
-                _ => {
-                    if pile.build {
-                        Mark::Build
-                    } else {
-                        Mark::Group
+        BasePile {
+            id: pile.id,
+            ..BasePile::new(
+                cards.clone(),
+                pile.value,
+                match cards.len() {
+                    0 => Mark::Empty,
+                    1 => Mark::Single,
+                    _ => {
+                        if pile.build {
+                            Mark::Build
+                        } else {
+                            Mark::Group
+                        }
                     }
-                }
-            },
-        )
+                },
+            )
+        }
     }
 }
 
 /// Game status and telemetry
 #[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Status {
     pub game: u8,
     pub round: u8,
-    pub turn: bool,
+    pub turn: PlayerId,
+    /// Player who moves after the current one
+    pub next: PlayerId,
     pub hand: u8,
     pub floor: u8,
     pub seed: Seed,
+    pub dealer: PlayerId,
+    pub games: u8,
+    pub over: bool,
+}
+
+impl Status {
+    /// Get a copy of the status that is safe to show players and spectators
+    ///
+    /// The seed is zeroed, since it gives away the order of the whole deck.
+    pub fn redacted(self) -> Status {
+        Status {
+            seed: Seed::default(),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "game {}/{} round {} | {} to move, {} deals | hand {} floor {} | seed {}",
+            self.game + 1,
+            self.games,
+            self.round + 1,
+            self.turn,
+            self.dealer,
+            self.hand,
+            self.floor,
+            self.seed
+                .iter()
+                .map(|x| format!("{:02x}", x))
+                .collect::<String>()
+        )
+    }
 }
 
 /// API level player scorecard
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scorecard {
     pub aces: u8,
     pub most_cards: u8,
@@ -78,101 +135,307 @@ pub struct Scorecard {
 }
 
 impl Scorecard {
-    fn dealer(score: &Score) -> Self {
-        let points = score.dealer_points();
+    fn new(score: &Score, p: PlayerId) -> Self {
+        let points = score.points(p);
         Scorecard {
-            aces: score.dealer_aces,
+            aces: score.aces(p),
             most_cards: points[0],
             most_spades: points[1],
             suipi_count: points[2],
             ten_of_diamonds: points[3],
             two_of_spades: points[4],
-            total: score.dealer_total(),
+            total: score.total(p),
         }
     }
+}
 
-    fn opponent(score: &Score) -> Self {
-        let points = score.opponent_points();
-        Scorecard {
-            aces: score.opponent_aces,
-            most_cards: points[0],
-            most_spades: points[1],
-            suipi_count: points[2],
-            ten_of_diamonds: points[3],
-            two_of_spades: points[4],
-            total: score.opponent_total(),
+/// Kinds of API level scoring events
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ScoreKind {
+    /// A card worth points was captured
+    Point = 0,
+    /// The floor was cleared
+    Suipi = 1,
+    /// The lead for most cards changed
+    MostCards = 2,
+    /// The lead for most spades changed
+    MostSpades = 3,
+}
+
+/// API level scoring event, for showing toasts like "Dealer takes 10♦ (+2)"
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ScoreEvent {
+    pub kind: ScoreKind,
+    /// Player who scored, or who took the lead
+    pub player: PlayerId,
+    /// Card captured by a point event, otherwise an invalid card
+    pub card: u8,
+    pub points: u8,
+    /// Is a lead now tied, leaving nobody ahead?
+    pub tied: bool,
+}
+
+impl ScoreEvent {
+    /// Get the scoring event for a game event, if it is one
+    pub fn new(e: &Event) -> Option<Self> {
+        let event = ScoreEvent {
+            kind: ScoreKind::Point,
+            player: PlayerId::default(),
+            card: u8::from(Card::invalid()),
+            points: 0,
+            tied: false,
+        };
+        match *e {
+            Event::PointEarned {
+                player,
+                card,
+                points,
+            } => Some(ScoreEvent {
+                player,
+                card: u8::from(card),
+                points,
+                ..event
+            }),
+            Event::Suipi(player) => Some(ScoreEvent {
+                kind: ScoreKind::Suipi,
+                player,
+                points: 1,
+                ..event
+            }),
+            Event::LeaderChanged { category, player } => Some(ScoreEvent {
+                kind: match category {
+                    Category::MostCards => ScoreKind::MostCards,
+                    Category::MostSpades => ScoreKind::MostSpades,
+                },
+                player: player.unwrap_or_default(),
+                points: match category {
+                    Category::MostCards => 3,
+                    Category::MostSpades => 1,
+                },
+                tied: player.is_none(),
+                ..event
+            }),
+            Event::MatchDecided(_) => None,
         }
     }
 }
 
-/// Initialize a new game from the given seed
+/// Run the body of an exported function, aborting instead of unwinding into the caller if it
+/// panics
+///
+/// Unwinding out of an `extern "C"` function is undefined behavior, and these functions have
+/// no way to report the panic instead.
+fn guard<T>(f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| process::abort())
+}
+
+/// Run the body of an exported function that returns a message, returning the panic as an
+/// internal error message if it panics
+fn guard_str(f: impl FnOnce() -> String) -> *const c_char {
+    let x = panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|e| StateError::Internal(panic_message(e)).to_string());
+    // A nul byte would end the message early, so it is dropped
+    CString::new(x.replace('\0', ""))
+        .unwrap_or_default()
+        .into_raw()
+}
+
+/// Free a string returned by the engine
+///
+/// # Safety
+///
+/// The pointer must be a string returned by this library that has not been freed yet, or null.
+#[no_mangle]
+pub unsafe extern "C" fn free_string(x: *const c_char) {
+    if !x.is_null() {
+        guard(|| drop(unsafe { CString::from_raw(x as *mut c_char) }));
+    }
+}
+
+/// Initialize a new game from the given seed, returning null if the game could not be set up
 ///
 /// # Safety
 ///
 /// This function dereferences a raw pointer. If this pointer does not point to a valid Seed
 /// struct, this function will fail.
 #[no_mangle]
-pub unsafe extern "C" fn new_game(seed: *const Seed) -> Box<Game> {
-    let mut g = Game::default();
-    if !seed.is_null() {
-        g.seed(unsafe { *seed });
-    }
-    g.deal();
-    Box::new(g)
+pub unsafe extern "C" fn new_game(seed: *const Seed) -> Option<Box<Game>> {
+    guard(|| {
+        let mut builder = Game::builder();
+        if !seed.is_null() {
+            builder = builder.seed(unsafe { *seed });
+        }
+        builder.build().ok().map(Box::new)
+    })
 }
 
 /// Get the status signals for a game
 #[no_mangle]
 #[allow(clippy::borrowed_box)]
 pub extern "C" fn status(g: &Box<Game>) -> Box<Status> {
-    Box::new(Status {
-        game: g.game,
-        round: g.round,
-        turn: g.state.turn,
-        hand: g.state.player().card_count() as u8,
-        floor: g.state.floor_count() as u8,
-        seed: g.rng.rng_borrow().get_seed(),
-    })
+    guard(|| Box::new(g.status()))
+}
+
+/// Get the current deal, for showing progress like "Deal 3 of 6"
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn current_deal(g: &Box<Game>) -> Box<Deal> {
+    guard(|| Box::new(g.deals().last().copied().unwrap_or_default()))
+}
+
+/// Get the number of deals in a match played to the end
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn deal_total(g: &Box<Game>) -> u8 {
+    guard(|| g.deal_total() as u8)
 }
 
 /// Read both player's hands, the current player's first
 #[no_mangle]
 #[allow(clippy::borrowed_box)]
-pub extern "C" fn read_hands(g: &Box<Game>) -> Box<[u8; 16]> {
-    let mut cards = [0; 16];
-    for (i, c) in cards.iter_mut().enumerate() {
-        let di = i % 8;
-        let p = if g.state.turn ^ (i >= 8) {
-            &g.state.dealer
-        } else {
-            &g.state.opponent
-        };
-        *c = u8::from(
-            p.hand[di]
-                .cards
-                .first()
-                .unwrap_or(&Card::invalid())
-                .to_owned(),
-        );
-    }
-    Box::new(cards)
+pub extern "C" fn read_hands(g: &Box<Game>) -> Box<[u8; HANDS_SIZE]> {
+    guard(|| {
+        let mut cards = [0; HANDS_SIZE];
+        for (i, c) in cards.iter_mut().enumerate() {
+            let di = i % HAND_SIZE;
+            let p = g.state.seat(if i < HAND_SIZE {
+                g.state.turn
+            } else {
+                g.state.turn.other()
+            });
+            *c = u8::from(
+                p.hand[di]
+                    .cards
+                    .first()
+                    .unwrap_or(&Card::invalid())
+                    .to_owned(),
+            );
+        }
+        Box::new(cards)
+    })
 }
 
-/// Read the current floor piles
+/// Count the cards of each value that are still to come, indexed by value from ace to king
+///
+/// Only what the current player can see is used, so the other player's hand is counted along
+/// with the deck, and the deck order stays hidden.
 #[no_mangle]
 #[allow(clippy::borrowed_box)]
-pub extern "C" fn read_floor(g: &Box<Game>) -> Box<[Pile; 13]> {
-    let mut piles = [Pile::default(); 13];
-    for (i, p) in piles.iter_mut().enumerate() {
-        let f = &g.state.floor[i];
-        p.value = f.value;
-        p.build = f.is_build();
-        p.owner = f.owner;
-        for (j, c) in f.cards.iter().enumerate() {
-            p.cards[j] = u8::from(c.to_owned());
+pub extern "C" fn deck_histogram(g: &Box<Game>) -> Box<[u8; VALUE_SLOTS]> {
+    guard(|| Box::new(g.state.unseen_values(g.state.turn)))
+}
+
+/// Get the number of piles that fit on the floor
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn floor_size(g: &Box<Game>) -> usize {
+    guard(|| g.state.floor.len())
+}
+
+/// Read up to `len` of the current floor piles, returning how many were read
+///
+/// # Safety
+///
+/// This function dereferences a raw pointer. `out` must have room for `len` piles.
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub unsafe extern "C" fn read_floor(g: &Box<Game>, out: *mut Pile, len: usize) -> usize {
+    guard(|| {
+        let n = len.min(g.state.floor.len());
+        for (i, f) in g.state.floor.iter().take(n).enumerate() {
+            let mut p = Pile {
+                value: f.value,
+                build: f.is_build(),
+                owner: f.owner,
+                id: f.id,
+                ..Pile::default()
+            };
+            for (j, c) in f.cards.iter().enumerate() {
+                p.cards[j] = u8::from(c.to_owned());
+            }
+            unsafe { out.add(i).write(p) };
+        }
+        n
+    })
+}
+
+/// Write the game as the given player sees it into a state mirror, bumping its version
+///
+/// The mirror starts with the version as four little endian bytes, then the game, round, turn,
+/// next player, dealer, number of games, whether the match is over, floor size, deck size, and
+/// last scorer. The floor piles and both hands follow at fixed offsets, with empty slots holding
+/// the invalid card id. Cards in the other player's hand also hold the invalid card id.
+pub(crate) fn fill_mirror(g: &Game, viewer: PlayerId, buf: &mut [u8; MIRROR_SIZE]) {
+    let version = u32::from_le_bytes(buf[..4].try_into().unwrap()).wrapping_add(1);
+    let status = g.status();
+    let s = g.state.redacted_for(viewer);
+    buf.fill(0);
+    buf[..4].copy_from_slice(&version.to_le_bytes());
+    buf[4..14].copy_from_slice(&[
+        status.game,
+        status.round,
+        status.turn as u8,
+        status.next as u8,
+        status.dealer as u8,
+        status.games,
+        status.over as u8,
+        s.floor.len() as u8,
+        s.deck.len() as u8,
+        s.last_score as u8,
+    ]);
+    let floor = buf[MIRROR_FLOOR_OFFSET..MIRROR_HANDS_OFFSET].chunks_exact_mut(MIRROR_PILE_SIZE);
+    for (x, f) in floor.zip(s.floor.iter()) {
+        x[..MAX_PILE_CARDS].fill(u8::from(Card::invalid()));
+        for (y, c) in x.iter_mut().zip(f.cards.iter()) {
+            *y = u8::from(*c);
+        }
+        x[MAX_PILE_CARDS..].copy_from_slice(&[
+            f.value,
+            f.is_build() as u8,
+            f.owner as u8,
+            f.id.to_le_bytes()[0],
+            f.id.to_le_bytes()[1],
+        ]);
+    }
+    let hands = buf[MIRROR_HANDS_OFFSET..].chunks_exact_mut(HAND_SIZE);
+    for (x, p) in hands.zip([PlayerId::Opponent, PlayerId::Dealer]) {
+        x.fill(u8::from(Card::invalid()));
+        for (y, h) in x.iter_mut().zip(s.seat(p).hand.iter()) {
+            *y = u8::from(h.cards.first().copied().unwrap_or(Card::invalid()));
         }
     }
-    Box::new(piles)
+}
+
+/// Start keeping a state mirror of the game as the given player sees it, returning a pointer
+/// to its `MIRROR_SIZE` bytes
+///
+/// The mirror is rewritten after every move, turn, and undo, so web clients can read it from
+/// memory instead of calling into the engine. The other player's hand is hidden. Calling this
+/// again switches the viewer. The pointer is valid until the game is freed.
+///
+/// The viewer is 0 for the opponent and 1 for the dealer. Any other viewer leaves the mirror as it
+/// was and returns null.
+#[no_mangle]
+pub extern "C" fn enable_mirror(g: &mut Box<Game>, viewer: u8) -> *const u8 {
+    guard(|| match PlayerId::try_from(viewer) {
+        Ok(p) => g.enable_mirror(p).as_ptr(),
+        Err(_) => std::ptr::null(),
+    })
+}
+
+/// Send scoring events to the given function as they happen, or stop sending them if it is null
+#[no_mangle]
+pub extern "C" fn set_score_callback(g: &mut Box<Game>, f: Option<extern "C" fn(ScoreEvent)>) {
+    guard(|| match f {
+        Some(f) => g.set_listener(move |e| {
+            if let Some(x) = ScoreEvent::new(e) {
+                f(x);
+            }
+        }),
+        None => g.set_listener(|_| ()),
+    })
 }
 
 /// Attempt to apply a move to the game state
@@ -182,50 +445,354 @@ pub extern "C" fn read_floor(g: &Box<Game>) -> Box<[Pile; 13]> {
 /// This function calls `std::ffi::CStr::from_ptr`, which is an unsafe function.
 #[no_mangle]
 pub unsafe extern "C" fn apply_move(g: &mut Box<Game>, a: *const c_char) -> *const c_char {
-    CString::new(
+    guard_str(|| {
         if let Ok(annotation) = unsafe { CStr::from_ptr(a) }.to_str() {
-            match Annotation::new(String::from(annotation)).to_move() {
+            match g.apply_annotation(annotation) {
                 Err(e) => e.to_string(),
-                Ok(m) => {
-                    if let Err(e) = g.apply(m) {
-                        e.to_string()
-                    } else {
-                        "".to_string() // Ok
-                    }
-                }
+                Ok(_) => "".to_string(), // Ok
             }
         } else {
             "Error: Invalid CString".to_string()
-        },
-    )
-    .unwrap()
-    .into_raw()
+        }
+    })
+}
+
+/// Attempt to apply a move to the game state, writing the error's code to `code` as well as
+/// returning its message
+///
+/// # Safety
+///
+/// This function calls `std::ffi::CStr::from_ptr`, which is an unsafe function.
+#[no_mangle]
+pub unsafe extern "C" fn apply_move_with_code(
+    g: &mut Box<Game>,
+    a: *const c_char,
+    code: &mut ErrorCode,
+) -> *const c_char {
+    // Left in place if the engine panics
+    *code = ErrorCode::StateInternal;
+    guard_str(|| {
+        let (x, message) = match unsafe { CStr::from_ptr(a) }.to_str() {
+            Ok(annotation) => match g.apply_annotation(annotation) {
+                Err(e) => (e.code(), e.to_string()),
+                Ok(_) => (ErrorCode::Success, "".to_string()),
+            },
+            Err(_) => (
+                ErrorCode::InvalidCString,
+                "Error: Invalid CString".to_string(),
+            ),
+        };
+        *code = x;
+        message
+    })
 }
 
-/// End the current player's turn
+/// End the current player's turn, returning an error if the engine failed and the turn was not
+/// ended
+///
+/// The returned string must be passed to `free_string`.
+#[no_mangle]
+pub extern "C" fn next_turn(g: &mut Box<Game>) -> *const c_char {
+    guard_str(|| match g.try_tick() {
+        Err(e) => e.to_string(),
+        Ok(_) => "".to_string(), // Ok
+    })
+}
+
+/// Advance the turn clock, returning true if the current player ran out of time
+///
+/// This is the only way the engine learns the time, so the match duration only covers time up
+/// to the last touch. Touch the game before every move to keep it right.
+#[no_mangle]
+pub extern "C" fn touch(g: &mut Box<Game>, now_ms: u64) -> bool {
+    guard(|| g.touch(now_ms).is_some())
+}
+
+/// Get every move played in the match, one per line, like "1. Opponent *C&3"
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn get_history(g: &Box<Game>) -> *const c_char {
+    guard_str(|| {
+        g.moves()
+            .iter()
+            .map(|x| format!("{}. {} {}\n", x.turn + 1, x.player, x.annotation))
+            .collect::<String>()
+    })
+}
+
+/// Undo the most recent move, returning the reason if nothing was undone
+#[no_mangle]
+pub extern "C" fn undo(g: &mut Box<Game>) -> *const c_char {
+    guard_str(|| match g.undo() {
+        Err(e) => e.to_string(),
+        Ok(_) => "".to_string(), // Ok
+    })
+}
+
+/// Play the most recently undone move again, returning the reason if nothing was redone
+#[no_mangle]
+pub extern "C" fn redo(g: &mut Box<Game>) -> *const c_char {
+    guard_str(|| match g.redo() {
+        Err(e) => e.to_string(),
+        Ok(_) => "".to_string(), // Ok
+    })
+}
+
+/// Suggest a move that merges the current player's hand card into the matching floor pile
+///
+/// Returns an empty string if there is no such move.
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn suggest_merge(g: &Box<Game>, hand_index: u8) -> *const c_char {
+    guard_str(|| {
+        g.state
+            .merge_suggestion(hand_index as usize)
+            .unwrap_or_default()
+    })
+}
+
+/// List the annotation of every legal capture the current player's hand card could make, one
+/// per line
 #[no_mangle]
-pub extern "C" fn next_turn(g: &mut Box<Game>) {
-    g.tick();
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn captures_for(g: &Box<Game>, hand_index: u8) -> *const c_char {
+    guard_str(|| {
+        let lines = g
+            .state
+            .captures_for(hand_index as usize)
+            .into_iter()
+            .map(|x| x.annotation)
+            .collect::<Vec<String>>();
+        lines.join("\n")
+    })
 }
 
-/// Undo the most recent move
+/// List every set of floor piles that could be built into a pile of the given value, one per
+/// line, such as "A+C"
 #[no_mangle]
-pub extern "C" fn undo(g: &mut Box<Game>) {
-    g.undo();
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn build_sets(g: &Box<Game>, target: u8) -> *const c_char {
+    guard_str(|| {
+        let lines = g
+            .state
+            .build_sets(target)
+            .iter()
+            .map(|set| {
+                set.iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>()
+                    .join("+")
+            })
+            .collect::<Vec<String>>();
+        lines.join("\n")
+    })
+}
+
+/// List every legal way to merge the current player's hand card with the floor piles that have
+/// its value, one per line
+///
+/// Each line has the move's annotation, followed by the ids of the cards in the pile it leaves
+/// on the floor, if it isn't a capture. Groups are listed before captures.
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn merge_options(g: &Box<Game>, hand_index: u8) -> *const c_char {
+    guard_str(|| {
+        let lines = g
+            .state
+            .merge_options(hand_index as usize)
+            .into_iter()
+            .map(|x| {
+                let cards = x.result.iter().flat_map(|p| p.cards.iter());
+                std::iter::once(x.annotation)
+                    .chain(cards.map(|&c| u8::from(c).to_string()))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .collect::<Vec<String>>();
+        lines.join("\n")
+    })
+}
+
+/// Get the move recommended for the current player, as its annotation and a reason code
+/// separated by a space, such as "*C&3 point-card"
+///
+/// The reason code is one of "suipi", "point-card", "capture", "build", "group", or "discard".
+/// Returns an empty string if the player has no move.
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn get_hint(g: &Box<Game>) -> *const c_char {
+    guard_str(|| g.hint().map(|x| x.to_string()).unwrap_or_default())
+}
+
+/// Get the annotation of the move the bot at the given difficulty would play for the current
+/// player, from 0 for the easiest up to `MAX_DIFFICULTY`
+///
+/// The bot only uses what the current player can see. Returns an empty string if the player has
+/// no move.
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn get_bot_move(g: &Box<Game>, difficulty: u8) -> *const c_char {
+    guard_str(|| {
+        let m = match g.is_over() {
+            true => None,
+            false => bot::for_difficulty(difficulty, random()).choose_move(&g.state),
+        };
+        m.map(|x| x.to_annotation()).unwrap_or_default()
+    })
+}
+
+/// Get the grade of a move played earlier in the match, by its position among every move
+/// played, searching `depth` moves ahead
+///
+/// The grade is one of "brilliant", "good", "inaccuracy", "mistake", or "blunder". Returns an
+/// empty string if there is no such move or it could not be graded.
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub extern "C" fn get_move_grade(g: &Box<Game>, turn: usize, depth: u8) -> *const c_char {
+    guard_str(|| {
+        let grade = g.move_stats(turn, depth).and_then(|x| x.grade);
+        grade.map(|x| x.to_string()).unwrap_or_default()
+    })
+}
+
+/// Estimate the first player's chance of winning after each of up to `len` moves played so far,
+/// from `playouts` random playouts per move, returning how many were read
+///
+/// # Safety
+///
+/// This function dereferences a raw pointer. `out` must have room for `len` values.
+#[no_mangle]
+#[allow(clippy::borrowed_box)]
+pub unsafe extern "C" fn read_win_probabilities(
+    g: &Box<Game>,
+    playouts: u32,
+    out: *mut f32,
+    len: usize,
+) -> usize {
+    guard(|| {
+        let curve = g.win_probabilities(playouts);
+        let n = len.min(curve.len());
+        for (i, x) in curve.iter().take(n).enumerate() {
+            unsafe { out.add(i).write(*x as f32) };
+        }
+        n
+    })
+}
+
+/// Encode the games' observations into a buffer, `OBSERVATION_SIZE` values per game
+///
+/// # Safety
+///
+/// This function dereferences raw pointers. `games` must point to `n` valid games, and `out`
+/// must have room for `n * OBSERVATION_SIZE` values.
+#[no_mangle]
+pub unsafe extern "C" fn encode_games(games: *const Box<Game>, n: usize, out: *mut f32) {
+    guard(|| {
+        let games = unsafe { std::slice::from_raw_parts(games, n) };
+        let out = unsafe { std::slice::from_raw_parts_mut(out, n * OBSERVATION_SIZE) };
+        out.fill(0.0);
+        for (g, row) in games.iter().zip(out.chunks_exact_mut(OBSERVATION_SIZE)) {
+            observe::write(&g.state, row);
+        }
+    })
+}
+
+/// Get the message for an error code from `apply_move_with_code` in the given language
+///
+/// An unknown language falls back to English, and an unknown code gets an "unknown error"
+/// message.
+#[no_mangle]
+pub extern "C" fn error_message(code: u16, locale: u8) -> *const c_char {
+    guard_str(|| {
+        let locale = Locale::try_from(locale).unwrap_or_default();
+        match ErrorCode::try_from(code) {
+            Ok(x) => locale.code_message(x).to_string(),
+            Err(_) => locale.unknown_message().to_string(),
+        }
+    })
 }
 
 /// Get an array of score cards for the completed games
 #[no_mangle]
 #[allow(clippy::borrowed_box)]
 pub extern "C" fn get_scores(g: &Box<Game>) -> Box<[Scorecard; 4]> {
-    let mut scores = [Scorecard::default(); 4];
-    for i in 0..g.game {
-        let j = (i * 2) as usize;
-        if j > 2 {
-            break;
+    guard(|| {
+        let mut scores = [Scorecard::default(); 4];
+        for i in 0..g.game {
+            let j = (i * 2) as usize;
+            if j > 2 {
+                break;
+            }
+            scores[j] = Scorecard::new(&g.scores[i as usize], PlayerId::Opponent);
+            scores[j + 1] = Scorecard::new(&g.scores[i as usize], PlayerId::Dealer);
         }
-        scores[j] = Scorecard::opponent(&g.scores[i as usize]);
-        scores[j + 1] = Scorecard::dealer(&g.scores[i as usize]);
+        Box::new(scores)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::INJECT_PANIC;
+
+    #[test]
+    fn test_free_string() {
+        let mut g = unsafe { new_game(&Seed::default()) }.unwrap();
+        unsafe { free_string(next_turn(&mut g)) };
+        unsafe { free_string(std::ptr::null()) };
+    }
+
+    /// Take back a string handed out over FFI
+    fn take(x: *const c_char) -> String {
+        unsafe { CString::from_raw(x as *mut c_char) }
+            .into_string()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_guard_str() {
+        assert_eq!(take(guard_str(|| "a\0b".to_string())), "ab");
+        assert_eq!(
+            take(guard_str(|| panic!("oops"))),
+            "State Error: Internal error - oops"
+        );
+    }
+
+    #[test]
+    fn test_contained_ffi_panics() {
+        let mut g = unsafe { new_game(&Seed::default()) }.unwrap();
+        let a = CString::new("!1").unwrap();
+        let mut code = ErrorCode::Success;
+        INJECT_PANIC.set(true);
+        let e = take(unsafe { apply_move_with_code(&mut g, a.as_ptr(), &mut code) });
+        assert_eq!(e, "State Error: Internal error - injected fault");
+        assert_eq!(code, ErrorCode::StateInternal);
+        assert_eq!(take(unsafe { apply_move(&mut g, a.as_ptr()) }), "");
+    }
+
+    #[test]
+    fn test_error_message() {
+        assert_eq!(
+            take(error_message(
+                ErrorCode::StateNotYourTurn as u16,
+                Locale::German as u8
+            )),
+            "Du bist nicht am Zug"
+        );
+        assert_eq!(
+            take(error_message(
+                ErrorCode::Success as u16,
+                Locale::Spanish as u8
+            )),
+            ""
+        );
+        assert_eq!(
+            take(error_message(999, Locale::German as u8)),
+            "Unbekannter Fehler"
+        );
+        assert_eq!(
+            take(error_message(ErrorCode::StateNotYourTurn as u16, 7)),
+            "It is not your turn"
+        );
     }
-    Box::new(scores)
 }
@@ -0,0 +1,27 @@
+use playsuipi_core::sim::Fixture;
+use std::env;
+use std::process::ExitCode;
+
+/// Get the seed for the nth fixture
+fn seed(n: u64) -> [u8; 32] {
+    let mut seed = [0; 32];
+    seed[..8].copy_from_slice(&n.to_le_bytes());
+    seed
+}
+
+fn main() -> ExitCode {
+    let count = env::args()
+        .nth(1)
+        .and_then(|x| x.parse::<u64>().ok())
+        .unwrap_or(32);
+    for n in 0..count {
+        match Fixture::record(seed(n)) {
+            Ok(x) => println!("{}", x),
+            Err(e) => {
+                eprintln!("[!] {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,108 @@
+use playsuipi_core::game::Game;
+use playsuipi_core::protocol::{ProtocolError, Request, Response};
+use playsuipi_core::session::Session;
+use std::collections::HashMap;
+use std::env;
+use std::io::{BufRead, BufReader, Read, Result as IOResult, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The longest line a client may send, in bytes
+const MAX_LINE: usize = 4096;
+
+/// Everything the connection threads share
+struct Server {
+    session: Session,
+    streams: HashMap<u64, TcpStream>,
+}
+
+impl Server {
+    /// Write each response to the connection it is addressed to
+    fn send(&mut self, replies: Vec<(u64, Response)>) {
+        for (conn, r) in replies {
+            if let Some(s) = self.streams.get_mut(&conn) {
+                let _ = writeln!(s, "{}", r);
+            }
+        }
+    }
+}
+
+/// Read the next line into `buf` without its line ending, returning `None` at the end of the
+/// stream and `Some(false)` for a line longer than `MAX_LINE`, which is skipped
+fn read_line(reader: &mut impl BufRead, buf: &mut Vec<u8>) -> IOResult<Option<bool>> {
+    buf.clear();
+    if reader.take(MAX_LINE as u64 + 1).read_until(b'\n', buf)? == 0 {
+        return Ok(None);
+    }
+    if buf.last() != Some(&b'\n') && buf.len() > MAX_LINE {
+        // Throw away the rest of the line a piece at a time
+        while buf.last() != Some(&b'\n') {
+            buf.clear();
+            if reader.take(MAX_LINE as u64).read_until(b'\n', buf)? == 0 {
+                break;
+            }
+        }
+        return Ok(Some(false));
+    }
+    while let Some(b'\n' | b'\r') = buf.last() {
+        buf.pop();
+    }
+    Ok(Some(true))
+}
+
+fn serve(server: Arc<Mutex<Server>>, conn: u64, stream: TcpStream) -> IOResult<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut buf = Vec::new();
+    {
+        let mut s = server.lock().unwrap();
+        s.streams.insert(conn, stream);
+        let replies = s.session.join(conn);
+        s.send(replies);
+    }
+    while let Some(fits) = read_line(&mut reader, &mut buf)? {
+        let mut s = server.lock().unwrap();
+        let replies = if !fits {
+            vec![(
+                conn,
+                Response::Error(ProtocolError::MessageTooLong.to_string()),
+            )]
+        } else {
+            match Request::parse(&String::from_utf8_lossy(&buf)) {
+                Ok(r) => s.session.handle(conn, r),
+                Err(e) => vec![(conn, Response::Error(e.to_string()))],
+            }
+        };
+        s.send(replies);
+        if s.session.connections().contains(&conn) {
+            continue;
+        }
+        break;
+    }
+    let mut s = server.lock().unwrap();
+    s.session.leave(conn);
+    s.streams.remove(&conn);
+    Ok(())
+}
+
+fn main() -> IOResult<()> {
+    let addr = env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("127.0.0.1:7878"));
+    let listener = TcpListener::bind(&addr)?;
+    let server = Arc::new(Mutex::new(Server {
+        session: Session::new(Game::builder().build().unwrap()),
+        streams: HashMap::new(),
+    }));
+    println!("[*] Listening on {}", addr);
+    for (conn, stream) in (1..).zip(listener.incoming()) {
+        let stream = stream?;
+        let server = server.clone();
+        thread::spawn(move || {
+            if let Err(e) = serve(server, conn, stream) {
+                eprintln!("[!] Connection {} failed: {}", conn, e);
+            }
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,607 @@
+use crate::action::{Annotation, Move};
+use crate::card::Suit;
+use crate::consts::{DECK_SIZE, GAME_POINTS, MAX_DIFFICULTY, SUIT_SIZE};
+use crate::ismcts::{IsmctsBot, DEFAULT_ITERATIONS};
+use crate::rng::{ChaCha20Rng, Seed, SeedableRng, SliceRandom};
+use crate::score::{PlayerScore, Score};
+use crate::search::rank_moves_sampled;
+use crate::state::State;
+use rand::Rng;
+
+/// A computer player
+pub trait Bot {
+    /// Choose a move for the current player, if they have any
+    fn choose_move(&mut self, state: &State) -> Option<Move>;
+}
+
+/// Get a bot that plays at the given difficulty, from 0 for the easiest up to `MAX_DIFFICULTY`
+///
+/// The levels are a random bot, the balanced greedy bot, a short tree search, and a full one.
+pub fn for_difficulty(difficulty: u8, seed: Seed) -> Box<dyn Bot + Send> {
+    match difficulty.min(MAX_DIFFICULTY) {
+        0 => Box::new(RandomBot::new(seed)),
+        1 => Box::new(GreedyBot::new()),
+        2 => Box::new(IsmctsBot::new(seed).with_iterations(DEFAULT_ITERATIONS / 5)),
+        _ => Box::new(IsmctsBot::new(seed)),
+    }
+}
+
+/// A bot that plays any legal move, each as likely as the others
+pub struct RandomBot {
+    rng: ChaCha20Rng,
+}
+
+impl RandomBot {
+    /// Create a bot that picks its moves with the given seed
+    pub fn new(seed: Seed) -> Self {
+        RandomBot {
+            rng: ChaCha20Rng::from_seed(seed),
+        }
+    }
+}
+
+impl Bot for RandomBot {
+    fn choose_move(&mut self, state: &State) -> Option<Move> {
+        state.legal_moves().choose(&mut self.rng).cloned()
+    }
+}
+
+/// How much a greedy bot values each thing a move can do
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Weights {
+    /// For each point a captured card is worth
+    pub points: i32,
+    /// For each card captured
+    pub cards: i32,
+    /// For each spade captured
+    pub spades: i32,
+    /// For clearing the floor
+    pub suipi: i32,
+    /// Against each point of a point card left on the floor
+    pub points_given: i32,
+    /// For each card added to a build or group the player owns
+    pub builds: i32,
+    /// Against each unseen card that could sweep a lone pile left on the floor
+    pub sweep_risk: i32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Personality::Balanced.weights()
+    }
+}
+
+impl Weights {
+    /// Score a move for the current player, or nothing if it is not legal
+    pub fn evaluate(&self, state: &State, m: &Move) -> Option<i32> {
+        let p = state.turn;
+        let mut after = state.clone();
+        after.apply(m.clone()).ok()?;
+        let (me, now) = (state.seat(p), after.seat(p));
+        let captured = &now.into_pair_cards()[me.into_pair_cards().len()..];
+        let mut score = captured
+            .iter()
+            .map(|c| {
+                let spade = (c.suit == Suit::Spades as u8) as i32;
+                c.points() as i32 * self.points + self.cards + spade * self.spades
+            })
+            .sum::<i32>();
+        if now.suipi_count > me.suipi_count {
+            score += self.suipi;
+        }
+        // Giving up a point card to the floor hands it to whoever captures it
+        let played = me
+            .hand
+            .iter()
+            .flat_map(|x| x.cards.first())
+            .find(|c| !now.hand.iter().any(|x| x.cards.first() == Some(c)));
+        if let Some(c) = played.filter(|c| !captured.contains(c)) {
+            score -= c.points() as i32 * self.points_given;
+        }
+        let stacked = |s: &State| {
+            s.floor
+                .iter()
+                .filter(|x| x.cards.len() > 1 && x.owner == p)
+                .map(|x| x.cards.len() as i32)
+                .sum::<i32>()
+        };
+        score += (stacked(&after) - stacked(state)).max(0) * self.builds;
+        // A lone pile can be swept by any card of its value the other player might hold
+        if after.floor_count() == 1 {
+            let unseen = after.unseen_values(p);
+            let value = after.floor.iter().find(|x| !x.is_empty()).unwrap().value;
+            score -= self.sweep_risk * unseen.get(value as usize).copied().unwrap_or(0) as i32;
+        }
+        Some(score)
+    }
+}
+
+/// A style of play for a greedy bot, given by a preset of weights
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Personality {
+    /// Goes for point cards first, then as many cards and spades as it can take
+    #[default]
+    Balanced = 0,
+    /// Stacks cards into builds and groups to capture later
+    AggressiveBuilder = 1,
+    /// Takes spades whenever it can, to win Most Spades
+    SpadeHoarder = 2,
+    /// Clears the floor whenever it can, even at the cost of points
+    SweepHunter = 3,
+}
+
+impl Personality {
+    /// Every personality, in order of id
+    pub const ALL: [Personality; 4] = [
+        Personality::Balanced,
+        Personality::AggressiveBuilder,
+        Personality::SpadeHoarder,
+        Personality::SweepHunter,
+    ];
+
+    /// Get a personality by its id
+    pub fn from_id(id: u8) -> Option<Personality> {
+        Personality::ALL.get(id as usize).copied()
+    }
+
+    /// Get a personality by its name, such as "spade-hoarder"
+    pub fn from_name(name: &str) -> Option<Personality> {
+        Personality::ALL
+            .into_iter()
+            .find(|x| x.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Get the name of the personality
+    pub fn name(self) -> &'static str {
+        match self {
+            Personality::Balanced => "balanced",
+            Personality::AggressiveBuilder => "aggressive-builder",
+            Personality::SpadeHoarder => "spade-hoarder",
+            Personality::SweepHunter => "sweep-hunter",
+        }
+    }
+
+    /// Get the weights a greedy bot plays this personality with
+    pub fn weights(self) -> Weights {
+        let balanced = Weights {
+            points: 12,
+            cards: 2,
+            spades: 1,
+            suipi: 15,
+            points_given: 8,
+            builds: 0,
+            sweep_risk: 3,
+        };
+        match self {
+            Personality::Balanced => balanced,
+            Personality::AggressiveBuilder => Weights {
+                builds: 6,
+                sweep_risk: 1,
+                ..balanced
+            },
+            Personality::SpadeHoarder => Weights {
+                spades: 10,
+                ..balanced
+            },
+            Personality::SweepHunter => Weights {
+                suipi: 60,
+                sweep_risk: 0,
+                ..balanced
+            },
+        }
+    }
+}
+
+/// A bot that plays the move that looks best right away, without searching ahead
+///
+/// How it weighs up a move is set by its weights, which come from a personality. The balanced
+/// personality goes for point cards first, then as many cards and spades as it can take, and
+/// avoids leaving a lone pile that the other player could sweep.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GreedyBot {
+    pub weights: Weights,
+}
+
+impl GreedyBot {
+    /// Create a greedy bot with the balanced personality
+    pub fn new() -> Self {
+        GreedyBot::default()
+    }
+
+    /// Create a greedy bot with the given personality
+    pub fn with_personality(p: Personality) -> Self {
+        GreedyBot {
+            weights: p.weights(),
+        }
+    }
+
+    /// Score a move for the current player with the balanced weights, or nothing if it is not
+    /// legal
+    pub fn evaluate(state: &State, m: &Move) -> Option<i32> {
+        Weights::default().evaluate(state, m)
+    }
+}
+
+impl Bot for GreedyBot {
+    fn choose_move(&mut self, state: &State) -> Option<Move> {
+        let mut best = None;
+        for m in state.legal_moves() {
+            let Some(score) = self.weights.evaluate(state, &m) else {
+                continue;
+            };
+            // Ties go to the first move found
+            if best.as_ref().is_none_or(|(x, _)| score > *x) {
+                best = Some((score, m));
+            }
+        }
+        best.map(|(_, m)| m)
+    }
+}
+
+/// A move chosen by a bot, with the reasons for playing it
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BotMove {
+    pub annotation: String,
+    /// Why the move was chosen, for players learning the game
+    pub rationale: String,
+}
+
+/// A bot that tries to win a set share of its games by easing off when it is ahead
+///
+/// It plays the engine's best move unless the score suggests it is more likely to win than its
+/// target, in which case it mixes in weaker moves, more often the further ahead it is.
+pub struct AdaptiveBot {
+    /// Share of games the bot aims to win, from 0 to 1
+    pub target: f64,
+    /// Number of moves to look ahead when ranking moves
+    pub depth: u8,
+    /// Number of deals of the hidden cards to rank moves over
+    pub samples: u32,
+    rng: ChaCha20Rng,
+}
+
+impl AdaptiveBot {
+    /// Create a bot aiming to win the given share of games
+    pub fn new(target: f64, seed: Seed) -> Self {
+        AdaptiveBot {
+            target: target.clamp(0.0, 1.0),
+            depth: 2,
+            samples: 4,
+            rng: ChaCha20Rng::from_seed(seed),
+        }
+    }
+
+    /// Get the chance of playing a weaker move than the best one in the given position
+    pub fn slack(&self, state: &State) -> f64 {
+        let chance = win_chance(state);
+        if chance <= self.target || self.target >= 1.0 {
+            0.0
+        } else {
+            (chance - self.target) / (1.0 - self.target)
+        }
+    }
+
+    /// Score every move the current player could make, best first, without looking at the
+    /// cards they can't see
+    fn rank(&mut self, state: &State) -> Vec<(String, i32)> {
+        rank_moves_sampled(state, self.depth, self.samples, &mut self.rng)
+    }
+
+    /// Choose the annotation of a move for the current player, if they have any
+    pub fn choose(&mut self, state: &State) -> Option<String> {
+        let ranked = self.rank(state);
+        let (best, _) = ranked.first()?;
+        if ranked.len() > 1 && self.rng.gen_bool(self.slack(state)) {
+            let i = self.rng.gen_range(1..ranked.len());
+            Some(ranked[i].0.clone())
+        } else {
+            Some(best.clone())
+        }
+    }
+
+    /// Choose a move for the current player, and explain it
+    pub fn choose_explained(&mut self, state: &State) -> Option<BotMove> {
+        let annotation = self.choose(state)?;
+        Some(BotMove {
+            rationale: explain(state, &annotation)?,
+            annotation,
+        })
+    }
+}
+
+impl Bot for AdaptiveBot {
+    fn choose_move(&mut self, state: &State) -> Option<Move> {
+        Annotation::new(self.choose(state)?).to_move().ok()
+    }
+}
+
+/// Describe what a move does for the current player, such as
+/// "builds 9 for the 9♣ in hand; secures Most Spades"
+///
+/// Returns nothing if the move is not legal.
+pub fn explain(state: &State, annotation: &str) -> Option<String> {
+    let p = state.turn;
+    let mut after = state.clone();
+    after
+        .apply(Annotation::new(annotation.to_string()).to_move().ok()?)
+        .ok()?;
+    let (me, now) = (state.seat(p), after.seat(p));
+    let held = |v: u8| {
+        now.hand
+            .iter()
+            .flat_map(|x| x.cards.first())
+            .find(|c| c.value == v)
+    };
+    let new = after
+        .floor
+        .iter()
+        .find(|x| !x.is_empty() && !state.floor.contains(x));
+
+    let mut reasons = vec![];
+    let captured = &now.into_pair_cards()[me.into_pair_cards().len()..];
+    if !captured.is_empty() {
+        let prized = captured
+            .iter()
+            .filter(|c| c.is_point_card())
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+        reasons.push(match (captured.len(), prized.is_empty()) {
+            (1, true) => format!("captures the {}", captured[0]),
+            (n, true) => format!("captures {} cards", n),
+            (n, false) => format!(
+                "captures {} cards, including the {}",
+                n,
+                prized.join(" and ")
+            ),
+        });
+    } else if let Some(x) = new.filter(|x| x.is_build()) {
+        reasons.push(match held(x.value) {
+            Some(c) => format!("builds {} for the {} in hand", x.value, c),
+            None => format!("builds {}", x.value),
+        });
+    } else if let Some(x) = new.filter(|x| x.is_group()) {
+        reasons.push(match held(x.value) {
+            Some(c) => format!("groups the {}s for the {} in hand", x.value, c),
+            None => format!("groups the {}s", x.value),
+        });
+    } else if let Some(c) = new.and_then(|x| x.cards.first()) {
+        reasons.push(format!("discards the {}", c));
+    }
+    if now.suipi_count > me.suipi_count {
+        reasons.push(String::from("clears the floor for a suipi"));
+    }
+    let (before, after) = (PlayerScore::from(me), PlayerScore::from(now));
+    if before.total_cards * 2 <= DECK_SIZE && after.total_cards * 2 > DECK_SIZE {
+        reasons.push(String::from("secures Most Cards"));
+    }
+    if before.total_spades * 2 <= SUIT_SIZE && after.total_spades * 2 > SUIT_SIZE {
+        reasons.push(String::from("secures Most Spades"));
+    }
+    Some(reasons.join("; "))
+}
+
+/// Estimate the chance the current player wins the game from the live score
+fn win_chance(state: &State) -> f64 {
+    let score = Score::from(state);
+    let lead = score.total(state.turn) as f64 - score.total(state.turn.other()) as f64;
+    // A lead of a quarter of the points on offer is worth about three to one
+    1.0 / (1.0 + (-lead * 4.0 / GAME_POINTS as f64).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Card;
+    use crate::game::Game;
+    use crate::pile::Pile;
+    use crate::state::PlayerId;
+
+    #[test]
+    fn test_explain() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_eq!(
+            explain(&g.state, "*C&3").unwrap(),
+            "captures 2 cards, including the 2♠"
+        );
+        assert_eq!(explain(&g.state, "!1").unwrap(), "discards the A♥");
+        assert!(explain(&g.state, "*A&1").is_none());
+
+        let mut s = g.state.clone();
+        s.opponent.pairs = (0..6).map(|x| Pile::card(x + 3, 3)).collect();
+        assert_eq!(
+            explain(&s, "*C&3").unwrap(),
+            "captures 2 cards, including the 2♠; secures Most Spades"
+        );
+
+        // Build the four of clubs and ace of hearts up to five, with a five in hand
+        s.opponent.hand[1] = Pile::card(5, 2);
+        assert_eq!(explain(&s, "A+1").unwrap(), "builds 5 for the 5♥ in hand");
+
+        let mut bot = AdaptiveBot::new(0.5, [1; 32]);
+        let choice = bot.choose_explained(&g.state).unwrap();
+        assert_eq!(
+            Some(choice.annotation),
+            AdaptiveBot::new(0.5, [1; 32])
+                .rank(&g.state)
+                .first()
+                .map(|x| x.0.clone())
+        );
+        assert!(!choice.rationale.is_empty());
+    }
+
+    #[test]
+    fn test_random_bot() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let legal = g.state.legal_moves();
+        let mut bot = RandomBot::new([1; 32]);
+        let picks = (0..50)
+            .map(|_| bot.choose_move(&g.state).unwrap())
+            .collect::<Vec<Move>>();
+        assert!(picks.iter().all(|x| legal.contains(x)));
+        assert!(picks.iter().any(|x| *x != picks[0]));
+
+        // The same seed picks the same moves
+        let mut again = RandomBot::new([1; 32]);
+        assert_eq!(again.choose_move(&g.state).as_ref(), picks.first());
+
+        // Bots can be swapped for one another
+        let mut bots: Vec<Box<dyn Bot>> = vec![
+            Box::new(RandomBot::new([2; 32])),
+            Box::new(AdaptiveBot::new(0.5, [2; 32])),
+        ];
+        for bot in bots.iter_mut() {
+            let mut s = g.state.clone();
+            s.apply(bot.choose_move(&g.state).unwrap()).unwrap();
+        }
+        assert_eq!(bot.choose_move(&State::default()), None);
+    }
+
+    #[test]
+    fn test_for_difficulty() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let legal = g.state.legal_moves();
+        for d in 0..=MAX_DIFFICULTY + 1 {
+            let m = for_difficulty(d, [1; 32]).choose_move(&g.state).unwrap();
+            assert!(legal.contains(&m));
+        }
+        assert_eq!(
+            for_difficulty(1, [1; 32]).choose_move(&State::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_greedy_bot() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let mut bot = GreedyBot::new();
+        let m = bot.choose_move(&g.state).unwrap();
+        let mut s = g.state.clone();
+        s.apply(m).unwrap();
+        // Takes the two of spades
+        assert!(s.opponent.into_pair_cards().contains(&Card::from(40)));
+
+        // With nothing to capture, keep the ace and discard the five
+        let mut s = g.state.clone();
+        s.floor = vec![Pile::card(13, 1), Pile::empty(), Pile::empty()];
+        s.opponent.hand = vec![Pile::card(1, 2), Pile::card(5, 0)];
+        let discard = Annotation::new(String::from("!2")).to_move().unwrap();
+        assert_eq!(bot.choose_move(&s), Some(discard.clone()));
+
+        // Leaving a lone pile to be swept costs points
+        let mut s = g.state.clone();
+        s.floor = vec![Pile::card(4, 0), Pile::card(9, 3), Pile::empty()];
+        s.opponent.hand = vec![Pile::card(9, 2)];
+        let take = Annotation::new(String::from("*B&1")).to_move().unwrap();
+        let lone = GreedyBot::evaluate(&s, &take).unwrap();
+        s.floor[2] = Pile::card(13, 1);
+        assert!(GreedyBot::evaluate(&s, &take).unwrap() > lone);
+        assert_eq!(GreedyBot::evaluate(&s, &discard), None);
+    }
+
+    #[test]
+    fn test_personalities() {
+        for (i, p) in Personality::ALL.into_iter().enumerate() {
+            assert_eq!(Personality::from_id(i as u8), Some(p));
+            assert_eq!(Personality::from_name(p.name()), Some(p));
+        }
+        assert_eq!(Personality::from_id(4), None);
+        assert_eq!(
+            Personality::from_name(" Sweep-Hunter"),
+            Some(Personality::SweepHunter)
+        );
+        assert_eq!(GreedyBot::new().weights, Weights::default());
+
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let annotation = |s: &State, p| {
+            GreedyBot::with_personality(p)
+                .choose_move(s)
+                .unwrap()
+                .to_annotation()
+        };
+
+        // Pair the aces, or take the two spades on the floor with the eight of spades
+        let mut s = g.state.clone();
+        s.floor = vec![Pile::card(1, 2), Pile::card(5, 3), Pile::card(3, 3)];
+        s.opponent.hand = vec![Pile::card(1, 0), Pile::card(8, 3)];
+        assert_eq!(annotation(&s, Personality::Balanced), "*A&1");
+        assert_eq!(annotation(&s, Personality::SpadeHoarder), "*B+C&2");
+
+        // Taking the four leaves the nine to be swept, so only the sweep hunter risks it, while
+        // the builder stacks the ace on the four for its five
+        s.floor = vec![Pile::card(4, 1), Pile::card(9, 0), Pile::empty()];
+        s.opponent.hand = vec![Pile::card(4, 2), Pile::card(1, 0), Pile::card(5, 3)];
+        assert_eq!(annotation(&s, Personality::Balanced), "!3");
+        assert_eq!(annotation(&s, Personality::SweepHunter), "*A&1");
+        assert_eq!(annotation(&s, Personality::AggressiveBuilder), "A+2");
+    }
+
+    #[test]
+    fn test_greedy_beats_random() {
+        let mut wins = 0;
+        for i in 0..10 {
+            let mut g = Game::builder()
+                .seed([i; 32])
+                .rules(crate::rules::Rules {
+                    games: 1,
+                    ..crate::rules::Rules::default()
+                })
+                .build()
+                .unwrap();
+            let mut random = RandomBot::new([i; 32]);
+            while !g.is_over() {
+                let m = match g.state.turn {
+                    PlayerId::Opponent => GreedyBot::new().choose_move(&g.state),
+                    PlayerId::Dealer => random.choose_move(&g.state),
+                };
+                if let Some(m) = m {
+                    g.apply(m).unwrap();
+                }
+                g.tick();
+            }
+            if g.result.unwrap().winner == Some(PlayerId::Opponent) {
+                wins += 1;
+            }
+        }
+        assert!(wins >= 8, "greedy won {} of 10", wins);
+    }
+
+    #[test]
+    fn test_adaptive_bot() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let best = AdaptiveBot::new(0.5, [1; 32]).rank(&g.state)[0].0.clone();
+        // An even game never needs easing off
+        let mut bot = AdaptiveBot::new(0.5, [1; 32]);
+        assert_eq!(bot.slack(&g.state), 0.0);
+        assert_eq!(bot.choose(&g.state), Some(best.clone()));
+
+        // The other player's hand and the deck order make no difference
+        let redacted = g.state.redacted_for(g.state.turn);
+        assert_eq!(
+            AdaptiveBot::new(0.5, [1; 32]).rank(&redacted),
+            AdaptiveBot::new(0.5, [1; 32]).rank(&g.state)
+        );
+        let mut shuffled = g.state.clone();
+        shuffled.deck.make_contiguous().reverse();
+        let other = shuffled.turn.other();
+        let hand = shuffled.seat(other).hand.clone();
+        shuffled.seat_mut(other).hand = shuffled
+            .deck
+            .drain(..hand.len())
+            .map(Pile::single)
+            .collect();
+        shuffled
+            .deck
+            .extend(hand.iter().flat_map(|x| x.cards.iter().copied()));
+        assert_eq!(
+            AdaptiveBot::new(0.5, [1; 32]).rank(&shuffled),
+            AdaptiveBot::new(0.5, [1; 32]).rank(&g.state)
+        );
+
+        // Having captured every card makes a win nearly certain
+        let mut ahead = g.state.clone();
+        ahead.opponent.pairs = (0..52).map(|x| Pile::single(Card::from(x))).collect();
+        assert!(bot.slack(&ahead) > 0.9);
+        assert!((0..20).any(|_| bot.choose(&ahead) != Some(best.clone())));
+        assert_eq!(AdaptiveBot::new(1.0, [1; 32]).slack(&ahead), 0.0);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::consts::INVALID_CARD_ID;
+use crate::locale::Locale;
 use std::fmt;
 
 const SUITS: [&str; 4] = ["♣", "♦", "♥", "♠"];
@@ -33,6 +35,7 @@ pub enum Suit {
 
 /// A playing card
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Card {
     pub value: u8,
     pub suit: u8,
@@ -53,12 +56,30 @@ impl Card {
     pub fn invalid() -> Self {
         Card::create(Value::Invalid, Suit::Clubs)
     }
+
+    /// Get the points the card scores on its own: one for an ace or the 2♠, two for the 10♦
+    pub fn points(&self) -> u8 {
+        if *self == Card::create(Value::Ten, Suit::Diamonds) {
+            2
+        } else if self.is_point_card() {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Check if the card scores points on its own: an ace, the 10♦, or the 2♠
+    pub fn is_point_card(&self) -> bool {
+        self.value == Value::Ace as u8
+            || *self == Card::create(Value::Ten, Suit::Diamonds)
+            || *self == Card::create(Value::Two, Suit::Spades)
+    }
 }
 
 impl From<u8> for Card {
     fn from(id: u8) -> Self {
         match id {
-            52.. => Card::invalid(),
+            INVALID_CARD_ID.. => Card::invalid(),
             _ => Card::new((id % 13) + 1, id / 13),
         }
     }
@@ -67,20 +88,26 @@ impl From<u8> for Card {
 impl From<Card> for u8 {
     fn from(c: Card) -> Self {
         if c.value == Value::Invalid as u8 {
-            52
+            INVALID_CARD_ID
         } else {
             (c.suit * 13) + c.value - 1
         }
     }
 }
 
+/// Get the display name of a card value
+pub fn value_name(value: u8) -> &'static str {
+    VALUES.get(value as usize).unwrap_or(&VALUES[0])
+}
+
 impl fmt::Display for Card {
+    /// Show the card as a symbol like "Q♥", or as its English name with `{:#}`
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}{}",
-            VALUES[self.value as usize], SUITS[self.suit as usize]
-        )
+        if f.alternate() {
+            write!(f, "{}", Locale::English.card_name(*self))
+        } else {
+            write!(f, "{}{}", value_name(self.value), SUITS[self.suit as usize])
+        }
     }
 }
 
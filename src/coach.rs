@@ -0,0 +1,192 @@
+use crate::action::{Address, Annotation, Move};
+use crate::bot::{Bot, GreedyBot};
+use crate::card::{Card, Suit};
+use crate::pile::Mark;
+use crate::state::State;
+use std::fmt;
+
+/// Advice about a move that was just played
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Hint {
+    /// The move captured nothing, but this move would have
+    MissedCapture(String),
+    /// This move captures at least as many cards, spades, and point cards as the move did, and
+    /// more of at least one
+    BetterMove(String),
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hint::MissedCapture(x) => write!(f, "You could have captured with {}", x),
+            Hint::BetterMove(x) => write!(f, "{} would have captured more", x),
+        }
+    }
+}
+
+/// Why a move is recommended, as a short code clients can match on
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HintReason {
+    /// The move clears the floor
+    Suipi,
+    /// The move captures a card that scores points on its own
+    PointCard,
+    /// The move captures cards
+    Capture,
+    /// The move makes a build to capture later
+    Build,
+    /// The move groups cards to capture later
+    Group,
+    /// Nothing better could be done than getting rid of a card
+    Discard,
+}
+
+impl fmt::Display for HintReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                HintReason::Suipi => "suipi",
+                HintReason::PointCard => "point-card",
+                HintReason::Capture => "capture",
+                HintReason::Build => "build",
+                HintReason::Group => "group",
+                HintReason::Discard => "discard",
+            }
+        )
+    }
+}
+
+/// A move recommended to the current player, with the reason for it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MoveHint {
+    pub annotation: String,
+    pub reason: HintReason,
+}
+
+impl fmt::Display for MoveHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.annotation, self.reason)
+    }
+}
+
+/// Recommend a move for the current player, using only what they can see
+///
+/// The move is the one the balanced greedy bot would play. Returns `None` if the player has no
+/// move.
+pub fn hint(state: &State) -> Option<MoveHint> {
+    let view = state.redacted_for(state.turn);
+    let m = GreedyBot::new().choose_move(&view)?;
+    let reason = hint_reason(&view, &m)?;
+    Some(MoveHint {
+        annotation: m.to_annotation(),
+        reason,
+    })
+}
+
+/// Work out why a move is worth playing from what it does to the state
+fn hint_reason(state: &State, m: &Move) -> Option<HintReason> {
+    let card = m.actions.iter().find_map(|a| match a.address {
+        Address::Hand(i) => state.player().hand.get(i as usize)?.cards.first().copied(),
+        Address::Floor(_) => None,
+    })?;
+    let before = state.player().into_pair_cards().len();
+    let mut after = state.clone();
+    after.apply(m.clone()).ok()?;
+    let taken = after.player().into_pair_cards().split_off(before);
+    let mark = after
+        .floor
+        .iter()
+        .find(|x| x.cards.contains(&card))
+        .map(|x| x.mark);
+    Some(match mark {
+        _ if after.player().suipi_count > state.player().suipi_count => HintReason::Suipi,
+        _ if taken.iter().any(Card::is_point_card) => HintReason::PointCard,
+        _ if !taken.is_empty() => HintReason::Capture,
+        Some(Mark::Build) => HintReason::Build,
+        Some(Mark::Group) => HintReason::Group,
+        _ => HintReason::Discard,
+    })
+}
+
+/// Count the cards, spades, and point cards the current player would capture with a move
+fn captures(state: &State, annotation: &str) -> Option<[usize; 3]> {
+    let before = state.player().into_pair_cards().len();
+    let mut after = state.clone();
+    after
+        .apply(Annotation::new(annotation.to_string()).to_move().ok()?)
+        .ok()?;
+    let cards = after.player().into_pair_cards().split_off(before);
+    let count = |f: fn(&Card) -> bool| cards.iter().filter(|&c| f(c)).count();
+    Some([
+        cards.len(),
+        count(|c| c.suit == Suit::Spades as u8),
+        count(Card::is_point_card),
+    ])
+}
+
+/// Compare a move with the other moves the current player could have made instead
+///
+/// This does not search ahead, so it only points out captures that were missed or beaten
+/// outright, on cards, spades, and point cards. Returns no hints if the move is not legal.
+pub fn review(state: &State, annotation: &str) -> Vec<Hint> {
+    let Some(taken) = captures(state, annotation) else {
+        return vec![];
+    };
+    let best = state
+        .legal_moves()
+        .into_iter()
+        .map(|m| m.to_annotation())
+        .filter_map(|x| captures(state, &x).map(|c| (x, c)))
+        .filter(|(_, c)| c != &taken && c.iter().zip(taken).all(|(x, y)| *x >= y))
+        .max_by_key(|(_, c)| *c);
+    match best {
+        Some((x, _)) if taken[0] == 0 => vec![Hint::MissedCapture(x)],
+        Some((x, _)) => vec![Hint::BetterMove(x)],
+        None => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::rng::Seed;
+
+    #[test]
+    fn test_review() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_eq!(
+            review(&g.state, "!1"),
+            vec![Hint::MissedCapture(String::from("*C&3"))]
+        );
+        assert!(review(&g.state, "*C&3").is_empty());
+        assert!(review(&g.state, "*A&1").is_empty());
+        // Taking the eights gets as many cards and spades, but misses the two of spades
+        assert_eq!(
+            review(&g.state, "*D&6"),
+            vec![Hint::BetterMove(String::from("*C&3"))]
+        );
+        assert_eq!(
+            Hint::BetterMove(String::from("*C&3")).to_string(),
+            "*C&3 would have captured more"
+        );
+    }
+
+    #[test]
+    fn test_hint() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let x = hint(&g.state).unwrap();
+        assert_eq!(x.annotation, "*C&3");
+        assert_eq!(x.reason, HintReason::PointCard);
+        assert_eq!(x.to_string(), "*C&3 point-card");
+
+        let reason = |x: &str| hint_reason(&g.state, &Annotation::new(x.into()).to_move().unwrap());
+        assert_eq!(reason("*D&6"), Some(HintReason::Capture));
+        assert_eq!(reason("!2"), Some(HintReason::Discard));
+        // Building the two of spades up to three, to take with the three of spades
+        assert_eq!(reason("C+1"), Some(HintReason::Build));
+        assert_eq!(reason("C&3"), None);
+    }
+}
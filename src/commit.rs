@@ -0,0 +1,102 @@
+use crate::card::Card;
+use sha2::{Digest as _, Sha256};
+
+/// A SHA-256 digest
+pub type Digest = [u8; 32];
+
+/// Hash bytes with SHA-256
+pub fn sha256(data: &[u8]) -> Digest {
+    Sha256::digest(data).into()
+}
+
+/// Format bytes as lowercase hex
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// A hash published at the start of a game that binds the server to the order of its deck
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DealCommitment {
+    /// Number of the game in the match, counting from 0
+    pub game: u8,
+    pub hash: Digest,
+}
+
+/// The deck order and salt behind a commitment, published once its game is over
+///
+/// Clients check a reveal against the commitment they were sent, then deal the order
+/// themselves to check every card they saw came from it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DealReveal {
+    pub game: u8,
+    /// Random bytes hashed with the deck, so the order cannot be guessed from the hash
+    pub salt: [u8; 32],
+    /// The deck as it was shuffled, dealt from the front
+    pub order: Vec<Card>,
+}
+
+impl DealReveal {
+    /// Get the commitment to this deal
+    pub fn commitment(&self) -> DealCommitment {
+        let mut data = b"suipi-deal".to_vec();
+        data.push(self.game);
+        data.extend_from_slice(&self.salt);
+        data.extend(self.order.iter().map(|&c| u8::from(c)));
+        DealCommitment {
+            game: self.game,
+            hash: sha256(&data),
+        }
+    }
+
+    /// Check that this is the deal behind the given commitment
+    pub fn verify(&self, c: &DealCommitment) -> bool {
+        self.commitment() == *c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            to_hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            to_hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Long enough to need a second block for the padding
+        assert_eq!(
+            to_hex(&sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn test_reveal() {
+        let reveal = DealReveal {
+            game: 0,
+            salt: [7; 32],
+            order: (0..52).map(Card::from).collect(),
+        };
+        let c = reveal.commitment();
+        assert!(reveal.verify(&c));
+
+        // Changing the order, salt, or game breaks the commitment
+        let mut x = reveal.clone();
+        x.order.swap(0, 1);
+        assert!(!x.verify(&c));
+        let mut x = reveal.clone();
+        x.salt[0] = 8;
+        assert!(!x.verify(&c));
+        let x = DealReveal { game: 1, ..reveal };
+        assert!(!x.verify(&DealCommitment { game: 1, ..c }));
+    }
+}
@@ -0,0 +1,108 @@
+/// Number of cards in a full deck
+pub const DECK_SIZE: usize = 52;
+
+/// Number of cards of each suit
+pub const SUIT_SIZE: usize = 13;
+
+/// Number of entries in a table indexed by card value, where the first is unused
+pub const VALUE_SLOTS: usize = SUIT_SIZE + 1;
+
+/// Number of cards dealt to each player per round
+pub const HAND_SIZE: usize = 8;
+
+/// Number of cards in both player's hands
+pub const HANDS_SIZE: usize = 16;
+
+/// Number of piles that fit on the floor by default
+pub const FLOOR_SIZE: usize = 13;
+
+/// Most piles that can fit on the floor, which is limited by the floor addresses `A` to `V`
+pub const MAX_FLOOR_SIZE: usize = 22;
+
+/// Number of move templates for each hand card in the action space: a discard, then a pair, build,
+/// and group onto each floor pile up to the largest floor
+pub const ACTIONS_PER_CARD: usize = 1 + 3 * MAX_FLOOR_SIZE;
+
+/// Number of move templates in the action space used by policies
+pub const ACTION_SPACE_SIZE: usize = HAND_SIZE * ACTIONS_PER_CARD;
+
+/// Number of values in an observation of a game state, which is the stride between observations
+/// in a batch: where each card is, then the value, kind, and owner of each floor pile up to the
+/// largest floor, then the hand sizes, deck size, and last scorer
+pub const OBSERVATION_SIZE: usize = 4 * DECK_SIZE + 3 * MAX_FLOOR_SIZE + 4;
+
+/// Number of cards dealt to the floor at the start of a game
+pub const FLOOR_DEAL_SIZE: usize = 4;
+
+/// Max number of cards in an API level pile
+pub const MAX_PILE_CARDS: usize = 20;
+
+/// Number of bytes per floor pile in the state mirror: the card ids, then the value, build flag,
+/// owner, and id as two little endian bytes
+pub const MIRROR_PILE_SIZE: usize = MAX_PILE_CARDS + 5;
+
+/// Offset of the floor piles in the state mirror, after the version and status bytes
+pub const MIRROR_FLOOR_OFFSET: usize = 16;
+
+/// Offset of the hands in the state mirror, the opponent's first
+pub const MIRROR_HANDS_OFFSET: usize = MIRROR_FLOOR_OFFSET + MAX_FLOOR_SIZE * MIRROR_PILE_SIZE;
+
+/// Number of bytes in the state mirror
+pub const MIRROR_SIZE: usize = MIRROR_HANDS_OFFSET + HANDS_SIZE;
+
+/// Points awarded for cards and categories in a game, excluding the suipi bonus
+pub const GAME_POINTS: u8 = 11;
+
+/// Most turns a player can take in a game, which bounds their suipi bonus
+pub const GAME_TURNS: usize = (DECK_SIZE - FLOOR_DEAL_SIZE) / 2;
+
+/// Number of times hands are dealt in a game
+pub const GAME_DEALS: usize = (DECK_SIZE - FLOOR_DEAL_SIZE) / HANDS_SIZE;
+
+/// Longest annotation the engine will parse
+pub const MAX_ANNOTATION_LENGTH: usize = 64;
+
+/// Most actions a move can have, which uses every floor pile and a hand card once
+pub const MAX_MOVE_ACTIONS: usize = MAX_FLOOR_SIZE + 1;
+
+/// Most moves that can stand in a game at once, not counting ones that were taken back
+///
+/// Every move plays a card from a hand, so a game has at most `2 * GAME_TURNS` moves. This only
+/// guards against a state that has gone wrong.
+pub const MAX_GAME_MOVES: usize = 20 * GAME_TURNS;
+
+/// Most moves each player may take back in a game, whatever the undo policy
+///
+/// Undoing and replaying moves grows the match's journal, so this stops a client growing it
+/// without end.
+pub const MAX_GAME_UNDOS: u8 = 200;
+
+/// Number of deals of the hidden cards a played move is graded over
+pub const GRADE_SAMPLES: u32 = 4;
+
+/// Hardest bot difficulty, higher difficulties are treated as this one
+pub const MAX_DIFFICULTY: u8 = 3;
+
+/// Largest value a build may have
+pub const MAX_BUILD_VALUE: u8 = 10;
+
+/// Card id used to represent the absence of a card
+pub const INVALID_CARD_ID: u8 = 52;
+
+/// First action byte that addresses a hand pile (annotation `1`)
+pub const HAND_ADDRESS_MIN: u8 = 1;
+
+/// Last action byte that addresses a hand pile (annotation `8`)
+pub const HAND_ADDRESS_MAX: u8 = 8;
+
+/// Action byte separating additional floor builds from the rest of a move (annotation `;`)
+pub const BUILD_SEPARATOR: u8 = 9;
+
+/// First action byte that addresses a floor pile (annotation `A`)
+pub const FLOOR_ADDRESS_MIN: u8 = 10;
+
+/// Last action byte that addresses a floor pile (annotation `V`)
+pub const FLOOR_ADDRESS_MAX: u8 = 31;
+
+/// Action byte flag marking an active operation
+pub const ACTIVE_FLAG: u8 = 0b00100000;
@@ -0,0 +1,437 @@
+use crate::action::{MoveError, ParsingError};
+use crate::game::GameError;
+use crate::pile::PileError;
+use crate::state::{AuditError, StateError};
+use std::fmt;
+
+/// Any error produced by the Suipi engine
+#[derive(Debug, Eq, PartialEq)]
+pub enum Error {
+    Parsing(ParsingError),
+    Move(MoveError),
+    Pile(PileError),
+    State(StateError),
+    Audit(AuditError),
+    Game(GameError),
+}
+
+impl From<ParsingError> for Error {
+    fn from(value: ParsingError) -> Error {
+        Error::Parsing(value)
+    }
+}
+
+impl From<MoveError> for Error {
+    fn from(value: MoveError) -> Error {
+        Error::Move(value)
+    }
+}
+
+impl From<PileError> for Error {
+    fn from(value: PileError) -> Error {
+        Error::Pile(value)
+    }
+}
+
+impl From<StateError> for Error {
+    fn from(value: StateError) -> Error {
+        Error::State(value)
+    }
+}
+
+impl From<AuditError> for Error {
+    fn from(value: AuditError) -> Error {
+        Error::Audit(value)
+    }
+}
+
+impl From<GameError> for Error {
+    fn from(value: GameError) -> Error {
+        Error::Game(value)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parsing(e) => e.fmt(f),
+            Error::Move(e) => e.fmt(f),
+            Error::Pile(e) => e.fmt(f),
+            Error::State(e) => e.fmt(f),
+            Error::Audit(e) => e.fmt(f),
+            Error::Game(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    // The wrapped error is displayed as this error, so its source is this error's source
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parsing(e) => e.source(),
+            Error::Move(e) => e.source(),
+            Error::Pile(e) => e.source(),
+            Error::State(e) => e.source(),
+            Error::Audit(e) => e.source(),
+            Error::Game(e) => e.source(),
+        }
+    }
+}
+
+/// A stable number for every error the engine can produce, for clients to branch on
+///
+/// Each kind of error has its own range: parsing errors from 100, move errors from 200, pile
+/// errors from 300, state errors from 400, audit errors from 500, and game errors from 600. New
+/// codes are only ever added, so existing numbers never change.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ErrorCode {
+    Success = 0,
+    /// The string passed to the engine was not valid UTF-8
+    InvalidCString = 1,
+    ParsingInvalidByte = 100,
+    ParsingInvalidAddress = 101,
+    ParsingInvalidOperationCharacter = 102,
+    ParsingInvalidAddressCharacter = 103,
+    ParsingBlankAnnotation = 104,
+    ParsingNoShortcutMatch = 105,
+    ParsingAmbiguousShortcut = 106,
+    ParsingAnnotationTooLong = 107,
+    ParsingTooManyActions = 108,
+    ParsingNonCanonicalAnnotation = 109,
+    MoveDuplicateAddress = 200,
+    MoveInvalidHandAddressCount = 201,
+    MoveInvalidHandAddressPosition = 202,
+    MoveInvalidBuildSegment = 203,
+    MoveCaptureWithoutPair = 204,
+    PileInvalidBuildArg = 300,
+    PileInvalidGroupArg = 301,
+    PileInvalidPairArg = 302,
+    PileBuildEqualValues = 303,
+    PileBuildHigherThanTen = 304,
+    PileGroupDifferentValues = 305,
+    PileGroupTwoSingles = 306,
+    PilePairDifferentValues = 307,
+    PileValueMismatch = 308,
+    PileGroupFaceCards = 309,
+    PilePairFaceGroup = 310,
+    StateInvalidAddress = 400,
+    StateInvalidDiscard = 401,
+    StateInvalidInput = 402,
+    StateFloorIsFull = 403,
+    StatePileIsNotEmpty = 404,
+    StateOwnTooManyPiles = 405,
+    StateUnpairablePileValue = 406,
+    StateDuplicateFloorValue = 407,
+    StateMultipleBuildsNotAllowed = 408,
+    StateNotYourTurn = 409,
+    StateUncapturedPile = 410,
+    StateMoveLimitReached = 411,
+    StateInternal = 412,
+    AuditDuplicateCard = 500,
+    AuditMissingCard = 501,
+    AuditInvalidCard = 502,
+    AuditInvalidFloorSize = 503,
+    AuditInvalidFloorPile = 504,
+    AuditInvalidHandPile = 505,
+    AuditDuplicateFloorValue = 506,
+    AuditOwnTooManyPiles = 507,
+    AuditNoLegalMove = 508,
+    GameUnsupportedPlayerCount = 600,
+    GameUnsupportedFloorSize = 601,
+    GameNothingToUndo = 602,
+    GameUndoDisabled = 603,
+    GameUndoLimitReached = 604,
+    GameTurnIsOver = 605,
+    GameOpponentMoved = 606,
+    GamePileCaptured = 607,
+    GameStaleTurnToken = 608,
+    GameMatchIsOver = 609,
+    GameNothingToRedo = 610,
+    GameFloorCanStall = 611,
+}
+
+impl ErrorCode {
+    /// Every error code, in order
+    pub const ALL: [ErrorCode; 62] = [
+        ErrorCode::Success,
+        ErrorCode::InvalidCString,
+        ErrorCode::ParsingInvalidByte,
+        ErrorCode::ParsingInvalidAddress,
+        ErrorCode::ParsingInvalidOperationCharacter,
+        ErrorCode::ParsingInvalidAddressCharacter,
+        ErrorCode::ParsingBlankAnnotation,
+        ErrorCode::ParsingNoShortcutMatch,
+        ErrorCode::ParsingAmbiguousShortcut,
+        ErrorCode::ParsingAnnotationTooLong,
+        ErrorCode::ParsingTooManyActions,
+        ErrorCode::ParsingNonCanonicalAnnotation,
+        ErrorCode::MoveDuplicateAddress,
+        ErrorCode::MoveInvalidHandAddressCount,
+        ErrorCode::MoveInvalidHandAddressPosition,
+        ErrorCode::MoveInvalidBuildSegment,
+        ErrorCode::MoveCaptureWithoutPair,
+        ErrorCode::PileInvalidBuildArg,
+        ErrorCode::PileInvalidGroupArg,
+        ErrorCode::PileInvalidPairArg,
+        ErrorCode::PileBuildEqualValues,
+        ErrorCode::PileBuildHigherThanTen,
+        ErrorCode::PileGroupDifferentValues,
+        ErrorCode::PileGroupTwoSingles,
+        ErrorCode::PilePairDifferentValues,
+        ErrorCode::PileValueMismatch,
+        ErrorCode::PileGroupFaceCards,
+        ErrorCode::PilePairFaceGroup,
+        ErrorCode::StateInvalidAddress,
+        ErrorCode::StateInvalidDiscard,
+        ErrorCode::StateInvalidInput,
+        ErrorCode::StateFloorIsFull,
+        ErrorCode::StatePileIsNotEmpty,
+        ErrorCode::StateOwnTooManyPiles,
+        ErrorCode::StateUnpairablePileValue,
+        ErrorCode::StateDuplicateFloorValue,
+        ErrorCode::StateMultipleBuildsNotAllowed,
+        ErrorCode::StateNotYourTurn,
+        ErrorCode::StateUncapturedPile,
+        ErrorCode::StateMoveLimitReached,
+        ErrorCode::StateInternal,
+        ErrorCode::AuditDuplicateCard,
+        ErrorCode::AuditMissingCard,
+        ErrorCode::AuditInvalidCard,
+        ErrorCode::AuditInvalidFloorSize,
+        ErrorCode::AuditInvalidFloorPile,
+        ErrorCode::AuditInvalidHandPile,
+        ErrorCode::AuditDuplicateFloorValue,
+        ErrorCode::AuditOwnTooManyPiles,
+        ErrorCode::AuditNoLegalMove,
+        ErrorCode::GameUnsupportedPlayerCount,
+        ErrorCode::GameUnsupportedFloorSize,
+        ErrorCode::GameNothingToUndo,
+        ErrorCode::GameUndoDisabled,
+        ErrorCode::GameUndoLimitReached,
+        ErrorCode::GameTurnIsOver,
+        ErrorCode::GameOpponentMoved,
+        ErrorCode::GamePileCaptured,
+        ErrorCode::GameStaleTurnToken,
+        ErrorCode::GameMatchIsOver,
+        ErrorCode::GameNothingToRedo,
+        ErrorCode::GameFloorCanStall,
+    ];
+}
+
+impl TryFrom<u16> for ErrorCode {
+    type Error = u16;
+
+    fn try_from(value: u16) -> Result<ErrorCode, u16> {
+        ErrorCode::ALL
+            .into_iter()
+            .find(|&x| x as u16 == value)
+            .ok_or(value)
+    }
+}
+
+impl From<&ParsingError> for ErrorCode {
+    fn from(value: &ParsingError) -> ErrorCode {
+        match value {
+            ParsingError::InvalidByte => ErrorCode::ParsingInvalidByte,
+            ParsingError::InvalidAddress => ErrorCode::ParsingInvalidAddress,
+            ParsingError::InvalidOperationCharacter => ErrorCode::ParsingInvalidOperationCharacter,
+            ParsingError::InvalidAddressCharacter => ErrorCode::ParsingInvalidAddressCharacter,
+            ParsingError::BlankAnnotation => ErrorCode::ParsingBlankAnnotation,
+            ParsingError::NoShortcutMatch => ErrorCode::ParsingNoShortcutMatch,
+            ParsingError::AmbiguousShortcut => ErrorCode::ParsingAmbiguousShortcut,
+            ParsingError::AnnotationTooLong => ErrorCode::ParsingAnnotationTooLong,
+            ParsingError::TooManyActions => ErrorCode::ParsingTooManyActions,
+            ParsingError::NonCanonicalAnnotation => ErrorCode::ParsingNonCanonicalAnnotation,
+        }
+    }
+}
+
+impl From<&MoveError> for ErrorCode {
+    fn from(value: &MoveError) -> ErrorCode {
+        match value {
+            MoveError::DuplicateAddress => ErrorCode::MoveDuplicateAddress,
+            MoveError::InvalidHandAddressCount => ErrorCode::MoveInvalidHandAddressCount,
+            MoveError::InvalidHandAddressPosition => ErrorCode::MoveInvalidHandAddressPosition,
+            MoveError::InvalidBuildSegment => ErrorCode::MoveInvalidBuildSegment,
+            MoveError::CaptureWithoutPair => ErrorCode::MoveCaptureWithoutPair,
+        }
+    }
+}
+
+impl From<&PileError> for ErrorCode {
+    fn from(value: &PileError) -> ErrorCode {
+        match value {
+            PileError::InvalidBuildArg => ErrorCode::PileInvalidBuildArg,
+            PileError::InvalidGroupArg => ErrorCode::PileInvalidGroupArg,
+            PileError::InvalidPairArg => ErrorCode::PileInvalidPairArg,
+            PileError::BuildEqualValues => ErrorCode::PileBuildEqualValues,
+            PileError::BuildHigherThanTen => ErrorCode::PileBuildHigherThanTen,
+            PileError::GroupDifferentValues => ErrorCode::PileGroupDifferentValues,
+            PileError::GroupTwoSingles => ErrorCode::PileGroupTwoSingles,
+            PileError::PairDifferentValues => ErrorCode::PilePairDifferentValues,
+            PileError::ValueMismatch => ErrorCode::PileValueMismatch,
+            PileError::GroupFaceCards => ErrorCode::PileGroupFaceCards,
+            PileError::PairFaceGroup => ErrorCode::PilePairFaceGroup,
+        }
+    }
+}
+
+impl From<&StateError> for ErrorCode {
+    fn from(value: &StateError) -> ErrorCode {
+        match value {
+            StateError::InvalidAddress => ErrorCode::StateInvalidAddress,
+            StateError::InvalidDiscard => ErrorCode::StateInvalidDiscard,
+            StateError::InvalidInput => ErrorCode::StateInvalidInput,
+            // Wrapped errors keep the code of the error they wrap
+            StateError::InvalidMove(e) => e.into(),
+            StateError::InvalidPile(e) => e.into(),
+            StateError::FloorIsFull => ErrorCode::StateFloorIsFull,
+            StateError::PileIsNotEmpty => ErrorCode::StatePileIsNotEmpty,
+            StateError::OwnTooManyPiles => ErrorCode::StateOwnTooManyPiles,
+            StateError::UnpairablePileValue(_) => ErrorCode::StateUnpairablePileValue,
+            StateError::DuplicateFloorValue => ErrorCode::StateDuplicateFloorValue,
+            StateError::MultipleBuildsNotAllowed => ErrorCode::StateMultipleBuildsNotAllowed,
+            StateError::NotYourTurn => ErrorCode::StateNotYourTurn,
+            StateError::UncapturedPile(_) => ErrorCode::StateUncapturedPile,
+            StateError::MoveLimitReached => ErrorCode::StateMoveLimitReached,
+            StateError::Internal(_) => ErrorCode::StateInternal,
+        }
+    }
+}
+
+impl From<&AuditError> for ErrorCode {
+    fn from(value: &AuditError) -> ErrorCode {
+        match value {
+            AuditError::DuplicateCard(_) => ErrorCode::AuditDuplicateCard,
+            AuditError::MissingCard(_) => ErrorCode::AuditMissingCard,
+            AuditError::InvalidCard(_) => ErrorCode::AuditInvalidCard,
+            AuditError::InvalidFloorSize(_) => ErrorCode::AuditInvalidFloorSize,
+            AuditError::InvalidFloorPile(_) => ErrorCode::AuditInvalidFloorPile,
+            AuditError::InvalidHandPile(..) => ErrorCode::AuditInvalidHandPile,
+            AuditError::DuplicateFloorValue => ErrorCode::AuditDuplicateFloorValue,
+            AuditError::OwnTooManyPiles(_) => ErrorCode::AuditOwnTooManyPiles,
+            AuditError::NoLegalMove => ErrorCode::AuditNoLegalMove,
+        }
+    }
+}
+
+impl From<&GameError> for ErrorCode {
+    fn from(value: &GameError) -> ErrorCode {
+        match value {
+            GameError::UnsupportedPlayerCount(_) => ErrorCode::GameUnsupportedPlayerCount,
+            GameError::UnsupportedFloorSize(_) => ErrorCode::GameUnsupportedFloorSize,
+            GameError::NothingToUndo => ErrorCode::GameNothingToUndo,
+            GameError::UndoDisabled => ErrorCode::GameUndoDisabled,
+            GameError::UndoLimitReached => ErrorCode::GameUndoLimitReached,
+            GameError::TurnIsOver => ErrorCode::GameTurnIsOver,
+            GameError::OpponentMoved => ErrorCode::GameOpponentMoved,
+            GameError::PileCaptured => ErrorCode::GamePileCaptured,
+            GameError::StaleTurnToken => ErrorCode::GameStaleTurnToken,
+            GameError::MatchIsOver => ErrorCode::GameMatchIsOver,
+            GameError::NothingToRedo => ErrorCode::GameNothingToRedo,
+            GameError::FloorCanStall => ErrorCode::GameFloorCanStall,
+        }
+    }
+}
+
+impl From<&Error> for ErrorCode {
+    fn from(value: &Error) -> ErrorCode {
+        match value {
+            Error::Parsing(e) => e.into(),
+            Error::Move(e) => e.into(),
+            Error::Pile(e) => e.into(),
+            Error::State(e) => e.into(),
+            Error::Audit(e) => e.into(),
+            Error::Game(e) => e.into(),
+        }
+    }
+}
+
+impl Error {
+    /// Get the stable code for the error
+    pub fn code(&self) -> ErrorCode {
+        self.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Annotation;
+    use crate::game::Game;
+    use crate::rng::Seed;
+
+    /// Parse and apply an annotation using the `?` operator
+    fn play(g: &mut Game, x: &str) -> Result<(), Error> {
+        let m = Annotation::new(String::from(x)).to_move()?;
+        g.apply(m)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_conversions() {
+        let mut g = Game::default();
+        g.seed(Seed::default());
+        g.deal();
+
+        assert_eq!(
+            play(&mut g, ""),
+            Err(Error::Parsing(ParsingError::BlankAnnotation))
+        );
+        assert_eq!(
+            play(&mut g, "*A&3"),
+            Err(Error::State(StateError::InvalidPile(
+                PileError::PairDifferentValues
+            )))
+        );
+        assert_eq!(play(&mut g, "*C&3"), Ok(()));
+    }
+
+    #[test]
+    fn test_error_source() {
+        let e = Error::from(StateError::InvalidMove(MoveError::DuplicateAddress));
+        // Printing the chain of sources shows the message once
+        let mut chain = vec![e.to_string()];
+        let mut source = std::error::Error::source(&e);
+        while let Some(x) = source {
+            chain.push(x.to_string());
+            source = x.source();
+        }
+        assert_eq!(chain, vec![e.to_string()]);
+        let boxed: Box<dyn std::error::Error> = Box::new(Error::from(PileError::GroupTwoSingles));
+        assert_eq!(boxed.to_string(), PileError::GroupTwoSingles.to_string());
+        assert!(boxed.source().is_none());
+    }
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(
+            Error::from(ParsingError::BlankAnnotation).code(),
+            ErrorCode::ParsingBlankAnnotation
+        );
+        assert_eq!(
+            Error::from(StateError::InvalidPile(PileError::PairDifferentValues)).code(),
+            ErrorCode::PilePairDifferentValues
+        );
+        assert_eq!(
+            Error::from(StateError::InvalidMove(MoveError::DuplicateAddress)).code(),
+            ErrorCode::MoveDuplicateAddress
+        );
+        assert_eq!(
+            Error::from(GameError::MatchIsOver).code(),
+            ErrorCode::GameMatchIsOver
+        );
+        assert_eq!(ErrorCode::StateNotYourTurn as u32, 409);
+    }
+
+    #[test]
+    fn test_error_code_from_number() {
+        for x in ErrorCode::ALL {
+            assert_eq!(ErrorCode::try_from(x as u16), Ok(x));
+        }
+        assert_eq!(ErrorCode::try_from(409), Ok(ErrorCode::StateNotYourTurn));
+        assert_eq!(ErrorCode::try_from(2), Err(2));
+        assert_eq!(ErrorCode::try_from(699), Err(699));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::action::{Annotation, Move};
+use crate::bot::{Bot, GreedyBot};
+use crate::consts::GAME_POINTS;
+use crate::game::Game;
+use crate::ismcts::determinize;
+use crate::rng::{ChaCha20Rng, Seed, SeedableRng};
+use crate::score::Score;
+use crate::sim::candidate_moves;
+use crate::state::{PlayerId, State};
+use std::fmt;
+
+/// How a best response did against a bot over a batch of games
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExploitReport {
+    pub games: usize,
+    pub responder_wins: usize,
+    pub bot_wins: usize,
+    /// Total points the best response scored, minus the total points the bot scored
+    pub margin: i64,
+}
+
+impl ExploitReport {
+    /// Get the average lead of the best response over the bot per game, as a fraction of the
+    /// points in a game
+    ///
+    /// Higher is more exploitable. A bot the best response can't take advantage of scores zero
+    /// or less.
+    pub fn exploitability(&self) -> f64 {
+        self.margin as f64 / (self.games.max(1) as f64 * GAME_POINTS as f64)
+    }
+}
+
+impl fmt::Display for ExploitReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Games: {}", self.games)?;
+        writeln!(
+            f,
+            "Wins: best response {}, bot {}",
+            self.responder_wins, self.bot_wins
+        )?;
+        writeln!(f, "Exploitability: {:.3}", self.exploitability())
+    }
+}
+
+/// Measure how exploitable a bot is by playing a best response against it
+///
+/// For every move, the best response samples the cards it can't see `samples` times, plays each
+/// of its moves out to the end of the game with the bot playing itself and the greedy bot in its
+/// own seat, and picks the move that did best. It knows how the bot plays, but not its cards.
+/// One game is played from each seed in each seat.
+pub fn exploitability(bot: &mut dyn Bot, seeds: &[Seed], samples: u32) -> ExploitReport {
+    let mut report = ExploitReport::default();
+    for &seed in seeds {
+        let mut rng = ChaCha20Rng::from_seed(seed);
+        rng.set_stream(1);
+        for responder in [PlayerId::Opponent, PlayerId::Dealer] {
+            let mut s = Game::builder().seed(seed).build().unwrap().state;
+            play_out(&mut s, responder.other(), bot, &mut |view, bot| {
+                best_response(view, bot, samples, &mut rng)
+            });
+            let score = Score::from(&s);
+            let (x, y) = (score.total(responder), score.total(responder.other()));
+            report.games += 1;
+            report.margin += x as i64 - y as i64;
+            match x.cmp(&y) {
+                std::cmp::Ordering::Greater => report.responder_wins += 1,
+                std::cmp::Ordering::Less => report.bot_wins += 1,
+                std::cmp::Ordering::Equal => {}
+            }
+        }
+    }
+    report
+}
+
+/// Pick the move that does best on average against the bot over sampled deals of the hidden
+/// cards
+fn best_response(
+    view: &State,
+    bot: &mut dyn Bot,
+    samples: u32,
+    rng: &mut ChaCha20Rng,
+) -> Option<Move> {
+    let me = view.turn;
+    let moves = view.legal_moves();
+    if moves.len() < 2 {
+        return moves.into_iter().next();
+    }
+    let mut best = None;
+    let mut best_total = i64::MIN;
+    for m in moves {
+        let mut total = 0;
+        for _ in 0..samples.max(1) {
+            let mut s = determinize(view, rng);
+            // Every legal move only uses cards the player can see
+            s.apply(m.clone()).unwrap();
+            s.turn = me.other();
+            play_out(&mut s, me.other(), bot, &mut |view, _| {
+                GreedyBot::new().choose_move(view)
+            });
+            let score = Score::from(&s);
+            total += score.total(me) as i64 - score.total(me.other()) as i64;
+        }
+        if total > best_total {
+            best_total = total;
+            best = Some(m);
+        }
+    }
+    best
+}
+
+/// Play the game out to the end, with the bot in the given seat and the other seat choosing
+/// its own moves
+///
+/// Each player only sees their own view of the state. A move that isn't legal is replaced by
+/// the first legal one.
+fn play_out<F>(s: &mut State, seat: PlayerId, bot: &mut dyn Bot, other: &mut F)
+where
+    F: FnMut(&State, &mut dyn Bot) -> Option<Move>,
+{
+    loop {
+        if s.dealer.card_count() == 0 && s.opponent.card_count() == 0 {
+            if s.deck.is_empty() {
+                break;
+            }
+            s.deal_hands();
+        }
+        let view = s.redacted_for(s.turn);
+        let m = match s.turn == seat {
+            true => bot.choose_move(&view),
+            false => other(&view, &mut *bot),
+        };
+        let m = m
+            .filter(|m| s.clone().apply(m.clone()).is_ok())
+            .or_else(|| {
+                let x = candidate_moves(s).into_iter().next()?;
+                Annotation::new(x).to_move().ok()
+            });
+        match m {
+            Some(m) => {
+                // The move was checked against a copy of the state
+                s.apply(m).unwrap();
+                s.turn = s.turn.other();
+            }
+            None => break,
+        }
+    }
+    s.pickup_floor();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::RandomBot;
+
+    #[test]
+    fn test_exploitability() {
+        let report = exploitability(&mut RandomBot::new([5; 32]), &[[0; 32]], 2);
+        assert_eq!(report.games, 2);
+        // Moves picked at random leave plenty to take advantage of
+        assert!(report.exploitability() > 0.0, "{}", report);
+        assert!(report.responder_wins > report.bot_wins, "{}", report);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::action::{Annotation, Move};
+use crate::game::Game;
+use crate::rng::Seed;
+
+/// Parse arbitrary bytes as an annotation, returning true if it was a valid move
+///
+/// This must never panic, no matter what bytes it is given.
+pub fn fuzz_annotation(data: &[u8]) -> bool {
+    let annotation = Annotation::new(String::from_utf8_lossy(data).into_owned());
+    match annotation.to_move() {
+        Ok(m) => m.is_valid().is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Play a game from the given seed, interpreting a byte stream as moves
+///
+/// Each move is encoded as a length byte followed by that many action bytes. Moves that are
+/// rejected by the engine are skipped, and moves that are applied end the current turn. Returns
+/// the number of moves that were successfully applied.
+pub fn fuzz_moves(seed: Seed, data: &[u8]) -> usize {
+    let mut g = Game::builder().seed(seed).build().unwrap();
+    let mut applied = 0;
+    let mut bytes = data.iter();
+    while let Some(&n) = bytes.next() {
+        if g.is_over() {
+            break;
+        }
+        let xs = bytes.by_ref().take(n as usize % 8 + 1).copied().collect();
+        if let Ok(m) = Move::from_bytes(xs) {
+            if g.apply(m).is_ok() {
+                g.tick();
+                applied += 1;
+            }
+        }
+    }
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use rand::RngCore;
+
+    #[test]
+    fn test_fuzz_annotation() {
+        assert!(fuzz_annotation(b"*C&3"));
+        assert!(fuzz_annotation(b"A+B+C&D+E&1"));
+        assert!(!fuzz_annotation(b""));
+        assert!(!fuzz_annotation(b"*A+A&6"));
+        assert!(!fuzz_annotation(b"\xff\xfe\x00*"));
+        assert!(!fuzz_annotation("*🂡&1".as_bytes()));
+    }
+
+    #[test]
+    fn test_fuzz_moves() {
+        // Pair the two of spades with the two of diamonds, then discard the ten of diamonds
+        assert_eq!(fuzz_moves(Seed::default(), &[1, 32 + 12, 3, 0, 1]), 2);
+        // Floor address bytes past M are rejected instead of indexing out of bounds
+        assert_eq!(fuzz_moves(Seed::default(), &[1, 23, 1]), 0);
+    }
+
+    #[test]
+    fn test_fuzz_moves_random_bytes() {
+        let mut rng = Rng::from_seed([7; 32]);
+        for _ in 0..50 {
+            let mut seed = Seed::default();
+            let mut data = [0; 4096];
+            rng.rng_borrow_mut().fill_bytes(&mut seed);
+            rng.rng_borrow_mut().fill_bytes(&mut data);
+            for x in data.iter_mut() {
+                // Keep most bytes within the action encoding so that moves get applied
+                *x &= 0b00111111;
+            }
+            fuzz_moves(seed, &data);
+        }
+    }
+}
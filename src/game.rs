@@ -1,53 +1,862 @@
-use crate::action::Move;
-use crate::rng::{Rng, Seed};
-use crate::score::Score;
-use crate::state::{State, StateError};
+use crate::action::{Annotation, Move, Profile};
+use crate::api::{fill_mirror, Status};
+use crate::card::Card;
+use crate::coach::{self, review, Hint, MoveHint};
+use crate::commit::{DealCommitment, DealReveal};
+use crate::consts::{
+    FLOOR_DEAL_SIZE, FLOOR_SIZE, GAME_DEALS, GAME_POINTS, GAME_TURNS, GRADE_SAMPLES, HANDS_SIZE,
+    MAX_FLOOR_SIZE, MAX_GAME_MOVES, MAX_GAME_UNDOS, MIRROR_SIZE,
+};
+use crate::error::Error;
+use crate::journal::{Checkpoint, Entry, Journal};
+use crate::locale::Locale;
+use crate::metrics::{Failure, Metrics};
+use crate::pile::Pile;
+use crate::replay::{ReplayError, Tag};
+use crate::result::{MatchResult, Termination};
+use crate::rng::{os_seed, ChaCha20Rng, Rng, Seed, SeedableRng, SliceRandom};
+use crate::rules::{FloorOverflow, Misdeal, Rules, TimeoutPolicy, UndoPolicy};
+use crate::save::{header, Codec, Reader, SaveError};
+use crate::score::{Category, PlayerScore, Score};
+use crate::search::rank_moves_sampled;
+use crate::sim::candidate_moves;
+use crate::state::{Keyframe, PlayerId, State, StateError};
+use rand::RngCore;
+use std::any::Any;
+use std::cmp::Ordering;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Game management errors
+#[derive(Debug, Eq, PartialEq)]
+pub enum GameError {
+    UnsupportedPlayerCount(u8),
+    UnsupportedFloorSize(usize),
+    NothingToUndo,
+    UndoDisabled,
+    UndoLimitReached,
+    TurnIsOver,
+    OpponentMoved,
+    PileCaptured,
+    StaleTurnToken,
+    MatchIsOver,
+    NothingToRedo,
+    FloorCanStall,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Locale::English.game_message(self))
+    }
+}
+
+impl std::error::Error for GameError {}
+
+/// The result of successfully applying a move
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MoveOutcome {
+    pub turn: PlayerId,
+    pub captured: Vec<Card>,
+    pub suipi: bool,
+    /// Every card moved by the move, in order, so clients can animate it
+    pub keyframes: Vec<Keyframe>,
+    /// Advice on the move, if the game is coaching its players
+    pub hints: Vec<Hint>,
+}
+
+/// What happened when a player ran out of time
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Timeout {
+    /// A move was played on the player's behalf
+    Moved(PlayerId, String),
+    /// The player forfeited the match
+    Forfeited(PlayerId),
+}
+
+/// A move that was played, as it was recorded
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayedMove {
+    /// Position of the move among every move played in the match
+    pub turn: usize,
+    pub player: PlayerId,
+    /// Canonical annotation for the move
+    pub annotation: String,
+    /// Annotation as it was entered
+    pub raw: String,
+    /// Time the player took to move, if the match is played on a clock
+    pub elapsed_ms: Option<u64>,
+}
+
+impl PlayedMove {
+    /// Get the move that was played
+    pub fn to_move(&self) -> Move {
+        // Canonical annotations are written by `Move::to_annotation`, so they always parse
+        Annotation::new(self.annotation.clone()).to_move().unwrap()
+    }
+}
+
+/// Cards dealt at the start of a round
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Deal {
+    /// Number of the deal in the match, counting from 1
+    pub number: u8,
+    pub game: u8,
+    pub round: u8,
+    /// Cards dealt to both hands
+    pub hands_dealt: u8,
+    /// Cards dealt to the floor, which only happens at the start of a game
+    pub floor_dealt: u8,
+    pub deck_remaining_after: u8,
+    /// Number of moves played in the match before the deal
+    pub first_move: u16,
+}
+
+/// How a played move compares to the alternatives the engine found
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveStats {
+    pub player: PlayerId,
+    pub annotation: String,
+    pub elapsed_ms: Option<u64>,
+    /// Every legal move the player could have made with the engine's score for it, best first
+    pub ranked: Vec<(String, i32)>,
+    /// Position of the played move among the ranked moves, if it was one of them
+    pub rank: Option<usize>,
+    /// Grade of the played move, if it was ranked
+    pub grade: Option<Tag>,
+}
+
+/// A move on the match timeline, for scrubbing through a game
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimelineEntry {
+    /// Position of the move among every move played in the match
+    pub turn: usize,
+    pub game: u8,
+    pub round: u8,
+    pub player: PlayerId,
+    pub annotation: String,
+    /// Cards the move captured, in the order they were taken
+    pub captured: Vec<Card>,
+    /// Did the move clear the floor?
+    pub suipi: bool,
+    /// Hash of the floor right after the move, for telling positions apart
+    pub floor_hash: u64,
+}
+
+/// A copy of a game's state and progress, for saving or sending to clients
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    pub status: Status,
+    pub state: State,
+    /// Scores for each game played so far, the game in progress last
+    pub scores: Vec<Score>,
+    pub moves: Vec<PlayedMove>,
+    pub result: Option<MatchResult>,
+    /// Commitments to the deal of every game so far, when the deal is hidden
+    pub commitments: Vec<DealCommitment>,
+    /// Deals of finished games, revealed for checking against their commitments
+    pub reveals: Vec<DealReveal>,
+}
+
+/// Information about a player taking part in a match
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerInfo {
+    pub name: String,
+    pub rating: Option<u16>,
+    pub client_id: Option<String>,
+}
+
+impl PlayerInfo {
+    /// Get player info with just a display name
+    pub fn named(name: &str) -> PlayerInfo {
+        PlayerInfo {
+            name: name.to_string(),
+            ..PlayerInfo::default()
+        }
+    }
+}
+
+/// Something that happened during the match that clients may want to react to
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// A player cleared the floor
+    Suipi(PlayerId),
+    /// A player captured a card worth points on its own
+    PointEarned {
+        player: PlayerId,
+        card: Card,
+        points: u8,
+    },
+    /// The player leading a category changed, or the category became tied
+    LeaderChanged {
+        category: Category,
+        player: Option<PlayerId>,
+    },
+    /// The outcome of the match can no longer change
+    MatchDecided(MatchResult),
+}
+
+/// A structured record of something the engine did, for hosts to log
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LogRecord {
+    /// Cards were dealt for a new round
+    Deal { game: u8, round: u8 },
+    /// A move was played
+    Move {
+        player: PlayerId,
+        annotation: String,
+    },
+    /// A move was rejected
+    Rejected {
+        player: PlayerId,
+        annotation: String,
+        error: String,
+    },
+    /// A game was finished and scored
+    Scored { game: u8, opponent: u8, dealer: u8 },
+}
+
+/// A callback receiving log records
+pub type Logger = Box<dyn FnMut(&LogRecord) + Send>;
+
+/// A callback receiving events as they happen
+pub type Listener = Box<dyn FnMut(&Event) + Send>;
+
+/// An opaque value identifying the game state a move was submitted against
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TurnToken(u64);
+
+impl From<u64> for TurnToken {
+    fn from(value: u64) -> TurnToken {
+        TurnToken(value)
+    }
+}
+
+impl From<TurnToken> for u64 {
+    fn from(value: TurnToken) -> u64 {
+        value.0
+    }
+}
+
+impl fmt::Display for TurnToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// A move queued by a player ahead of their turn
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingMove {
+    pub annotation: String,
+    piles: Vec<u16>,
+}
+
+impl Codec for PendingMove {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.annotation.encode(w);
+        self.piles.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(PendingMove {
+            annotation: Codec::decode(r)?,
+            piles: Codec::decode(r)?,
+        })
+    }
+}
+
+/// What happened to a queued move when it was resolved
+#[derive(Debug, Eq, PartialEq)]
+pub enum Resolution {
+    /// The move was played as the given annotation
+    Applied(PlayerId, String),
+    /// The move no longer fits the game state and was dropped
+    Conflict(PlayerId, String, Error),
+}
 
-#[derive(Default)]
 pub struct Game {
     pub game: u8,
     pub round: u8,
     pub rng: Rng,
+    pub rules: Rules,
+    pub dealer: PlayerId,
     pub state: State,
     pub scores: Vec<Score>,
-    history: Vec<State>,
+    pub forfeit: Option<PlayerId>,
+    pub result: Option<MatchResult>,
+    pub players: [Option<PlayerInfo>; 2],
+    /// Review every move played through annotations, reporting missed captures
+    pub coach: bool,
+    /// Earlier states for undo, shared with any forks of the game
+    history: Vec<Arc<State>>,
+    /// Length of the start of the undo history that the latest checkpoint already stores
+    history_kept: usize,
+    undos: [u8; 2],
+    /// Moves played in the current game, including ones taken back
+    game_moves: usize,
+    turn_started: Option<u64>,
+    pending: [Option<PendingMove>; 2],
+    version: u64,
+    events: Vec<Event>,
+    touched: Option<(u64, u64)>,
+    played: Vec<PlayedMove>,
+    /// Has the current player moved since their turn started?
+    moved: bool,
+    deals: Vec<Deal>,
+    /// Everything done to the match, which the rest of the game can be rebuilt from
+    journal: Vec<Entry>,
+    /// Moves taken back, most recent last, until another move is played
+    undone: Vec<String>,
+    snapshot_interval: Option<usize>,
+    checkpoints: Vec<Checkpoint>,
+    hidden: bool,
+    commitments: Vec<DealCommitment>,
+    reveals: Vec<DealReveal>,
+    /// The deal of the game in progress, until it is revealed
+    sealed: Option<DealReveal>,
+    /// Secret the commitment salts are drawn from, kept apart from the seed
+    salt_key: Seed,
+    /// Secret turn tokens are derived from, so a token gives nothing away about the seed
+    ///
+    /// It is never saved, so a restored game hands out new tokens.
+    token_key: Seed,
+    /// Seat the state mirror is written for, and the mirror itself
+    mirror: Option<(PlayerId, Box<[u8; MIRROR_SIZE]>)>,
+    metrics: Metrics,
+    logger: Option<Logger>,
+    listener: Option<Listener>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game {
+            game: 0,
+            round: 0,
+            rng: Rng::default(),
+            rules: Rules::default(),
+            dealer: PlayerId::Dealer,
+            state: State::default(),
+            scores: vec![],
+            forfeit: None,
+            result: None,
+            players: [None, None],
+            coach: false,
+            history: vec![],
+            history_kept: 0,
+            undos: [0; 2],
+            game_moves: 0,
+            turn_started: None,
+            pending: [None, None],
+            version: 0,
+            events: vec![],
+            touched: None,
+            played: vec![],
+            moved: false,
+            deals: vec![],
+            journal: vec![],
+            undone: vec![],
+            snapshot_interval: None,
+            checkpoints: vec![],
+            hidden: false,
+            commitments: vec![],
+            reveals: vec![],
+            sealed: None,
+            salt_key: Seed::default(),
+            token_key: os_seed(),
+            mirror: None,
+            metrics: Metrics::default(),
+            logger: None,
+            listener: None,
+        }
+    }
+}
+
+/// Get the message a panic was raised with
+pub(crate) fn panic_message(e: Box<dyn Any + Send>) -> String {
+    match e.downcast::<String>() {
+        Ok(x) => *x,
+        Err(e) => e
+            .downcast_ref::<&str>()
+            .unwrap_or(&"unknown panic")
+            .to_string(),
+    }
+}
+
+/// Hash the cards on the floor with FNV-1a, so the hash stays the same across builds
+fn floor_hash(floor: &[Pile]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for x in floor.iter().filter(|x| !x.is_empty()) {
+        for &c in x.cards.iter() {
+            hash ^= u8::from(c) as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash ^= u8::MAX as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Step by step game construction
+pub struct GameBuilder {
+    seed: Option<Seed>,
+    rules: Rules,
+    players: u8,
+    dealer: PlayerId,
+    info: [Option<PlayerInfo>; 2],
+    snapshot_interval: Option<usize>,
+    hidden: bool,
+}
+
+impl Default for GameBuilder {
+    fn default() -> Self {
+        GameBuilder {
+            seed: None,
+            rules: Rules::default(),
+            players: 2,
+            dealer: PlayerId::Dealer,
+            info: [None, None],
+            snapshot_interval: None,
+            hidden: false,
+        }
+    }
+}
+
+impl GameBuilder {
+    /// Shuffle the deck using the given RNG seed instead of a random one
+    pub fn seed(mut self, seed: Seed) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Play the match using the given rules
+    pub fn rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Set the number of players in the match
+    pub fn players(mut self, players: u8) -> Self {
+        self.players = players;
+        self
+    }
+
+    /// Set the player who deals, the other player moves first
+    pub fn dealer(mut self, dealer: PlayerId) -> Self {
+        self.dealer = dealer;
+        self
+    }
+
+    /// Attach metadata to the given player
+    pub fn player(mut self, p: PlayerId, info: PlayerInfo) -> Self {
+        self.info[p as usize] = Some(info);
+        self
+    }
+
+    /// Save a checkpoint in the journal at the end of a turn, once at least `n` entries have been
+    /// recorded since the last one, so late positions of long matches load quickly
+    pub fn snapshot_interval(mut self, n: usize) -> Self {
+        self.snapshot_interval = Some(n.max(1));
+        self
+    }
+
+    /// Keep the deal secret, committing to the deck order of each game when it is shuffled and
+    /// revealing it once the game is over
+    ///
+    /// This is for servers that hide the deck and hands from clients, so players can check
+    /// afterwards that the cards were not changed during the game. The status no longer reports
+    /// the seed, and the commitments are salted from a secret drawn from the operating system.
+    /// Captured cards reshuffled after a misdeal are not covered by the commitment.
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+
+    /// Build the game and deal the first round
+    pub fn build(self) -> Result<Game, GameError> {
+        if self.players != 2 {
+            return Err(GameError::UnsupportedPlayerCount(self.players));
+        } else if !(FLOOR_DEAL_SIZE..=MAX_FLOOR_SIZE).contains(&self.rules.floor_size) {
+            return Err(GameError::UnsupportedFloorSize(self.rules.floor_size));
+        } else if self.rules.floor_overflow == FloorOverflow::ForceCapture
+            && (!self.rules.unique_floor || self.rules.floor_size < FLOOR_SIZE)
+        {
+            // Only a unique floor with room for every value is sure to have something to capture
+            // once it is full, otherwise a player who can't discard either would have no move
+            return Err(GameError::FloorCanStall);
+        }
+        let mut g = Game {
+            rules: self.rules,
+            dealer: self.dealer,
+            players: self.info,
+            snapshot_interval: self.snapshot_interval,
+            hidden: self.hidden,
+            salt_key: match self.hidden {
+                true => os_seed(),
+                false => Seed::default(),
+            },
+            ..Game::default()
+        };
+        if let Some(seed) = self.seed {
+            g.seed(seed);
+        }
+        g.deal();
+        Ok(g)
+    }
 }
 
 impl Game {
+    /// Get a builder for configuring a new game
+    pub fn builder() -> GameBuilder {
+        GameBuilder::default()
+    }
+
     /// Initialize a game with a RNG seed value
     pub fn seed(&mut self, seed: Seed) {
         self.rng = Rng::from_seed(seed);
     }
 
+    /// Send every log record to the given callback
+    pub fn set_logger<F>(&mut self, f: F)
+    where
+        F: FnMut(&LogRecord) + Send + 'static,
+    {
+        self.logger = Some(Box::new(f));
+    }
+
+    /// Send every event to the given callback as it happens, as well as queueing it for
+    /// `poll_events`
+    pub fn set_listener<F>(&mut self, f: F)
+    where
+        F: FnMut(&Event) + Send + 'static,
+    {
+        self.listener = Some(Box::new(f));
+    }
+
+    /// Queue an event, and send it to the listener if there is one
+    fn emit(&mut self, e: Event) {
+        if let Some(f) = self.listener.as_mut() {
+            f(&e);
+        }
+        self.events.push(e);
+    }
+
+    /// Queue the scoring events for the changes between an earlier state and the current one
+    fn emit_scoring(&mut self, before: &State) {
+        for player in [PlayerId::Opponent, PlayerId::Dealer] {
+            let n = before.seat(player).into_pair_cards().len();
+            for card in self.state.seat(player).into_pair_cards().split_off(n) {
+                if card.is_point_card() {
+                    self.emit(Event::PointEarned {
+                        player,
+                        card,
+                        points: card.points(),
+                    });
+                }
+            }
+        }
+        if self.state.player().suipi_count > before.player().suipi_count {
+            self.emit(Event::Suipi(self.state.turn));
+        }
+        let (x, y) = (Score::from(before), Score::from(&self.state));
+        for (category, a, b) in [
+            (Category::MostCards, x.most_cards, y.most_cards),
+            (Category::MostSpades, x.most_spades, y.most_spades),
+        ] {
+            if a.player() != b.player() {
+                self.emit(Event::LeaderChanged {
+                    category,
+                    player: b.player(),
+                });
+            }
+        }
+    }
+
+    /// Send a log record to the logger, if there is one
+    fn log(&mut self, r: LogRecord) {
+        if let Some(f) = self.logger.as_mut() {
+            f(&r);
+        }
+    }
+
     /// Deal cards for a new round
     pub fn deal(&mut self) {
+        self.state.rules = self.rules.clone();
         if self.round == 0 {
+            // Until somebody captures, anything left to the last scorer goes to the dealer
+            self.state.last_score = self.dealer;
             self.scores.push(Score::from(&self.state));
             self.state.init_deck();
             self.state.shuffle_deck(self.rng.rng_borrow_mut());
+            if self.hidden && self.game < self.rules.games {
+                self.seal();
+            }
             self.state.deal_hands();
             self.state.deal_floor();
         } else {
+            if self.state.deck.len() < HANDS_SIZE {
+                match self.rules.misdeal {
+                    Misdeal::ShortHands => {}
+                    Misdeal::Reshuffle => {
+                        self.state.reshuffle_captures(self.rng.rng_borrow_mut());
+                    }
+                    Misdeal::Abort => {
+                        self.finish(None, Termination::Misdeal);
+                        return;
+                    }
+                }
+            }
             self.state.deal_hands();
         }
+        if self.dealer == PlayerId::Opponent {
+            // The dealer always deals the first card to the other player
+            std::mem::swap(&mut self.state.dealer.hand, &mut self.state.opponent.hand);
+        }
+        self.state.turn = self.dealer.other();
+        self.deals.push(Deal {
+            number: self.deals.len() as u8 + 1,
+            game: self.game,
+            round: self.round,
+            hands_dealt: (self.state.dealer.card_count() + self.state.opponent.card_count()) as u8,
+            floor_dealt: if self.round == 0 {
+                self.state.floor_count() as u8
+            } else {
+                0
+            },
+            deck_remaining_after: self.state.deck.len() as u8,
+            first_move: self.played.len() as u16,
+        });
+        self.log(LogRecord::Deal {
+            game: self.game,
+            round: self.round,
+        });
+    }
+
+    /// Commit to the order of the freshly shuffled deck
+    fn seal(&mut self) {
+        // The salt comes from a secret of its own, so hiding the deal does not change the cards,
+        // and the seed does not give the salt away
+        let mut rng = ChaCha20Rng::from_seed(self.salt_key);
+        rng.set_stream(self.game as u64);
+        let mut salt = [0; 32];
+        rng.fill_bytes(&mut salt);
+        let deal = DealReveal {
+            game: self.game,
+            salt,
+            order: self.state.deck.iter().copied().collect(),
+        };
+        self.commitments.push(deal.commitment());
+        self.sealed = Some(deal);
+    }
+
+    /// Reveal the deal of the game in progress
+    fn reveal(&mut self) {
+        if let Some(x) = self.sealed.take() {
+            self.reveals.push(x);
+        }
+    }
+
+    /// Is the deal kept secret and committed to?
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Get the commitments to the deal of every game so far, to send to clients
+    pub fn commitments(&self) -> &[DealCommitment] {
+        &self.commitments
+    }
+
+    /// Get the deals of the games that are over, to send to clients
+    pub fn reveals(&self) -> &[DealReveal] {
+        &self.reveals
+    }
+
+    /// Has every game in the match been played, or has the match been decided early?
+    pub fn is_over(&self) -> bool {
+        self.game >= self.rules.games || self.forfeit.is_some() || self.result.is_some()
+    }
+
+    /// Get the range of match scores the given player could still finish with
+    fn score_range(&self, p: PlayerId) -> (u16, u16) {
+        let done = self.completed_points(p);
+        if self.game >= self.rules.games {
+            return (done, done);
+        }
+        let me = PlayerScore::from(self.state.seat(p));
+        let them = PlayerScore::from(self.state.seat(p.other()));
+        let turns = self.state.seat(p).card_count() + self.state.deck.len() / 2;
+        (
+            done + me.locked() as u16,
+            done + (GAME_POINTS - them.locked()) as u16
+                + (me.suipi_count + turns) as u16
+                + self.future_points(),
+        )
+    }
+
+    /// Get the match score the given player has from the games that are over
+    fn completed_points(&self, p: PlayerId) -> u16 {
+        let played = self.game.min(self.rules.games) as usize;
+        self.scores[..played]
+            .iter()
+            .map(|s| s.total(p) as u16)
+            .sum()
+    }
+
+    /// Get the most points a player could score in the games after the current one
+    fn future_points(&self) -> u16 {
+        let left = self.rules.games.saturating_sub(self.game + 1);
+        left as u16 * (GAME_POINTS as u16 + GAME_TURNS as u16)
+    }
+
+    /// Record the match result once it can no longer change
+    fn decide(&mut self) {
+        if self.result.is_some() {
+            return;
+        }
+        let (opp_min, opp_max) = self.score_range(PlayerId::Opponent);
+        let (dealer_min, dealer_max) = self.score_range(PlayerId::Dealer);
+        let target = self.rules.target.unwrap_or(u16::MAX);
+        let winner = if self.game >= self.rules.games {
+            match opp_min.cmp(&dealer_min) {
+                Ordering::Equal => None,
+                Ordering::Greater => Some(PlayerId::Opponent),
+                Ordering::Less => Some(PlayerId::Dealer),
+            }
+        } else if self.rules.target.is_none() {
+            return;
+        } else if self.completed_points(PlayerId::Opponent) >= target
+            || self.completed_points(PlayerId::Dealer) >= target
+        {
+            // A game finished with a player past the target, so whoever leads has won, and a tie
+            // is played off in another game
+            let (opp, dealer) = (
+                self.completed_points(PlayerId::Opponent),
+                self.completed_points(PlayerId::Dealer),
+            );
+            match opp.cmp(&dealer) {
+                Ordering::Equal => return,
+                Ordering::Greater => Some(PlayerId::Opponent),
+                Ordering::Less => Some(PlayerId::Dealer),
+            }
+        } else {
+            // Once a player is sure to reach the target, the match ends with this game, so the
+            // other player can only catch up in what is left of it
+            let future = if opp_min >= target || dealer_min >= target {
+                self.future_points()
+            } else {
+                0
+            };
+            if opp_min > dealer_max - future {
+                Some(PlayerId::Opponent)
+            } else if dealer_min > opp_max - future {
+                Some(PlayerId::Dealer)
+            } else {
+                return;
+            }
+        };
+        self.finish(winner, Termination::Completed);
+    }
+
+    /// End the match with the given result
+    fn finish(&mut self, winner: Option<PlayerId>, termination: Termination) {
+        // The next game is dealt as soon as one ends, but it only counts once it is played
+        let dealt_only = self.game > 0 && self.round == 0 && self.game_moves == 0;
+        let games = (self.game + !dealt_only as u8).min(self.rules.games) as usize;
+        let per_game_scores = self.scores[..games.min(self.scores.len())]
+            .iter()
+            .map(|s| [PlayerId::Opponent, PlayerId::Dealer].map(|p| s.total(p)))
+            .collect();
+        let duration = self
+            .touched
+            .map_or(0, |(first, last)| last.saturating_sub(first));
+        let result = MatchResult {
+            winner,
+            per_game_scores,
+            termination,
+            duration: Duration::from_millis(duration),
+        };
+        self.emit(Event::MatchDecided(result.clone()));
+        self.result = Some(result);
+        self.reveal();
+    }
+
+    /// Resign the match on behalf of the given player
+    pub fn resign(&mut self, p: PlayerId) -> Result<&MatchResult, GameError> {
+        if self.is_over() {
+            return Err(GameError::MatchIsOver);
+        }
+        self.forfeit = Some(p);
+        self.finish(Some(p.other()), Termination::Resigned);
+        self.journal.push(Entry::Resign(p));
+        self.sync_mirror();
+        Ok(self.result.as_ref().unwrap())
+    }
+
+    /// Stop the match without a winner
+    pub fn abort(&mut self) -> Result<&MatchResult, GameError> {
+        if self.is_over() {
+            return Err(GameError::MatchIsOver);
+        }
+        self.finish(None, Termination::Aborted);
+        self.journal.push(Entry::Abort);
+        self.sync_mirror();
+        Ok(self.result.as_ref().unwrap())
+    }
+
+    /// Forfeit the match on behalf of a player who ran out of time
+    pub(crate) fn time_out(&mut self, p: PlayerId) -> Result<(), GameError> {
+        if self.is_over() {
+            return Err(GameError::MatchIsOver);
+        }
+        self.forfeit = Some(p);
+        self.finish(Some(p.other()), Termination::Timeout);
+        self.journal.push(Entry::Forfeit(p));
+        self.sync_mirror();
+        Ok(())
+    }
+
+    /// Take the events that happened since the last call
+    pub fn poll_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
     }
 
     /// Move the game state forward one turn
     pub fn tick(&mut self) {
-        // Handle Suipi condition
-        if self.state.floor_count() == 0 {
-            self.state.player_mut().suipi_count += 1;
+        self.journal.push(Entry::EndTurn);
+        self.turn_started = None;
+        self.version += 1;
+        // Pass the turn once the current player has moved
+        if std::mem::take(&mut self.moved) {
+            self.state.turn = self.pass_turn(self.state.turn, 0);
+            self.state.forget_simple_moves();
         }
-        // Toggle turn
-        self.state.turn = self.state.dealer.card_count() > self.state.opponent.card_count();
         // Handle end of round
         if self.state.dealer.card_count() == 0 && self.state.opponent.card_count() == 0 {
             // Handle end of game
             if self.state.deck.is_empty() {
+                let before = self.state.clone();
                 self.state.pickup_floor();
-                self.scores[self.game as usize] = Score::from(&self.state);
+                self.emit_scoring(&before);
+                let score = Score::from(&self.state);
+                self.log(LogRecord::Scored {
+                    game: self.game,
+                    opponent: score.opponent_total(),
+                    dealer: score.dealer_total(),
+                });
+                self.scores[self.game as usize] = score;
+                self.reveal();
                 self.state = State::default();
                 self.history = Vec::new();
+                self.history_kept = 0;
+                self.undos = [0; 2];
+                self.game_moves = 0;
+                self.pending = [None, None];
                 self.round = 0;
                 self.game += 1;
             } else {
@@ -58,79 +867,998 @@ impl Game {
             // Bump live scoring every turn
             self.scores[self.game as usize] = Score::from(&self.state);
         }
+        self.decide();
+        self.checkpoint();
+        self.sync_mirror();
+    }
+
+    /// Start keeping a mirror of the game as the given player sees it in a flat buffer, laid
+    /// out as `api::fill_mirror` describes, and get the buffer
+    ///
+    /// The mirror is kept up to date after every change to the game, and its version is bumped
+    /// each time, so clients can poll it instead of reading the game. Only the viewer's hand is
+    /// shown. Calling this again switches the viewer and keeps the same buffer.
+    pub fn enable_mirror(&mut self, viewer: PlayerId) -> &[u8; MIRROR_SIZE] {
+        let buf = match self.mirror.take() {
+            Some((_, buf)) => buf,
+            None => Box::new([0; MIRROR_SIZE]),
+        };
+        self.mirror = Some((viewer, buf));
+        self.sync_mirror();
+        self.mirror().unwrap()
+    }
+
+    /// Get the engine's counters for the game, such as moves applied and rejected
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Get the state mirror, if it is enabled
+    pub fn mirror(&self) -> Option<&[u8; MIRROR_SIZE]> {
+        self.mirror.as_ref().map(|(_, buf)| &**buf)
+    }
+
+    /// Rewrite the state mirror, if it is enabled
+    fn sync_mirror(&mut self) {
+        if let Some((viewer, mut buf)) = self.mirror.take() {
+            fill_mirror(self, viewer, &mut buf);
+            self.mirror = Some((viewer, buf));
+        }
+    }
+
+    /// Save a checkpoint if enough entries have been recorded since the last one
+    ///
+    /// The checkpoint leaves out the journal, and the part of the undo history the one before it
+    /// already stores, so checkpoints take space in proportion to the match.
+    fn checkpoint(&mut self) {
+        let Some(n) = self.snapshot_interval else {
+            return;
+        };
+        let last = self.checkpoints.last().map_or(0, |x| x.entry);
+        if self.journal.len() >= last + n {
+            let kept = self.history_kept;
+            self.checkpoints.push(Checkpoint {
+                entry: self.journal.len(),
+                kept,
+                save: self.encode(&self.history[kept..], &[]),
+            });
+            self.history_kept = self.history.len();
+        }
+    }
+
+    /// Get the undo history, to rebuild a later checkpoint's history from
+    pub(crate) fn undo_history(&self) -> &[Arc<State>] {
+        &self.history
+    }
+
+    /// Put back the start of the undo history a checkpoint left out
+    pub(crate) fn prepend_history(&mut self, mut prefix: Vec<Arc<State>>) {
+        prefix.append(&mut self.history);
+        self.history = prefix;
+    }
+
+    /// Carry on from a checkpoint, with the journal entries and checkpoints up to it
+    pub(crate) fn resume(&mut self, journal: Vec<Entry>, checkpoints: Vec<Checkpoint>) {
+        self.journal = journal;
+        self.checkpoints = checkpoints;
+        self.history_kept = self.history.len();
+    }
+
+    /// Get the player who moves after the given player, once they have played `played` more cards
+    ///
+    /// The turn passes to the other player, unless they have run out of cards while the given
+    /// player has not. Once both hands are empty, the next deal starts with the player who is not
+    /// dealing.
+    fn pass_turn(&self, p: PlayerId, played: usize) -> PlayerId {
+        let mine = self.state.seat(p).card_count().saturating_sub(played);
+        let theirs = self.state.seat(p.other()).card_count();
+        match (mine, theirs) {
+            (0, 0) => self.dealer.other(),
+            (_, 0) => p,
+            _ => p.other(),
+        }
+    }
+
+    /// Get the player who will move after the current player
+    pub fn next_to_act(&self) -> PlayerId {
+        let played = if self.moved { 0 } else { 1 };
+        self.pass_turn(self.state.turn, played)
+    }
+
+    /// End the current player's turn like `tick`, but if the engine panics, put the game back
+    /// the way it was and return the panic as an error
+    pub fn try_tick(&mut self) -> Result<(), StateError> {
+        let before = self.fork_for_search();
+        match panic::catch_unwind(AssertUnwindSafe(|| self.tick())) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                *self = Game {
+                    coach: self.coach,
+                    pending: std::mem::take(&mut self.pending),
+                    events: std::mem::take(&mut self.events),
+                    logger: self.logger.take(),
+                    listener: self.listener.take(),
+                    snapshot_interval: self.snapshot_interval,
+                    checkpoints: std::mem::take(&mut self.checkpoints),
+                    mirror: self.mirror.take(),
+                    metrics: std::mem::take(&mut self.metrics),
+                    ..before
+                };
+                self.sync_mirror();
+                Err(StateError::Internal(panic_message(e)))
+            }
+        }
+    }
+
+    /// Replace the current game state with the previous one
+    fn rollback(&mut self) -> Option<State> {
+        let prev = self.history.pop()?;
+        self.history_kept = self.history_kept.min(self.history.len());
+        let prev = Arc::try_unwrap(prev).unwrap_or_else(|x| (*x).clone());
+        Some(std::mem::replace(&mut self.state, prev))
     }
 
     /// Attempt to replace the current game state with the previous one
-    pub fn undo(&mut self) -> Option<State> {
-        let next = self.state.clone();
-        match self.history.pop() {
-            Some(prev) => {
-                self.state = prev;
-                Some(next)
+    ///
+    /// Returns the state that was undone.
+    pub fn undo(&mut self) -> Result<State, GameError> {
+        let prev = match self.history.last() {
+            _ if self.rules.undo == UndoPolicy::Disabled => Err(GameError::UndoDisabled),
+            None => Err(GameError::NothingToUndo),
+            Some(prev) => Ok(prev.turn),
+        }?;
+        match self.rules.undo {
+            UndoPolicy::OwnTurnOnly if prev != self.state.turn => Err(GameError::TurnIsOver),
+            UndoPolicy::Limited(n) if self.undos[prev as usize] >= n => {
+                Err(GameError::UndoLimitReached)
+            }
+            _ if self.undos[prev as usize] >= MAX_GAME_UNDOS => Err(GameError::UndoLimitReached),
+            _ => {
+                let undone = self.rollback().ok_or(GameError::NothingToUndo)?;
+                if let Some(m) = self.played.pop() {
+                    self.undone.push(m.annotation);
+                }
+                self.journal.push(Entry::Undo);
+                self.moved = false;
+                self.game_moves = self.game_moves.saturating_sub(1);
+                self.undos[prev as usize] = self.undos[prev as usize].saturating_add(1);
+                self.version += 1;
+                self.metrics.undos += 1;
+                self.sync_mirror();
+                Ok(undone)
             }
-            None => None,
+        }
+    }
+
+    /// Play the most recently undone move again
+    pub fn redo(&mut self) -> Result<MoveOutcome, Error> {
+        let mut undone = std::mem::take(&mut self.undone);
+        let x = undone.pop().ok_or(GameError::NothingToRedo)?;
+        let res = self.apply_annotation(&x);
+        if res.is_err() {
+            undone.push(x);
+        }
+        self.undone = undone;
+        res
+    }
+
+    /// Get the record of everything done to the match, to save it or rebuild it later
+    pub fn journal(&self) -> Journal {
+        Journal {
+            seed: self.rng.rng_borrow().get_seed(),
+            dealer: self.dealer,
+            rules: self.rules.clone(),
+            players: self.players.clone(),
+            entries: self.journal.clone(),
+            snapshot_interval: self.snapshot_interval,
+            checkpoints: self.checkpoints.clone(),
+        }
+    }
+
+    /// Attempt to undo the most recent move on behalf of the player who made it
+    pub fn undo_for(&mut self, p: PlayerId) -> Result<State, GameError> {
+        match self.history.last() {
+            Some(prev) if self.rules.undo != UndoPolicy::Disabled && prev.turn != p => {
+                Err(GameError::OpponentMoved)
+            }
+            _ => self.undo(),
         }
     }
 
     /// Attempt to apply a move to the current game state
+    ///
+    /// Fails if `MAX_GAME_MOVES` moves already stand in the current game. Moves that were undone
+    /// don't count, so undoing never stops a game from being finished.
     pub fn apply(&mut self, m: Move) -> Result<(), StateError> {
-        self.history.push(self.state.clone());
-        if let Err(e) = self.state.apply(m) {
-            self.undo();
+        let annotation = m.to_annotation();
+        let player = self.state.turn;
+        self.history.push(Arc::new(self.state.clone()));
+        #[cfg(not(target_arch = "wasm32"))]
+        let started = std::time::Instant::now();
+        let res = if self.game_moves >= MAX_GAME_MOVES {
+            Err(StateError::MoveLimitReached)
+        } else {
+            panic::catch_unwind(AssertUnwindSafe(|| self.state.apply(m)))
+                .unwrap_or_else(|e| Err(StateError::Internal(panic_message(e))))
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        if res.is_ok() {
+            self.metrics.apply_time += started.elapsed();
+        }
+        if let Err(e) = res {
+            self.metrics.fail(Failure::of_state(&e));
+            self.rollback();
+            self.log(LogRecord::Rejected {
+                player,
+                annotation,
+                error: e.to_string(),
+            });
             Err(e)
         } else {
+            self.version += 1;
+            self.game_moves += 1;
+            self.metrics.moves_applied += 1;
+            self.moved = true;
+            self.journal.push(Entry::Move(annotation.clone()));
+            self.undone.clear();
+            if let Some(before) = self.history.last().cloned() {
+                self.emit_scoring(&before);
+            }
+            self.log(LogRecord::Move {
+                player,
+                annotation: annotation.clone(),
+            });
+            self.played.push(PlayedMove {
+                turn: self.played.len(),
+                player,
+                raw: annotation.clone(),
+                annotation,
+                elapsed_ms: self
+                    .turn_started
+                    .zip(self.touched)
+                    .map(|(started, (_, now))| now.saturating_sub(started)),
+            });
+            self.sync_mirror();
             Ok(())
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::action::Annotation;
-    use crate::card::{Card, Suit, Value};
-    use crate::pile::{Mark, Pile};
+    /// Attempt to apply a move on behalf of the given player
+    pub fn apply_for(&mut self, p: PlayerId, m: Move) -> Result<(), StateError> {
+        if p != self.state.turn {
+            Err(StateError::NotYourTurn)
+        } else {
+            self.apply(m)
+        }
+    }
 
-    #[test]
-    fn test_sanity() {
-        // Setup with the default seed
-        let mut g = Game::default();
-        g.seed(Seed::default());
-        g.deal();
+    /// Deal a new game from the seed and play the moves on it, ending the turn after each one
+    ///
+    /// Fails with the position of the first illegal move.
+    pub fn replay(seed: Seed, moves: &[&str]) -> Result<Game, ReplayError> {
+        // The default setup is always supported
+        let mut g = Game::builder().seed(seed).build().unwrap();
+        for (i, x) in moves.iter().enumerate() {
+            g.apply_annotation(x)
+                .map_err(|e| ReplayError::IllegalMove(i, e))?;
+            g.tick();
+        }
+        Ok(g)
+    }
 
-        // Apply the move *C&3
-        let m = Annotation::new(String::from("*C&3")).to_move();
-        assert!(m.is_ok());
-        assert!(g.apply(m.unwrap()).is_ok());
+    /// Replay the first moves of the match on a new game with the same seed and rules
+    fn replay_to(&self, moves: usize) -> Option<Game> {
+        let mut g = Game::builder()
+            .seed(self.rng.rng_borrow().get_seed())
+            .rules(self.rules.clone())
+            .dealer(self.dealer)
+            .build()
+            .ok()?;
+        for m in self.played.get(..moves)? {
+            g.apply_annotation(&m.annotation).ok()?;
+            g.tick();
+        }
+        Some(g)
+    }
 
-        // Check that state matches expectations
-        assert_eq!(
-            g.state.floor,
-            vec![
-                Pile::single(Card::create(Value::Four, Suit::Clubs)),
-                Pile::single(Card::create(Value::Seven, Suit::Diamonds)),
-                Pile::single(Card::create(Value::Eight, Suit::Clubs)),
-                Pile::default(), // Pile::single(Card::create(Value::Two, Suit::Spades)),
-                Pile::default(),
-                Pile::default(),
-                Pile::default(),
-                Pile::default(),
-                Pile::default(),
-                Pile::default(),
-                Pile::default(),
-                Pile::default(),
-                Pile::default()
-            ]
-        );
+    /// Get the score of the game in progress right after the given move was played
+    ///
+    /// The match is replayed from its seed, so this works for moves in earlier games too.
+    pub fn score_at(&self, turn: usize) -> Option<Score> {
+        let mut g = self.replay_to(turn)?;
+        let game = g.game as usize;
+        g.apply_annotation(&self.played.get(turn)?.annotation)
+            .ok()?;
+        g.tick();
+        g.scores.get_mut(game).map(std::mem::take)
+    }
 
-        assert_eq!(
-            g.state.opponent.hand,
-            [
-                Pile::single(Card::create(Value::Ace, Suit::Hearts)),
-                Pile::single(Card::create(Value::King, Suit::Clubs)),
-                Pile::default(), // Pile::single(Card::create(Value::Two, Suit::Diamonds)),
-                Pile::single(Card::create(Value::Ace, Suit::Clubs)),
-                Pile::single(Card::create(Value::Seven, Suit::Clubs)),
+    /// Start a new game from the position before the given move, to explore other lines
+    ///
+    /// The branch has the same seed, rules, players, and moves up to that point, and nothing
+    /// played on it affects this game.
+    pub fn branch_at(&self, turn: usize) -> Option<Game> {
+        let mut g = self.replay_to(turn)?;
+        g.played = self.played[..turn].to_vec();
+        g.players = self.players.clone();
+        Some(g)
+    }
+
+    /// Copy the game for a bot to search ahead from
+    ///
+    /// The fork shares the undo history with this game rather than copying it, and has no
+    /// logger, listener, queued moves, checkpoints, mirror, metrics, or unread events. Nothing
+    /// played on it affects this game.
+    pub fn fork_for_search(&self) -> Game {
+        Game {
+            game: self.game,
+            round: self.round,
+            rng: self.rng.clone(),
+            rules: self.rules.clone(),
+            dealer: self.dealer,
+            state: self.state.clone(),
+            scores: self.scores.clone(),
+            forfeit: self.forfeit,
+            result: self.result.clone(),
+            players: self.players.clone(),
+            coach: false,
+            history: self.history.clone(),
+            history_kept: 0,
+            undos: self.undos,
+            game_moves: self.game_moves,
+            turn_started: self.turn_started,
+            pending: [None, None],
+            version: self.version,
+            events: vec![],
+            touched: self.touched,
+            played: self.played.clone(),
+            moved: self.moved,
+            deals: self.deals.clone(),
+            journal: self.journal.clone(),
+            undone: self.undone.clone(),
+            snapshot_interval: None,
+            checkpoints: vec![],
+            hidden: self.hidden,
+            commitments: self.commitments.clone(),
+            reveals: self.reveals.clone(),
+            sealed: self.sealed.clone(),
+            salt_key: self.salt_key,
+            token_key: self.token_key,
+            mirror: None,
+            metrics: Metrics::default(),
+            logger: None,
+            listener: None,
+        }
+    }
+
+    /// Compare the given move to the other moves the player could have made
+    ///
+    /// The engine scores each move by searching the given number of moves ahead from it, on
+    /// deals of the cards the player couldn't see. The same move is always graded the same.
+    pub fn move_stats(&self, turn: usize, depth: u8) -> Option<MoveStats> {
+        let m = self.played.get(turn)?;
+        let g = self.replay_to(turn)?;
+        let mut rng = ChaCha20Rng::from_seed(Seed::default());
+        rng.set_stream(turn as u64);
+        let ranked = rank_moves_sampled(&g.state, depth, GRADE_SAMPLES, &mut rng);
+        // The move is ranked under the annotation of the listed move that plays the same
+        let listed = g
+            .state
+            .listed_move(&m.to_move())
+            .map_or(m.annotation.clone(), |x| x.to_annotation());
+        Some(MoveStats {
+            player: m.player,
+            annotation: m.annotation.clone(),
+            elapsed_ms: m.elapsed_ms,
+            rank: ranked.iter().position(|(x, _)| *x == listed),
+            grade: Tag::grade(&ranked, &listed),
+            ranked,
+        })
+    }
+
+    /// Get an entry for every move played in the match so far, in order
+    ///
+    /// The match is replayed from its seed once, so this works for finished matches too.
+    pub fn timeline(&self) -> Vec<TimelineEntry> {
+        let Some(mut g) = self.replay_to(0) else {
+            return vec![];
+        };
+        let mut entries = vec![];
+        for (turn, m) in self.played.iter().enumerate() {
+            let (game, round) = (g.game, g.round);
+            let taken = g.state.seat(m.player).into_pair_cards().len();
+            let suipis = g.state.seat(m.player).suipi_count;
+            if g.apply_annotation(&m.annotation).is_err() {
+                break;
+            }
+            let seat = g.state.seat(m.player);
+            entries.push(TimelineEntry {
+                turn,
+                game,
+                round,
+                player: m.player,
+                annotation: m.annotation.clone(),
+                captured: seat.into_pair_cards().split_off(taken),
+                suipi: seat.suipi_count > suipis,
+                floor_hash: floor_hash(&g.state.floor),
+            });
+            g.tick();
+        }
+        entries
+    }
+
+    /// Estimate the first player's chance of winning the match after every move played so far
+    ///
+    /// Each estimate plays the rest of the match out `playouts` times with random moves, shuffling
+    /// the cards left in the deck and every later deal each time. A tie counts as half a win. The
+    /// playouts are seeded from the match seed, so a match always gives the same curve.
+    pub fn win_probabilities(&self, playouts: u32) -> Vec<f64> {
+        let Some(mut g) = self.replay_to(0) else {
+            return vec![];
+        };
+        let mut rng = ChaCha20Rng::from_seed(self.rng.rng_borrow().get_seed());
+        // Stay clear of the stream the deck is shuffled with
+        rng.set_stream(u64::MAX);
+        let mut curve = vec![];
+        for m in &self.played {
+            if g.apply_annotation(&m.annotation).is_err() {
+                break;
+            }
+            g.tick();
+            let mut wins = 0.0;
+            for _ in 0..playouts {
+                let mut f = g.fork_for_search();
+                let mut seed = Seed::default();
+                rng.fill_bytes(&mut seed);
+                f.rng = Rng::from_seed(seed);
+                f.state.deck.make_contiguous().shuffle(&mut rng);
+                wins += f.play_out(&mut rng);
+            }
+            curve.push(wins / playouts.max(1) as f64);
+        }
+        curve
+    }
+
+    /// Play random moves until the match is over, returning 1 if the first player won, 0 if they
+    /// lost, and 0.5 for a tie
+    fn play_out(&mut self, rng: &mut ChaCha20Rng) -> f64 {
+        while !self.is_over() {
+            let Some(x) = candidate_moves(&self.state).choose(rng).cloned() else {
+                break;
+            };
+            if self.apply_annotation(&x).is_err() {
+                break;
+            }
+            self.tick();
+        }
+        match self.result.as_ref().and_then(|x| x.winner) {
+            Some(PlayerId::Opponent) => 1.0,
+            Some(PlayerId::Dealer) => 0.0,
+            None => 0.5,
+        }
+    }
+
+    /// Export a copy of the game, with every card showing
+    pub fn export(&self) -> Snapshot {
+        Snapshot {
+            status: self.status(),
+            state: self.state.clone(),
+            scores: self.scores.clone(),
+            moves: self.played.clone(),
+            result: self.result.clone(),
+            commitments: self.commitments.clone(),
+            reveals: self.reveals.clone(),
+        }
+    }
+
+    /// Export a copy of the game that only shows what the given player is allowed to see
+    pub fn export_for(&self, p: PlayerId) -> Snapshot {
+        Snapshot {
+            status: self.status().redacted(),
+            state: self.state.redacted_for(p),
+            ..self.export()
+        }
+    }
+
+    /// Take a snapshot of the whole game, including the RNG's position, as bytes
+    ///
+    /// A restored game carries on exactly as this one would. Events not yet taken, journal
+    /// checkpoints, the state mirror, metrics, the logger, and the listener are not saved.
+    pub fn snapshot(&self) -> Vec<u8> {
+        self.encode(&self.history, &self.journal)
+    }
+
+    /// Write the game as bytes, with the given undo history and journal entries
+    fn encode(&self, history: &[Arc<State>], journal: &[Entry]) -> Vec<u8> {
+        let mut w = header();
+        [self.game, self.round].encode(&mut w);
+        self.rng.encode(&mut w);
+        self.rules.encode(&mut w);
+        self.dealer.encode(&mut w);
+        self.state.encode(&mut w);
+        self.scores.encode(&mut w);
+        self.forfeit.encode(&mut w);
+        self.result.encode(&mut w);
+        self.players.encode(&mut w);
+        self.coach.encode(&mut w);
+        history.len().encode(&mut w);
+        for x in history.iter() {
+            x.encode(&mut w);
+        }
+        self.undos.encode(&mut w);
+        self.game_moves.encode(&mut w);
+        self.turn_started.encode(&mut w);
+        self.pending.encode(&mut w);
+        self.version.encode(&mut w);
+        self.touched.encode(&mut w);
+        self.played.encode(&mut w);
+        self.moved.encode(&mut w);
+        self.deals.encode(&mut w);
+        journal.len().encode(&mut w);
+        for x in journal.iter() {
+            x.encode(&mut w);
+        }
+        self.undone.encode(&mut w);
+        self.snapshot_interval.encode(&mut w);
+        self.hidden.encode(&mut w);
+        self.commitments.encode(&mut w);
+        self.reveals.encode(&mut w);
+        self.sealed.encode(&mut w);
+        w.extend_from_slice(&self.salt_key);
+        w
+    }
+
+    /// Restore a game from a `snapshot`
+    ///
+    /// Bytes that don't hold a game any build could have saved are rejected, rather than
+    /// restored into a game that breaks later.
+    pub fn restore(bytes: &[u8]) -> Result<Game, SaveError> {
+        let mut r = Reader::new(bytes)?;
+        let [game, round] = Codec::decode(&mut r)?;
+        let mut g = Game {
+            game,
+            round,
+            rng: Codec::decode(&mut r)?,
+            rules: Codec::decode(&mut r)?,
+            dealer: Codec::decode(&mut r)?,
+            state: Codec::decode(&mut r)?,
+            scores: Codec::decode(&mut r)?,
+            forfeit: Codec::decode(&mut r)?,
+            result: Codec::decode(&mut r)?,
+            players: Codec::decode(&mut r)?,
+            coach: Codec::decode(&mut r)?,
+            ..Game::default()
+        };
+        g.history = Vec::<State>::decode(&mut r)?
+            .into_iter()
+            .map(Arc::new)
+            .collect();
+        g.undos = Codec::decode(&mut r)?;
+        g.game_moves = Codec::decode(&mut r)?;
+        g.turn_started = Codec::decode(&mut r)?;
+        g.pending = Codec::decode(&mut r)?;
+        g.version = Codec::decode(&mut r)?;
+        g.touched = Codec::decode(&mut r)?;
+        g.played = Codec::decode(&mut r)?;
+        g.moved = Codec::decode(&mut r)?;
+        g.deals = Codec::decode(&mut r)?;
+        g.journal = Codec::decode(&mut r)?;
+        g.undone = Codec::decode(&mut r)?;
+        g.snapshot_interval = Codec::decode(&mut r)?;
+        g.hidden = Codec::decode(&mut r)?;
+        g.commitments = Codec::decode(&mut r)?;
+        g.reveals = Codec::decode(&mut r)?;
+        g.sealed = Codec::decode(&mut r)?;
+        g.salt_key = r.take(32)?.try_into().unwrap();
+        r.finish()?;
+        g.check_progress()?;
+        Ok(g)
+    }
+
+    /// Check that a restored game's position in the match fits its scores and deals, and that
+    /// every move it recorded can be read back
+    fn check_progress(&self) -> Result<(), SaveError> {
+        if self.scores.len() != self.game as usize + 1 || self.round as usize >= GAME_DEALS {
+            return Err(SaveError::InvalidValue("game"));
+        }
+        // A match that ends on a misdeal moves on to a round it never deals
+        let current = self.deals.last().map(|x| (x.game, x.round));
+        if current != Some((self.game, self.round)) && self.result.is_none() {
+            return Err(SaveError::InvalidValue("deals"));
+        }
+        for m in &self.played {
+            if Annotation::new(m.annotation.clone()).to_move().is_err() {
+                return Err(SaveError::InvalidValue("move"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get every move played in the match so far
+    pub fn moves(&self) -> &[PlayedMove] {
+        &self.played
+    }
+
+    /// Get every deal in the match so far, the current one last
+    pub fn deals(&self) -> &[Deal] {
+        &self.deals
+    }
+
+    /// Get the number of deals in a match played to the end
+    pub fn deal_total(&self) -> usize {
+        self.rules.games as usize * GAME_DEALS
+    }
+
+    /// Get the deal a move was played in
+    pub fn deal_of(&self, turn: usize) -> Option<&Deal> {
+        if turn >= self.played.len() {
+            return None;
+        }
+        self.deals
+            .iter()
+            .rev()
+            .find(|x| x.first_move as usize <= turn)
+    }
+
+    /// Get a token for the current game state, which changes whenever a move is made or undone
+    ///
+    /// Tokens handed out before a game was restored are stale in the restored game.
+    pub fn turn_token(&self) -> TurnToken {
+        // Each version gets its own stream of a secret key, so tokens can't be worked back to
+        // the key, the seed, or each other
+        let mut rng = ChaCha20Rng::from_seed(self.token_key);
+        rng.set_stream(self.version);
+        TurnToken(rng.next_u64())
+    }
+
+    /// Attempt to apply a move from an annotation, if the game is still in the given state
+    ///
+    /// Servers should use this so that a move resent by a reconnecting client is not played
+    /// twice.
+    pub fn apply_with_token(&mut self, token: TurnToken, a: &str) -> Result<MoveOutcome, Error> {
+        if token != self.turn_token() {
+            return Err(GameError::StaleTurnToken.into());
+        }
+        self.apply_annotation(a)
+    }
+
+    /// Attempt to apply a move from an annotation to the current game state
+    pub fn apply_annotation(&mut self, a: &str) -> Result<MoveOutcome, Error> {
+        self.apply_annotation_with_profile(a, Profile::default())
+    }
+
+    /// Attempt to apply a move from an annotation read with the given profile
+    pub fn apply_annotation_with_profile(
+        &mut self,
+        a: &str,
+        profile: Profile,
+    ) -> Result<MoveOutcome, Error> {
+        let parsed = Annotation::with_profile(String::from(a), profile)
+            .expand(&self.state)
+            .and_then(|x| Ok((x.to_move()?, x)));
+        let (m, x) = match parsed {
+            Ok(x) => x,
+            Err(e) => {
+                self.metrics.fail(Failure::Parsing);
+                self.log(LogRecord::Rejected {
+                    player: self.state.turn,
+                    annotation: a.to_string(),
+                    error: e.to_string(),
+                });
+                return Err(e.into());
+            }
+        };
+        let turn = self.state.turn;
+        let pairs = self.state.player().pairs.len();
+        let suipis = self.state.player().suipi_count;
+        let hints = if self.coach {
+            review(&self.state, &x.value)
+        } else {
+            vec![]
+        };
+        self.apply(m)?;
+        if let Some(x) = self.played.last_mut() {
+            x.raw = a.to_string();
+        }
+        Ok(MoveOutcome {
+            turn,
+            captured: self.state.player().pairs[pairs..]
+                .iter()
+                .flat_map(|p| p.cards.clone())
+                .collect(),
+            suipi: self.state.player().suipi_count > suipis,
+            keyframes: self.state.keyframes.clone(),
+            hints,
+        })
+    }
+
+    /// Attempt to apply a move from an annotation read with the given profile on behalf of the
+    /// given player
+    pub fn apply_annotation_for(
+        &mut self,
+        p: PlayerId,
+        a: &str,
+        profile: Profile,
+    ) -> Result<MoveOutcome, Error> {
+        if p != self.state.turn {
+            Err(StateError::NotYourTurn.into())
+        } else {
+            self.apply_annotation_with_profile(a, profile)
+        }
+    }
+
+    /// Get the annotation of the move the engine would play for the current player
+    ///
+    /// Picks the move that captures the most cards, preferring earlier hand cards on a tie.
+    pub fn engine_move(&self) -> Option<String> {
+        let pairs = |m: &Move| {
+            let mut s = self.state.clone();
+            match s.apply(m.clone()) {
+                Ok(()) => s.player().into_pair_cards().len(),
+                _ => 0,
+            }
+        };
+        self.state
+            .legal_moves()
+            .into_iter()
+            .rev()
+            .max_by_key(pairs)
+            .map(|m| m.to_annotation())
+    }
+
+    /// Recommend a move for the current player, with the reason for it
+    ///
+    /// Only what the current player can see is used. Returns `None` once the match is over.
+    pub fn hint(&self) -> Option<MoveHint> {
+        if self.is_over() {
+            return None;
+        }
+        coach::hint(&self.state)
+    }
+
+    /// Get the annotation for discarding the current player's lowest card, if they can discard
+    fn lowest_discard(&self) -> Option<String> {
+        let (i, _) = self
+            .state
+            .player()
+            .hand
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| !x.is_empty())
+            .min_by_key(|(_, x)| x.value)?;
+        let x = format!("!{}", i + 1);
+        let m = Annotation::new(x.clone()).to_move().ok()?;
+        self.state.clone().apply(m).ok().map(|_| x)
+    }
+
+    /// Get the time left for the current player to move, including the grace period
+    pub fn time_left(&self, now_ms: u64) -> Option<u64> {
+        let clock = self.rules.clock?;
+        let elapsed = now_ms.saturating_sub(self.turn_started.unwrap_or(now_ms));
+        Some(
+            clock
+                .turn_ms
+                .saturating_add(clock.grace_ms)
+                .saturating_sub(elapsed),
+        )
+    }
+
+    /// Advance the turn clock to the given time in milliseconds
+    ///
+    /// The current player's clock starts on the first touch of their turn. Once their time and
+    /// grace period have run out the timeout policy is applied on their behalf, ending the turn.
+    ///
+    /// The match duration is measured from the first touch to the last, since this is the only
+    /// way the game learns the time. Clients that want it to be right must touch the game before
+    /// every move, including the one that ends the match.
+    pub fn touch(&mut self, now_ms: u64) -> Option<Timeout> {
+        if self.is_over() {
+            return None;
+        }
+        let first = self.touched.map_or(now_ms, |(first, _)| first);
+        self.touched = Some((first, now_ms.max(first)));
+        let clock = self.rules.clock?;
+        let started = *self.turn_started.get_or_insert(now_ms);
+        if now_ms.saturating_sub(started) <= clock.turn_ms.saturating_add(clock.grace_ms) {
+            return None;
+        }
+        let p = self.state.turn;
+        let x = match clock.timeout {
+            TimeoutPolicy::AutoDiscard => self.lowest_discard().or_else(|| self.engine_move()),
+            TimeoutPolicy::AutoMove => self.engine_move(),
+            TimeoutPolicy::Forfeit => None,
+        };
+        match x {
+            Some(x) if self.apply_annotation(&x).is_ok() => {
+                self.tick();
+                self.turn_started = Some(now_ms);
+                Some(Timeout::Moved(p, x))
+            }
+            _ => {
+                // The match is not over, so the player can always time out
+                let _ = self.time_out(p);
+                Some(Timeout::Forfeited(p))
+            }
+        }
+    }
+
+    /// Queue a move for the given player to play once it is their turn
+    ///
+    /// Floor piles in the move are tracked by id, so the move still refers to the same piles
+    /// after the floor is rearranged by the other player. Replaces any move already queued.
+    pub fn submit(&mut self, p: PlayerId, annotation: &str) -> Result<(), Error> {
+        Annotation::new(String::from(annotation))
+            .to_move()?
+            .is_valid()?;
+        let piles = annotation
+            .bytes()
+            .filter(|x| x.is_ascii_uppercase())
+            .map(|x| match self.state.floor.get((x - b'A') as usize) {
+                Some(f) if !f.is_empty() => Ok(f.id),
+                _ => Err(StateError::InvalidAddress),
+            })
+            .collect::<Result<Vec<u16>, StateError>>()?;
+        self.pending[p as usize] = Some(PendingMove {
+            annotation: annotation.to_string(),
+            piles,
+        });
+        Ok(())
+    }
+
+    /// Get the move queued by the given player
+    pub fn pending(&self, p: PlayerId) -> Option<&PendingMove> {
+        self.pending[p as usize].as_ref()
+    }
+
+    /// Remove the move queued by the given player
+    pub fn cancel(&mut self, p: PlayerId) -> Option<PendingMove> {
+        self.pending[p as usize].take()
+    }
+
+    /// Rewrite a queued move's floor addresses to where its piles are now
+    fn relocate(&self, m: &PendingMove) -> Option<String> {
+        let mut piles = m.piles.iter();
+        m.annotation
+            .chars()
+            .map(|x| match x {
+                'A'..='V' => self
+                    .state
+                    .address_of(*piles.next()?)
+                    .map(|a| a.to_string().remove(0)),
+                _ => Some(x),
+            })
+            .collect()
+    }
+
+    /// Play queued moves in turn order until the current player has nothing queued
+    ///
+    /// A queued move that no longer applies is dropped and reported as a conflict, leaving the
+    /// player to submit a new move.
+    pub fn resolve(&mut self) -> Vec<Resolution> {
+        let mut res = vec![];
+        while !self.is_over() {
+            let p = self.state.turn;
+            let Some(m) = self.pending[p as usize].take() else {
+                break;
+            };
+            let Some(x) = self.relocate(&m) else {
+                res.push(Resolution::Conflict(
+                    p,
+                    m.annotation,
+                    GameError::PileCaptured.into(),
+                ));
+                break;
+            };
+            match self.apply_annotation_for(p, &x, Profile::Standard) {
+                Ok(_) => {
+                    self.tick();
+                    res.push(Resolution::Applied(p, x));
+                }
+                Err(e) => {
+                    res.push(Resolution::Conflict(p, x, e));
+                    break;
+                }
+            }
+        }
+        res
+    }
+
+    /// Get the status signals for the game
+    ///
+    /// The seed is zeroed when the deal is hidden, since it gives away the deck.
+    pub fn status(&self) -> Status {
+        let status = Status {
+            game: self.game,
+            round: self.round,
+            turn: self.state.turn,
+            next: self.next_to_act(),
+            hand: self.state.player().card_count() as u8,
+            floor: self.state.floor_count() as u8,
+            seed: self.rng.rng_borrow().get_seed(),
+            dealer: self.dealer,
+            games: self.rules.games,
+            over: self.is_over(),
+        };
+        match self.hidden {
+            true => status.redacted(),
+            false => status,
+        }
+    }
+
+    /// Get the metadata attached to the given player
+    pub fn player_info(&self, p: PlayerId) -> Option<&PlayerInfo> {
+        self.players[p as usize].as_ref()
+    }
+
+    /// Get the display name of the given player, falling back to their seat
+    pub fn player_name(&self, p: PlayerId) -> String {
+        match self.player_info(p) {
+            Some(info) if !info.name.is_empty() => info.name.clone(),
+            _ => p.to_string(),
+        }
+    }
+
+    /// Get the piles on the floor
+    pub fn floor(&self) -> &[Pile] {
+        &self.state.floor
+    }
+
+    /// Get the hand of the given player
+    pub fn hand(&self, p: PlayerId) -> &[Pile] {
+        &self.state.seat(p).hand
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::{Address, Annotation};
+    use crate::card::{Card, Suit, Value};
+    use crate::consts::{DECK_SIZE, HAND_SIZE};
+    use crate::pile::{Mark, Pile};
+    use crate::rules::Clock;
+    use crate::state::Destination;
+
+    #[test]
+    fn test_sanity() {
+        // Setup with the default seed
+        let mut g = Game::default();
+        g.seed(Seed::default());
+        g.deal();
+
+        // Apply the move *C&3
+        let m = Annotation::new(String::from("*C&3")).to_move();
+        assert!(m.is_ok());
+        assert!(g.apply(m.unwrap()).is_ok());
+
+        // Check that state matches expectations
+        assert_eq!(
+            g.state.floor,
+            vec![
+                Pile::single(Card::create(Value::Four, Suit::Clubs)),
+                Pile::single(Card::create(Value::Seven, Suit::Diamonds)),
+                Pile::single(Card::create(Value::Eight, Suit::Clubs)),
+                Pile::default(), // Pile::single(Card::create(Value::Two, Suit::Spades)),
+                Pile::default(),
+                Pile::default(),
+                Pile::default(),
+                Pile::default(),
+                Pile::default(),
+                Pile::default(),
+                Pile::default(),
+                Pile::default(),
+                Pile::default()
+            ]
+        );
+
+        assert_eq!(
+            g.state.opponent.hand,
+            [
+                Pile::single(Card::create(Value::Ace, Suit::Hearts)),
+                Pile::single(Card::create(Value::King, Suit::Clubs)),
+                Pile::default(), // Pile::single(Card::create(Value::Two, Suit::Diamonds)),
+                Pile::single(Card::create(Value::Ace, Suit::Clubs)),
+                Pile::single(Card::create(Value::Seven, Suit::Clubs)),
                 Pile::single(Card::create(Value::Eight, Suit::Spades)),
                 Pile::single(Card::create(Value::King, Suit::Hearts)),
                 Pile::single(Card::create(Value::Three, Suit::Spades)),
@@ -165,4 +1893,1321 @@ mod tests {
 
         assert_eq!(g.state.dealer.pairs, vec![]);
     }
+
+    #[test]
+    fn test_builder() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_eq!(g.state.floor_count(), 4);
+        assert_eq!(g.state.dealer.card_count(), 8);
+        assert_eq!(g.state.opponent.card_count(), 8);
+        assert_eq!(g.dealer, PlayerId::Dealer);
+        assert_eq!(g.state.turn, PlayerId::Opponent);
+        assert!(!g.is_over());
+
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .rules(Rules {
+                games: 1,
+                ..Rules::default()
+            })
+            .players(2)
+            .dealer(PlayerId::Opponent)
+            .build()
+            .unwrap();
+        assert_eq!(g.state.turn, PlayerId::Dealer);
+        assert_eq!(
+            g.state.dealer.hand[0],
+            Pile::single(Card::create(Value::Ace, Suit::Hearts))
+        );
+        assert!(g.apply_annotation("!1").is_ok());
+        g.tick();
+        assert_eq!(g.state.turn, PlayerId::Opponent);
+        assert!(g.apply_annotation("!1").is_ok());
+        g.tick();
+        assert_eq!(g.state.turn, PlayerId::Dealer);
+
+        assert_eq!(
+            Game::builder().players(4).build().err(),
+            Some(GameError::UnsupportedPlayerCount(4))
+        );
+        for n in [3, 23] {
+            let rules = Rules {
+                floor_size: n,
+                ..Rules::default()
+            };
+            assert_eq!(
+                Game::builder().rules(rules).build().err(),
+                Some(GameError::UnsupportedFloorSize(n))
+            );
+        }
+        // A floor full of repeated values may leave nothing to capture and nowhere to discard
+        let rules = Rules {
+            unique_floor: false,
+            ..Rules::default()
+        };
+        assert_eq!(
+            Game::builder().rules(rules.clone()).build().err(),
+            Some(GameError::FloorCanStall)
+        );
+        let rules = Rules {
+            floor_overflow: FloorOverflow::AwardLastScorer,
+            ..rules
+        };
+        assert!(Game::builder().rules(rules).build().is_ok());
+        // and so may a floor too small to hold every value
+        let rules = Rules {
+            floor_size: 4,
+            ..Rules::default()
+        };
+        assert_eq!(
+            Game::builder().rules(rules.clone()).build().err(),
+            Some(GameError::FloorCanStall)
+        );
+        let rules = Rules {
+            floor_overflow: FloorOverflow::AwardLastScorer,
+            ..rules
+        };
+        assert!(Game::builder().rules(rules).build().is_ok());
+    }
+
+    #[test]
+    fn test_overflow_before_any_capture() {
+        for dealer in [PlayerId::Dealer, PlayerId::Opponent] {
+            let rules = Rules {
+                floor_size: 4,
+                floor_overflow: FloorOverflow::AwardLastScorer,
+                ..Rules::default()
+            };
+            let mut g = Game::builder()
+                .seed(Seed::default())
+                .rules(rules)
+                .dealer(dealer)
+                .build()
+                .unwrap();
+            assert_eq!(g.state.floor_count(), 4);
+            let card = g.state.player().hand[0].cards[0];
+            g.apply_annotation("!1").unwrap();
+            // Nobody has captured, so the dealer takes the discard
+            assert_eq!(g.state.seat(dealer).pairs.last().unwrap().cards, vec![card]);
+            assert!(g.state.seat(dealer.other()).pairs.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_redo() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_eq!(g.redo(), Err(Error::Game(GameError::NothingToRedo)));
+        g.apply_annotation("*C&3").unwrap();
+        let floor = g.state.floor.clone();
+        g.undo().unwrap();
+        g.redo().unwrap();
+        assert_eq!(g.state.floor, floor);
+        assert_eq!(g.moves().len(), 1);
+
+        // Playing a different move forgets what was undone
+        g.undo().unwrap();
+        g.apply_annotation("!1").unwrap();
+        assert_eq!(g.redo(), Err(Error::Game(GameError::NothingToRedo)));
+        assert_eq!(g.journal().project().unwrap().state.floor, g.state.floor);
+    }
+
+    #[test]
+    fn test_hidden_deal() {
+        let mut open = Game::builder().seed(Seed::default()).build().unwrap();
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .hidden()
+            .build()
+            .unwrap();
+        assert!(g.is_hidden() && !open.is_hidden());
+        assert!(open.commitments().is_empty());
+        // Hiding the deal does not change the cards
+        assert_eq!(g.state.opponent.hand, open.state.opponent.hand);
+        assert_eq!(g.commitments().len(), 1);
+        assert!(g.reveals().is_empty());
+
+        // Clients only see a redacted state along with the commitment
+        let view = g.export_for(PlayerId::Opponent);
+        assert!(view.state.deck.iter().all(|&c| c == Card::invalid()));
+        assert_eq!(view.commitments, g.commitments());
+
+        // The commitment survives a save, and the game carries on the same way
+        let mut h = Game::restore(&g.snapshot()).unwrap();
+        while !g.is_over() {
+            if let Some(x) = candidate_moves(&g.state).first() {
+                g.apply_annotation(x).unwrap();
+                h.apply_annotation(x).unwrap();
+                open.apply_annotation(x).unwrap();
+            }
+            g.tick();
+            h.tick();
+            open.tick();
+            assert_eq!(g.reveals().len(), g.game.min(g.rules.games) as usize);
+        }
+        assert_eq!(h.reveals(), g.reveals());
+        assert_eq!(g.commitments().len(), g.rules.games as usize);
+        assert_eq!(g.scores, open.scores);
+        for (c, x) in g.commitments().iter().zip(g.reveals()) {
+            assert!(x.verify(c));
+            assert_eq!(x.order.len(), DECK_SIZE);
+        }
+        // Dealing the revealed order gives the hands the first game started with
+        let order = &g.reveals()[0].order;
+        assert_eq!(g.deals()[0].hands_dealt as usize, HANDS_SIZE);
+        assert_eq!(
+            Game::builder()
+                .seed(Seed::default())
+                .build()
+                .unwrap()
+                .state
+                .opponent
+                .hand,
+            (0..HAND_SIZE)
+                .map(|i| Pile::single(order[2 * i]))
+                .collect::<Vec<Pile>>()
+        );
+
+        // Resigning reveals the game in progress
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .hidden()
+            .build()
+            .unwrap();
+        g.resign(PlayerId::Dealer).unwrap();
+        assert!(g.reveals()[0].verify(&g.commitments()[0]));
+    }
+
+    #[test]
+    fn test_save_restore() {
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .player(PlayerId::Dealer, PlayerInfo::named("Bo"))
+            .build()
+            .unwrap();
+        // Play into the second round, so the RNG has been used for more than the first shuffle
+        while g.round < 1 {
+            let x = candidate_moves(&g.state)[0].clone();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+        g.apply_annotation(&candidate_moves(&g.state)[0].clone())
+            .unwrap();
+        let bytes = g.snapshot();
+        let mut h = Game::restore(&bytes).unwrap();
+        assert_eq!(h.snapshot(), bytes);
+        assert_eq!(h.state.floor, g.state.floor);
+        assert_eq!(h.players, g.players);
+        assert_eq!(h.journal(), g.journal());
+        // The undo history is saved too
+        assert!(Game::restore(&bytes).unwrap().undo().is_ok());
+
+        // Both games carry on the same way, dealing the same cards, to the end of the match
+        while !g.is_over() {
+            if let Some(x) = candidate_moves(&g.state).first() {
+                g.apply_annotation(x).unwrap();
+                h.apply_annotation(x).unwrap();
+            }
+            g.tick();
+            h.tick();
+            assert_eq!(h.state.deck, g.state.deck);
+            assert_eq!(h.snapshot(), g.snapshot());
+        }
+        assert_eq!(h.result, g.result);
+
+        assert_eq!(
+            Game::restore(&bytes[..40]).err(),
+            Some(SaveError::Truncated)
+        );
+        assert_eq!(Game::restore(b"{}").err(), Some(SaveError::NotASave));
+    }
+
+    #[test]
+    fn test_restore_corrupt() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let copy = || Game::restore(&g.snapshot()).unwrap();
+        let mut h = copy();
+        h.state.floor[0].value += 1;
+        assert_eq!(
+            Game::restore(&h.snapshot()).err(),
+            Some(SaveError::InvalidValue("pile"))
+        );
+        let mut h = copy();
+        h.state.floor.pop();
+        assert_eq!(
+            Game::restore(&h.snapshot()).err(),
+            Some(SaveError::InvalidValue("floor size"))
+        );
+        let mut h = copy();
+        h.state.dealer.hand.extend(h.state.opponent.hand.clone());
+        assert_eq!(
+            Game::restore(&h.snapshot()).err(),
+            Some(SaveError::InvalidValue("hand"))
+        );
+
+        let mut h = copy();
+        h.game = 1;
+        assert_eq!(
+            Game::restore(&h.snapshot()).err(),
+            Some(SaveError::InvalidValue("game"))
+        );
+
+        // Damaging any byte is either caught or restores a game that still plays on
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        for a in ["*C&3", "1", "!1"] {
+            g.apply_annotation(a).unwrap();
+            g.tick();
+        }
+        let bytes = g.snapshot();
+        for i in 0..bytes.len() {
+            for x in [0x00, 0x7f, 0xff] {
+                let mut b = bytes.clone();
+                b[i] = x;
+                if let Ok(mut h) = Game::restore(&b) {
+                    for m in h.moves() {
+                        m.to_move();
+                    }
+                    h.tick();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let g = Game::replay(Seed::default(), &["*C&3", "1", "!1"]).unwrap();
+        assert_eq!(g.moves().len(), 3);
+        assert_eq!(g.moves()[1].annotation, "!1");
+        assert_eq!(g.state.turn, PlayerId::Dealer);
+
+        let mut h = Game::builder().seed(Seed::default()).build().unwrap();
+        for x in ["*C&3", "1", "!1"] {
+            h.apply_annotation(x).unwrap();
+            h.tick();
+        }
+        assert_eq!(g.snapshot(), h.snapshot());
+
+        assert!(matches!(
+            Game::replay(Seed::default(), &["*C&3", "*A&1"]),
+            Err(ReplayError::IllegalMove(1, _))
+        ));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert!(g.apply_annotation("").is_err());
+        assert!(g.apply_annotation("*A&3").is_err());
+        assert!(g.apply_annotation("*A&A&3").is_err());
+        g.apply_annotation("*C&3").unwrap();
+        g.undo().unwrap();
+        g.apply_annotation("*C&3").unwrap();
+        let m = g.metrics();
+        assert_eq!(m.moves_applied, 2);
+        assert_eq!(m.undos, 1);
+        assert_eq!(m.failures_of(Failure::Parsing), 1);
+        assert_eq!(m.failures_of(Failure::Pile), 1);
+        assert_eq!(m.failures_of(Failure::Move), 1);
+        assert_eq!(m.total_failures(), 3);
+        assert!(m.apply_time > Duration::ZERO);
+
+        // Searching a fork does not count towards the game
+        let mut f = g.fork_for_search();
+        f.tick();
+        f.apply_annotation("!1").unwrap();
+        assert_eq!(g.metrics().moves_applied, 2);
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_eq!(g.undo().err(), Some(GameError::NothingToUndo));
+
+        // A rejected move does not leave anything to undo
+        assert!(g.apply_annotation("*A&3").is_err());
+        assert_eq!(g.undo().err(), Some(GameError::NothingToUndo));
+
+        assert!(g.apply_annotation("*C&3").is_ok());
+        g.tick();
+        assert!(g.apply_annotation("!1").is_ok());
+        g.tick();
+        assert_eq!(
+            g.undo_for(PlayerId::Opponent).err(),
+            Some(GameError::OpponentMoved)
+        );
+        let undone = g.undo_for(PlayerId::Dealer).unwrap();
+        assert_eq!(undone.floor_count(), 4);
+        assert_eq!(g.state.floor_count(), 3);
+        assert_eq!(g.state.turn, PlayerId::Dealer);
+
+        assert!(g.undo().is_ok());
+        assert_eq!(g.state.floor_count(), 4);
+        assert_eq!(g.undo().err(), Some(GameError::NothingToUndo));
+
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .rules(Rules {
+                undo: UndoPolicy::Disabled,
+                ..Rules::default()
+            })
+            .build()
+            .unwrap();
+        assert!(g.apply_annotation("*C&3").is_ok());
+        assert_eq!(g.undo().err(), Some(GameError::UndoDisabled));
+        assert_eq!(
+            g.undo_for(PlayerId::Opponent).err(),
+            Some(GameError::UndoDisabled)
+        );
+    }
+
+    #[test]
+    fn test_move_limit() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        for _ in 0..MAX_GAME_UNDOS {
+            g.apply_annotation("*C&3").unwrap();
+            g.undo().unwrap();
+        }
+        // Moves that were taken back don't count, so the game can still be played, but the
+        // player can't take back any more
+        assert_eq!(g.game_moves, 0);
+        g.apply_annotation("*C&3").unwrap();
+        assert_eq!(g.undo().err(), Some(GameError::UndoLimitReached));
+        assert_eq!(g.state.floor_count(), 3);
+
+        // The count carries over through a save
+        let mut g = Game::restore(&g.snapshot()).unwrap();
+        assert_eq!(g.undo().err(), Some(GameError::UndoLimitReached));
+        g.tick();
+        g.apply_annotation("!1").unwrap();
+        g.undo().unwrap();
+
+        g.game_moves = MAX_GAME_MOVES;
+        assert_eq!(
+            g.apply_annotation("!1").err(),
+            Some(Error::State(StateError::MoveLimitReached))
+        );
+    }
+
+    #[test]
+    fn test_undo_policy() {
+        let rules = |undo| Rules {
+            undo,
+            ..Rules::default()
+        };
+
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .rules(rules(UndoPolicy::OwnTurnOnly))
+            .build()
+            .unwrap();
+        assert!(g.apply_annotation("*C&3").is_ok());
+        assert!(g.undo().is_ok());
+        assert!(g.apply_annotation("*C&3").is_ok());
+        g.tick();
+        assert_eq!(g.undo().err(), Some(GameError::TurnIsOver));
+
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .rules(rules(UndoPolicy::Limited(1)))
+            .build()
+            .unwrap();
+        assert!(g.apply_annotation("*C&3").is_ok());
+        assert!(g.undo().is_ok());
+        assert!(g.apply_annotation("*C&3").is_ok());
+        assert_eq!(g.undo().err(), Some(GameError::UndoLimitReached));
+        g.tick();
+        assert!(g.apply_annotation("!1").is_ok());
+        assert!(g.undo().is_ok());
+    }
+
+    #[test]
+    fn test_safe_api() {
+        let mut g = Game::default();
+        g.seed(Seed::default());
+        g.deal();
+
+        assert!(g.apply_annotation("*A&3").is_err());
+        assert_eq!(g.floor().iter().filter(|p| !p.is_empty()).count(), 4);
+
+        assert_eq!(
+            g.apply_annotation("*C&3"),
+            Ok(MoveOutcome {
+                turn: PlayerId::Opponent,
+                captured: vec![
+                    Card::create(Value::Two, Suit::Spades),
+                    Card::create(Value::Two, Suit::Diamonds),
+                ],
+                suipi: false,
+                keyframes: vec![
+                    Keyframe {
+                        card: Card::create(Value::Two, Suit::Spades),
+                        from: Address::Floor(2),
+                        to: Destination::Captured,
+                    },
+                    Keyframe {
+                        card: Card::create(Value::Two, Suit::Diamonds),
+                        from: Address::Hand(2),
+                        to: Destination::Captured,
+                    },
+                    Keyframe {
+                        card: Card::create(Value::Eight, Suit::Clubs),
+                        from: Address::Floor(3),
+                        to: Destination::Pile(Address::Floor(2)),
+                    },
+                ],
+                hints: vec![],
+            })
+        );
+        assert!(g.hand(PlayerId::Opponent)[2].is_empty());
+        assert_eq!(g.hand(PlayerId::Dealer).len(), 8);
+
+        g.tick();
+        let status = g.status();
+        assert_eq!(status.turn, PlayerId::Dealer);
+        assert_eq!(status.hand, 8);
+        assert_eq!(status.floor, 3);
+        assert_eq!(
+            status.to_string(),
+            format!(
+                "game 1/2 round 1 | Dealer to move, Dealer deals | hand 8 floor 3 | seed {}",
+                "0".repeat(64)
+            )
+        );
+
+        assert_eq!(
+            g.apply_annotation("!1"),
+            Ok(MoveOutcome {
+                turn: PlayerId::Dealer,
+                captured: vec![],
+                suipi: false,
+                keyframes: vec![Keyframe {
+                    card: Card::create(Value::Ten, Suit::Diamonds),
+                    from: Address::Hand(0),
+                    to: Destination::Pile(Address::Floor(3)),
+                }],
+                hints: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn test_coach() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert!(g.apply_annotation("!1").unwrap().hints.is_empty());
+        g.tick();
+        g.coach = true;
+        assert_eq!(
+            g.apply_annotation("!1").unwrap().hints,
+            vec![Hint::MissedCapture(String::from("*B+C+E&3"))]
+        );
+    }
+
+    #[test]
+    fn test_suipi_on_move() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        // Leave the two of spades alone on the floor
+        g.state.floor.retain(|p| p.value == 2);
+        g.state.floor.resize(13, Pile::default());
+
+        let outcome = g.apply_annotation("*A&3").unwrap();
+        assert!(outcome.suipi);
+        assert_eq!(g.state.opponent.suipi_count, 1);
+        let leads = |category| Event::LeaderChanged {
+            category,
+            player: Some(PlayerId::Opponent),
+        };
+        assert_eq!(
+            g.poll_events(),
+            vec![
+                Event::PointEarned {
+                    player: PlayerId::Opponent,
+                    card: Card::create(Value::Two, Suit::Spades),
+                    points: 1,
+                },
+                Event::Suipi(PlayerId::Opponent),
+                leads(Category::MostCards),
+                leads(Category::MostSpades),
+            ]
+        );
+
+        // The empty floor is not counted again at the end of the turn
+        g.tick();
+        assert_eq!(g.state.opponent.suipi_count, 1);
+        assert!(!g.apply_annotation("!1").unwrap().suipi);
+
+        assert!(g.undo().is_ok());
+        assert!(g.undo().is_ok());
+        assert_eq!(g.state.opponent.suipi_count, 0);
+    }
+
+    #[test]
+    fn test_touch_timeout() {
+        let timed = |timeout| {
+            let clock = Clock {
+                turn_ms: 1000,
+                grace_ms: 500,
+                timeout,
+            };
+            let rules = Rules {
+                clock: Some(clock),
+                ..Rules::default()
+            };
+            Game::builder()
+                .seed(Seed::default())
+                .rules(rules)
+                .build()
+                .unwrap()
+        };
+
+        // Untimed games never time out
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_eq!(g.touch(0), None);
+        assert_eq!(g.touch(u64::MAX), None);
+        assert_eq!(g.time_left(0), None);
+
+        // Discard the lowest card once the grace period is over
+        let mut g = timed(TimeoutPolicy::AutoDiscard);
+        assert_eq!(g.touch(0), None);
+        assert_eq!(g.time_left(1000), Some(500));
+        assert_eq!(g.touch(1500), None);
+        assert_eq!(
+            g.touch(1501),
+            Some(Timeout::Moved(PlayerId::Opponent, String::from("!1")))
+        );
+        assert_eq!(g.state.turn, PlayerId::Dealer);
+        assert_eq!(g.time_left(1501), Some(1500));
+        assert_eq!(g.touch(2000), None);
+
+        // Play the capture the engine picks
+        let mut g = timed(TimeoutPolicy::AutoMove);
+        g.touch(0);
+        assert_eq!(
+            g.touch(2000),
+            Some(Timeout::Moved(PlayerId::Opponent, String::from("*C&3")))
+        );
+        assert_eq!(g.state.opponent.pairs.len(), 1);
+
+        // Forfeit the match
+        let mut g = timed(TimeoutPolicy::Forfeit);
+        g.touch(0);
+        assert_eq!(g.touch(2000), Some(Timeout::Forfeited(PlayerId::Opponent)));
+        assert!(g.is_over());
+        assert_eq!(g.touch(4000), None);
+        let result = g.result.unwrap();
+        assert_eq!(result.winner, Some(PlayerId::Dealer));
+        assert_eq!(result.termination, Termination::Timeout);
+        assert_eq!(result.duration, Duration::from_millis(2000));
+
+        // Clocks too long to add up never run out
+        let rules = Rules {
+            clock: Some(Clock {
+                turn_ms: u64::MAX,
+                grace_ms: u64::MAX,
+                timeout: TimeoutPolicy::Forfeit,
+            }),
+            ..Rules::default()
+        };
+        let mut g = Game::builder().rules(rules).build().unwrap();
+        assert_eq!(g.touch(0), None);
+        assert_eq!(g.time_left(0), Some(u64::MAX));
+        assert_eq!(g.touch(u64::MAX), None);
+        assert!(!g.is_over());
+    }
+
+    #[test]
+    fn test_pending_moves() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_eq!(
+            g.submit(PlayerId::Dealer, "*E&1"),
+            Err(Error::State(StateError::InvalidAddress))
+        );
+
+        // The dealer's queued build follows the two of spades after the sevens are paired
+        assert!(g.submit(PlayerId::Dealer, "C+2").is_ok());
+        assert_eq!(g.resolve(), vec![]);
+        assert!(g.submit(PlayerId::Opponent, "*B&5").is_ok());
+        assert_eq!(
+            g.resolve(),
+            vec![
+                Resolution::Applied(PlayerId::Opponent, String::from("*B&5")),
+                Resolution::Applied(PlayerId::Dealer, String::from("B+2")),
+            ]
+        );
+        assert_eq!(g.state.turn, PlayerId::Opponent);
+        assert_eq!(g.pending(PlayerId::Dealer), None);
+
+        // Queued moves on piles that get captured first are dropped
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert!(g.submit(PlayerId::Dealer, "C+2").is_ok());
+        assert!(g.submit(PlayerId::Opponent, "*C&3").is_ok());
+        assert_eq!(
+            g.resolve(),
+            vec![
+                Resolution::Applied(PlayerId::Opponent, String::from("*C&3")),
+                Resolution::Conflict(
+                    PlayerId::Dealer,
+                    String::from("C+2"),
+                    Error::Game(GameError::PileCaptured)
+                ),
+            ]
+        );
+        assert_eq!(g.state.turn, PlayerId::Dealer);
+
+        assert!(g.submit(PlayerId::Dealer, "!1").is_ok());
+        assert!(g.cancel(PlayerId::Dealer).is_some());
+        assert_eq!(g.resolve(), vec![]);
+    }
+
+    #[test]
+    fn test_turn_token() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        let token = g.turn_token();
+        assert_eq!(TurnToken::from(u64::from(token)), token);
+
+        // A rejected move keeps the token valid
+        assert!(g.apply_with_token(token, "*A&3").is_err());
+        assert_eq!(g.turn_token(), token);
+
+        assert!(g.apply_with_token(token, "*C&3").is_ok());
+        assert_eq!(
+            g.apply_with_token(token, "*C&3"),
+            Err(Error::Game(GameError::StaleTurnToken))
+        );
+
+        let applied = g.turn_token();
+        g.tick();
+        assert_ne!(g.turn_token(), applied);
+        let ticked = g.turn_token();
+        assert!(g.undo().is_ok());
+        assert_ne!(g.turn_token(), ticked);
+        assert_ne!(g.turn_token(), token);
+
+        // Tokens come from a secret of the game's own, not from its seed
+        let h = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_ne!(h.turn_token(), token);
+        assert_ne!(
+            Game::restore(&g.snapshot()).unwrap().turn_token(),
+            g.turn_token()
+        );
+    }
+
+    /// Play the first candidate move every turn until the match is over
+    fn play_out(g: &mut Game) {
+        while !g.is_over() {
+            let x = candidate_moves(&g.state)[0].clone();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+    }
+
+    #[test]
+    fn test_match_decided() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        play_out(&mut g);
+        let events = g
+            .poll_events()
+            .into_iter()
+            .filter(|e| matches!(e, Event::MatchDecided(_)))
+            .collect::<Vec<Event>>();
+        let result = g.result.clone().unwrap();
+        assert_eq!(events, vec![Event::MatchDecided(result.clone())]);
+        assert_eq!(g.poll_events(), vec![]);
+        assert_eq!(g.game, 2);
+        assert_eq!(result.games(), 2);
+        assert_eq!(result.termination, Termination::Completed);
+        for p in [PlayerId::Opponent, PlayerId::Dealer] {
+            let total = g.scores[..2].iter().map(|s| s.total(p) as u16).sum();
+            assert_eq!(result.total(p), total);
+        }
+        let leader = if result.total(PlayerId::Opponent) > result.total(PlayerId::Dealer) {
+            PlayerId::Opponent
+        } else {
+            PlayerId::Dealer
+        };
+        assert_eq!(result.winner, Some(leader));
+
+        // Playing to a target ends the match as soon as a player is sure to finish the game past
+        // it and ahead
+        let rules = Rules {
+            target: Some(4),
+            ..Rules::default()
+        };
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .rules(rules)
+            .build()
+            .unwrap();
+        play_out(&mut g);
+        let result = g.result.clone().unwrap();
+        let winner = result.winner.unwrap();
+        assert_eq!(g.game, 1);
+        assert_eq!(result.games(), 1);
+        assert!(result.total(winner) >= 4);
+        assert!(result.total(winner) > result.total(winner.other()));
+        assert_eq!(g.poll_events().pop(), Some(Event::MatchDecided(result)));
+    }
+
+    #[test]
+    fn test_target_catch_up() {
+        let rules = Rules {
+            target: Some(4),
+            ..Rules::default()
+        };
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .rules(rules)
+            .build()
+            .unwrap();
+        let card = |v, s| Card::create(v, s);
+        let mut cards = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades]
+            .map(|s| card(Value::Ace, s))
+            .to_vec();
+        cards.push(card(Value::Ten, Suit::Diamonds));
+        g.state.opponent.pairs = vec![Pile::new(cards, 0, Mark::Pair)];
+        // Six points are past the target, but the dealer could still overtake them this game
+        g.decide();
+        assert_eq!(g.result, None);
+
+        // With no cards left to play, the dealer can reach four points at most
+        g.state.opponent.pairs[0]
+            .cards
+            .push(card(Value::Two, Suit::Spades));
+        g.state.opponent.hand = Default::default();
+        g.state.dealer.hand = Default::default();
+        g.state.deck.clear();
+        assert_eq!(g.score_range(PlayerId::Dealer).1 - g.future_points(), 4);
+        g.decide();
+        assert_eq!(g.result.unwrap().winner, Some(PlayerId::Opponent));
+    }
+
+    #[test]
+    fn test_player_info() {
+        let alice = PlayerInfo {
+            name: String::from("Alice"),
+            rating: Some(1500),
+            client_id: Some(String::from("web-1")),
+        };
+        let g = Game::builder()
+            .seed(Seed::default())
+            .player(PlayerId::Dealer, alice.clone())
+            .player(PlayerId::Opponent, PlayerInfo::default())
+            .build()
+            .unwrap();
+        assert_eq!(g.player_info(PlayerId::Dealer), Some(&alice));
+        assert_eq!(g.player_name(PlayerId::Dealer), "Alice");
+        assert_eq!(g.player_name(PlayerId::Opponent), "Opponent");
+
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_eq!(g.player_info(PlayerId::Opponent), None);
+        assert_eq!(g.player_name(PlayerId::Opponent), "Opponent");
+    }
+
+    #[test]
+    fn test_misdeal() {
+        let short_deck = |misdeal| {
+            let rules = Rules {
+                misdeal,
+                ..Rules::default()
+            };
+            let mut g = Game::builder()
+                .seed(Seed::default())
+                .rules(rules)
+                .build()
+                .unwrap();
+            // Leave five cards in the deck, and count the rest as captured
+            let captured = g.state.deck.split_off(5).into_iter().collect();
+            g.state.opponent.pairs = vec![Pile::new(captured, 0, Mark::Pair)];
+            g.state
+                .opponent
+                .hand
+                .iter_mut()
+                .for_each(|x| *x = Pile::empty());
+            g.state
+                .dealer
+                .hand
+                .iter_mut()
+                .for_each(|x| *x = Pile::empty());
+            g.tick();
+            g
+        };
+
+        let g = short_deck(Misdeal::ShortHands);
+        assert_eq!(g.round, 1);
+        assert_eq!(
+            g.state.opponent.card_count() + g.state.dealer.card_count(),
+            5
+        );
+        assert!(g.state.deck.is_empty());
+        assert_eq!(g.deals().last().unwrap().hands_dealt, 5);
+
+        let g = short_deck(Misdeal::Reshuffle);
+        assert_eq!(g.state.opponent.card_count(), 8);
+        assert_eq!(g.state.dealer.card_count(), 8);
+        assert!(g.state.opponent.pairs.is_empty());
+        assert_eq!(g.state.deck.len(), 32 - 16);
+
+        let g = short_deck(Misdeal::Abort);
+        assert!(g.is_over());
+        assert_eq!(g.result.unwrap().termination, Termination::Misdeal);
+    }
+
+    #[test]
+    fn test_resign_and_abort() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert!(g.apply_annotation("*C&3").is_ok());
+        g.tick();
+        let result = g.resign(PlayerId::Dealer).unwrap().clone();
+        assert_eq!(result.winner, Some(PlayerId::Opponent));
+        assert_eq!(result.termination, Termination::Resigned);
+        assert_eq!(result.games(), 1);
+        assert_eq!(result.duration, Duration::ZERO);
+        assert!(g.is_over());
+        assert_eq!(g.abort().err(), Some(GameError::MatchIsOver));
+        assert_eq!(g.poll_events().pop(), Some(Event::MatchDecided(result)));
+
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        g.touch(1000);
+        g.touch(61000);
+        let result = g.abort().unwrap();
+        assert_eq!(result.winner, None);
+        assert_eq!(result.termination, Termination::Aborted);
+        assert_eq!(result.duration, Duration::from_secs(60));
+
+        // Time after the last touch is not counted, so touching before each move keeps the
+        // duration right up to the move that ends the match
+        let duration = |touch_last: bool| {
+            let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+            g.touch(1000);
+            g.apply_annotation("*C&3").unwrap();
+            g.tick();
+            if touch_last {
+                g.touch(9000);
+            }
+            g.resign(PlayerId::Dealer).unwrap().duration
+        };
+        assert_eq!(duration(true), Duration::from_secs(8));
+        assert_eq!(duration(false), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_played_moves() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert!(g.apply_annotation("!*C&3").is_err());
+        assert!(g.apply_annotation("*C&3").is_ok());
+        g.tick();
+        assert!(g.apply_annotation("1").is_ok());
+        g.tick();
+        assert_eq!(
+            g.moves(),
+            [
+                PlayedMove {
+                    turn: 0,
+                    player: PlayerId::Opponent,
+                    annotation: String::from("*C&3"),
+                    raw: String::from("*C&3"),
+                    elapsed_ms: None,
+                },
+                PlayedMove {
+                    turn: 1,
+                    player: PlayerId::Dealer,
+                    annotation: String::from("!1"),
+                    raw: String::from("1"),
+                    elapsed_ms: None,
+                },
+            ]
+        );
+
+        assert_eq!(
+            g.moves()[1].to_move(),
+            Annotation::new(String::from("!1")).to_move().unwrap()
+        );
+
+        assert!(g.undo().is_ok());
+        assert_eq!(g.moves().len(), 1);
+    }
+
+    #[test]
+    fn test_move_stats() {
+        let clock = Clock {
+            turn_ms: 1000,
+            grace_ms: 0,
+            timeout: TimeoutPolicy::Forfeit,
+        };
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .rules(Rules {
+                clock: Some(clock),
+                ..Rules::default()
+            })
+            .build()
+            .unwrap();
+        g.touch(100);
+        g.touch(800);
+        g.apply_annotation("!8").unwrap();
+        g.tick();
+        g.apply_annotation("!4").unwrap();
+        g.tick();
+
+        let stats = g.move_stats(0, 1).unwrap();
+        assert_eq!(stats.player, PlayerId::Opponent);
+        assert_eq!(stats.elapsed_ms, Some(700));
+        assert_eq!(
+            stats.ranked.len(),
+            g.branch_at(0).unwrap().state.legal_moves().len()
+        );
+        assert_eq!(stats.ranked[0].0, "*C&3");
+        assert!(stats.rank.unwrap() > 0);
+        assert!(stats.ranked.windows(2).all(|x| x[0].1 >= x[1].1));
+        // Discarding the eight passes up a capture of the two of spades
+        assert_eq!(stats.grade, Some(Tag::Blunder));
+        // Moves made without touching the clock have no time
+        assert_eq!(g.move_stats(1, 1).unwrap().elapsed_ms, None);
+        assert!(g.move_stats(2, 1).is_none());
+
+        // Captures of several piles are ranked too, under whichever annotation plays them
+        let stats = ["*B+C+E&3", "*E+C+B&3"].map(|a| {
+            let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+            g.apply_annotation("!1").unwrap();
+            g.tick();
+            g.apply_annotation(a).unwrap();
+            g.move_stats(1, 1).unwrap()
+        });
+        assert!(stats[0].grade.is_some() && stats[0].rank.is_some());
+        assert_eq!(
+            (stats[0].rank, stats[0].grade),
+            (stats[1].rank, stats[1].grade)
+        );
+    }
+
+    #[test]
+    fn test_shortcuts() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert!(g.apply_annotation("cA").is_err());
+        assert_eq!(g.apply_annotation("cC").unwrap().captured.len(), 2);
+        g.tick();
+        g.apply_annotation("t1").unwrap();
+        assert_eq!(g.moves()[0].annotation, "*C&3");
+        assert_eq!(g.moves()[1].annotation, "!1");
+        assert_eq!(g.moves()[1].raw, "t1");
+    }
+
+    #[test]
+    fn test_logger() {
+        use std::sync::{Arc, Mutex};
+        let records = Arc::new(Mutex::new(vec![]));
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        let sink = records.clone();
+        g.set_logger(move |r| sink.lock().unwrap().push(r.clone()));
+
+        assert!(g.apply_annotation("*A&1").is_err());
+        assert!(g.apply_annotation("?").is_err());
+        g.apply_annotation("*C&3").unwrap();
+        g.tick();
+        let logged = std::mem::take(&mut *records.lock().unwrap());
+        assert_eq!(logged.len(), 3);
+        assert!(matches!(
+            &logged[0],
+            LogRecord::Rejected { player: PlayerId::Opponent, annotation, .. } if annotation == "*A&1"
+        ));
+        assert!(matches!(&logged[1], LogRecord::Rejected { annotation, .. } if annotation == "?"));
+        assert_eq!(
+            logged[2],
+            LogRecord::Move {
+                player: PlayerId::Opponent,
+                annotation: String::from("*C&3")
+            }
+        );
+
+        // Play out the first game
+        while g.game == 0 {
+            let x = g.engine_move().unwrap();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+        let logged = records.lock().unwrap();
+        let deals = logged
+            .iter()
+            .filter(|x| matches!(x, LogRecord::Deal { .. }))
+            .count();
+        // Two more rounds, then the first round of the next game
+        assert_eq!(deals, 3);
+        assert!(logged.contains(&LogRecord::Scored {
+            game: 0,
+            opponent: g.scores[0].opponent_total(),
+            dealer: g.scores[0].dealer_total(),
+        }));
+        assert_eq!(logged.last(), Some(&LogRecord::Deal { game: 1, round: 0 }));
+    }
+
+    #[test]
+    fn test_score_events() {
+        use std::sync::{Arc, Mutex};
+        let heard = Arc::new(Mutex::new(vec![]));
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        let sink = heard.clone();
+        g.set_listener(move |e| sink.lock().unwrap().push(e.clone()));
+
+        // Taking the two of spades with a three is worth a point and both leads
+        g.apply_annotation("*C&3").unwrap();
+        g.tick();
+        let first = g.poll_events();
+        assert_eq!(
+            first[0],
+            Event::PointEarned {
+                player: PlayerId::Opponent,
+                card: Card::create(Value::Two, Suit::Spades),
+                points: 1,
+            }
+        );
+        while g.game == 0 {
+            let x = g.engine_move().unwrap();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+        let events = heard.lock().unwrap().clone();
+        assert_eq!([first, g.poll_events()].concat(), events);
+
+        // Every point card captured in the game was announced, including the floor pickup
+        for p in [PlayerId::Opponent, PlayerId::Dealer] {
+            let earned = events
+                .iter()
+                .map(|e| match e {
+                    Event::PointEarned { player, points, .. } if *player == p => *points,
+                    _ => 0,
+                })
+                .sum::<u8>();
+            let score = &g.scores[0];
+            let points = score.points(p);
+            assert_eq!(earned, score.aces(p) + points[3] + points[4]);
+        }
+        let lead = events.iter().rev().find_map(|e| match e {
+            Event::LeaderChanged {
+                category: Category::MostCards,
+                player,
+            } => Some(*player),
+            _ => None,
+        });
+        assert_eq!(lead, Some(g.scores[0].most_cards.player()));
+    }
+
+    #[test]
+    fn test_turn_order() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_eq!(g.status().turn, PlayerId::Opponent);
+        assert_eq!(g.status().next, PlayerId::Dealer);
+        // Ending a turn without moving keeps it with the same player
+        g.tick();
+        assert_eq!(g.state.turn, PlayerId::Opponent);
+        g.apply_annotation("*C&3").unwrap();
+        assert_eq!(g.next_to_act(), PlayerId::Dealer);
+        g.tick();
+        assert_eq!(g.state.turn, PlayerId::Dealer);
+
+        // A player keeps the turn while the other has no cards to play
+        g.state.opponent.hand = vec![Pile::empty(); 8];
+        assert_eq!(g.next_to_act(), PlayerId::Dealer);
+        g.apply_annotation("!1").unwrap();
+        g.tick();
+        assert_eq!(g.state.turn, PlayerId::Dealer);
+    }
+
+    #[test]
+    fn test_deals() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert_eq!(
+            g.deals(),
+            [Deal {
+                number: 1,
+                game: 0,
+                round: 0,
+                hands_dealt: 16,
+                floor_dealt: 4,
+                deck_remaining_after: 32,
+                first_move: 0,
+            }]
+        );
+        assert_eq!(g.deal_total(), 6);
+        assert!(g.deal_of(0).is_none());
+
+        while g.game == 0 {
+            let x = g.engine_move().unwrap();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+        let deals = g.deals();
+        assert_eq!(deals.len(), 4);
+        assert_eq!(deals[2].number, 3);
+        assert_eq!(deals[2].round, 2);
+        assert_eq!(deals[2].floor_dealt, 0);
+        assert_eq!(deals[2].deck_remaining_after, 0);
+        assert_eq!(deals[2].first_move, 32);
+        assert_eq!((deals[3].game, deals[3].round), (1, 0));
+        assert_eq!(g.deal_of(15).map(|x| x.number), Some(1));
+        assert_eq!(g.deal_of(16).map(|x| x.number), Some(2));
+        assert_eq!(g.deal_of(47).map(|x| x.number), Some(3));
+    }
+
+    #[test]
+    fn test_fork_for_search() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        for _ in 0..3 {
+            let x = g.engine_move().unwrap();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+        let mut f = g.fork_for_search();
+        assert!(Arc::ptr_eq(&f.history[0], &g.history[0]));
+        assert_eq!(f.state.floor, g.state.floor);
+        assert_eq!(f.moves(), g.moves());
+
+        let x = f.engine_move().unwrap();
+        f.apply_annotation(&x).unwrap();
+        f.tick();
+        assert_eq!(f.moves().len(), 4);
+        assert_eq!(g.moves().len(), 3);
+        // Undoing on either side leaves the other alone
+        f.undo().unwrap();
+        f.undo().unwrap();
+        assert_eq!(g.history.len(), 3);
+        g.undo().unwrap();
+        assert_eq!(f.state.floor, g.state.floor);
+        assert_eq!(f.state.player().hand, g.state.player().hand);
+        assert_eq!(f.moves(), g.moves());
+    }
+
+    #[test]
+    fn test_contained_panics() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        let floor = g.state.floor.clone();
+        crate::state::INJECT_PANIC.set(true);
+        let e = g.apply_annotation("!1").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "State Error: Internal error - injected fault"
+        );
+        assert_eq!(g.state.floor, floor);
+        assert!(g.moves().is_empty());
+        g.apply_annotation("!1").unwrap();
+
+        g.scores.clear();
+        assert!(matches!(g.try_tick(), Err(StateError::Internal(_))));
+        assert_eq!(g.state.turn, PlayerId::Opponent);
+        assert_eq!(g.moves().len(), 1);
+    }
+
+    #[test]
+    fn test_win_probabilities() {
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .rules(Rules {
+                games: 1,
+                ..Rules::default()
+            })
+            .build()
+            .unwrap();
+        assert!(g.win_probabilities(2).is_empty());
+        while !g.is_over() {
+            let x = g.engine_move().unwrap();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+        let curve = g.win_probabilities(2);
+        assert_eq!(curve.len(), g.played.len());
+        assert!(curve.iter().all(|x| (0.0..=1.0).contains(x)));
+        // Once the match is over there is nothing left to estimate
+        let won = match g.result.as_ref().unwrap().winner {
+            Some(PlayerId::Opponent) => 1.0,
+            Some(PlayerId::Dealer) => 0.0,
+            None => 0.5,
+        };
+        assert_eq!(curve.last(), Some(&won));
+        assert_eq!(g.win_probabilities(2), curve);
+    }
+
+    #[test]
+    fn test_timeline() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        assert!(g.timeline().is_empty());
+        let empty = floor_hash(&g.state.floor);
+        for x in ["*C&3", "!1", "!1"] {
+            g.apply_annotation(x).unwrap();
+            g.tick();
+        }
+        let timeline = g.timeline();
+        assert_eq!(timeline.len(), 3);
+        assert_eq!(timeline[0].player, PlayerId::Opponent);
+        assert_eq!(timeline[0].annotation, "*C&3");
+        assert_eq!(
+            timeline[0].captured,
+            vec![
+                Card::create(Value::Two, Suit::Spades),
+                Card::create(Value::Two, Suit::Diamonds)
+            ]
+        );
+        assert!(!timeline[0].suipi);
+        assert_eq!(timeline[1].player, PlayerId::Dealer);
+        assert!(timeline[1].captured.is_empty());
+        assert_eq!(timeline[2].turn, 2);
+        assert_eq!(timeline[2].floor_hash, floor_hash(&g.state.floor));
+
+        // Every position differs, and empty piles left behind do not change the hash
+        assert_ne!(timeline[0].floor_hash, empty);
+        assert_ne!(timeline[0].floor_hash, timeline[1].floor_hash);
+        let mut floor = g.state.floor.clone();
+        floor.insert(0, Pile::default());
+        assert_eq!(floor_hash(&floor), timeline[2].floor_hash);
+
+        // Finished games are replayed in full
+        while !g.is_over() {
+            let x = g.engine_move().unwrap();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+        let timeline = g.timeline();
+        assert_eq!(timeline.len(), g.moves().len());
+        assert_eq!(timeline.last().unwrap().game, g.rules.games - 1);
+    }
+
+    #[test]
+    fn test_score_at() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        let mut totals = vec![];
+        while g.game < 1 {
+            let x = candidate_moves(&g.state)[0].clone();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+            let s = &g.scores[totals.len() / 48];
+            totals.push([s.total(PlayerId::Opponent), s.total(PlayerId::Dealer)]);
+        }
+        assert_eq!(g.moves().len(), 48);
+        for (i, t) in totals.iter().enumerate() {
+            let s = g.score_at(i).unwrap();
+            assert_eq!([s.total(PlayerId::Opponent), s.total(PlayerId::Dealer)], *t);
+        }
+        assert!(g.score_at(48).is_none());
+    }
+
+    #[test]
+    fn test_branch_at() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        g.players[0] = Some(PlayerInfo::named("Ada"));
+        for x in ["*C&3", "!1", "!1"] {
+            g.apply_annotation(x).unwrap();
+            g.tick();
+        }
+        let mut b = g.branch_at(1).unwrap();
+        assert_eq!(b.moves(), &g.moves()[..1]);
+        assert_eq!(b.player_name(PlayerId::Opponent), "Ada");
+        assert_eq!(b.state.turn, PlayerId::Dealer);
+        assert_eq!(b.state.floor_count(), 3);
+
+        // Playing on the branch leaves the original alone
+        b.apply_annotation("!4").unwrap();
+        b.tick();
+        assert_eq!(b.moves().len(), 2);
+        assert_eq!(g.moves().len(), 3);
+        assert_eq!(g.moves()[1].annotation, "!1");
+
+        assert_eq!(g.branch_at(0).unwrap().moves().len(), 0);
+        assert!(g.branch_at(4).is_none());
+    }
 }
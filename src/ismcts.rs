@@ -0,0 +1,379 @@
+use crate::action::{Address, Annotation, Move};
+use crate::bot::Bot;
+use crate::card::Card;
+use crate::consts::GAME_POINTS;
+use crate::pile::Pile;
+use crate::rng::{ChaCha20Rng, Seed, SeedableRng, SliceRandom};
+use crate::score::Score;
+use crate::state::{PlayerId, State};
+
+/// Iterations an `IsmctsBot` runs for each move unless told otherwise
+pub const DEFAULT_ITERATIONS: u32 = 1000;
+
+/// How much the search favours trying moves it has looked at less, over moves that have done well
+pub const DEFAULT_EXPLORATION: f64 = 0.7;
+
+/// A move the search looked at, and how well it did
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimate {
+    pub annotation: String,
+    /// Number of playouts that started with the move
+    pub visits: u32,
+    /// Average score of those playouts for the player to move, in game points
+    pub score: f64,
+}
+
+/// A move in the search tree, told apart by the hand card it plays as well as its annotation,
+/// since the same hand address holds different cards in different samples
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Action {
+    annotation: String,
+    card: Card,
+}
+
+/// A node of the search tree, reached by playing its action
+#[derive(Debug)]
+struct Node {
+    action: Option<Action>,
+    /// The player who played the action
+    player: PlayerId,
+    children: Vec<usize>,
+    visits: u32,
+    /// Number of times the action could have been played when its parent was visited
+    available: u32,
+    /// Sum of the playout scores for the player who played the action
+    total: f64,
+}
+
+impl Node {
+    fn new(action: Option<Action>, player: PlayerId) -> Node {
+        Node {
+            action,
+            player,
+            children: vec![],
+            visits: 0,
+            available: 0,
+            total: 0.0,
+        }
+    }
+
+    /// Score a child for selection, trading how well it has done against how little it was tried
+    fn ucb(&self, exploration: f64) -> f64 {
+        let visits = self.visits as f64;
+        self.total / visits + exploration * ((self.available as f64).ln() / visits).sqrt()
+    }
+}
+
+/// A bot that searches with information set Monte Carlo tree search
+///
+/// The bot only uses what the player to move can see. For each iteration it deals the cards it
+/// cannot see into the other player's hand and the deck at random, then walks one tree shared by
+/// every sample, only following moves that are legal in that sample. Once it leaves the tree, it
+/// plays the game out to the end with a fast random policy that prefers captures, and scores the
+/// result by the difference in game points. It plays the move it searched the most.
+pub struct IsmctsBot {
+    rng: ChaCha20Rng,
+    pub iterations: u32,
+    pub exploration: f64,
+}
+
+impl IsmctsBot {
+    /// Create a bot that samples and plays out games with the given seed
+    pub fn new(seed: Seed) -> Self {
+        IsmctsBot {
+            rng: ChaCha20Rng::from_seed(seed),
+            iterations: DEFAULT_ITERATIONS,
+            exploration: DEFAULT_EXPLORATION,
+        }
+    }
+
+    /// Run the given number of iterations for each move
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// Search from a position, returning every move the current player could make, most searched
+    /// first
+    ///
+    /// The hidden cards of the state are never looked at, so a redacted state works as well as
+    /// a full one.
+    pub fn search(&mut self, state: &State) -> Vec<Estimate> {
+        let me = state.turn;
+        let mut tree = vec![Node::new(None, me.other())];
+        // The player to move can see everything their own moves depend on, so every sample has
+        // the same moves at the root
+        let Some(root) = moves(&mut state.clone()) else {
+            return vec![];
+        };
+        for _ in 0..self.iterations {
+            let mut s = determinize(state, &mut self.rng);
+            let mut path = vec![0];
+            let mut node = 0;
+            let mut legal = Some(root.clone());
+            // Walk down the tree, adding a node for the first move not tried yet
+            while let Some(legal) = legal.take().or_else(|| moves(&mut s)) {
+                let mut untried = vec![];
+                let mut known = vec![];
+                for x in legal {
+                    match tree[node]
+                        .children
+                        .iter()
+                        .find(|&&c| tree[c].action.as_ref() == Some(&x.1))
+                    {
+                        Some(&c) => known.push((x.0, c)),
+                        None => untried.push(x),
+                    }
+                }
+                for (_, c) in known.iter() {
+                    tree[*c].available += 1;
+                }
+                if let Some((m, action)) = untried.choose(&mut self.rng).cloned() {
+                    let mut child = Node::new(Some(action), s.turn);
+                    child.available = 1;
+                    tree.push(child);
+                    let c = tree.len() - 1;
+                    tree[node].children.push(c);
+                    play(&mut s, m);
+                    path.push(c);
+                    break;
+                }
+                let (m, c) = known
+                    .into_iter()
+                    .max_by(|a, b| {
+                        let (x, y) = (&tree[a.1], &tree[b.1]);
+                        x.ucb(self.exploration).total_cmp(&y.ucb(self.exploration))
+                    })
+                    .unwrap();
+                play(&mut s, m);
+                path.push(c);
+                node = c;
+            }
+            let score = self.playout(s, me);
+            for &i in path.iter() {
+                let n = &mut tree[i];
+                n.visits += 1;
+                n.total += if n.player == me { score } else { -score };
+            }
+        }
+        let mut estimates = tree[0]
+            .children
+            .iter()
+            .map(|&c| Estimate {
+                annotation: tree[c].action.as_ref().unwrap().annotation.clone(),
+                visits: tree[c].visits,
+                score: tree[c].total / tree[c].visits as f64,
+            })
+            .collect::<Vec<Estimate>>();
+        estimates.sort_by(|a, b| b.visits.cmp(&a.visits).then(b.score.total_cmp(&a.score)));
+        estimates
+    }
+
+    /// Play the game out to the end, returning the difference in game points for the given player
+    fn playout(&mut self, mut s: State, p: PlayerId) -> f64 {
+        while self.rollout_move(&mut s) {}
+        let score = Score::from(&s);
+        (score.total(p) as f64 - score.total(p.other()) as f64) / GAME_POINTS as f64
+    }
+
+    /// Play a quick random move, returning false once the game is over
+    ///
+    /// Rather than working out every legal move, this tries captures of a single floor pile with
+    /// a matching hand card first, since most good moves take something, then discards, in a
+    /// random order, and plays the first one that is legal.
+    fn rollout_move(&mut self, s: &mut State) -> bool {
+        if s.dealer.card_count() == 0 && s.opponent.card_count() == 0 {
+            if s.deck.is_empty() {
+                s.pickup_floor();
+                return false;
+            }
+            s.deal_hands();
+        }
+        let hand = s
+            .player()
+            .hand
+            .iter()
+            .enumerate()
+            .filter(|x| !x.1.is_empty());
+        let mut captures = vec![];
+        let mut discards = vec![];
+        for (i, h) in hand {
+            let i = Address::Hand(i as u8);
+            for (j, f) in s.floor.iter().enumerate() {
+                if !f.is_empty() && f.value == h.value {
+                    captures.push(format!("*{}&{}", Address::Floor(j as u8), i));
+                }
+            }
+            discards.push(format!("!{}", i));
+        }
+        captures.shuffle(&mut self.rng);
+        discards.shuffle(&mut self.rng);
+        for x in captures.into_iter().chain(discards) {
+            let mut next = s.clone();
+            if next.apply(Annotation::new(x).to_move().unwrap()).is_ok() {
+                next.turn = next.turn.other();
+                *s = next;
+                return true;
+            }
+        }
+        // Fall back on every legal move, such as a build when discarding is not allowed
+        match moves(s) {
+            Some(legal) => {
+                let m = legal.choose(&mut self.rng).unwrap().0.clone();
+                play(s, m);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Bot for IsmctsBot {
+    fn choose_move(&mut self, state: &State) -> Option<Move> {
+        let best = self.search(state).into_iter().next()?;
+        Annotation::new(best.annotation).to_move().ok()
+    }
+}
+
+/// Deal the cards the player to move cannot see at random, keeping everything they can see
+pub fn determinize(state: &State, rng: &mut ChaCha20Rng) -> State {
+    let me = state.turn;
+    let mut unseen = state.unseen_by(me);
+    unseen.shuffle(rng);
+    let mut s = state.clone();
+    for x in s.seat_mut(me.other()).hand.iter_mut() {
+        if !x.is_empty() {
+            *x = unseen.pop().map(Pile::single).unwrap_or_default();
+        }
+    }
+    unseen.truncate(state.deck.len());
+    s.deck = unseen.into();
+    s.forget_simple_moves();
+    s
+}
+
+/// Get the moves the player to move could make, dealing new hands at the end of a round
+///
+/// Returns nothing once the game is over, after giving the floor to the last player to capture.
+fn moves(s: &mut State) -> Option<Vec<(Move, Action)>> {
+    if s.dealer.card_count() == 0 && s.opponent.card_count() == 0 {
+        if s.deck.is_empty() {
+            s.pickup_floor();
+            return None;
+        }
+        s.deal_hands();
+    }
+    let moves = s
+        .legal_moves()
+        .into_iter()
+        .filter_map(|m| {
+            let card = m.actions.iter().find_map(|a| match a.address {
+                Address::Hand(i) => s.player().hand.get(i as usize)?.cards.first().copied(),
+                Address::Floor(_) => None,
+            })?;
+            let annotation = m.to_annotation();
+            Some((m, Action { annotation, card }))
+        })
+        .collect::<Vec<(Move, Action)>>();
+    // A player without a legal move cannot go on, so the game is scored as it stands
+    if moves.is_empty() {
+        s.pickup_floor();
+        return None;
+    }
+    Some(moves)
+}
+
+/// Play a move and pass the turn
+fn play(s: &mut State, m: Move) {
+    // Every move comes from the legal moves of the same state
+    s.apply(m).unwrap();
+    s.turn = s.turn.other();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::RandomBot;
+    use crate::consts::DECK_SIZE;
+    use crate::game::Game;
+
+    #[test]
+    fn test_search() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let mut bot = IsmctsBot::new([1; 32]).with_iterations(200);
+        let estimates = bot.search(&g.state);
+        // Every move is looked at, and the visits add up to the iterations
+        assert_eq!(estimates.len(), g.state.legal_moves().len());
+        assert_eq!(estimates.iter().map(|x| x.visits).sum::<u32>(), 200);
+        assert!(estimates.windows(2).all(|x| x[0].visits >= x[1].visits));
+
+        // The hidden cards make no difference, since they are never looked at
+        let mut other = IsmctsBot::new([1; 32]).with_iterations(200);
+        let redacted = g.state.redacted_for(PlayerId::Opponent);
+        assert_eq!(other.search(&redacted), estimates);
+
+        // The same seed picks the same move
+        let m = IsmctsBot::new([2; 32])
+            .with_iterations(100)
+            .choose_move(&g.state);
+        assert!(m.is_some());
+        assert_eq!(
+            IsmctsBot::new([2; 32])
+                .with_iterations(100)
+                .choose_move(&g.state),
+            m
+        );
+    }
+
+    #[test]
+    fn test_determinize() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let mut rng = ChaCha20Rng::from_seed([3; 32]);
+        let s = determinize(&g.state.redacted_for(PlayerId::Opponent), &mut rng);
+        assert_eq!(s.opponent.hand, g.state.opponent.hand);
+        assert_eq!(s.floor, g.state.floor);
+        assert_eq!(s.deck.len(), g.state.deck.len());
+        assert_eq!(s.dealer.card_count(), g.state.dealer.card_count());
+        // Every card is dealt exactly once
+        let mut cards = s
+            .deck
+            .iter()
+            .chain(
+                s.dealer
+                    .hand
+                    .iter()
+                    .chain(s.opponent.hand.iter())
+                    .chain(s.floor.iter())
+                    .flat_map(|x| x.cards.iter()),
+            )
+            .map(|&c| u8::from(c))
+            .collect::<Vec<u8>>();
+        cards.sort();
+        assert_eq!(cards, (0..DECK_SIZE as u8).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_ismcts_beats_random() {
+        let mut wins = 0;
+        for i in 0..4u8 {
+            let mut g = Game::builder().seed([i; 32]).build().unwrap();
+            let mut bots: [Box<dyn Bot>; 2] = [
+                Box::new(RandomBot::new([i; 32])),
+                Box::new(IsmctsBot::new([i; 32]).with_iterations(40)),
+            ];
+            while g.game < 1 {
+                let p = g.state.turn;
+                let view = g.state.redacted_for(p);
+                if let Some(m) = bots[p as usize].choose_move(&view) {
+                    g.apply(m).unwrap();
+                }
+                g.tick();
+            }
+            let score = &g.scores[0];
+            if score.total(PlayerId::Dealer) > score.total(PlayerId::Opponent) {
+                wins += 1;
+            }
+        }
+        assert!(wins >= 3, "won {} of 4", wins);
+    }
+}
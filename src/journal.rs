@@ -0,0 +1,329 @@
+use crate::error::Error;
+use crate::game::{Game, PlayerInfo};
+use crate::rng::Seed;
+use crate::rules::Rules;
+use crate::save::SaveError;
+use crate::state::PlayerId;
+use std::fmt;
+
+/// Journal errors
+#[derive(Debug, Eq, PartialEq)]
+pub enum JournalError {
+    InvalidSetup(Error),
+    Rejected(usize, Error),
+    /// The checkpoint taken after the given number of entries could not be restored
+    InvalidCheckpoint(usize, SaveError),
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Journal Error: {}",
+            match self {
+                JournalError::InvalidSetup(e) => format!("The match setup is invalid - {}", e),
+                JournalError::Rejected(i, e) =>
+                    format!("Entry {} could not be replayed - {}", i + 1, e),
+                JournalError::InvalidCheckpoint(n, e) => format!(
+                    "The checkpoint after entry {} could not be restored - {}",
+                    n, e
+                ),
+            }
+        )
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+/// Something done to a match, in the order it happened
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Entry {
+    /// The player to move played a move, given by its canonical annotation
+    Move(String),
+    /// The current player's turn was ended
+    EndTurn,
+    /// The most recent move was taken back
+    Undo,
+    Resign(PlayerId),
+    /// The player ran out of time and forfeited the match
+    Forfeit(PlayerId),
+    Abort,
+}
+
+/// A saved copy of the match after some number of entries, so it can be rebuilt without
+/// replaying them all
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    /// Number of entries played before the save was taken
+    pub entry: usize,
+    /// Number of undo history states kept from the checkpoint before this one
+    pub kept: usize,
+    /// The match as saved by `Game::snapshot`, without the journal entries or the kept history
+    pub save: Vec<u8>,
+}
+
+/// The authoritative record of a match, which the game state is a projection of
+///
+/// Everything done to a match through `Game` is recorded as an entry, so a match can be saved
+/// as its setup and entries, then rebuilt by playing them again. Clock readings are not
+/// recorded, but the moves and forfeits they caused are.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Journal {
+    pub seed: Seed,
+    pub dealer: PlayerId,
+    pub rules: Rules,
+    pub players: [Option<PlayerInfo>; 2],
+    pub entries: Vec<Entry>,
+    /// Take a checkpoint at the end of a turn once this many entries have been recorded since
+    /// the last one
+    pub snapshot_interval: Option<usize>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Journal {
+    /// Rebuild the match by playing every entry on a new game
+    pub fn project(&self) -> Result<Game, JournalError> {
+        self.project_to(self.entries.len())
+    }
+
+    /// Rebuild the match as it was after the first `n` entries
+    ///
+    /// Starts from the latest checkpoint at or before the `n`th entry, if there is one.
+    pub fn project_to(&self, n: usize) -> Result<Game, JournalError> {
+        let n = n.min(self.entries.len());
+        let (mut g, start) = match self.checkpoints.iter().rposition(|x| x.entry <= n) {
+            Some(i) => {
+                let c = &self.checkpoints[i];
+                let mut g = self.restore_checkpoint(i)?;
+                g.resume(
+                    self.entries[..c.entry].to_vec(),
+                    self.checkpoints[..=i].to_vec(),
+                );
+                (g, c.entry)
+            }
+            None => (self.setup()?, 0),
+        };
+        for (i, x) in self.entries.iter().enumerate().take(n).skip(start) {
+            let res = match x {
+                Entry::Move(a) => g.apply_annotation(a).map(|_| ()),
+                Entry::EndTurn => g.try_tick().map_err(Error::from),
+                Entry::Undo => g.undo().map(|_| ()).map_err(Error::from),
+                Entry::Resign(p) => g.resign(*p).map(|_| ()).map_err(Error::from),
+                Entry::Forfeit(p) => g.time_out(*p).map_err(Error::from),
+                Entry::Abort => g.abort().map(|_| ()).map_err(Error::from),
+            };
+            res.map_err(|e| JournalError::Rejected(i, e))?;
+        }
+        Ok(g)
+    }
+
+    /// Restore the game saved by a checkpoint, with its full undo history
+    fn restore_checkpoint(&self, i: usize) -> Result<Game, JournalError> {
+        let c = &self.checkpoints[i];
+        let invalid = |e| JournalError::InvalidCheckpoint(c.entry, e);
+        let mut g = Game::restore(&c.save).map_err(invalid)?;
+        if c.kept > 0 {
+            let prev = match i.checked_sub(1) {
+                Some(j) => self.restore_checkpoint(j)?,
+                None => return Err(invalid(SaveError::InvalidValue("history"))),
+            };
+            let prefix = prev
+                .undo_history()
+                .get(..c.kept)
+                .ok_or(invalid(SaveError::InvalidValue("history")))?;
+            g.prepend_history(prefix.to_vec());
+        }
+        Ok(g)
+    }
+
+    /// Start a new game with the match setup
+    fn setup(&self) -> Result<Game, JournalError> {
+        let mut builder = Game::builder()
+            .seed(self.seed)
+            .dealer(self.dealer)
+            .rules(self.rules.clone());
+        if let Some(n) = self.snapshot_interval {
+            builder = builder.snapshot_interval(n);
+        }
+        for p in [PlayerId::Opponent, PlayerId::Dealer] {
+            if let Some(info) = &self.players[p as usize] {
+                builder = builder.player(p, info.clone());
+            }
+        }
+        builder
+            .build()
+            .map_err(|e| JournalError::InvalidSetup(e.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameError;
+    use crate::result::Termination;
+
+    #[test]
+    fn test_project() {
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .player(PlayerId::Dealer, PlayerInfo::named("Bo"))
+            .build()
+            .unwrap();
+        for x in ["*C&3", "!1", "!1"] {
+            g.apply_annotation(x).unwrap();
+            g.tick();
+        }
+        g.undo().unwrap();
+        let journal = g.journal();
+        assert_eq!(
+            journal.entries,
+            vec![
+                Entry::Move(String::from("*C&3")),
+                Entry::EndTurn,
+                Entry::Move(String::from("!1")),
+                Entry::EndTurn,
+                Entry::Move(String::from("!1")),
+                Entry::EndTurn,
+                Entry::Undo,
+            ]
+        );
+
+        let h = journal.project().unwrap();
+        assert_eq!(h.state.floor, g.state.floor);
+        assert_eq!(h.state.turn, g.state.turn);
+        assert_eq!(h.moves(), g.moves());
+        assert_eq!(h.players, g.players);
+        assert_eq!(h.journal(), journal);
+
+        // Earlier positions are projections too
+        let h = journal.project_to(2).unwrap();
+        assert_eq!(h.moves().len(), 1);
+        assert_eq!(h.state.turn, PlayerId::Dealer);
+
+        g.resign(PlayerId::Opponent).unwrap();
+        let h = g.journal().project().unwrap();
+        assert_eq!(h.result.unwrap().termination, Termination::Resigned);
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .snapshot_interval(10)
+            .build()
+            .unwrap();
+        while g.round < 2 {
+            let x = crate::sim::candidate_moves(&g.state)[0].clone();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+        let journal = g.journal();
+        assert!(journal.checkpoints.len() >= 2);
+        // Checkpoints are taken at the end of turns, at least ten entries apart
+        let mut last = 0;
+        for x in journal.checkpoints.iter() {
+            assert!(x.entry >= last + 10);
+            assert_eq!(journal.entries[x.entry - 1], Entry::EndTurn);
+            last = x.entry;
+        }
+
+        // Loading from a checkpoint gives the same match as replaying every entry
+        let full = Journal {
+            checkpoints: vec![],
+            ..journal.clone()
+        };
+        for n in [5, last, last + 1, journal.entries.len()] {
+            let (h, k) = (journal.project_to(n).unwrap(), full.project_to(n).unwrap());
+            assert_eq!(h.snapshot(), k.snapshot());
+        }
+        assert_eq!(journal.project().unwrap().journal(), journal);
+
+        let mut broken = journal.clone();
+        broken.checkpoints[0].kept = 1;
+        let first = broken.checkpoints[0].entry;
+        assert_eq!(
+            broken.project_to(first).err(),
+            Some(JournalError::InvalidCheckpoint(
+                first,
+                SaveError::InvalidValue("history")
+            ))
+        );
+
+        broken = journal.clone();
+        broken.checkpoints[0].save.truncate(8);
+        assert_eq!(
+            broken.project_to(first).err(),
+            Some(JournalError::InvalidCheckpoint(first, SaveError::Truncated))
+        );
+    }
+
+    #[test]
+    fn test_compact_checkpoints() {
+        let mut g = Game::builder()
+            .seed(Seed::default())
+            .snapshot_interval(1)
+            .build()
+            .unwrap();
+        while g.game < 1 {
+            let x = crate::sim::candidate_moves(&g.state)[0].clone();
+            g.apply_annotation(&x).unwrap();
+            if g.moves().len().is_multiple_of(5) {
+                g.undo().unwrap();
+                g.apply_annotation(&x).unwrap();
+            }
+            g.tick();
+        }
+        let journal = g.journal();
+        // Each checkpoint stores only the history added since the one before it
+        let largest = journal.checkpoints.iter().map(|x| x.save.len()).max();
+        let whole = Journal {
+            checkpoints: vec![],
+            ..journal.clone()
+        };
+        let full = journal
+            .checkpoints
+            .iter()
+            .map(|x| whole.project_to(x.entry).unwrap());
+        let full = full.map(|h| h.snapshot().len()).max();
+        assert!(largest.unwrap() * 4 < full.unwrap());
+
+        // The history left out of a checkpoint comes back, so moves before it can be undone
+        for c in journal.checkpoints.iter().step_by(7) {
+            let mut h = journal.project_to(c.entry).unwrap();
+            let mut k = whole.project_to(c.entry).unwrap();
+            assert_eq!(h.snapshot(), k.snapshot());
+            while k.undo().is_ok() {
+                h.undo().unwrap();
+                assert_eq!(h.snapshot(), k.snapshot());
+            }
+        }
+    }
+
+    #[test]
+    fn test_project_errors() {
+        let journal = Journal {
+            entries: vec![Entry::EndTurn, Entry::Move(String::from("*A&1"))],
+            ..Journal::default()
+        };
+        assert!(matches!(
+            journal.project(),
+            Err(JournalError::Rejected(1, _))
+        ));
+        let journal = Journal {
+            entries: vec![Entry::Undo],
+            ..Journal::default()
+        };
+        let e = journal.project().err().unwrap();
+        assert_eq!(
+            e,
+            JournalError::Rejected(0, Error::Game(GameError::NothingToUndo))
+        );
+        assert_eq!(
+            e.to_string(),
+            "Journal Error: Entry 1 could not be replayed - Game Error: There is no move to undo"
+        );
+    }
+}
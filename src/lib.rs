@@ -1,8 +1,35 @@
 pub mod action;
 pub mod api;
+pub mod bot;
 pub mod card;
+pub mod coach;
+pub mod commit;
+pub mod consts;
+pub mod error;
+pub mod exploit;
+pub mod fuzz;
 pub mod game;
+pub mod ismcts;
+pub mod journal;
+pub mod locale;
+pub mod manager;
+pub mod matchplay;
+pub mod metrics;
+pub mod observe;
 pub mod pile;
+pub mod protocol;
+pub mod rating;
+pub mod replay;
+pub mod result;
 pub mod rng;
+pub mod rules;
+pub mod save;
+pub mod scenario;
 pub mod score;
+pub mod search;
+pub mod session;
+pub mod sim;
 pub mod state;
+pub mod stats;
+
+pub use error::Error;
@@ -0,0 +1,601 @@
+use crate::action::ParsingError;
+use crate::card::{value_name, Card};
+use crate::error::{Error, ErrorCode};
+use crate::game::GameError;
+use crate::state::{AuditError, Orphan, StateError};
+use std::fmt;
+
+/// A language for engine strings shown to players
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Locale {
+    #[default]
+    English = 0,
+    Spanish = 1,
+    German = 2,
+}
+
+const VALUE_NAMES: [[&str; 14]; 3] = [
+    [
+        "?", "Ace", "Two", "Three", "Four", "Five", "Six", "Seven", "Eight", "Nine", "Ten", "Jack",
+        "Queen", "King",
+    ],
+    [
+        "?", "As", "Dos", "Tres", "Cuatro", "Cinco", "Seis", "Siete", "Ocho", "Nueve", "Diez",
+        "Jota", "Reina", "Rey",
+    ],
+    [
+        "?", "Ass", "Zwei", "Drei", "Vier", "Fünf", "Sechs", "Sieben", "Acht", "Neun", "Zehn",
+        "Bube", "Dame", "König",
+    ],
+];
+
+const SUIT_NAMES: [[&str; 4]; 3] = [
+    ["Clubs", "Diamonds", "Hearts", "Spades"],
+    ["Tréboles", "Diamantes", "Corazones", "Picas"],
+    ["Kreuz", "Karo", "Herz", "Pik"],
+];
+
+/// A message in English, Spanish, and German
+type Phrase = [&'static str; 3];
+
+const PARSING_ERROR: Phrase = ["Parsing Error", "Error de análisis", "Analysefehler"];
+const STATE_ERROR: Phrase = ["State Error", "Error de estado", "Zustandsfehler"];
+const AUDIT_ERROR: Phrase = ["Audit Error", "Error de auditoría", "Prüfungsfehler"];
+const GAME_ERROR: Phrase = ["Game Error", "Error de partida", "Spielfehler"];
+const INVALID_MOVE: Phrase = ["Invalid move", "Jugada no válida", "Ungültiger Zug"];
+const INVALID_PILE: Phrase = ["Invalid pile", "Montón no válido", "Ungültiger Stapel"];
+const UNKNOWN_ERROR: Phrase = ["Unknown error", "Error desconocido", "Unbekannter Fehler"];
+
+/// Messages for errors that carry details, with `{}` in place of each one
+const ORPHAN: Phrase = [
+    "pile {} needs a {} in hand",
+    "el montón {} necesita un {} en la mano",
+    "Stapel {} braucht eine {} auf der Hand",
+];
+const DUPLICATE_CARD: Phrase = [
+    "Card {} appears more than once",
+    "La carta {} aparece más de una vez",
+    "Karte {} kommt mehrmals vor",
+];
+const MISSING_CARD: Phrase = ["Card {} is missing", "Falta la carta {}", "Karte {} fehlt"];
+const INVALID_CARD: Phrase = [
+    "Card {} is not a valid card",
+    "La carta {} no es una carta válida",
+    "Karte {} ist keine gültige Karte",
+];
+const FLOOR_SIZE: Phrase = [
+    "Floor has {} slots",
+    "La mesa tiene {} huecos",
+    "Der Tisch hat {} Plätze",
+];
+const FLOOR_PILE: Phrase = [
+    "Floor pile {} is malformed",
+    "El montón {} de la mesa está mal formado",
+    "Tischstapel {} ist fehlerhaft",
+];
+const HAND_PILE: Phrase = [
+    "{} hand pile {} is malformed",
+    "En la mano de {} el montón {} está mal formado",
+    "Bei {} ist Handstapel {} fehlerhaft",
+];
+const OWNED_PILES: Phrase = [
+    "{} owns too many piles",
+    "{} tiene demasiados montones",
+    "{} besitzt zu viele Stapel",
+];
+const PLAYER_COUNT: Phrase = [
+    "{} players is not supported",
+    "No se admiten {} jugadores",
+    "{} Spieler werden nicht unterstützt",
+];
+const FLOOR_SIZE_RULE: Phrase = [
+    "A floor of {} piles is not supported",
+    "No se admite una mesa de {} montones",
+    "Ein Tisch mit {} Stapeln wird nicht unterstützt",
+];
+
+/// The message for each error code, without the details a particular error may carry
+fn code_phrase(code: ErrorCode) -> Phrase {
+    match code {
+        ErrorCode::Success => ["", "", ""],
+        ErrorCode::InvalidCString => [
+            "Error: Invalid CString",
+            "Error: CString no válido",
+            "Fehler: Ungültiger CString",
+        ],
+        ErrorCode::ParsingInvalidByte => ["Invalid byte", "Byte no válido", "Ungültiges Byte"],
+        ErrorCode::ParsingInvalidAddress => [
+            "Invalid pile address",
+            "Dirección de montón no válida",
+            "Ungültige Stapeladresse",
+        ],
+        ErrorCode::ParsingInvalidOperationCharacter => [
+            "Invalid operation char",
+            "Carácter de operación no válido",
+            "Ungültiges Operationszeichen",
+        ],
+        ErrorCode::ParsingInvalidAddressCharacter => [
+            "Invalid address char",
+            "Carácter de dirección no válido",
+            "Ungültiges Adresszeichen",
+        ],
+        ErrorCode::ParsingBlankAnnotation => [
+            "Annotation is blank",
+            "La anotación está vacía",
+            "Die Notation ist leer",
+        ],
+        ErrorCode::ParsingNoShortcutMatch => [
+            "No hand card fits the shortcut",
+            "Ninguna carta de la mano encaja con el atajo",
+            "Keine Handkarte passt zum Kürzel",
+        ],
+        ErrorCode::ParsingAmbiguousShortcut => [
+            "More than one hand card fits the shortcut",
+            "Más de una carta de la mano encaja con el atajo",
+            "Mehr als eine Handkarte passt zum Kürzel",
+        ],
+        ErrorCode::ParsingAnnotationTooLong => [
+            "Annotation is too long",
+            "La anotación es demasiado larga",
+            "Die Notation ist zu lang",
+        ],
+        ErrorCode::ParsingTooManyActions => [
+            "Move has too many actions",
+            "La jugada tiene demasiadas acciones",
+            "Der Zug hat zu viele Aktionen",
+        ],
+        ErrorCode::ParsingNonCanonicalAnnotation => [
+            "Annotation is not in canonical form",
+            "La anotación no está en forma canónica",
+            "Die Notation ist nicht in kanonischer Form",
+        ],
+        ErrorCode::MoveDuplicateAddress => [
+            "You cannot use the same address multiple times in a single move",
+            "No puedes usar la misma dirección varias veces en una jugada",
+            "Du kannst dieselbe Adresse nicht mehrmals in einem Zug verwenden",
+        ],
+        ErrorCode::MoveInvalidHandAddressCount => [
+            "You must use exactly one hand address in your move",
+            "Debes usar exactamente una dirección de la mano en tu jugada",
+            "Du musst genau eine Handadresse in deinem Zug verwenden",
+        ],
+        ErrorCode::MoveInvalidHandAddressPosition => [
+            "The hand address must be the last address in your move",
+            "La dirección de la mano debe ser la última de tu jugada",
+            "Die Handadresse muss die letzte Adresse in deinem Zug sein",
+        ],
+        ErrorCode::MoveInvalidBuildSegment => [
+            "Additional builds must only combine floor piles",
+            "Las construcciones adicionales solo pueden combinar montones de la mesa",
+            "Zusätzliche Bauten dürfen nur Tischstapel kombinieren",
+        ],
+        ErrorCode::MoveCaptureWithoutPair => [
+            "A capture must pair your hand card with the floor",
+            "Una captura debe emparejar tu carta de la mano con la mesa",
+            "Ein Stich muss deine Handkarte mit dem Tisch paaren",
+        ],
+        ErrorCode::PileInvalidBuildArg => [
+            "You may not build using a group",
+            "No puedes construir usando un grupo",
+            "Du darfst nicht mit einer Gruppe bauen",
+        ],
+        ErrorCode::PileInvalidGroupArg => [
+            "You may not group using a pair",
+            "No puedes agrupar usando una pareja",
+            "Du darfst nicht mit einem Paar gruppieren",
+        ],
+        ErrorCode::PileInvalidPairArg => [
+            "Invalid pair argument",
+            "Argumento de pareja no válido",
+            "Ungültiges Paarargument",
+        ],
+        ErrorCode::PileBuildEqualValues => [
+            "You may not build two cards with the same value",
+            "No puedes construir con dos cartas del mismo valor",
+            "Du darfst nicht zwei Karten mit demselben Wert bauen",
+        ],
+        ErrorCode::PileBuildHigherThanTen => [
+            "You may not build a value larger than 10",
+            "No puedes construir un valor mayor que 10",
+            "Du darfst keinen Wert über 10 bauen",
+        ],
+        ErrorCode::PileGroupDifferentValues => [
+            "You may not group two cards with different values",
+            "No puedes agrupar dos cartas de distinto valor",
+            "Du darfst nicht zwei Karten mit unterschiedlichen Werten gruppieren",
+        ],
+        ErrorCode::PileGroupTwoSingles => [
+            "You may not group two individual cards together",
+            "No puedes agrupar dos cartas sueltas",
+            "Du darfst nicht zwei einzelne Karten gruppieren",
+        ],
+        ErrorCode::PilePairDifferentValues => [
+            "You may not pair a card with a pile that has a different value",
+            "No puedes emparejar una carta con un montón de distinto valor",
+            "Du darfst keine Karte mit einem Stapel anderen Werts paaren",
+        ],
+        ErrorCode::PileValueMismatch => [
+            "The pile value does not match its cards",
+            "El valor del montón no coincide con sus cartas",
+            "Der Stapelwert passt nicht zu seinen Karten",
+        ],
+        ErrorCode::PileGroupFaceCards => [
+            "You may not group face cards",
+            "No puedes agrupar figuras",
+            "Du darfst keine Bildkarten gruppieren",
+        ],
+        ErrorCode::PilePairFaceGroup => [
+            "Face cards may only be paired one for one",
+            "Las figuras solo se pueden emparejar de una en una",
+            "Bildkarten dürfen nur einzeln gepaart werden",
+        ],
+        ErrorCode::StateInvalidAddress => [
+            "Invalid address",
+            "Dirección no válida",
+            "Ungültige Adresse",
+        ],
+        ErrorCode::StateInvalidDiscard => [
+            "Invalid discard",
+            "Descarte no válido",
+            "Ungültiges Abwerfen",
+        ],
+        ErrorCode::StateInvalidInput => ["Invalid input", "Entrada no válida", "Ungültige Eingabe"],
+        ErrorCode::StateFloorIsFull => {
+            ["Floor is full", "La mesa está llena", "Der Tisch ist voll"]
+        }
+        ErrorCode::StatePileIsNotEmpty => [
+            "Pile is not empty",
+            "El montón no está vacío",
+            "Der Stapel ist nicht leer",
+        ],
+        ErrorCode::StateOwnTooManyPiles => [
+            "Owning too may piles",
+            "Tienes demasiados montones",
+            "Zu viele eigene Stapel",
+        ],
+        ErrorCode::StateUnpairablePileValue => [
+            "Un-pairable pile value",
+            "Valor de montón sin pareja",
+            "Nicht paarbarer Stapelwert",
+        ],
+        ErrorCode::StateDuplicateFloorValue => [
+            "Duplicate floor card",
+            "Carta repetida en la mesa",
+            "Doppelte Tischkarte",
+        ],
+        ErrorCode::StateMultipleBuildsNotAllowed => [
+            "Multiple builds in one move are not allowed",
+            "No se permiten varias construcciones en una jugada",
+            "Mehrere Bauten in einem Zug sind nicht erlaubt",
+        ],
+        ErrorCode::StateNotYourTurn => [
+            "It is not your turn",
+            "No es tu turno",
+            "Du bist nicht am Zug",
+        ],
+        ErrorCode::StateUncapturedPile => [
+            "Every pile with the captured value must be taken",
+            "Hay que capturar todos los montones del valor capturado",
+            "Alle Stapel mit dem geschlagenen Wert müssen genommen werden",
+        ],
+        ErrorCode::StateMoveLimitReached => [
+            "Too many moves have been played this game",
+            "Se han jugado demasiadas jugadas en esta partida",
+            "In diesem Spiel wurden zu viele Züge gespielt",
+        ],
+        ErrorCode::StateInternal => ["Internal error", "Error interno", "Interner Fehler"],
+        ErrorCode::AuditDuplicateCard => [
+            "A card appears more than once",
+            "Una carta aparece más de una vez",
+            "Eine Karte kommt mehrmals vor",
+        ],
+        ErrorCode::AuditMissingCard => ["A card is missing", "Falta una carta", "Eine Karte fehlt"],
+        ErrorCode::AuditInvalidCard => [
+            "A card is not a valid card",
+            "Una carta no es una carta válida",
+            "Eine Karte ist keine gültige Karte",
+        ],
+        ErrorCode::AuditInvalidFloorSize => [
+            "Floor has the wrong number of slots",
+            "La mesa tiene un número incorrecto de huecos",
+            "Der Tisch hat die falsche Anzahl an Plätzen",
+        ],
+        ErrorCode::AuditInvalidFloorPile => [
+            "A floor pile is malformed",
+            "Un montón de la mesa está mal formado",
+            "Ein Tischstapel ist fehlerhaft",
+        ],
+        ErrorCode::AuditInvalidHandPile => [
+            "A hand pile is malformed",
+            "Un montón de la mano está mal formado",
+            "Ein Handstapel ist fehlerhaft",
+        ],
+        ErrorCode::AuditDuplicateFloorValue => [
+            "Duplicate floor value",
+            "Valor repetido en la mesa",
+            "Doppelter Tischwert",
+        ],
+        ErrorCode::AuditOwnTooManyPiles => [
+            "A player owns too many piles",
+            "Un jugador tiene demasiados montones",
+            "Ein Spieler besitzt zu viele Stapel",
+        ],
+        ErrorCode::AuditNoLegalMove => [
+            "No legal move available",
+            "No hay ninguna jugada legal",
+            "Kein legaler Zug möglich",
+        ],
+        ErrorCode::GameUnsupportedPlayerCount => [
+            "That number of players is not supported",
+            "No se admite ese número de jugadores",
+            "Diese Spielerzahl wird nicht unterstützt",
+        ],
+        ErrorCode::GameUnsupportedFloorSize => [
+            "That floor size is not supported",
+            "No se admite ese tamaño de mesa",
+            "Diese Tischgröße wird nicht unterstützt",
+        ],
+        ErrorCode::GameNothingToUndo => [
+            "There is no move to undo",
+            "No hay ninguna jugada que deshacer",
+            "Es gibt keinen Zug zum Zurücknehmen",
+        ],
+        ErrorCode::GameUndoDisabled => [
+            "Undo is disabled for this match",
+            "Deshacer está desactivado en esta partida",
+            "Zurücknehmen ist in diesem Match deaktiviert",
+        ],
+        ErrorCode::GameUndoLimitReached => [
+            "You have no undos left for this game",
+            "No te quedan jugadas por deshacer en este juego",
+            "Du hast in diesem Spiel keine Rücknahmen mehr",
+        ],
+        ErrorCode::GameTurnIsOver => [
+            "You may only undo moves during your turn",
+            "Solo puedes deshacer jugadas durante tu turno",
+            "Du darfst Züge nur während deines Zuges zurücknehmen",
+        ],
+        ErrorCode::GameOpponentMoved => [
+            "Your opponent has already moved since your last move",
+            "Tu rival ya ha jugado desde tu última jugada",
+            "Dein Gegner hat seit deinem letzten Zug bereits gezogen",
+        ],
+        ErrorCode::GamePileCaptured => [
+            "A pile used by your queued move has been captured",
+            "Un montón de tu jugada en cola ha sido capturado",
+            "Ein Stapel deines vorgemerkten Zugs wurde geschlagen",
+        ],
+        ErrorCode::GameStaleTurnToken => [
+            "The game has changed since your move was submitted",
+            "La partida ha cambiado desde que enviaste tu jugada",
+            "Das Spiel hat sich seit dem Absenden deines Zugs geändert",
+        ],
+        ErrorCode::GameMatchIsOver => [
+            "The match is already over",
+            "La partida ya ha terminado",
+            "Das Match ist bereits vorbei",
+        ],
+        ErrorCode::GameNothingToRedo => [
+            "There is no move to redo",
+            "No hay ninguna jugada que rehacer",
+            "Es gibt keinen Zug zum Wiederholen",
+        ],
+        ErrorCode::GameFloorCanStall => [
+            "A floor that can fill up with nothing to capture must not force captures",
+            "Una mesa que puede llenarse sin nada que capturar no puede obligar a capturar",
+            "Ein Tisch, der sich ohne schlagbare Stapel füllen kann, darf keinen Stich erzwingen",
+        ],
+    }
+}
+
+/// Put each detail in place of the next `{}` in a message
+fn fill(message: &str, details: &[&dyn fmt::Display]) -> String {
+    let mut out = String::new();
+    let mut parts = message.split("{}");
+    out.push_str(parts.next().unwrap_or_default());
+    for (part, x) in parts.zip(details) {
+        out.push_str(&x.to_string());
+        out.push_str(part);
+    }
+    out
+}
+
+impl Locale {
+    /// Get the name of a card value
+    pub fn value_name(self, value: u8) -> &'static str {
+        let names = &VALUE_NAMES[self as usize];
+        names.get(value as usize).unwrap_or(&names[0])
+    }
+
+    /// Get the name of a card suit
+    pub fn suit_name(self, suit: u8) -> &'static str {
+        SUIT_NAMES[self as usize].get(suit as usize).unwrap_or(&"?")
+    }
+
+    /// Get the full name of a card, such as "Ace of Spades"
+    pub fn card_name(self, c: Card) -> String {
+        let (value, suit) = (self.value_name(c.value), self.suit_name(c.suit));
+        match self {
+            Locale::English => format!("{} of {}", value, suit),
+            Locale::Spanish => format!("{} de {}", value, suit),
+            Locale::German => format!("{} {}", suit, value),
+        }
+    }
+
+    /// Get the message for an error code, without the details a particular error carries
+    pub fn code_message(self, code: ErrorCode) -> &'static str {
+        code_phrase(code)[self as usize]
+    }
+
+    /// Get the message for an error code the engine does not know
+    pub fn unknown_message(self) -> &'static str {
+        UNKNOWN_ERROR[self as usize]
+    }
+
+    /// Get an error's message in this language
+    ///
+    /// The English message is the error's `Display`.
+    pub fn error_message(self, e: &Error) -> String {
+        match e {
+            Error::Parsing(x) => self.parsing_message(x),
+            Error::Move(x) => self.code_message(x.into()).to_string(),
+            Error::Pile(x) => self.code_message(x.into()).to_string(),
+            Error::State(x) => self.state_message(x),
+            Error::Audit(x) => self.audit_message(x),
+            Error::Game(x) => self.game_message(x),
+        }
+    }
+
+    pub(crate) fn parsing_message(self, e: &ParsingError) -> String {
+        format!(
+            "{}: {}",
+            PARSING_ERROR[self as usize],
+            self.code_message(e.into())
+        )
+    }
+
+    pub(crate) fn orphan_message(self, o: &Orphan) -> String {
+        fill(ORPHAN[self as usize], &[&o.pile, &value_name(o.value)])
+    }
+
+    pub(crate) fn state_message(self, e: &StateError) -> String {
+        let i = self as usize;
+        let message = self.code_message(e.into());
+        let message = match e {
+            StateError::InvalidMove(_) => format!("{} - {}", INVALID_MOVE[i], message),
+            StateError::InvalidPile(_) => format!("{} - {}", INVALID_PILE[i], message),
+            StateError::UnpairablePileValue(o) => {
+                format!("{} - {}", message, self.orphan_message(o))
+            }
+            StateError::UncapturedPile(a) => format!("{} ({})", message, a),
+            StateError::Internal(x) => format!("{} - {}", message, x),
+            _ => message.to_string(),
+        };
+        format!("{}: {}", STATE_ERROR[i], message)
+    }
+
+    pub(crate) fn audit_message(self, e: &AuditError) -> String {
+        let i = self as usize;
+        let message = match e {
+            AuditError::DuplicateCard(c) => fill(DUPLICATE_CARD[i], &[c]),
+            AuditError::MissingCard(c) => fill(MISSING_CARD[i], &[c]),
+            AuditError::InvalidCard(c) => fill(INVALID_CARD[i], &[&format!("{:?}", c)]),
+            AuditError::InvalidFloorSize(n) => fill(FLOOR_SIZE[i], &[n]),
+            AuditError::InvalidFloorPile(n) => fill(FLOOR_PILE[i], &[n]),
+            AuditError::InvalidHandPile(p, n) => fill(HAND_PILE[i], &[p, n]),
+            AuditError::OwnTooManyPiles(p) => fill(OWNED_PILES[i], &[p]),
+            AuditError::DuplicateFloorValue | AuditError::NoLegalMove => {
+                self.code_message(e.into()).to_string()
+            }
+        };
+        format!("{}: {}", AUDIT_ERROR[i], message)
+    }
+
+    pub(crate) fn game_message(self, e: &GameError) -> String {
+        let i = self as usize;
+        let message = match e {
+            GameError::UnsupportedPlayerCount(n) => fill(PLAYER_COUNT[i], &[n]),
+            GameError::UnsupportedFloorSize(n) => fill(FLOOR_SIZE_RULE[i], &[n]),
+            _ => self.code_message(e.into()).to_string(),
+        };
+        format!("{}: {}", GAME_ERROR[i], message)
+    }
+}
+
+impl TryFrom<u8> for Locale {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Locale, u8> {
+        match value {
+            0 => Ok(Locale::English),
+            1 => Ok(Locale::Spanish),
+            2 => Ok(Locale::German),
+            _ => Err(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::{Address, MoveError};
+    use crate::card::{Suit, Value};
+    use crate::pile::PileError;
+    use crate::state::PlayerId;
+
+    #[test]
+    fn test_card_names() {
+        let c = Card::create(Value::Queen, Suit::Hearts);
+        assert_eq!(Locale::English.card_name(c), "Queen of Hearts");
+        assert_eq!(Locale::Spanish.card_name(c), "Reina de Corazones");
+        assert_eq!(Locale::German.card_name(c), "Herz Dame");
+        assert_eq!(format!("{:#}", c), "Queen of Hearts");
+        assert_eq!(c.to_string(), "Q♥");
+        assert_eq!(Locale::German.value_name(14), "?");
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let e = Error::from(StateError::InvalidPile(PileError::BuildHigherThanTen));
+        assert_eq!(
+            Locale::Spanish.error_message(&e),
+            "Error de estado: Montón no válido - No puedes construir un valor mayor que 10"
+        );
+        assert_eq!(
+            Locale::German.error_message(&Error::from(StateError::NotYourTurn)),
+            "Zustandsfehler: Du bist nicht am Zug"
+        );
+        // Details are kept in place even where the sentence order changes
+        assert_eq!(
+            Locale::Spanish.error_message(&Error::from(GameError::UnsupportedPlayerCount(3))),
+            "Error de partida: No se admiten 3 jugadores"
+        );
+        let e = Error::from(StateError::UnpairablePileValue(Orphan {
+            pile: Address::Floor(2),
+            value: 8,
+        }));
+        assert_eq!(
+            Locale::German.error_message(&e),
+            "Zustandsfehler: Nicht paarbarer Stapelwert - Stapel C braucht eine 8 auf der Hand"
+        );
+        assert_eq!(
+            Locale::Spanish.code_message(ErrorCode::StateUnpairablePileValue),
+            "Valor de montón sin pareja"
+        );
+    }
+
+    #[test]
+    fn test_english_matches_display() {
+        let c = Card::create(Value::Ace, Suit::Spades);
+        let errors = [
+            Error::from(ParsingError::AnnotationTooLong),
+            Error::from(MoveError::CaptureWithoutPair),
+            Error::from(PileError::GroupFaceCards),
+            Error::from(StateError::InvalidMove(MoveError::DuplicateAddress)),
+            Error::from(StateError::InvalidPile(PileError::ValueMismatch)),
+            Error::from(StateError::UnpairablePileValue(Orphan {
+                pile: Address::Floor(0),
+                value: 13,
+            })),
+            Error::from(StateError::UncapturedPile(Address::Floor(3))),
+            Error::from(StateError::Internal(String::from("oops"))),
+            Error::from(StateError::OwnTooManyPiles),
+            Error::from(AuditError::DuplicateCard(c)),
+            Error::from(AuditError::MissingCard(c)),
+            Error::from(AuditError::InvalidCard(Card { value: 0, suit: 9 })),
+            Error::from(AuditError::InvalidFloorSize(7)),
+            Error::from(AuditError::InvalidFloorPile(2)),
+            Error::from(AuditError::InvalidHandPile(PlayerId::Dealer, 4)),
+            Error::from(AuditError::OwnTooManyPiles(PlayerId::Opponent)),
+            Error::from(AuditError::NoLegalMove),
+            Error::from(GameError::UnsupportedPlayerCount(3)),
+            Error::from(GameError::UnsupportedFloorSize(30)),
+            Error::from(GameError::FloorCanStall),
+        ];
+        for e in errors {
+            assert_eq!(Locale::English.error_message(&e), e.to_string());
+        }
+    }
+}
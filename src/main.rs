@@ -1,4 +1,9 @@
 use playsuipi_core::api;
+use playsuipi_core::bot::Bot;
+use playsuipi_core::consts::{HANDS_SIZE, HAND_SIZE, INVALID_CARD_ID};
+use playsuipi_core::game::Game;
+use playsuipi_core::ismcts::IsmctsBot;
+use playsuipi_core::state::PlayerId;
 use std::env;
 use std::ffi::{CStr, CString};
 use std::fs::File;
@@ -73,7 +78,7 @@ fn show_pile(pile: api::Pile, status: &api::Status) -> String {
         .cards
         .iter()
         .map(|&x| x.to_owned())
-        .filter(|&x| x != 52)
+        .filter(|&x| x != INVALID_CARD_ID)
         .collect::<Vec<u8>>();
     let owned = if pile.owner == status.turn { "*" } else { "" };
     if cards.is_empty() {
@@ -96,16 +101,24 @@ fn show_pile(pile: api::Pile, status: &api::Status) -> String {
     }
 }
 
-fn show_hand(hand: [u8; 16]) -> String {
+fn show_hand(hand: [u8; HANDS_SIZE]) -> String {
     hand.iter()
-        .take(8) // Current player's cards
+        .take(HAND_SIZE) // Current player's cards
         .enumerate()
         .map(|(i, x)| format!("{}=({})", (i as u8 + 49) as char, show_card(x)))
         .collect::<Vec<String>>()
         .join(", ")
 }
 
-fn show_floor(floor: Box<[api::Pile; 13]>, status: &api::Status) -> String {
+#[allow(clippy::borrowed_box)]
+fn read_floor(g: &Box<Game>) -> Vec<api::Pile> {
+    let mut piles = vec![api::Pile::default(); api::floor_size(g)];
+    let n = unsafe { api::read_floor(g, piles.as_mut_ptr(), piles.len()) };
+    piles.truncate(n);
+    piles
+}
+
+fn show_floor(floor: &[api::Pile], status: &api::Status) -> String {
     floor
         .iter()
         .enumerate()
@@ -168,17 +181,20 @@ fn get_seed<R: Read>(r: R) -> IOResult<[u8; 32]> {
     let mut seed = [0; 32];
     lines
         .split('\n')
-        .filter_map(|str| match str.parse::<u8>() {
-            Ok(x) => Some(x),
-            Err(_) => None,
-        })
+        .filter_map(|str| str.parse::<u8>().ok())
         .enumerate()
         .for_each(|(i, x)| seed[i] = x);
     Ok(seed)
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    // Usage: playsuipi_core [--bot] [seed file]
+    let mut args: Vec<String> = env::args().collect();
+    let mut bot = args
+        .iter()
+        .position(|x| x == "--bot")
+        .map(|i| args.remove(i))
+        .map(|_| IsmctsBot::new(rand::random()));
     let seed = if args.len() > 1 {
         match File::open(args[1].as_str()) {
             Ok(f) => match get_seed(f) {
@@ -190,29 +206,45 @@ fn main() {
     } else {
         ptr::null()
     };
-    let mut g = unsafe { api::new_game(seed) };
+    let mut g = unsafe { api::new_game(seed) }.expect("Failed to set up the game");
     let mut status = api::status(&g);
     let mut game = status.game;
     let mut round = status.round;
     println!("[*] Seed: {:?}", status.seed);
     println!("{}", show_suipi());
-    while status.game < 2 {
-        if status.turn {
+    while !status.over {
+        if status.turn == PlayerId::Dealer {
             println!("\n[*] Dealer's turn:");
         } else {
             println!("\n[*] Opponent's turn:");
         }
-        println!("\nFloor: {}", show_floor(api::read_floor(&g), &status));
-        println!("Hand:  {}\n", show_hand(*api::read_hands(&g)));
-        unsafe {
-            loop {
-                let error = CStr::from_ptr(api::apply_move(&mut g, get_move().as_ptr()))
-                    .to_str()
-                    .unwrap();
-                if !error.is_empty() {
-                    println!("{}", error);
-                } else {
-                    break;
+        println!("\nFloor: {}", show_floor(&read_floor(&g), &status));
+        // The computer plays the dealer
+        if let Some(m) = bot
+            .as_mut()
+            .filter(|_| status.turn == PlayerId::Dealer)
+            .and_then(|b| b.choose_move(&g.state.redacted_for(PlayerId::Dealer)))
+        {
+            println!("> Computer plays {}", m);
+            g.apply(m).unwrap();
+        } else {
+            println!("Hand:  {}\n", show_hand(*api::read_hands(&g)));
+            unsafe {
+                loop {
+                    let m = get_move();
+                    if m.to_str().map(str::trim) == Ok("?") {
+                        let hint = CStr::from_ptr(api::get_hint(&g)).to_str().unwrap();
+                        println!("> Try {}", hint);
+                        continue;
+                    }
+                    let error = CStr::from_ptr(api::apply_move(&mut g, m.as_ptr()))
+                        .to_str()
+                        .unwrap();
+                    if !error.is_empty() {
+                        println!("{}", error);
+                    } else {
+                        break;
+                    }
                 }
             }
         }
@@ -0,0 +1,366 @@
+use crate::action::Profile;
+use crate::error::Error;
+use crate::game::{Game, GameBuilder, GameError, MoveOutcome};
+use crate::save::SaveError;
+use crate::state::PlayerId;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Id of a game hosted by a manager
+pub type GameId = u64;
+
+/// Game manager errors
+#[derive(Debug, Eq, PartialEq)]
+pub enum ManagerError {
+    UnknownGame(GameId),
+    /// A panic while the game was in use may have left it broken
+    Poisoned(GameId),
+    Rejected(Error),
+}
+
+impl fmt::Display for ManagerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Manager Error: {}",
+            match self {
+                ManagerError::UnknownGame(id) => format!("No game has id {}", id),
+                ManagerError::Poisoned(id) => format!("Game {} was broken by a panic", id),
+                ManagerError::Rejected(e) => format!("The move was rejected - {}", e),
+            }
+        )
+    }
+}
+
+impl std::error::Error for ManagerError {}
+
+/// Function given each evicted game's id and save, so it can be stored elsewhere
+pub type EvictHook = Box<dyn Fn(GameId, Vec<u8>) + Send + Sync>;
+
+/// A game and when it was last used
+struct Hosted {
+    /// The game, or `None` once it has been evicted
+    ///
+    /// Threads that found the game before it was evicted may still be waiting for its lock, so
+    /// it is taken out under the lock, and they find it gone instead of playing a move that
+    /// would be lost.
+    game: Mutex<Option<Game>>,
+    used_ms: AtomicU64,
+}
+
+impl Hosted {
+    fn new(game: Game, now_ms: u64) -> Hosted {
+        Hosted {
+            game: Mutex::new(Some(game)),
+            used_ms: AtomicU64::new(now_ms),
+        }
+    }
+
+    /// Run a function on the game while holding its lock
+    fn with<F, R>(&self, id: GameId, now_ms: u64, f: F) -> Result<R, ManagerError>
+    where
+        F: FnOnce(&mut Game) -> R,
+    {
+        let mut g = self.game.lock().map_err(|_| ManagerError::Poisoned(id))?;
+        let g = g.as_mut().ok_or(ManagerError::UnknownGame(id))?;
+        self.used_ms.fetch_max(now_ms, Ordering::Relaxed);
+        Ok(f(g))
+    }
+}
+
+/// Many games hosted at once, keyed by id, for servers
+///
+/// Each game has its own lock, so games can be played on different threads at the same time.
+/// Times are given by the caller in milliseconds, like `Game::touch`, and are used to expire
+/// games nobody has used for a while. Expired games can be handed to a hook to be saved, then
+/// restored under the same id when they are needed again.
+#[derive(Default)]
+pub struct GameManager {
+    games: RwLock<HashMap<GameId, Arc<Hosted>>>,
+    next_id: AtomicU64,
+    idle_ms: Option<u64>,
+    on_evict: Option<EvictHook>,
+}
+
+impl GameManager {
+    /// Create a manager with no games
+    pub fn new() -> Self {
+        GameManager::default()
+    }
+
+    /// Evict games that have not been used for the given time when `evict_idle` is called
+    pub fn set_idle_timeout(&mut self, idle_ms: u64) {
+        self.idle_ms = Some(idle_ms);
+    }
+
+    /// Hand every evicted game's save to the given function
+    pub fn set_evict_hook<F>(&mut self, f: F)
+    where
+        F: Fn(GameId, Vec<u8>) + Send + Sync + 'static,
+    {
+        self.on_evict = Some(Box::new(f));
+    }
+
+    /// Build a game and host it, returning its id
+    pub fn create(&self, builder: GameBuilder, now_ms: u64) -> Result<GameId, GameError> {
+        Ok(self.insert(builder.build()?, now_ms))
+    }
+
+    /// Host a game that was already built, returning its id
+    pub fn insert(&self, game: Game, now_ms: u64) -> GameId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.write().insert(id, Arc::new(Hosted::new(game, now_ms)));
+        id
+    }
+
+    /// Host a saved game again under the id it had, replacing any game with that id
+    pub fn restore(&self, id: GameId, save: &[u8], now_ms: u64) -> Result<(), SaveError> {
+        let hosted = Hosted::new(Game::restore(save)?, now_ms);
+        self.next_id
+            .fetch_max(id.saturating_add(1), Ordering::Relaxed);
+        self.write().insert(id, Arc::new(hosted));
+        Ok(())
+    }
+
+    /// Run a function on a game while holding its lock
+    pub fn with<F, R>(&self, id: GameId, now_ms: u64, f: F) -> Result<R, ManagerError>
+    where
+        F: FnOnce(&mut Game) -> R,
+    {
+        // Only hold the map's lock long enough to find the game
+        let hosted = self
+            .read()
+            .get(&id)
+            .cloned()
+            .ok_or(ManagerError::UnknownGame(id))?;
+        hosted.with(id, now_ms, f)
+    }
+
+    /// Play a move for the given player and end their turn
+    pub fn apply(
+        &self,
+        id: GameId,
+        p: PlayerId,
+        annotation: &str,
+        now_ms: u64,
+    ) -> Result<MoveOutcome, ManagerError> {
+        self.with(id, now_ms, |g| {
+            let outcome = g.apply_annotation_for(p, annotation, Profile::Standard)?;
+            g.try_tick()?;
+            Ok::<_, Error>(outcome)
+        })?
+        .map_err(ManagerError::Rejected)
+    }
+
+    /// Check whether a game is hosted
+    pub fn contains(&self, id: GameId) -> bool {
+        self.read().contains_key(&id)
+    }
+
+    /// Get the ids of every hosted game, in no particular order
+    pub fn ids(&self) -> Vec<GameId> {
+        self.read().keys().copied().collect()
+    }
+
+    /// Get the number of hosted games
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Check whether no games are hosted
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Stop hosting a game, returning it unless a panic has broken it
+    ///
+    /// Like `expire`, the game is taken out under its lock, so a thread still using it finishes
+    /// its move first, and threads waiting for it find it gone.
+    pub fn remove(&self, id: GameId) -> Option<Game> {
+        let hosted = self.write().remove(&id)?;
+        let mut g = hosted.game.lock().ok()?;
+        g.take()
+    }
+
+    /// Stop hosting every game that has not been used for `idle_ms`, returning their ids
+    ///
+    /// Each game is saved and handed to the evict hook, if there is one, unless a panic has
+    /// broken it.
+    pub fn expire(&self, now_ms: u64, idle_ms: u64) -> Vec<GameId> {
+        let expired = {
+            let mut games = self.write();
+            let ids = games
+                .iter()
+                .filter(|(_, x)| {
+                    now_ms.saturating_sub(x.used_ms.load(Ordering::Relaxed)) >= idle_ms
+                })
+                .map(|(&id, _)| id)
+                .collect::<Vec<GameId>>();
+            ids.into_iter()
+                .filter_map(|id| Some((id, games.remove(&id)?)))
+                .collect::<Vec<(GameId, Arc<Hosted>)>>()
+        };
+        // Games are saved after the map is unlocked, in case one is still finishing a move
+        for (id, hosted) in expired.iter() {
+            if let Ok(mut g) = hosted.game.lock() {
+                if let (Some(f), Some(g)) = (&self.on_evict, g.take()) {
+                    f(*id, g.snapshot());
+                }
+            }
+        }
+        expired.into_iter().map(|(id, _)| id).collect()
+    }
+
+    /// Stop hosting every game that has been idle for longer than the idle timeout
+    pub fn evict_idle(&self, now_ms: u64) -> Vec<GameId> {
+        match self.idle_ms {
+            Some(idle_ms) => self.expire(now_ms, idle_ms),
+            None => vec![],
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<GameId, Arc<Hosted>>> {
+        // The map is never left half changed, so a panic elsewhere does not make it unusable
+        self.games.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<GameId, Arc<Hosted>>> {
+        self.games.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Seed;
+    use crate::state::StateError;
+    use std::thread;
+
+    fn builder() -> GameBuilder {
+        Game::builder().seed(Seed::default())
+    }
+
+    #[test]
+    fn test_manager() {
+        let m = GameManager::new();
+        let a = m.create(builder(), 0).unwrap();
+        let b = m.create(builder(), 0).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(m.len(), 2);
+
+        let outcome = m.apply(a, PlayerId::Opponent, "*C&3", 10).unwrap();
+        assert_eq!(outcome.captured.len(), 2);
+        assert_eq!(
+            m.apply(a, PlayerId::Opponent, "!1", 10),
+            Err(ManagerError::Rejected(Error::State(
+                StateError::NotYourTurn
+            )))
+        );
+        assert_eq!(m.with(a, 10, |g| g.moves().len()), Ok(1));
+        assert_eq!(m.with(b, 0, |g| g.moves().len()), Ok(0));
+        assert_eq!(
+            m.apply(9, PlayerId::Opponent, "*C&3", 0).err(),
+            Some(ManagerError::UnknownGame(9))
+        );
+
+        // Only games left idle long enough expire
+        assert_eq!(m.expire(50, 50), vec![b]);
+        assert!(m.contains(a) && !m.contains(b));
+        assert_eq!(m.remove(a).unwrap().moves().len(), 1);
+        assert!(m.is_empty());
+        assert_eq!(
+            ManagerError::UnknownGame(9).to_string(),
+            "Manager Error: No game has id 9"
+        );
+    }
+
+    #[test]
+    fn test_eviction() {
+        let saved = Arc::new(Mutex::new(vec![]));
+        let mut m = GameManager::new();
+        assert!(m.evict_idle(u64::MAX).is_empty());
+        m.set_idle_timeout(1000);
+        let store = saved.clone();
+        m.set_evict_hook(move |id, save| store.lock().unwrap().push((id, save)));
+
+        let a = m.create(builder(), 0).unwrap();
+        let b = m.create(builder(), 0).unwrap();
+        m.apply(a, PlayerId::Opponent, "*C&3", 100).unwrap();
+        assert!(m.evict_idle(999).is_empty());
+        assert_eq!(m.evict_idle(1000), vec![b]);
+        assert_eq!(m.evict_idle(1100), vec![a]);
+        assert!(m.is_empty());
+
+        // Evicted games come back as they were, under the same ids
+        let saves = std::mem::take(&mut *saved.lock().unwrap());
+        assert_eq!(
+            saves.iter().map(|x| x.0).collect::<Vec<GameId>>(),
+            vec![b, a]
+        );
+        for (id, save) in saves.iter() {
+            m.restore(*id, save, 2000).unwrap();
+        }
+        assert_eq!(m.with(a, 2000, |g| g.moves().len()), Ok(1));
+        assert_ne!(m.create(builder(), 2000).unwrap(), a);
+        assert_eq!(m.restore(a, b"oops", 0), Err(SaveError::NotASave));
+    }
+
+    #[test]
+    fn test_evicted_game_is_not_played() {
+        let saved = Arc::new(Mutex::new(vec![]));
+        let mut m = GameManager::new();
+        let store = saved.clone();
+        m.set_evict_hook(move |_, save| store.lock().unwrap().push(save));
+        let a = m.create(builder(), 0).unwrap();
+
+        // A thread that found the game before it was evicted can't play a move on it after
+        let stale = m.read().get(&a).cloned().unwrap();
+        assert_eq!(m.expire(100, 100), vec![a]);
+        assert_eq!(
+            stale.with(a, 100, |g| g.apply_annotation("*C&3").is_ok()),
+            Err(ManagerError::UnknownGame(a))
+        );
+        let saves = saved.lock().unwrap();
+        assert_eq!(Game::restore(&saves[0]).unwrap().moves().len(), 0);
+
+        // Removing a game another thread still holds hands it back rather than dropping it
+        let b = m.create(builder(), 0).unwrap();
+        let stale = m.read().get(&b).cloned().unwrap();
+        assert_eq!(m.remove(b).unwrap().moves().len(), 0);
+        assert!(!m.contains(b));
+        assert_eq!(
+            stale.with(b, 100, |g| g.apply_annotation("*C&3").is_ok()),
+            Err(ManagerError::UnknownGame(b))
+        );
+    }
+
+    #[test]
+    fn test_concurrent_games() {
+        let m = GameManager::new();
+        let ids = (0..8)
+            .map(|_| m.create(builder(), 0).unwrap())
+            .collect::<Vec<GameId>>();
+        thread::scope(|s| {
+            for &id in ids.iter() {
+                let m = &m;
+                s.spawn(move || {
+                    for x in ["*C&3", "!1", "!1"] {
+                        let p = m.with(id, 1, |g| g.state.turn).unwrap();
+                        m.apply(id, p, x, 1).unwrap();
+                    }
+                });
+            }
+        });
+        for id in ids {
+            assert_eq!(m.with(id, 2, |g| g.moves().len()), Ok(3));
+        }
+
+        // A panic while using a game is reported instead of handing out the broken game
+        let id = m.create(builder(), 0).unwrap();
+        let res = thread::scope(|s| s.spawn(|| m.with(id, 0, |_| panic!("oops"))).join());
+        assert!(res.is_err());
+        assert_eq!(m.with(id, 0, |_| ()), Err(ManagerError::Poisoned(id)));
+    }
+}
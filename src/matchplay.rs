@@ -0,0 +1,79 @@
+use crate::result::MatchResult;
+use crate::rng::Seed;
+use crate::rules::Rules;
+use crate::sim::{play_match_with, Violation};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// The outcome of one match in a run
+pub type Outcome = Result<MatchResult, Violation>;
+
+/// Play a random match for each seed on a pool of threads
+///
+/// Each match only depends on its seed and the rules, so the outcomes are the same on every
+/// run, in the same order as the seeds. The callback is given each outcome and its seed's index
+/// as soon as the match is finished, which may be out of order.
+pub fn run<F>(seeds: &[Seed], rules: &Rules, threads: usize, mut on_outcome: F) -> Vec<Outcome>
+where
+    F: FnMut(usize, &Outcome),
+{
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    let mut outcomes = (0..seeds.len())
+        .map(|_| None)
+        .collect::<Vec<Option<Outcome>>>();
+    thread::scope(|s| {
+        for _ in 0..threads.max(1) {
+            let tx = tx.clone();
+            let next = &next;
+            s.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&seed) = seeds.get(i) else {
+                    break;
+                };
+                let outcome = play_match_with(seed, rules.clone(), |_, _, _| Ok(()))
+                    // Matches played to the end always have a result
+                    .map(|g| g.result.unwrap());
+                if tx.send((i, outcome)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+        for (i, outcome) in rx {
+            on_outcome(i, &outcome);
+            outcomes[i] = Some(outcome);
+        }
+    });
+    outcomes.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let seeds = (0..12).map(|i| [i; 32]).collect::<Vec<Seed>>();
+        let rules = Rules {
+            games: 1,
+            ..Rules::default()
+        };
+        let mut seen = vec![];
+        let outcomes = run(&seeds, &rules, 4, |i, x| {
+            assert!(x.is_ok());
+            seen.push(i);
+        });
+        seen.sort();
+        assert_eq!(seen, (0..12).collect::<Vec<usize>>());
+        assert_eq!(outcomes.len(), 12);
+        assert!(outcomes
+            .iter()
+            .all(|x| x.as_ref().unwrap().per_game_scores.len() == 1));
+
+        // The same seeds give the same results, however many threads play them
+        assert_eq!(run(&seeds, &rules, 1, |_, _| ()), outcomes);
+        assert!(run(&[], &rules, 4, |_, _| ()).is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::error::Error;
+use crate::state::StateError;
+use std::time::Duration;
+
+/// Ways a move can be rejected
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Failure {
+    /// The annotation could not be parsed
+    Parsing = 0,
+    /// The move was malformed, like using an address twice
+    Move = 1,
+    /// The move would make an invalid pile
+    Pile = 2,
+    /// The move broke a rule of the game, like discarding onto a full floor
+    Rule = 3,
+    /// The engine hit a bug
+    Internal = 4,
+}
+
+/// Number of kinds of failure
+pub const FAILURE_KINDS: usize = 5;
+
+impl Failure {
+    /// Get the kind of failure an error is
+    pub fn of(e: &Error) -> Failure {
+        match e {
+            Error::Parsing(_) => Failure::Parsing,
+            Error::Move(_) => Failure::Move,
+            Error::Pile(_) => Failure::Pile,
+            Error::State(e) => Failure::of_state(e),
+            Error::Audit(_) | Error::Game(_) => Failure::Rule,
+        }
+    }
+
+    /// Get the kind of failure a state error is
+    pub fn of_state(e: &StateError) -> Failure {
+        match e {
+            StateError::InvalidMove(_) => Failure::Move,
+            StateError::InvalidPile(_) => Failure::Pile,
+            StateError::Internal(_) => Failure::Internal,
+            _ => Failure::Rule,
+        }
+    }
+}
+
+/// Counters for monitoring the engine while it runs a game
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Metrics {
+    pub moves_applied: u64,
+    /// Moves rejected, indexed by kind of failure
+    pub failures: [u64; FAILURE_KINDS],
+    pub undos: u64,
+    /// Time spent applying the moves that were played, which is not measured on wasm
+    pub apply_time: Duration,
+}
+
+impl Metrics {
+    /// Count a rejected move
+    pub(crate) fn fail(&mut self, kind: Failure) {
+        self.failures[kind as usize] += 1;
+    }
+
+    /// Get the number of moves rejected for the given reason
+    pub fn failures_of(&self, kind: Failure) -> u64 {
+        self.failures[kind as usize]
+    }
+
+    /// Get the number of moves rejected for any reason
+    pub fn total_failures(&self) -> u64 {
+        self.failures.iter().sum()
+    }
+
+    /// Get the average time taken to apply a move
+    pub fn average_apply_time(&self) -> Duration {
+        match self.moves_applied {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos((self.apply_time.as_nanos() / n as u128) as u64),
+        }
+    }
+
+    /// Add another game's counters to these, to sum them across games
+    pub fn merge(&mut self, other: &Metrics) {
+        self.moves_applied += other.moves_applied;
+        for (x, y) in self.failures.iter_mut().zip(other.failures.iter()) {
+            *x += y;
+        }
+        self.undos += other.undos;
+        self.apply_time += other.apply_time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::ParsingError;
+    use crate::pile::PileError;
+
+    #[test]
+    fn test_failure_kinds() {
+        let kind = |e: StateError| Failure::of(&Error::from(e));
+        assert_eq!(kind(StateError::FloorIsFull), Failure::Rule);
+        assert_eq!(kind(PileError::GroupTwoSingles.into()), Failure::Pile);
+        assert_eq!(kind(StateError::Internal(String::new())), Failure::Internal);
+        assert_eq!(
+            Failure::of(&Error::from(ParsingError::BlankAnnotation)),
+            Failure::Parsing
+        );
+
+        let mut m = Metrics {
+            moves_applied: 4,
+            apply_time: Duration::from_micros(10),
+            ..Metrics::default()
+        };
+        m.fail(Failure::of_state(&StateError::NotYourTurn));
+        let n = m.clone();
+        m.merge(&n);
+        assert_eq!(m.failures_of(Failure::Rule), 2);
+        assert_eq!(m.total_failures(), 2);
+        assert_eq!(m.average_apply_time(), Duration::from_nanos(2500));
+        assert_eq!(Metrics::default().average_apply_time(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::card::Card;
+use crate::consts::{DECK_SIZE, HAND_SIZE, MAX_FLOOR_SIZE, OBSERVATION_SIZE, SUIT_SIZE};
+use crate::state::State;
+
+/// Encode what the player to move can see of a game state
+///
+/// The observation is `OBSERVATION_SIZE` values laid out as:
+///
+/// * `0..52`: cards in the player's hand
+/// * `52..104`: cards on the floor
+/// * `104..156`: cards the player has captured
+/// * `156..208`: cards the other player has captured
+/// * `208..274`: for each of the 22 floor piles a floor can hold, its value over 13, whether it
+///   is a build, and whether the player owns it, with slots past the end of the floor left at zero
+/// * `274..278`: both hand sizes over 8, the deck size over 52, and whether the player scored
+///   last
+pub fn encode(state: &State) -> Vec<f32> {
+    let mut out = vec![0.0; OBSERVATION_SIZE];
+    write(state, &mut out);
+    out
+}
+
+/// Encode many game states into one buffer, one row of `OBSERVATION_SIZE` values per state
+pub fn encode_batch(states: &[State]) -> Vec<f32> {
+    let mut out = vec![0.0; states.len() * OBSERVATION_SIZE];
+    for (s, row) in states.iter().zip(out.chunks_exact_mut(OBSERVATION_SIZE)) {
+        write(s, row);
+    }
+    out
+}
+
+/// Write an observation into a zeroed row
+pub(crate) fn write(state: &State, row: &mut [f32]) {
+    let me = state.seat(state.turn);
+    let them = state.seat(state.turn.other());
+    let mut mark = |plane: usize, cards: &mut dyn Iterator<Item = &Card>| {
+        for &c in cards {
+            let id = u8::from(c) as usize;
+            if id < DECK_SIZE {
+                row[plane * DECK_SIZE + id] = 1.0;
+            }
+        }
+    };
+    mark(0, &mut me.hand.iter().flat_map(|x| x.cards.iter()));
+    mark(1, &mut state.floor.iter().flat_map(|x| x.cards.iter()));
+    mark(2, &mut me.pairs.iter().flat_map(|x| x.cards.iter()));
+    mark(3, &mut them.pairs.iter().flat_map(|x| x.cards.iter()));
+
+    let piles = 4 * DECK_SIZE;
+    for (i, x) in state.floor.iter().take(MAX_FLOOR_SIZE).enumerate() {
+        if !x.is_empty() {
+            row[piles + i * 3] = x.value as f32 / SUIT_SIZE as f32;
+            row[piles + i * 3 + 1] = x.is_build() as u8 as f32;
+            row[piles + i * 3 + 2] = (x.is_build() && x.owner == state.turn) as u8 as f32;
+        }
+    }
+
+    let scalars = piles + 3 * MAX_FLOOR_SIZE;
+    row[scalars] = me.card_count() as f32 / HAND_SIZE as f32;
+    row[scalars + 1] = them.card_count() as f32 / HAND_SIZE as f32;
+    row[scalars + 2] = state.deck.len() as f32 / DECK_SIZE as f32;
+    row[scalars + 3] = (state.last_score == state.turn) as u8 as f32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::rng::Seed;
+
+    #[test]
+    fn test_encode() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        let first = encode(&g.state);
+        assert_eq!(first.len(), OBSERVATION_SIZE);
+        assert_eq!(first[..DECK_SIZE].iter().sum::<f32>(), 8.0);
+        assert_eq!(first[DECK_SIZE..2 * DECK_SIZE].iter().sum::<f32>(), 4.0);
+        // Floor pile A is the four of clubs
+        assert_eq!(first[4 * DECK_SIZE], 4.0 / 13.0);
+        assert_eq!(first[OBSERVATION_SIZE - 4], 1.0);
+
+        g.apply_annotation("*C&3").unwrap();
+        g.tick();
+        let second = encode(&g.state);
+        // The dealer sees the opponent's capture as the other player's cards
+        assert_eq!(
+            second[3 * DECK_SIZE..4 * DECK_SIZE].iter().sum::<f32>(),
+            2.0
+        );
+        assert_eq!(second[OBSERVATION_SIZE - 3], 7.0 / 8.0);
+        assert_eq!(second[OBSERVATION_SIZE - 1], 0.0);
+
+        let batch = encode_batch(&[
+            Game::builder().seed(Seed::default()).build().unwrap().state,
+            g.state,
+        ]);
+        assert_eq!(batch.len(), 2 * OBSERVATION_SIZE);
+        assert_eq!(batch[..OBSERVATION_SIZE], first[..]);
+        assert_eq!(batch[OBSERVATION_SIZE..], second[..]);
+        assert!(encode_batch(&[]).is_empty());
+
+        // Floor piles past the default floor size are encoded too
+        let mut s = crate::scenario::Scenario::parse("floor: 3D\nhand: 3H")
+            .unwrap()
+            .state;
+        s.rules.floor_size = 20;
+        s.floor.resize(20, crate::pile::Pile::empty());
+        s.floor.swap(0, 15);
+        let row = encode(&s);
+        assert_eq!(row[4 * DECK_SIZE + 15 * 3], 3.0 / 13.0);
+        assert_eq!(row[4 * DECK_SIZE + 16 * 3], 0.0);
+        assert_eq!(row[OBSERVATION_SIZE - 4], 1.0 / 8.0);
+    }
+}
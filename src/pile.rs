@@ -1,4 +1,7 @@
 use crate::card::Card;
+use crate::consts::MAX_BUILD_VALUE;
+use crate::locale::Locale;
+use crate::state::PlayerId;
 use std::fmt;
 
 /// Pile manipulation errors
@@ -12,31 +15,22 @@ pub enum PileError {
     GroupDifferentValues,
     GroupTwoSingles,
     PairDifferentValues,
+    ValueMismatch,
+    GroupFaceCards,
+    PairFaceGroup,
 }
 
 impl fmt::Display for PileError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                PileError::InvalidBuildArg => "You may not build using a group",
-                PileError::InvalidGroupArg => "You may not group using a pair",
-                PileError::InvalidPairArg => "Invalid pair argument",
-                PileError::BuildEqualValues => "You may not build two cards with the same value",
-                PileError::BuildHigherThanTen => "You may not build a value larger than 10",
-                PileError::GroupDifferentValues =>
-                    "You may not group two cards with different values",
-                PileError::GroupTwoSingles => "You may not group two individual cards together",
-                PileError::PairDifferentValues =>
-                    "You may not pair a card with a pile that has a different value",
-            }
-        )
+        f.write_str(Locale::English.code_message(self.into()))
     }
 }
 
+impl std::error::Error for PileError {}
+
 /// A pile type marker
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mark {
     #[default]
     Empty,
@@ -47,14 +41,59 @@ pub enum Mark {
 }
 
 /// A pile of cards
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+///
+/// Floor piles get an id that stays the same until they are captured, no matter how the floor
+/// is rearranged. Piles are compared by their contents, ignoring the id.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "UncheckedPile"))]
 pub struct Pile {
     pub cards: Vec<Card>,
     pub value: u8,
     pub mark: Mark,
-    pub owner: bool,
+    pub owner: PlayerId,
+    pub id: u16,
+}
+
+/// A pile as it is deserialized, before its value is checked against its cards
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct UncheckedPile {
+    cards: Vec<Card>,
+    value: u8,
+    mark: Mark,
+    owner: PlayerId,
+    id: u16,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedPile> for Pile {
+    type Error = PileError;
+
+    fn try_from(x: UncheckedPile) -> Result<Pile, PileError> {
+        let pile = Pile {
+            cards: x.cards,
+            value: x.value,
+            mark: x.mark,
+            owner: x.owner,
+            id: x.id,
+        };
+        pile.verify()?;
+        Ok(pile)
+    }
 }
 
+impl PartialEq for Pile {
+    fn eq(&self, other: &Pile) -> bool {
+        self.cards == other.cards
+            && self.value == other.value
+            && self.mark == other.mark
+            && self.owner == other.owner
+    }
+}
+
+impl Eq for Pile {}
+
 impl Pile {
     /// Create a new pile
     pub fn new(cards: Vec<Card>, value: u8, mark: Mark) -> Self {
@@ -62,7 +101,8 @@ impl Pile {
             cards,
             value,
             mark,
-            owner: false,
+            owner: PlayerId::default(),
+            id: 0,
         }
     }
 
@@ -106,6 +146,50 @@ impl Pile {
         self.mark == Mark::Pair
     }
 
+    /// Work out the value of the pile from its cards, if they make a valid pile of its kind
+    ///
+    /// Groups are stored as consecutive runs of cards that each add up to the value, so the
+    /// stored value is kept when it fits, otherwise the smallest value that fits is used. Pairs
+    /// are kept as they are.
+    pub fn recompute_value(&self) -> Option<u8> {
+        let sum = self.cards.iter().map(|c| c.value as u16).sum::<u16>();
+        let runs = |v: u16| {
+            v > 0
+                && self
+                    .cards
+                    .iter()
+                    .try_fold(0, |run, c| match run + c.value as u16 {
+                        x if x == v => Some(0),
+                        x if x < v => Some(x),
+                        _ => None,
+                    })
+                    == Some(0)
+        };
+        match self.mark {
+            Mark::Empty if self.cards.is_empty() => Some(0),
+            Mark::Single if self.cards.len() == 1 => Some(self.cards[0].value),
+            Mark::Build if self.cards.len() > 1 && sum <= MAX_BUILD_VALUE as u16 => Some(sum as u8),
+            Mark::Group if self.cards.len() > 1 => {
+                if self.value as u16 != sum && runs(self.value as u16) {
+                    Some(self.value)
+                } else {
+                    (1..sum).find(|&v| runs(v)).map(|v| v as u8)
+                }
+            }
+            Mark::Pair => Some(self.value),
+            _ => None,
+        }
+    }
+
+    /// Make sure the stored value matches the cards in the pile
+    pub fn verify(&self) -> Result<(), PileError> {
+        if self.recompute_value() == Some(self.value) {
+            Ok(())
+        } else {
+            Err(PileError::ValueMismatch)
+        }
+    }
+
     /// Replace the current pile with another
     pub fn replace(&mut self, p: Pile) -> Pile {
         let x = self.clone();
@@ -113,6 +197,7 @@ impl Pile {
         self.value = p.value;
         self.mark = p.mark;
         self.owner = p.owner;
+        self.id = p.id;
         x
     }
 
@@ -157,6 +242,29 @@ impl Pile {
         }
     }
 
+    /// Is this pile made of face cards, which can not be built?
+    pub fn is_face(&self) -> bool {
+        self.value > MAX_BUILD_VALUE
+    }
+
+    /// Can I group this pile when face cards may only be paired one for one?
+    pub fn face_groupable(x: &Pile) -> Result<(), PileError> {
+        if x.is_face() {
+            Err(PileError::GroupFaceCards)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Can I capture this pile when face cards may only be paired one for one?
+    pub fn face_pairable(x: &Pile) -> Result<(), PileError> {
+        if x.is_face() && x.cards.len() > 1 {
+            Err(PileError::PairFaceGroup)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Combine the cards from two piles
     pub fn cards(x: &mut Pile, y: &mut Pile) -> Vec<Card> {
         let mut cards = vec![];
@@ -171,7 +279,7 @@ impl Pile {
         Pile::buildable(y)?;
         if x.value == y.value && x.is_single() && y.is_single() {
             Err(PileError::BuildEqualValues)
-        } else if x.value + y.value > 10 {
+        } else if x.value + y.value > MAX_BUILD_VALUE {
             Err(PileError::BuildHigherThanTen)
         } else {
             Ok(Pile::new(Pile::cards(x, y), x.value + y.value, Mark::Build))
@@ -190,6 +298,17 @@ impl Pile {
         }
     }
 
+    /// Create a group from two piles of the same value that are being captured together
+    pub fn gather(x: &mut Pile, y: &mut Pile) -> Result<Pile, PileError> {
+        Pile::groupable(x)?;
+        Pile::groupable(y)?;
+        if x.value != y.value {
+            Err(PileError::GroupDifferentValues)
+        } else {
+            Ok(Pile::new(Pile::cards(x, y), x.value, Mark::Group))
+        }
+    }
+
     /// Create a pair pile using a pairable pile
     pub fn pair(x: &mut Pile, y: &mut Pile) -> Result<Pile, PileError> {
         Pile::pairable(y)?;
@@ -270,4 +389,56 @@ mod tests {
         let c = Pile::group(&mut a, &mut b);
         assert_eq!(c, Err(PileError::GroupTwoSingles));
     }
+
+    #[test]
+    fn test_face_cards() {
+        let king = Pile::card(13, 0);
+        assert!(king.is_face());
+        assert!(!Pile::card(10, 0).is_face());
+        assert_eq!(Pile::face_groupable(&king), Err(PileError::GroupFaceCards));
+        assert!(Pile::face_groupable(&Pile::card(5, 0)).is_ok());
+        assert!(Pile::face_pairable(&king).is_ok());
+        let kings = Pile::new(vec![Card::new(13, 0), Card::new(13, 1)], 13, Mark::Group);
+        assert_eq!(Pile::face_pairable(&kings), Err(PileError::PairFaceGroup));
+    }
+
+    #[test]
+    fn test_recompute_value() {
+        let cards = |xs: &[u8]| xs.iter().map(|&v| Card::new(v, 0)).collect::<Vec<Card>>();
+        assert_eq!(Pile::empty().recompute_value(), Some(0));
+        assert_eq!(Pile::card(7, 0).recompute_value(), Some(7));
+        assert_eq!(
+            Pile::new(cards(&[2, 3]), 9, Mark::Build).recompute_value(),
+            Some(5)
+        );
+        assert_eq!(
+            Pile::new(cards(&[6, 7]), 13, Mark::Build).recompute_value(),
+            None
+        );
+        assert_eq!(
+            Pile::new(cards(&[2, 3, 5]), 0, Mark::Group).recompute_value(),
+            Some(5)
+        );
+        // Both values fit, so the stored one is kept
+        assert_eq!(
+            Pile::new(cards(&[2, 2, 2, 2]), 4, Mark::Group).recompute_value(),
+            Some(4)
+        );
+        assert_eq!(
+            Pile::new(cards(&[2, 3]), 5, Mark::Group).recompute_value(),
+            None
+        );
+
+        assert!(Pile::new(cards(&[2, 3, 5]), 5, Mark::Group)
+            .verify()
+            .is_ok());
+        assert_eq!(
+            Pile::new(cards(&[2, 3]), 6, Mark::Build).verify(),
+            Err(PileError::ValueMismatch)
+        );
+        assert_eq!(
+            Pile::new(vec![], 3, Mark::Empty).verify(),
+            Err(PileError::ValueMismatch)
+        );
+    }
 }
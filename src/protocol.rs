@@ -0,0 +1,198 @@
+use crate::api::Status;
+use crate::commit::{to_hex, DealCommitment, DealReveal};
+use crate::pile::Pile;
+use crate::state::PlayerId;
+use std::fmt;
+
+/// Protocol message errors
+#[derive(Debug, Eq, PartialEq)]
+pub enum ProtocolError {
+    EmptyMessage,
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    InvalidSeat(String),
+    MessageTooLong,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Protocol Error: {}",
+            match self {
+                ProtocolError::EmptyMessage => "Empty message".to_string(),
+                ProtocolError::UnknownCommand(x) => format!("Unknown command {}", x),
+                ProtocolError::MissingArgument(x) => format!("Missing {} argument", x),
+                ProtocolError::InvalidSeat(x) => format!("Invalid seat {}", x),
+                ProtocolError::MessageTooLong => "Message is too long".to_string(),
+            }
+        )
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// A line sent from a client to the server
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Request {
+    /// Claim the given seat, or the first free one
+    Sit(Option<PlayerId>),
+    /// Mark the client's seat as ready to start
+    Ready,
+    /// Play a move, given as an annotation
+    Move(String),
+    /// Ask for the current game status, floor, and hand
+    View,
+    /// Leave the session
+    Quit,
+}
+
+impl Request {
+    /// Parse a request from a line of text
+    pub fn parse(line: &str) -> Result<Request, ProtocolError> {
+        let line = line.trim();
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        match command.to_uppercase().as_str() {
+            "" => Err(ProtocolError::EmptyMessage),
+            "SIT" => match arg.trim().to_lowercase().as_str() {
+                "" => Ok(Request::Sit(None)),
+                "opponent" => Ok(Request::Sit(Some(PlayerId::Opponent))),
+                "dealer" => Ok(Request::Sit(Some(PlayerId::Dealer))),
+                _ => Err(ProtocolError::InvalidSeat(arg.trim().to_string())),
+            },
+            "READY" => Ok(Request::Ready),
+            "MOVE" if arg.trim().is_empty() => Err(ProtocolError::MissingArgument("annotation")),
+            "MOVE" => Ok(Request::Move(arg.trim().to_string())),
+            "VIEW" => Ok(Request::View),
+            "QUIT" => Ok(Request::Quit),
+            _ => Err(ProtocolError::UnknownCommand(command.to_string())),
+        }
+    }
+}
+
+/// A line sent from the server to a client
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Response {
+    /// A client has taken a player seat
+    Seat(PlayerId),
+    /// The client is watching the game
+    Spectator,
+    /// A seated player is ready to start
+    Ready(PlayerId),
+    /// Both players are ready and the match has started
+    Start,
+    /// A player made a move
+    Moved(PlayerId, String),
+    /// It is now this player's turn
+    Turn(PlayerId),
+    /// The match is over, with the winner if there is one
+    Over(Option<PlayerId>),
+    Status(Status),
+    Floor(Vec<Pile>),
+    Hand(Vec<Pile>),
+    /// A commitment to the deal of a game, when the deal is hidden
+    Commit(DealCommitment),
+    /// The deal of a finished game, to check against its commitment
+    Reveal(DealReveal),
+    Error(String),
+    Bye,
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let piles = |xs: &Vec<Pile>| {
+            xs.iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+        match self {
+            Response::Seat(p) => write!(f, "SEAT {}", p),
+            Response::Spectator => write!(f, "SPECTATOR"),
+            Response::Ready(p) => write!(f, "READY {}", p),
+            Response::Start => write!(f, "START"),
+            Response::Moved(p, a) => write!(f, "MOVED {} {}", p, a),
+            Response::Turn(p) => write!(f, "TURN {}", p),
+            Response::Over(Some(p)) => write!(f, "OVER {}", p),
+            Response::Over(None) => write!(f, "OVER draw"),
+            Response::Status(s) => write!(f, "STATUS {}", s),
+            Response::Floor(xs) => write!(f, "FLOOR {}", piles(xs)),
+            Response::Hand(xs) => write!(f, "HAND {}", piles(xs)),
+            Response::Commit(c) => write!(f, "COMMIT {} {}", c.game, to_hex(&c.hash)),
+            Response::Reveal(x) => write!(
+                f,
+                "REVEAL {} {} {}",
+                x.game,
+                to_hex(&x.salt),
+                to_hex(&x.order.iter().map(|&c| u8::from(c)).collect::<Vec<u8>>())
+            ),
+            Response::Error(e) => write!(f, "ERROR {}", e),
+            Response::Bye => write!(f, "BYE"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Card;
+
+    #[test]
+    fn test_parse_requests() {
+        assert_eq!(
+            Request::parse("MOVE *A&1\n"),
+            Ok(Request::Move(String::from("*A&1")))
+        );
+        assert_eq!(Request::parse("view"), Ok(Request::View));
+        assert_eq!(Request::parse("SIT"), Ok(Request::Sit(None)));
+        assert_eq!(
+            Request::parse("SIT Dealer"),
+            Ok(Request::Sit(Some(PlayerId::Dealer)))
+        );
+        assert_eq!(
+            Request::parse("SIT north"),
+            Err(ProtocolError::InvalidSeat(String::from("north")))
+        );
+        assert_eq!(Request::parse(" QUIT "), Ok(Request::Quit));
+        assert_eq!(Request::parse(""), Err(ProtocolError::EmptyMessage));
+        assert_eq!(
+            Request::parse("MOVE"),
+            Err(ProtocolError::MissingArgument("annotation"))
+        );
+        assert_eq!(
+            Request::parse("DANCE now"),
+            Err(ProtocolError::UnknownCommand(String::from("DANCE")))
+        );
+    }
+
+    #[test]
+    fn test_format_responses() {
+        assert_eq!(Response::Seat(PlayerId::Dealer).to_string(), "SEAT Dealer");
+        assert_eq!(
+            Response::Moved(PlayerId::Opponent, String::from("*A&1")).to_string(),
+            "MOVED Opponent *A&1"
+        );
+        assert_eq!(Response::Over(None).to_string(), "OVER draw");
+        assert_eq!(
+            Response::Commit(DealCommitment {
+                game: 1,
+                hash: [0xab; 32]
+            })
+            .to_string(),
+            format!("COMMIT 1 {}", "ab".repeat(32))
+        );
+        assert_eq!(
+            Response::Reveal(DealReveal {
+                game: 0,
+                salt: [0; 32],
+                order: vec![Card::from(0), Card::from(51)],
+            })
+            .to_string(),
+            format!("REVEAL 0 {} 0033", "00".repeat(32))
+        );
+        assert_eq!(
+            Response::Hand(vec![Pile::card(1, 2), Pile::empty()]).to_string(),
+            "HAND A♥ ___"
+        );
+    }
+}
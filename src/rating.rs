@@ -0,0 +1,95 @@
+use crate::game::PlayerInfo;
+use crate::result::MatchResult;
+use crate::state::PlayerId;
+
+/// Rating given to players who have not been rated yet
+pub const DEFAULT_RATING: f64 = 1500.0;
+
+/// How far a single match can move a player's rating
+pub const K_FACTOR: f64 = 32.0;
+
+/// Get the expected match score for a player against another, from 0 to 1
+pub fn expected(rating: f64, other: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((other - rating) / 400.0))
+}
+
+/// Get the match score for the given player, 1 for a win, 0.5 for a tie, and 0 for a loss
+pub fn actual(result: &MatchResult, p: PlayerId) -> f64 {
+    match result.winner {
+        Some(w) if w == p => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    }
+}
+
+/// Get both players' new Elo ratings after a match, indexed by player id
+pub fn update(ratings: [f64; 2], result: &MatchResult, k: f64) -> [f64; 2] {
+    [PlayerId::Opponent, PlayerId::Dealer].map(|p| {
+        let (mine, theirs) = (ratings[p as usize], ratings[p.other() as usize]);
+        mine + k * (actual(result, p) - expected(mine, theirs))
+    })
+}
+
+/// Update the ratings stored in the player metadata after a match
+///
+/// Players without a rating start at the default rating.
+pub fn update_players(players: &mut [Option<PlayerInfo>; 2], result: &MatchResult, k: f64) {
+    let ratings = players.clone().map(|x| {
+        x.and_then(|info| info.rating)
+            .map_or(DEFAULT_RATING, f64::from)
+    });
+    for (x, r) in players.iter_mut().zip(update(ratings, result, k)) {
+        x.get_or_insert_with(PlayerInfo::default).rating =
+            Some(r.round().clamp(0.0, u16::MAX as f64) as u16);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::Termination;
+    use std::time::Duration;
+
+    fn result(winner: Option<PlayerId>) -> MatchResult {
+        MatchResult {
+            winner,
+            per_game_scores: vec![[0, 0]; 2],
+            termination: Termination::Completed,
+            duration: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_expected() {
+        assert_eq!(expected(1500.0, 1500.0), 0.5);
+        assert!((expected(1900.0, 1500.0) - 10.0 / 11.0).abs() < 1e-9);
+        assert!((expected(1500.0, 1900.0) + expected(1900.0, 1500.0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_update() {
+        let r = update([1500.0, 1500.0], &result(Some(PlayerId::Dealer)), K_FACTOR);
+        assert_eq!(r, [1484.0, 1516.0]);
+
+        let r = update([1500.0, 1500.0], &result(None), K_FACTOR);
+        assert_eq!(r, [1500.0, 1500.0]);
+
+        // An upset moves ratings further than an expected win
+        let upset = update([1900.0, 1500.0], &result(Some(PlayerId::Dealer)), K_FACTOR);
+        let expected = update(
+            [1900.0, 1500.0],
+            &result(Some(PlayerId::Opponent)),
+            K_FACTOR,
+        );
+        assert!(upset[1] - 1500.0 > expected[0] - 1900.0);
+    }
+
+    #[test]
+    fn test_update_players() {
+        let mut players = [None, Some(PlayerInfo::named("Alice"))];
+        update_players(&mut players, &result(Some(PlayerId::Opponent)), K_FACTOR);
+        assert_eq!(players[0].as_ref().unwrap().rating, Some(1516));
+        assert_eq!(players[1].as_ref().unwrap().rating, Some(1484));
+        assert_eq!(players[1].as_ref().unwrap().name, "Alice");
+    }
+}
@@ -0,0 +1,757 @@
+use crate::action::{Annotation, Lint};
+use crate::error::Error;
+use crate::game::{Deal, Game, GameError, PlayerInfo};
+use crate::result::{MatchResult, Termination};
+use crate::rng::Seed;
+use crate::rules::{Clock, FloorOverflow, Misdeal, Rules, TimeoutPolicy, UndoPolicy};
+use crate::state::PlayerId;
+use std::fmt;
+use std::time::Duration;
+
+/// Replay document errors
+#[derive(Debug, Eq, PartialEq)]
+pub enum ReplayError {
+    MissingSeed,
+    InvalidLine(usize),
+    IllegalMove(usize, Error),
+    InvalidRules(GameError),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Replay Error: {}",
+            match self {
+                ReplayError::MissingSeed => "Missing seed".to_string(),
+                ReplayError::InvalidLine(n) => format!("Line {} is malformed", n),
+                ReplayError::IllegalMove(i, e) => format!("Move {} is illegal - {}", i + 1, e),
+                ReplayError::InvalidRules(e) => format!("Rules are not supported - {}", e),
+            }
+        )
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Drop in score from the best move, in hundredths of a point, at which a move is an inaccuracy
+pub const INACCURACY_DELTA: i32 = 50;
+/// Drop in score from the best move at which a move is a mistake
+pub const MISTAKE_DELTA: i32 = 100;
+/// Drop in score from the best move at which a move is a blunder
+pub const BLUNDER_DELTA: i32 = 200;
+/// Lead over the next best move at which the best move is brilliant
+pub const BRILLIANT_MARGIN: i32 = 100;
+
+/// An evaluation of a move, for study
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Tag {
+    Brilliant,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Tag {
+    const ALL: [Tag; 5] = [
+        Tag::Brilliant,
+        Tag::Good,
+        Tag::Inaccuracy,
+        Tag::Mistake,
+        Tag::Blunder,
+    ];
+
+    /// Grade the move with the given annotation against every move ranked for its position
+    ///
+    /// `ranked` is best first, as from `rank_moves`. A best move is brilliant when every other
+    /// move scores at least `BRILLIANT_MARGIN` less. Returns `None` if the move was not ranked.
+    pub fn grade(ranked: &[(String, i32)], annotation: &str) -> Option<Tag> {
+        let i = ranked.iter().position(|(x, _)| x == annotation)?;
+        let best = ranked[0].1;
+        let margin = ranked.get(1).map(|x| best - x.1);
+        Some(match best - ranked[i].1 {
+            _ if i == 0 && margin.is_some_and(|x| x >= BRILLIANT_MARGIN) => Tag::Brilliant,
+            d if d >= BLUNDER_DELTA => Tag::Blunder,
+            d if d >= MISTAKE_DELTA => Tag::Mistake,
+            d if d >= INACCURACY_DELTA => Tag::Inaccuracy,
+            _ => Tag::Good,
+        })
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Tag::Brilliant => "brilliant",
+                Tag::Good => "good",
+                Tag::Inaccuracy => "inaccuracy",
+                Tag::Mistake => "mistake",
+                Tag::Blunder => "blunder",
+            }
+        )
+    }
+}
+
+/// A move in a replay, with any notes attached to it
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Turn {
+    pub annotation: String,
+    pub tag: Option<Tag>,
+    pub comment: Option<String>,
+}
+
+/// A recorded match that can be written out, read back, and played again
+///
+/// Replays are plain text: a header line for each piece of match info, followed by one line
+/// per move with an optional `$tag` and `{comment}`. Rules other than the number of games only
+/// get a header when they differ from the defaults. A finished match also gets headers for its
+/// result, how it ended, each game's score as opponent-dealer, and its duration in milliseconds.
+///
+/// ```text
+/// [Seed "0000…"]
+/// [Dealer "Dealer"]
+/// [Games "2"]
+/// [FloorSize "6"]
+/// [OpponentName "Ada"]
+/// [OpponentRating "1500"]
+/// [Result "Opponent"]
+/// [Termination "Resigned"]
+/// [Scores "3-1"]
+/// [Duration "61000"]
+/// 1. *C&3 $good {Takes the two of spades}
+/// 2. !1
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Replay {
+    pub seed: Seed,
+    pub dealer: PlayerId,
+    pub rules: Rules,
+    pub players: [Option<PlayerInfo>; 2],
+    /// How the match ended, once it has
+    pub result: Option<MatchResult>,
+    pub turns: Vec<Turn>,
+}
+
+impl Replay {
+    /// Record the moves played in a game so far
+    pub fn from_game(g: &Game) -> Replay {
+        Replay {
+            seed: g.rng.rng_borrow().get_seed(),
+            dealer: g.dealer,
+            rules: g.rules.clone(),
+            players: g.players.clone(),
+            result: g.result.clone(),
+            turns: g
+                .moves()
+                .iter()
+                .map(|x| Turn {
+                    annotation: x.annotation.clone(),
+                    ..Turn::default()
+                })
+                .collect(),
+        }
+    }
+
+    /// Play the recorded moves on a new game
+    pub fn to_game(&self) -> Result<Game, ReplayError> {
+        let mut builder = Game::builder()
+            .seed(self.seed)
+            .dealer(self.dealer)
+            .rules(self.rules.clone());
+        for p in [PlayerId::Opponent, PlayerId::Dealer] {
+            if let Some(info) = &self.players[p as usize] {
+                builder = builder.player(p, info.clone());
+            }
+        }
+        let mut g = builder.build().map_err(ReplayError::InvalidRules)?;
+        for (i, t) in self.turns.iter().enumerate() {
+            g.apply_annotation(&t.annotation)
+                .map_err(|e| ReplayError::IllegalMove(i, e))?;
+            g.tick();
+        }
+        Ok(g)
+    }
+
+    /// Get the deals of the recorded match, to group its moves by deal
+    pub fn deals(&self) -> Result<Vec<Deal>, ReplayError> {
+        Ok(self.to_game()?.deals().to_vec())
+    }
+
+    /// Attach a comment to a move
+    pub fn comment(&mut self, turn: usize, text: &str) -> Option<&Turn> {
+        let t = self.turns.get_mut(turn)?;
+        t.comment = Some(text.to_string());
+        Some(t)
+    }
+
+    /// Attach an evaluation tag to a move
+    pub fn tag(&mut self, turn: usize, tag: Tag) -> Option<&Turn> {
+        let t = self.turns.get_mut(turn)?;
+        t.tag = Some(tag);
+        Some(t)
+    }
+
+    /// Read a replay from its text form
+    pub fn read(text: &str) -> Result<Replay, ReplayError> {
+        let mut replay = Replay::default();
+        let mut seed = None;
+        for (n, line) in text.lines().enumerate().map(|(n, x)| (n + 1, x.trim())) {
+            if line.is_empty() {
+                continue;
+            } else if let Some((key, value)) = header(line) {
+                let seat = |x: &str| match x {
+                    "Opponent" => Some(PlayerId::Opponent),
+                    "Dealer" => Some(PlayerId::Dealer),
+                    _ => None,
+                };
+                let read = match key {
+                    "Seed" => parse_seed(value).map(|x| seed = Some(x)),
+                    "Dealer" => seat(value).map(|x| replay.dealer = x),
+                    "Result" | "Termination" | "Scores" | "Duration" => {
+                        read_result(&mut replay.result, key, value)
+                    }
+                    _ => match key.strip_prefix("Opponent") {
+                        Some(field) => read_player(&mut replay.players[0], field, value),
+                        None => match key.strip_prefix("Dealer") {
+                            Some(field) => read_player(&mut replay.players[1], field, value),
+                            None => read_rule(&mut replay.rules, key, value),
+                        },
+                    },
+                };
+                read.ok_or(ReplayError::InvalidLine(n))?;
+            } else {
+                replay
+                    .turns
+                    .push(parse_turn(line).ok_or(ReplayError::InvalidLine(n))?);
+            }
+        }
+        replay.seed = seed.ok_or(ReplayError::MissingSeed)?;
+        Ok(replay)
+    }
+}
+
+/// Get the header lines for the rules, skipping any left at their defaults apart from the number
+/// of games
+fn rule_headers(rules: &Rules) -> Vec<(&'static str, String)> {
+    let default = Rules::default();
+    let mut headers = vec![("Games", rules.games.to_string())];
+    let mut add = |key, differs: bool, value: String| {
+        if differs {
+            headers.push((key, value));
+        }
+    };
+    if let Some(x) = rules.target {
+        add("Target", true, x.to_string());
+    }
+    add(
+        "Undo",
+        rules.undo != default.undo,
+        match rules.undo {
+            UndoPolicy::Unlimited => "Unlimited".to_string(),
+            UndoPolicy::OwnTurnOnly => "OwnTurnOnly".to_string(),
+            UndoPolicy::Limited(n) => format!("Limited {}", n),
+            UndoPolicy::Disabled => "Disabled".to_string(),
+        },
+    );
+    add(
+        "MultiBuild",
+        rules.multi_build != default.multi_build,
+        rules.multi_build.to_string(),
+    );
+    if let Some(x) = rules.clock {
+        add(
+            "Clock",
+            true,
+            format!("{} {} {:?}", x.turn_ms, x.grace_ms, x.timeout),
+        );
+    }
+    add(
+        "UniqueFloor",
+        rules.unique_floor != default.unique_floor,
+        rules.unique_floor.to_string(),
+    );
+    add(
+        "FloorOverflow",
+        rules.floor_overflow != default.floor_overflow,
+        format!("{:?}", rules.floor_overflow),
+    );
+    add(
+        "FloorSize",
+        rules.floor_size != default.floor_size,
+        rules.floor_size.to_string(),
+    );
+    add(
+        "CaptureAll",
+        rules.capture_all != default.capture_all,
+        rules.capture_all.to_string(),
+    );
+    add(
+        "FacePairsOnly",
+        rules.face_pairs_only != default.face_pairs_only,
+        rules.face_pairs_only.to_string(),
+    );
+    add(
+        "Misdeal",
+        rules.misdeal != default.misdeal,
+        format!("{:?}", rules.misdeal),
+    );
+    headers
+}
+
+/// Set a rule from its header line, returning `None` if the rule or its value is unknown
+fn read_rule(rules: &mut Rules, key: &str, value: &str) -> Option<()> {
+    match key {
+        "Games" => rules.games = value.parse().ok()?,
+        "Target" => rules.target = Some(value.parse().ok()?),
+        "Undo" => {
+            rules.undo = match value.split_once(' ') {
+                Some(("Limited", n)) => UndoPolicy::Limited(n.parse().ok()?),
+                Some(_) => return None,
+                None => match value {
+                    "Unlimited" => UndoPolicy::Unlimited,
+                    "OwnTurnOnly" => UndoPolicy::OwnTurnOnly,
+                    "Disabled" => UndoPolicy::Disabled,
+                    _ => return None,
+                },
+            }
+        }
+        "MultiBuild" => rules.multi_build = value.parse().ok()?,
+        "Clock" => {
+            let mut words = value.split(' ');
+            let clock = Clock {
+                turn_ms: words.next()?.parse().ok()?,
+                grace_ms: words.next()?.parse().ok()?,
+                timeout: match words.next()? {
+                    "AutoDiscard" => TimeoutPolicy::AutoDiscard,
+                    "AutoMove" => TimeoutPolicy::AutoMove,
+                    "Forfeit" => TimeoutPolicy::Forfeit,
+                    _ => return None,
+                },
+            };
+            if words.next().is_some() {
+                return None;
+            }
+            rules.clock = Some(clock);
+        }
+        "UniqueFloor" => rules.unique_floor = value.parse().ok()?,
+        "FloorOverflow" => {
+            rules.floor_overflow = match value {
+                "ForceCapture" => FloorOverflow::ForceCapture,
+                "AwardLastScorer" => FloorOverflow::AwardLastScorer,
+                _ => return None,
+            }
+        }
+        "FloorSize" => rules.floor_size = value.parse().ok()?,
+        "CaptureAll" => rules.capture_all = value.parse().ok()?,
+        "FacePairsOnly" => rules.face_pairs_only = value.parse().ok()?,
+        "Misdeal" => {
+            rules.misdeal = match value {
+                "ShortHands" => Misdeal::ShortHands,
+                "Reshuffle" => Misdeal::Reshuffle,
+                "Abort" => Misdeal::Abort,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Set a field of a player's info from its header line, returning `None` if the field or its
+/// value is unknown
+fn read_player(info: &mut Option<PlayerInfo>, field: &str, value: &str) -> Option<()> {
+    let info = info.get_or_insert_with(PlayerInfo::default);
+    match field {
+        "Name" => info.name = value.to_string(),
+        "Rating" => info.rating = Some(value.parse().ok()?),
+        "ClientId" => info.client_id = Some(value.to_string()),
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Get the header lines for the result of a match
+fn result_headers(result: &MatchResult) -> Vec<(&'static str, String)> {
+    let scores = result
+        .per_game_scores
+        .iter()
+        .map(|x| format!("{}-{}", x[0], x[1]))
+        .collect::<Vec<String>>();
+    vec![
+        (
+            "Result",
+            result.winner.map_or("Draw".to_string(), |x| x.to_string()),
+        ),
+        ("Termination", format!("{:?}", result.termination)),
+        ("Scores", scores.join(" ")),
+        ("Duration", result.duration.as_millis().to_string()),
+    ]
+}
+
+/// Set part of the result of a match from its header line, returning `None` if the value is
+/// unknown
+fn read_result(result: &mut Option<MatchResult>, key: &str, value: &str) -> Option<()> {
+    let result = result.get_or_insert_with(|| MatchResult {
+        winner: None,
+        per_game_scores: vec![],
+        termination: Termination::Completed,
+        duration: Duration::ZERO,
+    });
+    match key {
+        "Result" => {
+            result.winner = match value {
+                "Opponent" => Some(PlayerId::Opponent),
+                "Dealer" => Some(PlayerId::Dealer),
+                "Draw" => None,
+                _ => return None,
+            }
+        }
+        "Termination" => {
+            result.termination = match value {
+                "Completed" => Termination::Completed,
+                "Resigned" => Termination::Resigned,
+                "Timeout" => Termination::Timeout,
+                "Aborted" => Termination::Aborted,
+                "Misdeal" => Termination::Misdeal,
+                _ => return None,
+            }
+        }
+        "Scores" => {
+            result.per_game_scores = value
+                .split_whitespace()
+                .map(|x| {
+                    let (a, b) = x.split_once('-')?;
+                    Some([a.parse().ok()?, b.parse().ok()?])
+                })
+                .collect::<Option<Vec<[u8; 2]>>>()?
+        }
+        "Duration" => result.duration = Duration::from_millis(value.parse().ok()?),
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Split a `[Key "value"]` header line
+fn header(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.strip_prefix('[')?.strip_suffix(']')?.split_once(' ')?;
+    Some((key, value.strip_prefix('"')?.strip_suffix('"')?))
+}
+
+/// Parse a seed from 64 hex digits
+fn parse_seed(x: &str) -> Option<Seed> {
+    let mut seed = [0; 32];
+    if x.len() != 64 || !x.is_ascii() {
+        return None;
+    }
+    for (i, b) in seed.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&x[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(seed)
+}
+
+/// Parse a numbered move line
+fn parse_turn(line: &str) -> Option<Turn> {
+    let (number, rest) = line.split_once(". ")?;
+    number.parse::<usize>().ok()?;
+    let (rest, comment) = match rest.split_once('{') {
+        Some((x, c)) => (x, Some(unescape(c.trim_end().strip_suffix('}')?)?)),
+        None => (rest, None),
+    };
+    let mut words = rest.split_whitespace();
+    let annotation = words.next()?.to_string();
+    let tag = match words.next() {
+        Some(x) => Some(
+            *Tag::ALL
+                .iter()
+                .find(|t| Some(t.to_string().as_str()) == x.strip_prefix('$'))?,
+        ),
+        None => None,
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    Some(Turn {
+        annotation,
+        tag,
+        comment,
+    })
+}
+
+/// Get the form a move is written in, so every replay formats its moves the same way
+///
+/// Annotations that do not parse are written as they are.
+fn tidy(annotation: &str) -> String {
+    let lints = Annotation::new(annotation.to_string()).lint();
+    lints
+        .into_iter()
+        .flatten()
+        .find_map(|x| match x {
+            Lint::Rewrite(x) => Some(x),
+            Lint::Redundant(_) => None,
+        })
+        .unwrap_or_else(|| annotation.to_string())
+}
+
+/// Escape a comment so it fits on one line between braces
+fn escape(x: &str) -> String {
+    x.replace('\\', "\\\\")
+        .replace('}', "\\}")
+        .replace('\n', "\\n")
+}
+
+/// Undo the escaping of a comment, failing on an unescaped closing brace
+fn unescape(x: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = x.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => out.push('\n'),
+                c => out.push(c),
+            },
+            '}' => return None,
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seed = self
+            .seed
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect::<String>();
+        writeln!(f, "[Seed \"{}\"]", seed)?;
+        writeln!(f, "[Dealer \"{}\"]", self.dealer)?;
+        for (key, value) in rule_headers(&self.rules) {
+            writeln!(f, "[{} \"{}\"]", key, value)?;
+        }
+        for (seat, info) in ["Opponent", "Dealer"].iter().zip(&self.players) {
+            if let Some(info) = info {
+                writeln!(f, "[{}Name \"{}\"]", seat, info.name)?;
+                if let Some(x) = info.rating {
+                    writeln!(f, "[{}Rating \"{}\"]", seat, x)?;
+                }
+                if let Some(x) = &info.client_id {
+                    writeln!(f, "[{}ClientId \"{}\"]", seat, x)?;
+                }
+            }
+        }
+        for (key, value) in self.result.iter().flat_map(result_headers) {
+            writeln!(f, "[{} \"{}\"]", key, value)?;
+        }
+        for (i, t) in self.turns.iter().enumerate() {
+            write!(f, "{}. {}", i + 1, tidy(&t.annotation))?;
+            if let Some(tag) = t.tag {
+                write!(f, " ${}", tag)?;
+            }
+            if let Some(c) = &t.comment {
+                write!(f, " {{{}}}", escape(c))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grade() {
+        let ranked: Vec<_> = [("a", 300), ("b", 150), ("c", 120), ("d", 60), ("e", 40)]
+            .iter()
+            .map(|&(x, s)| (x.to_string(), s))
+            .collect();
+        assert_eq!(Tag::grade(&ranked, "a"), Some(Tag::Brilliant));
+        assert_eq!(Tag::grade(&ranked, "b"), Some(Tag::Mistake));
+        assert_eq!(Tag::grade(&ranked, "c"), Some(Tag::Mistake));
+        assert_eq!(Tag::grade(&ranked, "d"), Some(Tag::Blunder));
+        assert_eq!(Tag::grade(&ranked[3..], "e"), Some(Tag::Good));
+        assert_eq!(Tag::grade(&ranked[1..], "b"), Some(Tag::Good));
+        assert_eq!(Tag::grade(&ranked[1..], "d"), Some(Tag::Inaccuracy));
+        assert_eq!(Tag::grade(&ranked, "f"), None);
+
+        // A forced move is only good
+        assert_eq!(Tag::grade(&ranked[..1], "a"), Some(Tag::Good));
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let mut g = Game::builder()
+            .seed([7; 32])
+            .player(PlayerId::Opponent, PlayerInfo::named("Ada"))
+            .build()
+            .unwrap();
+        for _ in 0..3 {
+            let x = g.engine_move().unwrap();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+        let mut r = Replay::from_game(&g);
+        r.tag(0, Tag::Good);
+        r.comment(0, "Keeps the {spades} close\\at hand\nfor later");
+        r.tag(2, Tag::Blunder);
+        assert!(r.comment(3, "Not played yet").is_none());
+
+        let text = r.to_string();
+        assert!(text.starts_with(&format!("[Seed \"{}\"]\n", "07".repeat(32))));
+        assert!(text.contains("[OpponentName \"Ada\"]\n"));
+        assert!(text.contains(" $good {Keeps the {spades\\} close\\\\at hand\\nfor later}\n"));
+        assert_eq!(Replay::read(&text), Ok(r.clone()));
+
+        let h = r.to_game().unwrap();
+        assert_eq!(h.moves(), g.moves());
+        assert_eq!(h.player_name(PlayerId::Opponent), "Ada");
+        assert_eq!(r.deals().unwrap(), g.deals());
+    }
+
+    #[test]
+    fn test_result_headers() {
+        let mut g = Game::builder().seed([7; 32]).build().unwrap();
+        g.touch(1000);
+        let x = g.engine_move().unwrap();
+        g.apply_annotation(&x).unwrap();
+        g.tick();
+        g.touch(4000);
+        g.resign(PlayerId::Dealer).unwrap();
+        let r = Replay::from_game(&g);
+        let text = r.to_string();
+        assert!(text.contains(&format!(
+            "[Result \"Opponent\"]\n[Termination \"Resigned\"]\n[Scores \"3-0\"]\n\
+             [Duration \"3000\"]\n1. {}\n",
+            x
+        )));
+        let read = Replay::read(&text).unwrap();
+        assert_eq!(read.result, g.result);
+        assert_eq!(read, r);
+
+        // Unfinished matches have no result
+        assert!(!Replay::default().to_string().contains("Result"));
+        let seed = format!("[Seed \"{}\"]\n", "00".repeat(32));
+        assert_eq!(Replay::read(&seed).unwrap().result, None);
+        let draw = Replay::read(&format!(
+            "{}[Result \"Draw\"]\n[Termination \"Aborted\"]\n[Scores \"\"]\n",
+            seed
+        ))
+        .unwrap()
+        .result
+        .unwrap();
+        assert_eq!(draw.winner, None);
+        assert_eq!(draw.termination, Termination::Aborted);
+        assert_eq!(draw.games(), 0);
+        for x in [
+            "[Result \"Nobody\"]",
+            "[Scores \"3-\"]",
+            "[Termination \"Rain\"]",
+        ] {
+            assert_eq!(
+                Replay::read(&format!("{}{}\n", seed, x)),
+                Err(ReplayError::InvalidLine(2))
+            );
+        }
+    }
+
+    #[test]
+    fn test_variant_rules() {
+        let rules = Rules {
+            games: 1,
+            target: Some(11),
+            undo: UndoPolicy::Limited(2),
+            multi_build: true,
+            clock: Some(Clock {
+                turn_ms: 30_000,
+                grace_ms: 5_000,
+                timeout: TimeoutPolicy::AutoMove,
+            }),
+            unique_floor: false,
+            floor_overflow: FloorOverflow::AwardLastScorer,
+            floor_size: 6,
+            capture_all: true,
+            face_pairs_only: true,
+            misdeal: Misdeal::Reshuffle,
+        };
+        let mut g = Game::builder()
+            .seed([3; 32])
+            .rules(rules.clone())
+            .player(
+                PlayerId::Dealer,
+                PlayerInfo {
+                    name: "Bo".to_string(),
+                    rating: Some(1500),
+                    client_id: Some("web-7".to_string()),
+                },
+            )
+            .build()
+            .unwrap();
+        for _ in 0..4 {
+            let x = g.engine_move().unwrap();
+            g.apply_annotation(&x).unwrap();
+            g.tick();
+        }
+        let r = Replay::from_game(&g);
+        let text = r.to_string();
+        assert!(text.contains("[FloorSize \"6\"]\n"));
+        assert!(text.contains("[Undo \"Limited 2\"]\n"));
+        assert!(text.contains("[DealerRating \"1500\"]\n"));
+        let read = Replay::read(&text).unwrap();
+        assert_eq!(read, r);
+        assert_eq!(read.rules, rules);
+
+        let h = read.to_game().unwrap();
+        assert_eq!(h.rules, rules);
+        assert_eq!(h.players, g.players);
+        assert_eq!(h.moves(), g.moves());
+        assert_eq!(h.state.floor, g.state.floor);
+
+        // Default rules get no header beyond the number of games
+        let plain = Replay::default().to_string();
+        assert!(!plain.contains("FloorSize"));
+        assert!(plain.contains("[Games \"2\"]\n"));
+    }
+
+    #[test]
+    fn test_write_tidies_moves() {
+        let seed = format!("[Seed \"{}\"]\n", "00".repeat(32));
+        let r = Replay::read(&format!("{}1. 1&A\n2. 3 $good\n3. t3\n", seed)).unwrap();
+        let text = r.to_string();
+        assert!(text.contains("\n1. A&1\n2. !3 $good\n"));
+        // Shortcuts can't be expanded without the game, so they are left alone
+        assert!(text.ends_with("\n3. t3\n"));
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert_eq!(Replay::read("1. !1\n"), Err(ReplayError::MissingSeed));
+        let seed = format!("[Seed \"{}\"]\n", "00".repeat(32));
+        assert_eq!(
+            Replay::read(&format!("{}1. !1 $lucky\n", seed)),
+            Err(ReplayError::InvalidLine(2))
+        );
+        assert_eq!(
+            Replay::read(&format!("{}[Colour \"red\"]\n", seed)),
+            Err(ReplayError::InvalidLine(2))
+        );
+        assert_eq!(
+            Replay::read(&format!("{}[Undo \"Limited many\"]\n", seed)),
+            Err(ReplayError::InvalidLine(2))
+        );
+        let r = Replay::read(&format!("{}[FloorSize \"99\"]\n", seed)).unwrap();
+        assert_eq!(
+            r.to_game().err(),
+            Some(ReplayError::InvalidRules(GameError::UnsupportedFloorSize(
+                99
+            )))
+        );
+        let r = Replay::read(&format!("{}1. *A&1\n", seed)).unwrap();
+        assert!(matches!(r.to_game(), Err(ReplayError::IllegalMove(0, _))));
+    }
+}
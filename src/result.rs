@@ -0,0 +1,47 @@
+use crate::state::PlayerId;
+use std::time::Duration;
+
+/// How a match came to an end
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Termination {
+    /// The match was played out or decided by the score
+    Completed,
+    /// A player resigned
+    Resigned,
+    /// A player ran out of time
+    Timeout,
+    /// The match was stopped without a winner
+    Aborted,
+    /// The deck ran out partway through a deal, and the rules stop the match
+    Misdeal,
+}
+
+/// The final result of a match
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchResult {
+    pub winner: Option<PlayerId>,
+    /// Each game's score, indexed by player id
+    pub per_game_scores: Vec<[u8; 2]>,
+    pub termination: Termination,
+    /// Time between the first and last times the game was touched
+    ///
+    /// The engine has no clock of its own, so time after the last touch is not counted.
+    pub duration: Duration,
+}
+
+impl MatchResult {
+    /// Get the match score for the given player
+    pub fn total(&self, p: PlayerId) -> u16 {
+        self.per_game_scores
+            .iter()
+            .map(|s| s[p as usize] as u16)
+            .sum()
+    }
+
+    /// Get the number of games that were started
+    pub fn games(&self) -> usize {
+        self.per_game_scores.len()
+    }
+}
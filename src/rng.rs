@@ -1,5 +1,7 @@
 use rand::prelude::random;
+use rand::rngs::OsRng;
 pub use rand::seq::SliceRandom;
+use rand::RngCore;
 pub use rand::SeedableRng;
 pub use rand_chacha::ChaCha20Rng;
 use std::default::Default;
@@ -7,7 +9,17 @@ use std::default::Default;
 /// Suipi RNG seed
 pub type Seed = [u8; 32];
 
+/// Get a seed from the operating system's secure random source
+///
+/// Unlike game seeds, these are for secrets that must not be worked out from anything else.
+pub fn os_seed() -> Seed {
+    let mut seed = Seed::default();
+    OsRng.fill_bytes(&mut seed);
+    seed
+}
+
 /// Suipi game random number generator
+#[derive(Clone)]
 pub struct Rng(ChaCha20Rng);
 
 impl Rng {
@@ -0,0 +1,113 @@
+use crate::consts::FLOOR_SIZE;
+
+/// When players are allowed to undo their moves
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UndoPolicy {
+    /// Any move may be undone (casual play)
+    #[default]
+    Unlimited,
+    /// Moves may only be undone before the turn is over
+    OwnTurnOnly,
+    /// Each player may undo this many moves per game
+    Limited(u8),
+    /// Moves may never be undone (ranked play)
+    Disabled,
+}
+
+/// What happens when a player runs out of time on their turn
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeoutPolicy {
+    /// Discard the lowest card in the player's hand, or play an engine move if they can't discard
+    #[default]
+    AutoDiscard,
+    /// Play the move the engine would pick
+    AutoMove,
+    /// The player forfeits the match
+    Forfeit,
+}
+
+/// Turn clock settings
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Clock {
+    /// Time each player has to make a move, in milliseconds
+    pub turn_ms: u64,
+    /// Extra time allowed after the turn time runs out, in milliseconds
+    pub grace_ms: u64,
+    pub timeout: TimeoutPolicy,
+}
+
+/// What happens when a player discards while every floor slot is taken
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FloorOverflow {
+    /// The standard rule: the discard is rejected, so the player has to capture instead
+    ///
+    /// Games only allow this with a unique floor of at least 13 piles, where a full floor always
+    /// has something to capture.
+    #[default]
+    ForceCapture,
+    /// The discarded card goes to the last player to capture, as if they picked up the floor, or
+    /// to the dealer if nobody has captured yet this game
+    AwardLastScorer,
+}
+
+/// What happens when the deck runs out partway through a deal
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Misdeal {
+    /// Deal the cards that are left, so the players get short hands for the last round
+    #[default]
+    ShortHands,
+    /// Shuffle the captured cards back into the deck, so full hands can be dealt
+    Reshuffle,
+    /// Stop the match without a winner
+    Abort,
+}
+
+/// Match rules and variant settings
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rules {
+    /// Number of games in the match, or the most that will be played towards a target
+    pub games: u8,
+    /// End the match as soon as a player is sure to reach this score
+    pub target: Option<u16>,
+    pub undo: UndoPolicy,
+    /// Allow a move to create several independent builds at once
+    pub multi_build: bool,
+    /// Limit the time a player has to move, or `None` for untimed play
+    pub clock: Option<Clock>,
+    /// Require every floor pile to have a different value, so matching values must be grouped
+    pub unique_floor: bool,
+    /// What happens to a discard when the floor is full, for variants that allow floors which
+    /// can fill up with nothing to capture
+    pub floor_overflow: FloorOverflow,
+    /// Number of piles that fit on the floor, up to `MAX_FLOOR_SIZE`
+    pub floor_size: usize,
+    /// Require a capture to take every floor pile with the captured value
+    pub capture_all: bool,
+    /// Only allow face cards to be paired one for one, so they can never be grouped
+    pub face_pairs_only: bool,
+    pub misdeal: Misdeal,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            games: 2,
+            target: None,
+            undo: UndoPolicy::default(),
+            multi_build: false,
+            clock: None,
+            unique_floor: true,
+            floor_overflow: FloorOverflow::default(),
+            floor_size: FLOOR_SIZE,
+            capture_all: false,
+            face_pairs_only: false,
+            misdeal: Misdeal::default(),
+        }
+    }
+}
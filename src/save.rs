@@ -0,0 +1,771 @@
+use crate::action::Address;
+use crate::card::Card;
+use crate::commit::{DealCommitment, DealReveal};
+use crate::consts::{FLOOR_DEAL_SIZE, HAND_SIZE, INVALID_CARD_ID, MAX_FLOOR_SIZE};
+use crate::game::{Deal, PlayedMove, PlayerInfo};
+use crate::journal::Entry;
+use crate::pile::{Mark, Pile};
+use crate::result::{MatchResult, Termination};
+use crate::rng::{ChaCha20Rng, Rng, Seed, SeedableRng};
+use crate::rules::{Clock, FloorOverflow, Misdeal, Rules, TimeoutPolicy, UndoPolicy};
+use crate::score::{Score, Winner};
+use crate::state::{Destination, Keyframe, Player, PlayerId, State};
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+/// Bytes every save starts with
+pub const MAGIC: &[u8; 5] = b"SUIPI";
+
+/// Version of the save format written by this build
+pub const VERSION: u8 = 1;
+
+/// Save errors
+#[derive(Debug, Eq, PartialEq)]
+pub enum SaveError {
+    NotASave,
+    UnsupportedVersion(u8),
+    Truncated,
+    /// A field held a value no game could have, named by the field
+    InvalidValue(&'static str),
+    TrailingBytes,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Save Error: {}",
+            match self {
+                SaveError::NotASave => "The data is not a saved game".to_string(),
+                SaveError::UnsupportedVersion(v) => format!("Save version {} is not supported", v),
+                SaveError::Truncated => "The save ended unexpectedly".to_string(),
+                SaveError::InvalidValue(x) => format!("The save has an invalid {}", x),
+                SaveError::TrailingBytes => "The save has unexpected data at the end".to_string(),
+            }
+        )
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// Cursor over the bytes of a save
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Start reading a save, checking its header
+    pub fn new(bytes: &'a [u8]) -> Result<Self, SaveError> {
+        let mut r = Reader { bytes };
+        if r.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(SaveError::NotASave);
+        }
+        match u8::decode(&mut r)? {
+            VERSION => Ok(r),
+            v => Err(SaveError::UnsupportedVersion(v)),
+        }
+    }
+
+    /// Take the next `n` bytes
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], SaveError> {
+        if self.bytes.len() < n {
+            return Err(SaveError::Truncated);
+        }
+        let (x, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(x)
+    }
+
+    /// Check every byte has been read
+    pub fn finish(self) -> Result<(), SaveError> {
+        match self.bytes.is_empty() {
+            true => Ok(()),
+            false => Err(SaveError::TrailingBytes),
+        }
+    }
+}
+
+/// Start a save with its header
+pub(crate) fn header() -> Vec<u8> {
+    let mut w = MAGIC.to_vec();
+    w.push(VERSION);
+    w
+}
+
+/// A value that can be written to and read from a save
+pub(crate) trait Codec: Sized {
+    fn encode(&self, w: &mut Vec<u8>);
+    fn decode(r: &mut Reader) -> Result<Self, SaveError>;
+}
+
+/// Read an enum tag, which must be below `n`
+fn tag(r: &mut Reader, n: u8, what: &'static str) -> Result<u8, SaveError> {
+    match u8::decode(r)? {
+        x if x < n => Ok(x),
+        _ => Err(SaveError::InvalidValue(what)),
+    }
+}
+
+macro_rules! int_codec {
+    ($($t:ty),*) => {$(
+        impl Codec for $t {
+            fn encode(&self, w: &mut Vec<u8>) {
+                w.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+                let x = r.take(std::mem::size_of::<$t>())?;
+                Ok(<$t>::from_le_bytes(x.try_into().unwrap()))
+            }
+        }
+    )*};
+}
+
+int_codec!(u8, u16, u32, u64, u128);
+
+impl Codec for usize {
+    fn encode(&self, w: &mut Vec<u8>) {
+        (*self as u64).encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        usize::try_from(u64::decode(r)?).map_err(|_| SaveError::InvalidValue("length"))
+    }
+}
+
+impl Codec for bool {
+    fn encode(&self, w: &mut Vec<u8>) {
+        (*self as u8).encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(tag(r, 2, "flag")? == 1)
+    }
+}
+
+impl Codec for String {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.len().encode(w);
+        w.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        let n = usize::decode(r)?;
+        String::from_utf8(r.take(n)?.to_vec()).map_err(|_| SaveError::InvalidValue("string"))
+    }
+}
+
+impl<T: Codec> Codec for Option<T> {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.is_some().encode(w);
+        if let Some(x) = self {
+            x.encode(w);
+        }
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        match bool::decode(r)? {
+            true => Ok(Some(T::decode(r)?)),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<T: Codec> Codec for Vec<T> {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.len().encode(w);
+        for x in self.iter() {
+            x.encode(w);
+        }
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        let n = usize::decode(r)?;
+        // Every value takes at least a byte, so a bad length can't allocate more than the save
+        if n > r.bytes.len() {
+            return Err(SaveError::Truncated);
+        }
+        (0..n).map(|_| T::decode(r)).collect()
+    }
+}
+
+impl<T: Codec> Codec for VecDeque<T> {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.len().encode(w);
+        for x in self.iter() {
+            x.encode(w);
+        }
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(Vec::decode(r)?.into())
+    }
+}
+
+impl<T: Codec> Codec for [T; 2] {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self[0].encode(w);
+        self[1].encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok([T::decode(r)?, T::decode(r)?])
+    }
+}
+
+impl<A: Codec, B: Codec> Codec for (A, B) {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.0.encode(w);
+        self.1.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok((A::decode(r)?, B::decode(r)?))
+    }
+}
+
+impl Codec for Duration {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.as_secs().encode(w);
+        self.subsec_nanos().encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        let secs = u64::decode(r)?;
+        match u32::decode(r)? {
+            nanos if nanos < 1_000_000_000 => Ok(Duration::new(secs, nanos)),
+            _ => Err(SaveError::InvalidValue("duration")),
+        }
+    }
+}
+
+impl Codec for Rng {
+    /// The seed, stream, and position in the stream, so the RNG carries on where it left off
+    fn encode(&self, w: &mut Vec<u8>) {
+        let x = self.rng_borrow();
+        w.extend_from_slice(&x.get_seed());
+        x.get_stream().encode(w);
+        x.get_word_pos().encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        let seed: Seed = r.take(32)?.try_into().unwrap();
+        let mut x = ChaCha20Rng::from_seed(seed);
+        x.set_stream(u64::decode(r)?);
+        x.set_word_pos(u128::decode(r)?);
+        let mut rng = Rng::from_seed(seed);
+        *rng.rng_borrow_mut() = x;
+        Ok(rng)
+    }
+}
+
+impl Codec for Card {
+    fn encode(&self, w: &mut Vec<u8>) {
+        u8::from(*self).encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        match u8::decode(r)? {
+            x if x <= INVALID_CARD_ID => Ok(Card::from(x)),
+            _ => Err(SaveError::InvalidValue("card")),
+        }
+    }
+}
+
+impl Codec for PlayerId {
+    fn encode(&self, w: &mut Vec<u8>) {
+        (*self as u8).encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match tag(r, 2, "player")? {
+            0 => PlayerId::Opponent,
+            _ => PlayerId::Dealer,
+        })
+    }
+}
+
+impl Codec for Mark {
+    fn encode(&self, w: &mut Vec<u8>) {
+        (*self as u8).encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match tag(r, 5, "pile mark")? {
+            0 => Mark::Empty,
+            1 => Mark::Single,
+            2 => Mark::Build,
+            3 => Mark::Group,
+            _ => Mark::Pair,
+        })
+    }
+}
+
+impl Codec for Pile {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.cards.encode(w);
+        self.value.encode(w);
+        self.mark.encode(w);
+        self.owner.encode(w);
+        self.id.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        let p = Pile {
+            cards: Codec::decode(r)?,
+            value: Codec::decode(r)?,
+            mark: Codec::decode(r)?,
+            owner: Codec::decode(r)?,
+            id: Codec::decode(r)?,
+        };
+        p.verify().map_err(|_| SaveError::InvalidValue("pile"))?;
+        Ok(p)
+    }
+}
+
+impl Codec for Player {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.hand.encode(w);
+        self.pairs.encode(w);
+        self.suipi_count.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        let p = Player {
+            hand: Vec::<Pile>::decode(r)?,
+            pairs: Codec::decode(r)?,
+            suipi_count: Codec::decode(r)?,
+        };
+        if p.hand.len() > HAND_SIZE {
+            return Err(SaveError::InvalidValue("hand"));
+        }
+        Ok(p)
+    }
+}
+
+impl Codec for Address {
+    fn encode(&self, w: &mut Vec<u8>) {
+        match self {
+            Address::Hand(i) => [0, *i].encode(w),
+            Address::Floor(i) => [1, *i].encode(w),
+        }
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match tag(r, 2, "address")? {
+            0 => Address::Hand(u8::decode(r)?),
+            _ => Address::Floor(u8::decode(r)?),
+        })
+    }
+}
+
+impl Codec for Keyframe {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.card.encode(w);
+        self.from.encode(w);
+        match self.to {
+            Destination::Pile(a) => {
+                0u8.encode(w);
+                a.encode(w);
+            }
+            Destination::Captured => 1u8.encode(w),
+        }
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(Keyframe {
+            card: Codec::decode(r)?,
+            from: Codec::decode(r)?,
+            to: match tag(r, 2, "destination")? {
+                0 => Destination::Pile(Address::decode(r)?),
+                _ => Destination::Captured,
+            },
+        })
+    }
+}
+
+impl Codec for UndoPolicy {
+    fn encode(&self, w: &mut Vec<u8>) {
+        match self {
+            UndoPolicy::Unlimited => 0u8.encode(w),
+            UndoPolicy::OwnTurnOnly => 1u8.encode(w),
+            UndoPolicy::Limited(n) => [2, *n].encode(w),
+            UndoPolicy::Disabled => 3u8.encode(w),
+        }
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match tag(r, 4, "undo policy")? {
+            0 => UndoPolicy::Unlimited,
+            1 => UndoPolicy::OwnTurnOnly,
+            2 => UndoPolicy::Limited(u8::decode(r)?),
+            _ => UndoPolicy::Disabled,
+        })
+    }
+}
+
+impl Codec for Clock {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.turn_ms.encode(w);
+        self.grace_ms.encode(w);
+        (self.timeout as u8).encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(Clock {
+            turn_ms: Codec::decode(r)?,
+            grace_ms: Codec::decode(r)?,
+            timeout: match tag(r, 3, "timeout policy")? {
+                0 => TimeoutPolicy::AutoDiscard,
+                1 => TimeoutPolicy::AutoMove,
+                _ => TimeoutPolicy::Forfeit,
+            },
+        })
+    }
+}
+
+impl Codec for Rules {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.games.encode(w);
+        self.target.encode(w);
+        self.undo.encode(w);
+        self.multi_build.encode(w);
+        self.clock.encode(w);
+        self.unique_floor.encode(w);
+        (self.floor_overflow as u8).encode(w);
+        self.floor_size.encode(w);
+        self.capture_all.encode(w);
+        self.face_pairs_only.encode(w);
+        (self.misdeal as u8).encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        let rules = Rules {
+            games: Codec::decode(r)?,
+            target: Codec::decode(r)?,
+            undo: Codec::decode(r)?,
+            multi_build: Codec::decode(r)?,
+            clock: Codec::decode(r)?,
+            unique_floor: Codec::decode(r)?,
+            floor_overflow: match tag(r, 2, "floor overflow rule")? {
+                0 => FloorOverflow::ForceCapture,
+                _ => FloorOverflow::AwardLastScorer,
+            },
+            floor_size: Codec::decode(r)?,
+            capture_all: Codec::decode(r)?,
+            face_pairs_only: Codec::decode(r)?,
+            misdeal: match tag(r, 3, "misdeal rule")? {
+                0 => Misdeal::ShortHands,
+                1 => Misdeal::Reshuffle,
+                _ => Misdeal::Abort,
+            },
+        };
+        // Games are only built with floor sizes in this range
+        if !(FLOOR_DEAL_SIZE..=MAX_FLOOR_SIZE).contains(&rules.floor_size) {
+            return Err(SaveError::InvalidValue("floor size"));
+        }
+        Ok(rules)
+    }
+}
+
+impl Codec for State {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.deck.encode(w);
+        self.floor.encode(w);
+        self.dealer.encode(w);
+        self.opponent.encode(w);
+        self.turn.encode(w);
+        self.last_score.encode(w);
+        self.rules.encode(w);
+        self.last_pile_id.encode(w);
+        self.keyframes.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        let mut s = State::default();
+        s.deck = Codec::decode(r)?;
+        s.floor = Codec::decode(r)?;
+        s.dealer = Codec::decode(r)?;
+        s.opponent = Codec::decode(r)?;
+        s.turn = Codec::decode(r)?;
+        s.last_score = Codec::decode(r)?;
+        s.rules = Codec::decode(r)?;
+        s.last_pile_id = Codec::decode(r)?;
+        s.keyframes = Codec::decode(r)?;
+        if s.floor.len() != s.rules.floor_size {
+            return Err(SaveError::InvalidValue("floor size"));
+        }
+        Ok(s)
+    }
+}
+
+impl Codec for Winner {
+    fn encode(&self, w: &mut Vec<u8>) {
+        match self {
+            Winner::Dealer(n) => [0, *n].encode(w),
+            Winner::Opponent(n) => [1, *n].encode(w),
+            Winner::Tie => 2u8.encode(w),
+        }
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match tag(r, 3, "winner")? {
+            0 => Winner::Dealer(u8::decode(r)?),
+            1 => Winner::Opponent(u8::decode(r)?),
+            _ => Winner::Tie,
+        })
+    }
+}
+
+impl Codec for Score {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.dealer_aces.encode(w);
+        self.opponent_aces.encode(w);
+        self.most_cards.encode(w);
+        self.most_spades.encode(w);
+        self.suipi_bonus.encode(w);
+        self.ten_of_diamonds.encode(w);
+        self.two_of_spades.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(Score {
+            dealer_aces: Codec::decode(r)?,
+            opponent_aces: Codec::decode(r)?,
+            most_cards: Codec::decode(r)?,
+            most_spades: Codec::decode(r)?,
+            suipi_bonus: Codec::decode(r)?,
+            ten_of_diamonds: Codec::decode(r)?,
+            two_of_spades: Codec::decode(r)?,
+        })
+    }
+}
+
+impl Codec for MatchResult {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.winner.encode(w);
+        self.per_game_scores.encode(w);
+        (self.termination as u8).encode(w);
+        self.duration.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(MatchResult {
+            winner: Codec::decode(r)?,
+            per_game_scores: Codec::decode(r)?,
+            termination: match tag(r, 5, "termination")? {
+                0 => Termination::Completed,
+                1 => Termination::Resigned,
+                2 => Termination::Timeout,
+                3 => Termination::Aborted,
+                _ => Termination::Misdeal,
+            },
+            duration: Codec::decode(r)?,
+        })
+    }
+}
+
+impl Codec for PlayerInfo {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.name.encode(w);
+        self.rating.encode(w);
+        self.client_id.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(PlayerInfo {
+            name: Codec::decode(r)?,
+            rating: Codec::decode(r)?,
+            client_id: Codec::decode(r)?,
+        })
+    }
+}
+
+impl Codec for PlayedMove {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.turn.encode(w);
+        self.player.encode(w);
+        self.annotation.encode(w);
+        self.raw.encode(w);
+        self.elapsed_ms.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(PlayedMove {
+            turn: Codec::decode(r)?,
+            player: Codec::decode(r)?,
+            annotation: Codec::decode(r)?,
+            raw: Codec::decode(r)?,
+            elapsed_ms: Codec::decode(r)?,
+        })
+    }
+}
+
+impl Codec for Deal {
+    fn encode(&self, w: &mut Vec<u8>) {
+        w.extend_from_slice(&[
+            self.number,
+            self.game,
+            self.round,
+            self.hands_dealt,
+            self.floor_dealt,
+            self.deck_remaining_after,
+        ]);
+        self.first_move.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(Deal {
+            number: Codec::decode(r)?,
+            game: Codec::decode(r)?,
+            round: Codec::decode(r)?,
+            hands_dealt: Codec::decode(r)?,
+            floor_dealt: Codec::decode(r)?,
+            deck_remaining_after: Codec::decode(r)?,
+            first_move: Codec::decode(r)?,
+        })
+    }
+}
+
+impl Codec for DealCommitment {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.game.encode(w);
+        w.extend_from_slice(&self.hash);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(DealCommitment {
+            game: Codec::decode(r)?,
+            hash: r.take(32)?.try_into().unwrap(),
+        })
+    }
+}
+
+impl Codec for DealReveal {
+    fn encode(&self, w: &mut Vec<u8>) {
+        self.game.encode(w);
+        w.extend_from_slice(&self.salt);
+        self.order.encode(w);
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(DealReveal {
+            game: Codec::decode(r)?,
+            salt: r.take(32)?.try_into().unwrap(),
+            order: Codec::decode(r)?,
+        })
+    }
+}
+
+impl Codec for Entry {
+    fn encode(&self, w: &mut Vec<u8>) {
+        match self {
+            Entry::Move(a) => {
+                0u8.encode(w);
+                a.encode(w);
+            }
+            Entry::EndTurn => 1u8.encode(w),
+            Entry::Undo => 2u8.encode(w),
+            Entry::Resign(p) => [3, *p as u8].encode(w),
+            Entry::Forfeit(p) => [4, *p as u8].encode(w),
+            Entry::Abort => 5u8.encode(w),
+        }
+    }
+
+    fn decode(r: &mut Reader) -> Result<Self, SaveError> {
+        Ok(match tag(r, 6, "journal entry")? {
+            0 => Entry::Move(String::decode(r)?),
+            1 => Entry::EndTurn,
+            2 => Entry::Undo,
+            3 => Entry::Resign(PlayerId::decode(r)?),
+            4 => Entry::Forfeit(PlayerId::decode(r)?),
+            _ => Entry::Abort,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Codec>(x: &T) -> T {
+        let mut w = header();
+        x.encode(&mut w);
+        let mut r = Reader::new(&w).unwrap();
+        let y = T::decode(&mut r).unwrap();
+        r.finish().unwrap();
+        y
+    }
+
+    #[test]
+    fn test_codec() {
+        let rules = Rules {
+            target: Some(21),
+            undo: UndoPolicy::Limited(3),
+            clock: Some(Clock {
+                turn_ms: 30_000,
+                grace_ms: 500,
+                timeout: TimeoutPolicy::Forfeit,
+            }),
+            misdeal: Misdeal::Reshuffle,
+            ..Rules::default()
+        };
+        assert_eq!(round_trip(&rules), rules);
+        let mut p = Pile::new(vec![Card::from(3), Card::from(40)], 6, Mark::Build);
+        p.id = 7;
+        assert_eq!(round_trip(&p), p);
+        assert_eq!(round_trip(&p).id, p.id);
+        let entries = vec![
+            Entry::Move(String::from("*C&3")),
+            Entry::Resign(PlayerId::Dealer),
+        ];
+        assert_eq!(round_trip(&entries), entries);
+        assert_eq!(round_trip(&Winner::Opponent(2)), Winner::Opponent(2));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Reader::new(b"SUIP").err(), Some(SaveError::NotASave));
+        assert_eq!(
+            Reader::new(b"SUIPI\x09").err(),
+            Some(SaveError::UnsupportedVersion(9))
+        );
+        let mut r = Reader::new(b"SUIPI\x01\x07").unwrap();
+        assert_eq!(
+            Winner::decode(&mut r),
+            Err(SaveError::InvalidValue("winner"))
+        );
+        let mut r = Reader::new(b"SUIPI\x01\xff\x00").unwrap();
+        assert_eq!(Vec::<u8>::decode(&mut r), Err(SaveError::Truncated));
+        let mut r = Reader::new(b"SUIPI\x01\x35").unwrap();
+        assert_eq!(Card::decode(&mut r), Err(SaveError::InvalidValue("card")));
+        let mut w = header();
+        1u64.encode(&mut w);
+        2_000_000_000u32.encode(&mut w);
+        let mut r = Reader::new(&w).unwrap();
+        assert_eq!(
+            Duration::decode(&mut r),
+            Err(SaveError::InvalidValue("duration"))
+        );
+        for floor_size in [0, 3, 24, usize::MAX] {
+            let mut w = header();
+            Rules {
+                floor_size,
+                ..Rules::default()
+            }
+            .encode(&mut w);
+            let mut r = Reader::new(&w).unwrap();
+            assert_eq!(
+                Rules::decode(&mut r),
+                Err(SaveError::InvalidValue("floor size"))
+            );
+        }
+        let r = Reader::new(b"SUIPI\x01\x00").unwrap();
+        assert_eq!(r.finish(), Err(SaveError::TrailingBytes));
+        assert_eq!(
+            SaveError::InvalidValue("winner").to_string(),
+            "Save Error: The save has an invalid winner"
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use crate::action::Annotation;
+use crate::card::Card;
+use crate::consts::{DECK_SIZE, HAND_SIZE};
+use crate::error::Error;
+use crate::pile::{Mark, Pile};
+use crate::state::{PlayerId, State};
+use std::fmt;
+
+/// Scenario parsing errors
+#[derive(Debug, Eq, PartialEq)]
+pub enum ScenarioError {
+    InvalidLine(usize),
+    InvalidCard(usize, String),
+    InvalidPile(usize, String),
+    InvalidRule(usize, String),
+    DuplicateCard(usize, Card),
+    TooManyPiles(usize),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Scenario Error: {}",
+            match self {
+                ScenarioError::InvalidLine(n) => format!("Line {} is malformed", n),
+                ScenarioError::InvalidCard(n, x) => format!("Line {} has an invalid card {}", n, x),
+                ScenarioError::InvalidPile(n, x) => format!("Line {} has an invalid pile {}", n, x),
+                ScenarioError::InvalidRule(n, x) => format!("Line {} has an unknown rule {}", n, x),
+                ScenarioError::DuplicateCard(n, c) => format!("Line {} uses the {} again", n, c),
+                ScenarioError::TooManyPiles(n) => format!("Line {} has too many piles", n),
+            }
+        )
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+/// What a move in a scenario is expected to do
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expect {
+    Ok,
+    /// The move is rejected, with an error message containing the given text, if any
+    Err(Option<String>),
+}
+
+/// A move to try in a scenario, and what it should do
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Expectation {
+    pub line: usize,
+    pub annotation: String,
+    pub expect: Expect,
+}
+
+/// An expectation that did not hold, with what the move did instead
+#[derive(Debug, Eq, PartialEq)]
+pub struct Mismatch {
+    pub expectation: Expectation,
+    pub actual: Result<(), Error>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let x = &self.expectation;
+        write!(f, "Line {}: {} ", x.line, x.annotation)?;
+        match &self.actual {
+            Ok(_) => write!(f, "was accepted"),
+            Err(e) => write!(f, "was rejected - {}", e),
+        }
+    }
+}
+
+/// A game position written out by hand, with moves to try in it
+///
+/// Scenarios are plain text, one section per line. Cards are written as a value and a suit
+/// letter, like `10D` or `QH`. Floor piles are single cards, builds like `{2S+6H}=8`, or groups
+/// like `[7C 7H]=7`, followed by `*` if the player to move owns them. Every card not placed goes
+/// in the deck.
+///
+/// ```text
+/// # The two of spades is up for grabs
+/// rules: unique_floor=false
+/// floor: 4C 7D {2S+6H}=8*
+/// hand: AH 8S
+/// other: 10D
+/// expect: *C&2 -> ok
+/// expect: *A&1 -> err Invalid pile
+/// ```
+///
+/// Each expected move is tried on its own from the starting position.
+#[derive(Clone, Debug, Default)]
+pub struct Scenario {
+    pub state: State,
+    pub expectations: Vec<Expectation>,
+}
+
+impl Scenario {
+    /// Read a scenario from its text form
+    pub fn parse(text: &str) -> Result<Scenario, ScenarioError> {
+        let mut state = State::default();
+        let mut expectations = vec![];
+        let mut used = [false; DECK_SIZE];
+        let mut floor = vec![];
+        let (mut hand, mut other) = (vec![], vec![]);
+        for (n, line) in text.lines().enumerate().map(|(n, x)| (n + 1, x.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once(':').ok_or(ScenarioError::InvalidLine(n))?;
+            let mut take = |x: &str| -> Result<Card, ScenarioError> {
+                let c = parse_card(x).ok_or(ScenarioError::InvalidCard(n, x.to_string()))?;
+                let id = u8::from(c) as usize;
+                if std::mem::replace(&mut used[id], true) {
+                    return Err(ScenarioError::DuplicateCard(n, c));
+                }
+                Ok(c)
+            };
+            match key.trim() {
+                "rules" => {
+                    for x in value.split_whitespace() {
+                        let (name, flag) = x
+                            .split_once('=')
+                            .and_then(|(k, v)| Some((k, v.parse::<bool>().ok()?)))
+                            .ok_or(ScenarioError::InvalidRule(n, x.to_string()))?;
+                        match name {
+                            "unique_floor" => state.rules.unique_floor = flag,
+                            "multi_build" => state.rules.multi_build = flag,
+                            "capture_all" => state.rules.capture_all = flag,
+                            "face_pairs_only" => state.rules.face_pairs_only = flag,
+                            _ => return Err(ScenarioError::InvalidRule(n, x.to_string())),
+                        }
+                    }
+                }
+                "floor" => {
+                    for x in split_piles(value).ok_or(ScenarioError::InvalidLine(n))? {
+                        let p = parse_pile(&x, state.turn, &mut take)?
+                            .ok_or(ScenarioError::InvalidPile(n, x.clone()))?;
+                        floor.push(p);
+                    }
+                }
+                "hand" | "other" => {
+                    let cards = value
+                        .split_whitespace()
+                        .map(|x| take(x).map(Pile::single))
+                        .collect::<Result<Vec<Pile>, ScenarioError>>()?;
+                    if cards.len() > HAND_SIZE {
+                        return Err(ScenarioError::TooManyPiles(n));
+                    }
+                    match key.trim() {
+                        "hand" => hand = cards,
+                        _ => other = cards,
+                    }
+                }
+                "expect" => {
+                    let (annotation, outcome) = value
+                        .split_once("->")
+                        .ok_or(ScenarioError::InvalidLine(n))?;
+                    let outcome = outcome.trim();
+                    let expect = match outcome.split_once(' ') {
+                        None if outcome == "ok" => Expect::Ok,
+                        None if outcome == "err" => Expect::Err(None),
+                        Some(("err", x)) => Expect::Err(Some(x.trim().to_string())),
+                        _ => return Err(ScenarioError::InvalidLine(n)),
+                    };
+                    expectations.push(Expectation {
+                        line: n,
+                        annotation: annotation.trim().to_string(),
+                        expect,
+                    });
+                }
+                _ => return Err(ScenarioError::InvalidLine(n)),
+            }
+            if floor.len() > state.rules.floor_size {
+                return Err(ScenarioError::TooManyPiles(n));
+            }
+        }
+
+        let (me, them) = (state.turn, state.turn.other());
+        for p in floor.iter_mut() {
+            state.last_pile_id += 1;
+            p.id = state.last_pile_id;
+        }
+        floor.resize(state.rules.floor_size, Pile::empty());
+        hand.resize(HAND_SIZE, Pile::empty());
+        other.resize(HAND_SIZE, Pile::empty());
+        state.floor = floor;
+        state.seat_mut(me).hand = hand;
+        state.seat_mut(them).hand = other;
+        state.deck = (0..DECK_SIZE)
+            .filter(|&i| !used[i])
+            .map(|i| Card::from(i as u8))
+            .collect();
+        Ok(Scenario {
+            state,
+            expectations,
+        })
+    }
+
+    /// Try every expected move from the starting position, returning the ones that did not do
+    /// what was expected
+    pub fn run(&self) -> Vec<Mismatch> {
+        self.expectations
+            .iter()
+            .filter_map(|x| {
+                let actual = self.try_move(&x.annotation);
+                let matched = match (&x.expect, &actual) {
+                    (Expect::Ok, Ok(_)) => true,
+                    (Expect::Err(None), Err(_)) => true,
+                    (Expect::Err(Some(text)), Err(e)) => e.to_string().contains(text.as_str()),
+                    _ => false,
+                };
+                (!matched).then(|| Mismatch {
+                    expectation: x.clone(),
+                    actual,
+                })
+            })
+            .collect()
+    }
+
+    /// Apply a move to a copy of the starting position
+    fn try_move(&self, annotation: &str) -> Result<(), Error> {
+        let m = Annotation::new(annotation.to_string())
+            .expand(&self.state)?
+            .to_move()?;
+        self.state.clone().apply(m)?;
+        Ok(())
+    }
+}
+
+/// Parse a card like `10D` or `QH`
+fn parse_card(x: &str) -> Option<Card> {
+    let (value, suit) = x.split_at(x.len().checked_sub(1)?);
+    let value = match value {
+        "A" => 1,
+        "J" => 11,
+        "Q" => 12,
+        "K" => 13,
+        _ => value.parse::<u8>().ok().filter(|v| (2..=10).contains(v))?,
+    };
+    let suit = match suit {
+        "C" => 0,
+        "D" => 1,
+        "H" => 2,
+        "S" => 3,
+        _ => return None,
+    };
+    Some(Card::new(value, suit))
+}
+
+/// Split a floor line into piles, keeping the cards of builds and groups together
+fn split_piles(x: &str) -> Option<Vec<String>> {
+    let mut piles = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in x.chars() {
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' if depth == 0 => return None,
+            '}' | ']' => depth -= 1,
+            _ => (),
+        }
+        if c.is_whitespace() && depth == 0 {
+            if !current.is_empty() {
+                piles.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if depth != 0 {
+        return None;
+    }
+    if !current.is_empty() {
+        piles.push(current);
+    }
+    Some(piles)
+}
+
+/// Parse a floor pile, given the player to move
+fn parse_pile<F>(x: &str, me: PlayerId, take: &mut F) -> Result<Option<Pile>, ScenarioError>
+where
+    F: FnMut(&str) -> Result<Card, ScenarioError>,
+{
+    let (x, mine) = match x.strip_suffix('*') {
+        Some(x) => (x, true),
+        None => (x, false),
+    };
+    let owner = if mine { me } else { me.other() };
+    let (body, value) = match x.rsplit_once('=') {
+        Some((body, v)) => match v.parse::<u8>() {
+            Ok(v) => (body, Some(v)),
+            Err(_) => return Ok(None),
+        },
+        None => (x, None),
+    };
+    let (cards, mark) =
+        if let Some(inner) = body.strip_prefix('{').and_then(|x| x.strip_suffix('}')) {
+            (
+                inner.split('+').map(str::trim).collect::<Vec<&str>>(),
+                Mark::Build,
+            )
+        } else if let Some(inner) = body.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            (inner.split_whitespace().collect(), Mark::Group)
+        } else if value.is_none() {
+            return Ok(Some(Pile::single(take(body)?)));
+        } else {
+            return Ok(None);
+        };
+    let cards = cards
+        .into_iter()
+        .map(&mut *take)
+        .collect::<Result<Vec<Card>, ScenarioError>>()?;
+    if cards.len() < 2 {
+        return Ok(None);
+    }
+    let value = match (mark, value) {
+        (_, Some(v)) => v,
+        (Mark::Build, None) => cards.iter().map(|c| c.value).sum(),
+        _ => cards[0].value,
+    };
+    let mut p = Pile::new(cards, value, mark);
+    p.owner = owner;
+    Ok(Some(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{Suit, Value};
+
+    #[test]
+    fn test_parse() {
+        let s = Scenario::parse(
+            "# A build of eight\n\
+             floor: 4C 7D {2S+6H}=8* [5C 5D]\n\
+             hand: AH 8S\n\
+             other: 10D\n\
+             expect: *C&2 -> ok\n\
+             expect: *A&1 -> err Invalid pile\n",
+        )
+        .unwrap();
+        let g = &s.state;
+        assert_eq!(g.floor_count(), 4);
+        assert_eq!(g.floor[2].value, 8);
+        assert!(g.floor[2].is_build());
+        assert_eq!(g.floor[2].owner, g.turn);
+        assert!(g.floor[3].is_group());
+        assert_eq!(g.floor[3].owner, g.turn.other());
+        assert_eq!(
+            g.player().hand[0],
+            Pile::single(Card::create(Value::Ace, Suit::Hearts))
+        );
+        assert_eq!(g.player().card_count(), 2);
+        assert_eq!(g.seat(g.turn.other()).card_count(), 1);
+        assert_eq!(g.audit(), Ok(()));
+        assert_eq!(s.expectations.len(), 2);
+        assert_eq!(
+            s.expectations[1].expect,
+            Expect::Err(Some(String::from("Invalid pile")))
+        );
+        assert!(s.run().is_empty());
+    }
+
+    #[test]
+    fn test_run() {
+        let s = Scenario::parse("floor: 4C 7D\nhand: 4H\nexpect: *B&1 -> ok\nexpect: t1 -> err")
+            .unwrap();
+        let failed = s.run();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].expectation.line, 3);
+        assert_eq!(
+            failed[0].to_string(),
+            "Line 3: *B&1 was rejected - State Error: Invalid pile - \
+             You may not pair a card with a pile that has a different value"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Scenario::parse("floor 4C").err(),
+            Some(ScenarioError::InvalidLine(1))
+        );
+        assert_eq!(
+            Scenario::parse("\nhand: 4C 1C").err(),
+            Some(ScenarioError::InvalidCard(2, String::from("1C")))
+        );
+        assert_eq!(
+            Scenario::parse("floor: 4C\nhand: 4C").err(),
+            Some(ScenarioError::DuplicateCard(
+                2,
+                Card::create(Value::Four, Suit::Clubs)
+            ))
+        );
+        assert_eq!(
+            Scenario::parse("floor: {4C}").err(),
+            Some(ScenarioError::InvalidPile(1, String::from("{4C}")))
+        );
+        assert_eq!(
+            Scenario::parse("rules: undo=true").err(),
+            Some(ScenarioError::InvalidRule(1, String::from("undo=true")))
+        );
+        assert_eq!(
+            Scenario::parse("expect: !1 -> maybe").err(),
+            Some(ScenarioError::InvalidLine(1))
+        );
+    }
+}
@@ -1,9 +1,11 @@
 use crate::card::{Card, Suit, Value};
-use crate::state::{Player, State};
+use crate::consts::{DECK_SIZE, SUIT_SIZE};
+use crate::state::{Player, PlayerId, State};
 use std::cmp::Ordering;
 
 /// Point value winners
-#[derive(Default, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Winner {
     Dealer(u8),
     Opponent(u8),
@@ -21,6 +23,15 @@ impl Winner {
         }
     }
 
+    /// Get the player who won the points, if there was one
+    pub fn player(&self) -> Option<PlayerId> {
+        match self {
+            Winner::Dealer(_) => Some(PlayerId::Dealer),
+            Winner::Opponent(_) => Some(PlayerId::Opponent),
+            Winner::Tie => None,
+        }
+    }
+
     /// Get the first winner or tie
     fn either(dealer: bool, opponent: bool, score: u8) -> Self {
         if dealer {
@@ -33,6 +44,15 @@ impl Winner {
     }
 }
 
+/// Points awarded to whichever player is ahead at the end of the game
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Category {
+    MostCards = 0,
+    MostSpades = 1,
+}
+
 /// Individual player score data
 #[derive(Default)]
 pub struct PlayerScore {
@@ -64,8 +84,29 @@ impl From<&Player> for PlayerScore {
     }
 }
 
+impl PlayerScore {
+    /// Get the points the player has secured, which can no longer be taken away this game
+    pub fn locked(&self) -> u8 {
+        let mut points = self.aces as u8;
+        if self.total_cards > DECK_SIZE / 2 {
+            points += 3;
+        }
+        if self.total_spades > SUIT_SIZE / 2 {
+            points += 1;
+        }
+        if self.ten_of_diamonds {
+            points += 2;
+        }
+        if self.two_of_spades {
+            points += 1;
+        }
+        points
+    }
+}
+
 /// End of game score data
-#[derive(Default)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Score {
     pub dealer_aces: u8,
     pub opponent_aces: u8,
@@ -110,6 +151,27 @@ impl Score {
         scores
     }
 
+    /// Get an array of awarded points for the given player
+    pub fn points(&self, p: PlayerId) -> [u8; 5] {
+        match p {
+            PlayerId::Dealer => self.dealer_points(),
+            PlayerId::Opponent => self.opponent_points(),
+        }
+    }
+
+    /// Get the number of aces collected by the given player
+    pub fn aces(&self, p: PlayerId) -> u8 {
+        match p {
+            PlayerId::Dealer => self.dealer_aces,
+            PlayerId::Opponent => self.opponent_aces,
+        }
+    }
+
+    /// Get the total score for the given player
+    pub fn total(&self, p: PlayerId) -> u8 {
+        self.points(p).iter().sum::<u8>() + self.aces(p)
+    }
+
     /// Get the total score for the dealer
     pub fn dealer_total(&self) -> u8 {
         self.dealer_points().iter().sum::<u8>() + self.dealer_aces
@@ -0,0 +1,276 @@
+use crate::error::Error;
+use crate::game::Game;
+use crate::ismcts::determinize;
+use crate::rng::ChaCha20Rng;
+use crate::score::Score;
+use crate::state::{State, StateError};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The result of searching for the best move
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SearchReport {
+    /// The best move for the player to move, if they have any
+    pub best: Option<String>,
+    /// How good the position is for the player to move, in hundredths of a point
+    pub score: i32,
+    /// The line of play both players are expected to follow, starting with the best move
+    pub principal_variation: Vec<String>,
+    /// Number of positions looked at
+    pub nodes: u64,
+    pub depth: u8,
+    /// Was the search stopped before it reached the depth it was asked for?
+    pub cancelled: bool,
+}
+
+impl SearchReport {
+    /// Play the principal variation on a scratch copy of the game, returning the state after
+    /// each move
+    pub fn preview(&self, g: &Game) -> Result<Vec<State>, Error> {
+        // The game's own moves always replay, unless its state was edited directly
+        let mut scratch = g
+            .branch_at(g.moves().len())
+            .ok_or(StateError::InvalidInput)?;
+        let mut states = vec![];
+        for x in self.principal_variation.iter() {
+            scratch.apply_annotation(x)?;
+            scratch.tick();
+            states.push(scratch.state.clone());
+        }
+        Ok(states)
+    }
+}
+
+/// Score a position for the player to move
+///
+/// Points count for the most, with captured cards breaking ties.
+fn evaluate(state: &State) -> i32 {
+    let score = Score::from(state);
+    let value = |p| score.total(p) as i32 * 100 + state.seat(p).into_pair_cards().len() as i32;
+    value(state.turn) - value(state.turn.other())
+}
+
+/// Search the game tree below a position, returning its score and the best line of play
+///
+/// Both hands and the deck are looked at, and each move simply passes the turn.
+fn negamax(
+    state: &State,
+    depth: u8,
+    mut alpha: i32,
+    beta: i32,
+    nodes: &mut u64,
+    cancel: Option<&AtomicBool>,
+) -> (i32, Vec<String>) {
+    *nodes += 1;
+    let moves = state.legal_moves();
+    let cancelled = cancel.is_some_and(|x| x.load(Ordering::Relaxed));
+    if depth == 0 || moves.is_empty() || cancelled {
+        return (evaluate(state), vec![]);
+    }
+    let mut best = (i32::MIN, vec![]);
+    for m in moves {
+        let x = m.to_annotation();
+        let mut next = state.clone();
+        next.apply(m).unwrap();
+        next.turn = next.turn.other();
+        let (score, mut line) = negamax(&next, depth - 1, -beta, -alpha, nodes, cancel);
+        if -score > best.0 {
+            line.insert(0, x);
+            best = (-score, line);
+        }
+        alpha = alpha.max(best.0);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+/// Score every move the current player could make, best first
+///
+/// Each move is scored by searching `depth` moves ahead, counting the move itself. Moves are
+/// given by their canonical annotation. The search sees the other player's hand, so anything
+/// shown to players should use `rank_moves_sampled` instead.
+pub fn rank_moves(state: &State, depth: u8) -> Vec<(String, i32)> {
+    let mut nodes = 0;
+    let mut ranked = state
+        .legal_moves()
+        .into_iter()
+        .map(|m| {
+            let annotation = m.to_annotation();
+            let mut next = state.clone();
+            next.apply(m).unwrap();
+            next.turn = next.turn.other();
+            let depth = depth.saturating_sub(1);
+            let (score, _) = negamax(&next, depth, -i32::MAX, i32::MAX, &mut nodes, None);
+            (annotation, -score)
+        })
+        .collect::<Vec<(String, i32)>>();
+    ranked.sort_by_key(|(_, score)| -score);
+    ranked
+}
+
+/// Score every move the current player could make, best first, using only what they can see
+///
+/// The moves are ranked with `rank_moves` on `samples` random deals of the cards the player
+/// can't see, and each move is given its average score. The other player's hand and the deck
+/// order are never looked at, so a redacted state gives the same ranking as a full one.
+pub fn rank_moves_sampled(
+    state: &State,
+    depth: u8,
+    samples: u32,
+    rng: &mut ChaCha20Rng,
+) -> Vec<(String, i32)> {
+    let view = state.redacted_for(state.turn);
+    let samples = samples.max(1);
+    let mut ranked: Vec<(String, i64)> = vec![];
+    for _ in 0..samples {
+        let s = determinize(&view, rng);
+        for (x, score) in rank_moves(&s, depth) {
+            match ranked.iter_mut().find(|(y, _)| *y == x) {
+                Some((_, total)) => *total += score as i64,
+                None => ranked.push((x, score as i64)),
+            }
+        }
+    }
+    ranked.sort_by_key(|(_, total)| -total);
+    ranked
+        .into_iter()
+        .map(|(x, total)| (x, (total / samples as i64) as i32))
+        .collect()
+}
+
+/// Look ahead the given number of moves to find the best move for the current player
+///
+/// The search sees both hands, and stops at the end of the round, since the next deal is not
+/// known to the players. It is meant for analysis, not for advising a player mid game.
+pub fn search(state: &State, depth: u8) -> SearchReport {
+    let mut nodes = 0;
+    let (score, line) = negamax(state, depth, -i32::MAX, i32::MAX, &mut nodes, None);
+    SearchReport {
+        best: line.first().cloned(),
+        score,
+        principal_variation: line,
+        nodes,
+        depth,
+        cancelled: false,
+    }
+}
+
+/// Search one move deeper at a time up to the given depth, until `cancel` is set
+///
+/// A cancelled search returns the line from the deepest search that finished. The search
+/// always looks at least one move ahead, so there is a best move whenever the player has one.
+pub fn search_cancellable(state: &State, depth: u8, cancel: &AtomicBool) -> SearchReport {
+    let mut report = search(state, depth.min(1));
+    for d in 2..=depth {
+        let (score, line) = negamax(
+            state,
+            d,
+            -i32::MAX,
+            i32::MAX,
+            &mut report.nodes,
+            Some(cancel),
+        );
+        if cancel.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            break;
+        }
+        report.best = line.first().cloned();
+        report.score = score;
+        report.principal_variation = line;
+        report.depth = d;
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pile::Pile;
+    use crate::rng::{Seed, SeedableRng};
+    use crate::state::PlayerId;
+
+    #[test]
+    fn test_search() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let report = search(&g.state, 3);
+        assert_eq!(report.principal_variation.len(), 3);
+        assert_eq!(report.best.as_ref(), report.principal_variation.first());
+        assert!(report.nodes > 3);
+        // Searching deeper than the round lasts stops at the end of the round
+        let mut s = g.state.clone();
+        s.opponent
+            .hand
+            .iter_mut()
+            .skip(1)
+            .for_each(|x| *x = Default::default());
+        s.dealer
+            .hand
+            .iter_mut()
+            .for_each(|x| *x = Default::default());
+        assert_eq!(search(&s, 4).principal_variation.len(), 1);
+    }
+
+    #[test]
+    fn test_rank_moves() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let ranked = rank_moves(&g.state, 2);
+        assert_eq!(ranked.len(), g.state.legal_moves().len());
+        assert_eq!(ranked[0].1, search(&g.state, 2).score);
+        assert!(ranked.iter().any(|(x, _)| x == "*C&3"));
+        assert!(rank_moves(&g.state, 0).iter().all(|(x, _)| !x.is_empty()));
+    }
+
+    #[test]
+    fn test_rank_moves_sampled() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let rank = |s: &State| rank_moves_sampled(s, 2, 3, &mut ChaCha20Rng::from_seed([1; 32]));
+        let ranked = rank(&g.state);
+        assert_eq!(ranked.len(), g.state.legal_moves().len());
+        assert!(ranked.windows(2).all(|x| x[0].1 >= x[1].1));
+        // The other player's hand makes no difference, since it is never looked at
+        let mut s = g.state.clone();
+        s.dealer.hand.swap(0, 7);
+        s.dealer.hand[1] = Pile::card(1, 0);
+        assert_eq!(rank(&s), ranked);
+    }
+
+    #[test]
+    fn test_search_cancellable() {
+        let g = Game::builder().seed(Seed::default()).build().unwrap();
+        let cancel = AtomicBool::new(false);
+        let report = search_cancellable(&g.state, 3, &cancel);
+        assert!(!report.cancelled);
+        assert_eq!(report.depth, 3);
+        assert_eq!(
+            report.principal_variation,
+            search(&g.state, 3).principal_variation
+        );
+
+        // A cancelled search still has the best move one move ahead
+        cancel.store(true, Ordering::Relaxed);
+        let report = search_cancellable(&g.state, 3, &cancel);
+        assert!(report.cancelled);
+        assert_eq!(report.depth, 1);
+        assert_eq!(report.best, search(&g.state, 1).best);
+    }
+
+    #[test]
+    fn test_preview() {
+        let mut g = Game::builder().seed(Seed::default()).build().unwrap();
+        g.apply_annotation("*C&3").unwrap();
+        g.tick();
+        let report = search(&g.state, 2);
+        let states = report.preview(&g).unwrap();
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[0].turn, PlayerId::Opponent);
+        assert_eq!(states[1].turn, PlayerId::Dealer);
+        assert_eq!(g.moves().len(), 1);
+
+        let stale = SearchReport {
+            principal_variation: vec![String::from("*M&1")],
+            ..report
+        };
+        assert!(stale.preview(&g).is_err());
+    }
+}
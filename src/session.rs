@@ -0,0 +1,454 @@
+use crate::action::Profile;
+use crate::game::Game;
+use crate::protocol::{Request, Response};
+use crate::state::PlayerId;
+use std::fmt;
+
+/// Session and seat management errors
+#[derive(Debug, Eq, PartialEq)]
+pub enum SessionError {
+    SeatTaken,
+    NoFreeSeat,
+    NotSeated,
+    NotStarted,
+    MatchIsOver,
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Session Error: {}",
+            match self {
+                SessionError::SeatTaken => "That seat is taken",
+                SessionError::NoFreeSeat => "There are no free seats",
+                SessionError::NotSeated => "You are not seated",
+                SessionError::NotStarted => "The match has not started",
+                SessionError::MatchIsOver => "The match is over",
+            }
+        )
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// The stage a session is in
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Phase {
+    /// Waiting for both seats to be claimed and readied
+    #[default]
+    Waiting,
+    Playing,
+    Finished,
+}
+
+/// Seat assignment for a hosted game
+///
+/// Seats can be claimed until the match is over, so a player who drops out mid-match can be
+/// replaced by claiming the free seat. The match starts once both seats are claimed and ready.
+#[derive(Clone, Debug, Default)]
+pub struct Lobby {
+    seats: [Option<u64>; 2],
+    ready: [bool; 2],
+    phase: Phase,
+}
+
+impl Lobby {
+    /// Create an empty lobby
+    pub fn new() -> Self {
+        Lobby::default()
+    }
+
+    /// Get the stage the lobby is in
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Get the connection sitting in the given seat
+    pub fn occupant(&self, p: PlayerId) -> Option<u64> {
+        self.seats[p as usize]
+    }
+
+    /// Get the seat a connection is sitting in, if any
+    pub fn seat_of(&self, conn: u64) -> Option<PlayerId> {
+        [PlayerId::Opponent, PlayerId::Dealer]
+            .into_iter()
+            .find(|&p| self.seats[p as usize] == Some(conn))
+    }
+
+    /// Claim the given seat, or the first free one
+    pub fn claim(&mut self, conn: u64, seat: Option<PlayerId>) -> Result<PlayerId, SessionError> {
+        if self.phase == Phase::Finished {
+            return Err(SessionError::MatchIsOver);
+        } else if let Some(p) = self
+            .seat_of(conn)
+            .filter(|&p| seat.is_none() || seat == Some(p))
+        {
+            return Ok(p);
+        }
+        let p = match seat {
+            Some(p) if self.seats[p as usize].is_some() => return Err(SessionError::SeatTaken),
+            Some(p) => p,
+            None => [PlayerId::Opponent, PlayerId::Dealer]
+                .into_iter()
+                .find(|&p| self.seats[p as usize].is_none())
+                .ok_or(SessionError::NoFreeSeat)?,
+        };
+        self.release(conn);
+        self.seats[p as usize] = Some(conn);
+        Ok(p)
+    }
+
+    /// Free the seat a connection is sitting in
+    pub fn release(&mut self, conn: u64) -> Option<PlayerId> {
+        let p = self.seat_of(conn)?;
+        self.seats[p as usize] = None;
+        self.ready[p as usize] = false;
+        Some(p)
+    }
+
+    /// Mark a connection's seat as ready, returning true if this starts the match
+    pub fn ready(&mut self, conn: u64) -> Result<bool, SessionError> {
+        let p = self.seat_of(conn).ok_or(SessionError::NotSeated)?;
+        self.ready[p as usize] = true;
+        if self.phase == Phase::Waiting && self.ready.iter().all(|&x| x) {
+            self.phase = Phase::Playing;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Mark the match as over
+    pub fn finish(&mut self) {
+        self.phase = Phase::Finished;
+    }
+}
+
+/// A game hosted for a set of connections, independent of how they are connected
+///
+/// Connections join as spectators and claim a seat to play. Every call returns the responses
+/// to send, addressed by connection id.
+pub struct Session {
+    pub game: Game,
+    pub lobby: Lobby,
+    members: Vec<u64>,
+}
+
+impl Session {
+    /// Host a session for the given game
+    pub fn new(game: Game) -> Self {
+        Session {
+            game,
+            lobby: Lobby::new(),
+            members: vec![],
+        }
+    }
+
+    /// Get every connection in the session
+    pub fn connections(&self) -> Vec<u64> {
+        self.members.clone()
+    }
+
+    /// Add a connection to the session as a spectator
+    pub fn join(&mut self, conn: u64) -> Vec<(u64, Response)> {
+        if !self.members.contains(&conn) {
+            self.members.push(conn);
+        }
+        let mut replies = vec![(conn, Response::Spectator)];
+        replies.append(&mut self.view(conn));
+        replies
+    }
+
+    /// Remove a connection from the session, freeing its seat
+    pub fn leave(&mut self, conn: u64) {
+        self.lobby.release(conn);
+        self.members.retain(|&x| x != conn);
+    }
+
+    /// Handle a request from a connection
+    pub fn handle(&mut self, conn: u64, r: Request) -> Vec<(u64, Response)> {
+        let res = match r {
+            Request::Sit(seat) => self.sit(conn, seat),
+            Request::Ready => self.ready(conn),
+            Request::Move(a) => self.play(conn, &a),
+            Request::View => Ok(self.view(conn)),
+            Request::Quit => {
+                self.leave(conn);
+                Ok(vec![(conn, Response::Bye)])
+            }
+        };
+        res.unwrap_or_else(|e| vec![(conn, Response::Error(e))])
+    }
+
+    /// Seat a connection and show it its hand
+    fn sit(&mut self, conn: u64, seat: Option<PlayerId>) -> Result<Vec<(u64, Response)>, String> {
+        let p = self.lobby.claim(conn, seat).map_err(|e| e.to_string())?;
+        let mut replies = self.broadcast(vec![Response::Seat(p)]);
+        replies.append(&mut self.view(conn));
+        Ok(replies)
+    }
+
+    /// Ready a connection's seat, starting the match once both players are ready
+    fn ready(&mut self, conn: u64) -> Result<Vec<(u64, Response)>, String> {
+        let started = self.lobby.ready(conn).map_err(|e| e.to_string())?;
+        let p = self.lobby.seat_of(conn).unwrap();
+        let mut news = vec![Response::Ready(p)];
+        if started {
+            news.push(Response::Start);
+            news.push(Response::Turn(self.game.state.turn));
+        }
+        Ok(self.broadcast(news))
+    }
+
+    /// Play a move for a seated connection, and tell everyone about it
+    fn play(&mut self, conn: u64, a: &str) -> Result<Vec<(u64, Response)>, String> {
+        let player = match self.lobby.phase() {
+            Phase::Waiting => Err(SessionError::NotStarted),
+            Phase::Finished => Err(SessionError::MatchIsOver),
+            Phase::Playing => self.lobby.seat_of(conn).ok_or(SessionError::NotSeated),
+        }
+        .map_err(|e| e.to_string())?;
+        self.game
+            .apply_annotation_for(player, a, Profile::Strict)
+            .map_err(|e| e.to_string())?;
+        let annotation = self.game.moves().last().unwrap().annotation.clone();
+        let (commitments, reveals) = (self.game.commitments().len(), self.game.reveals().len());
+        self.game.tick();
+        let mut news = vec![Response::Moved(player, annotation)];
+        // Reveal a finished game's deal, and commit to the next one
+        news.extend(
+            self.game.reveals()[reveals..]
+                .iter()
+                .cloned()
+                .map(Response::Reveal),
+        );
+        news.extend(
+            self.game.commitments()[commitments..]
+                .iter()
+                .map(|&c| Response::Commit(c)),
+        );
+        if self.game.is_over() {
+            self.lobby.finish();
+            news.push(Response::Over(
+                self.game.result.as_ref().and_then(|x| x.winner),
+            ));
+        } else {
+            news.push(Response::Turn(self.game.state.turn));
+        }
+        Ok(self.broadcast(news))
+    }
+
+    /// Describe the game as the given connection is allowed to see it
+    fn view(&self, conn: u64) -> Vec<(u64, Response)> {
+        let mut replies = vec![
+            Response::Status(self.game.status().redacted()),
+            Response::Floor(self.game.floor().to_vec()),
+        ];
+        replies.extend(self.game.commitments().iter().map(|&c| Response::Commit(c)));
+        replies.extend(self.game.reveals().iter().cloned().map(Response::Reveal));
+        if let Some(p) = self.lobby.seat_of(conn) {
+            replies.push(Response::Hand(self.game.hand(p).to_vec()));
+        }
+        replies.into_iter().map(|x| (conn, x)).collect()
+    }
+
+    /// Address the same responses to every connection
+    fn broadcast(&self, news: Vec<Response>) -> Vec<(u64, Response)> {
+        self.connections()
+            .into_iter()
+            .flat_map(|c| news.iter().map(move |x| (c, x.clone())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::ParsingError;
+    use crate::commit::to_hex;
+    use crate::rng::Seed;
+    use crate::state::StateError;
+
+    fn setup() -> Session {
+        Session::new(Game::builder().seed(Seed::default()).build().unwrap())
+    }
+
+    #[test]
+    fn test_lobby() {
+        let mut l = Lobby::new();
+        assert_eq!(l.claim(1, Some(PlayerId::Dealer)), Ok(PlayerId::Dealer));
+        assert_eq!(
+            l.claim(2, Some(PlayerId::Dealer)),
+            Err(SessionError::SeatTaken)
+        );
+        assert_eq!(l.claim(2, None), Ok(PlayerId::Opponent));
+        assert_eq!(l.claim(3, None), Err(SessionError::NoFreeSeat));
+        assert_eq!(l.ready(3), Err(SessionError::NotSeated));
+
+        // Switching seats gives up the old one
+        assert_eq!(l.ready(1), Ok(false));
+        assert_eq!(l.release(2), Some(PlayerId::Opponent));
+        assert_eq!(l.claim(1, Some(PlayerId::Opponent)), Ok(PlayerId::Opponent));
+        assert_eq!(l.occupant(PlayerId::Dealer), None);
+        assert_eq!(l.claim(2, None), Ok(PlayerId::Dealer));
+
+        assert_eq!(l.ready(1), Ok(false));
+        assert_eq!(l.phase(), Phase::Waiting);
+        assert_eq!(l.ready(2), Ok(true));
+        assert_eq!(l.phase(), Phase::Playing);
+        assert_eq!(l.ready(2), Ok(false));
+
+        l.finish();
+        assert_eq!(l.claim(3, None), Err(SessionError::MatchIsOver));
+    }
+
+    #[test]
+    fn test_seats_and_spectators() {
+        let mut s = setup();
+        s.join(1);
+        s.join(2);
+        let watcher = s.join(3);
+        assert_eq!(watcher[0], (3, Response::Spectator));
+        // Spectators never see a hand
+        assert!(!watcher.iter().any(|x| matches!(x.1, Response::Hand(_))));
+
+        let replies = s.handle(1, Request::Sit(None));
+        assert!(replies.contains(&(3, Response::Seat(PlayerId::Opponent))));
+        let hand = s.game.hand(PlayerId::Opponent).to_vec();
+        assert!(replies.contains(&(1, Response::Hand(hand))));
+        assert_eq!(
+            s.handle(2, Request::Sit(Some(PlayerId::Opponent))),
+            vec![(2, Response::Error(SessionError::SeatTaken.to_string()))]
+        );
+        s.handle(2, Request::Sit(None));
+        assert_eq!(s.lobby.seat_of(2), Some(PlayerId::Dealer));
+
+        s.leave(1);
+        assert_eq!(s.lobby.seat_of(1), None);
+        assert_eq!(s.connections(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_moves_are_broadcast() {
+        let mut s = setup();
+        for conn in 1..=3 {
+            s.join(conn);
+        }
+        s.handle(1, Request::Sit(None));
+        s.handle(2, Request::Sit(None));
+        assert_eq!(
+            s.handle(1, Request::Move(String::from("*C&3"))),
+            vec![(1, Response::Error(SessionError::NotStarted.to_string()))]
+        );
+        s.handle(1, Request::Ready);
+        let replies = s.handle(2, Request::Ready);
+        assert!(replies.contains(&(3, Response::Start)));
+        assert!(replies.contains(&(1, Response::Turn(PlayerId::Opponent))));
+
+        assert_eq!(
+            s.handle(2, Request::Move(String::from("!1"))),
+            vec![(2, Response::Error(StateError::NotYourTurn.to_string()))]
+        );
+        assert_eq!(
+            s.handle(3, Request::Move(String::from("!1"))),
+            vec![(3, Response::Error(SessionError::NotSeated.to_string()))]
+        );
+        // Moves from the network must be written out in full
+        for x in ["cC", "t1", "3"] {
+            assert_eq!(
+                s.handle(1, Request::Move(String::from(x))),
+                vec![(
+                    1,
+                    Response::Error(ParsingError::NonCanonicalAnnotation.to_string())
+                )]
+            );
+        }
+        let replies = s.handle(1, Request::Move(String::from("*C&3")));
+        assert_eq!(replies.len(), 6);
+        assert!(replies.contains(&(3, Response::Moved(PlayerId::Opponent, String::from("*C&3")))));
+        assert!(replies.contains(&(2, Response::Turn(PlayerId::Dealer))));
+        assert_eq!(s.handle(2, Request::Quit), vec![(2, Response::Bye)]);
+        assert_eq!(s.connections(), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_status_hides_seed() {
+        let mut s = Session::new(Game::builder().seed([7; 32]).build().unwrap());
+        let seed = s.game.status().seed;
+        let hex = seed
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect::<String>();
+        let mut replies = s.join(1);
+        replies.append(&mut s.join(2));
+        replies.append(&mut s.handle(1, Request::Sit(None)));
+        replies.append(&mut s.handle(2, Request::View));
+        let statuses = replies
+            .iter()
+            .filter_map(|(_, x)| match x {
+                Response::Status(status) => Some((*status, x.to_string())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(!statuses.is_empty());
+        for (status, line) in statuses {
+            assert!(line.starts_with("STATUS "));
+            assert_ne!(status.seed, seed);
+            assert!(!line.contains(&hex), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_hidden_deal() {
+        let seed = [9; 32];
+        let game = Game::builder().seed(seed).hidden().build().unwrap();
+        let mut s = Session::new(game);
+        let mut seen = s.join(1);
+        let commit = match seen.iter().find(|x| matches!(x.1, Response::Commit(_))) {
+            Some((_, Response::Commit(c))) => *c,
+            _ => panic!("no commitment"),
+        };
+        seen.append(&mut s.join(2));
+        seen.append(&mut s.handle(1, Request::Sit(None)));
+        seen.append(&mut s.handle(2, Request::Sit(None)));
+        seen.append(&mut s.handle(1, Request::Ready));
+        seen.append(&mut s.handle(2, Request::Ready));
+
+        // The deal is revealed to everyone at the end of the game
+        let mut reveal = None;
+        while s.lobby.phase() == Phase::Playing {
+            let p = s.game.state.turn;
+            let a = crate::sim::candidate_moves(&s.game.state)[0].clone();
+            let conn = s.lobby.occupant(p).unwrap();
+            for (c, x) in s.handle(conn, Request::Move(a)) {
+                match x {
+                    Response::Reveal(x) if c == 1 && x.game == 0 => reveal = Some(x),
+                    Response::Error(e) => panic!("{}", e),
+                    _ if reveal.is_none() => seen.push((c, x)),
+                    _ => {}
+                }
+            }
+        }
+        let reveal = reveal.unwrap();
+        assert!(reveal.verify(&commit));
+
+        // Nothing sent before the reveal gives away the seed or the salt
+        let (seed_hex, salt_hex) = (to_hex(&seed), to_hex(&reveal.salt));
+        for (_, x) in seen.iter() {
+            if let Response::Status(status) = x {
+                assert_eq!(status.seed, Seed::default());
+            }
+            let line = x.to_string();
+            assert!(
+                !line.contains(&seed_hex) && !line.contains(&salt_hex),
+                "{}",
+                line
+            );
+        }
+        // The salt doesn't come from the seed, so the same seed commits to the same deal
+        // differently each time
+        let other = Game::builder().seed(seed).hidden().build().unwrap();
+        assert_ne!(other.commitments()[0], commit);
+    }
+}
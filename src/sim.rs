@@ -0,0 +1,422 @@
+use crate::action::Annotation;
+use crate::bot::Bot;
+use crate::error::Error;
+use crate::game::Game;
+use crate::rng::{ChaCha20Rng, Seed, SeedableRng, SliceRandom};
+use crate::rules::Rules;
+use crate::state::{AuditError, PlayerId, State};
+use rand::RngCore;
+use std::fmt;
+
+/// An invariant violation found while simulating a game
+#[derive(Debug, Eq, PartialEq)]
+pub struct Violation {
+    pub seed: Seed,
+    pub moves: Vec<String>,
+    pub error: AuditError,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} after {} moves (seed: {:?}, moves: {})",
+            self.error,
+            self.moves.len(),
+            self.seed,
+            self.moves.join(" ")
+        )
+    }
+}
+
+/// Get the annotations for every simple move the current player could make
+///
+/// This covers discards, and pairing, building, or grouping a hand card with a single floor pile.
+/// Under the capture all rule, captures take every floor pile with the captured value.
+pub fn candidate_moves(state: &State) -> Vec<String> {
+    state.simple_moves().into_owned()
+}
+
+/// Play a random match from the given seed, calling a hook after every move and turn
+///
+/// The hook receives the game, the moves played so far, and whether the turn has ended. Returns
+/// the finished game, or the first error returned by the hook.
+pub fn play_match<F>(seed: Seed, hook: F) -> Result<Game, Violation>
+where
+    F: FnMut(&Game, &[String], bool) -> Result<(), AuditError>,
+{
+    play_match_with(seed, Rules::default(), hook)
+}
+
+/// Play a random match from the given seed under the given rules, calling a hook after every
+/// move and turn
+pub fn play_match_with<F>(seed: Seed, rules: Rules, mut hook: F) -> Result<Game, Violation>
+where
+    F: FnMut(&Game, &[String], bool) -> Result<(), AuditError>,
+{
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    rng.set_stream(1);
+    let mut g = Game::builder().seed(seed).rules(rules).build().unwrap();
+    let mut moves = vec![];
+    let fail = |moves: &Vec<String>, error| {
+        Err(Violation {
+            seed,
+            moves: moves.clone(),
+            error,
+        })
+    };
+    while !g.is_over() {
+        let x = match candidate_moves(&g.state).choose(&mut rng) {
+            Some(x) => x.to_owned(),
+            None => return fail(&moves, AuditError::NoLegalMove),
+        };
+        let m = Annotation::new(x.clone()).to_move().unwrap();
+        moves.push(x);
+        g.apply(m).unwrap();
+        if let Err(e) = hook(&g, &moves, false) {
+            return fail(&moves, e);
+        }
+        g.tick();
+        if let Err(e) = hook(&g, &moves, true) {
+            return fail(&moves, e);
+        }
+    }
+    Ok(g)
+}
+
+/// Play a random match from the given seed, auditing the state after every move
+///
+/// Returns the number of moves played if no invariant was violated.
+pub fn audit_game(seed: Seed) -> Result<usize, Violation> {
+    let mut count = 0;
+    play_match(seed, |g, moves, _| {
+        count = moves.len();
+        g.state.audit()
+    })?;
+    Ok(count)
+}
+
+/// Get `n` match seeds drawn from the given seed, so a run of matches can be repeated
+pub fn seeds(seed: Seed, n: usize) -> Vec<Seed> {
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    (0..n)
+        .map(|_| {
+            let mut x = Seed::default();
+            rng.fill_bytes(&mut x);
+            x
+        })
+        .collect()
+}
+
+/// Play `n` random games with seeds drawn from the given seed, auditing the state after every
+/// move
+///
+/// Returns the first invariant violation found, along with the seed and moves that caused it.
+pub fn simulate_with_audit(seed: Seed, n: usize) -> Result<(), Violation> {
+    for x in seeds(seed, n) {
+        audit_game(x)?;
+    }
+    Ok(())
+}
+
+/// Results of matches played between two bots, indexed by bot rather than by seat
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimReport {
+    pub matches: usize,
+    pub wins: [usize; 2],
+    pub ties: usize,
+    /// Total match score of each bot
+    pub points: [u32; 2],
+    /// Suipis made by each bot
+    pub sweeps: [usize; 2],
+}
+
+impl SimReport {
+    /// Get the fraction of matches won by the given bot
+    pub fn win_rate(&self, bot: usize) -> f64 {
+        self.wins[bot] as f64 / self.matches.max(1) as f64
+    }
+
+    /// Get the average match score of the given bot
+    pub fn average_score(&self, bot: usize) -> f64 {
+        self.points[bot] as f64 / self.matches.max(1) as f64
+    }
+
+    /// Get the average number of suipis the given bot made per match
+    pub fn sweeps_per_match(&self, bot: usize) -> f64 {
+        self.sweeps[bot] as f64 / self.matches.max(1) as f64
+    }
+}
+
+impl fmt::Display for SimReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Matches: {}", self.matches)?;
+        writeln!(
+            f,
+            "Ties: {:.1}%",
+            self.ties as f64 / self.matches.max(1) as f64 * 100.0
+        )?;
+        writeln!(f, "Bot | Wins   | Score | Suipis")?;
+        for (i, name) in ["A", "B"].iter().enumerate() {
+            writeln!(
+                f,
+                "{}   | {:.1}% | {:.2} | {:.2}",
+                name,
+                self.win_rate(i) * 100.0,
+                self.average_score(i),
+                self.sweeps_per_match(i)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Play a match between two bots from each of the given seeds
+///
+/// The bots swap seats from one seed to the next, with `bot_a` moving first on the first seed.
+pub fn run_selfplay(bot_a: &mut dyn Bot, bot_b: &mut dyn Bot, seeds: &[Seed]) -> SimReport {
+    run_selfplay_with(bot_a, bot_b, seeds, Rules::default())
+}
+
+/// Play a match between two bots from each of the given seeds under the given rules
+///
+/// Each bot only sees what its own seat can see. A bot that has no legal move to give when it
+/// needs one resigns the match.
+pub fn run_selfplay_with(
+    bot_a: &mut dyn Bot,
+    bot_b: &mut dyn Bot,
+    seeds: &[Seed],
+    rules: Rules,
+) -> SimReport {
+    let mut report = SimReport::default();
+    for (i, &seed) in seeds.iter().enumerate() {
+        let mut g = Game::builder()
+            .seed(seed)
+            .rules(rules.clone())
+            .build()
+            .unwrap();
+        // Bot playing each seat, indexed by player id
+        let seats = match i % 2 {
+            0 => [0, 1],
+            _ => [1, 0],
+        };
+        while !g.is_over() {
+            let p = g.state.turn;
+            let view = g.state.redacted_for(p);
+            let m = match seats[p as usize] {
+                0 => bot_a.choose_move(&view),
+                _ => bot_b.choose_move(&view),
+            };
+            let suipis = g.state.seat(p).suipi_count;
+            match m.map(|m| g.apply(m)) {
+                Some(Ok(())) => {
+                    if g.state.seat(p).suipi_count > suipis {
+                        report.sweeps[seats[p as usize]] += 1;
+                    }
+                    g.tick();
+                }
+                _ => {
+                    g.resign(p).unwrap();
+                }
+            }
+        }
+        let result = g.result.as_ref().unwrap();
+        report.matches += 1;
+        match result.winner {
+            Some(p) => report.wins[seats[p as usize]] += 1,
+            None => report.ties += 1,
+        }
+        for p in [PlayerId::Opponent, PlayerId::Dealer] {
+            report.points[seats[p as usize]] += result.total(p) as u32;
+        }
+    }
+    report
+}
+
+/// A random match kept as a regression fixture: its seed, its moves, and a fingerprint of every
+/// position reached by playing them
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Fixture {
+    pub seed: Seed,
+    pub moves: Vec<String>,
+    pub fingerprint: u64,
+}
+
+impl Fixture {
+    /// Play a random match from the given seed and record it
+    pub fn record(seed: Seed) -> Result<Fixture, Violation> {
+        let g = play_match(seed, |_, _, _| Ok(()))?;
+        let moves = g
+            .moves()
+            .iter()
+            .map(|x| x.annotation.clone())
+            .collect::<Vec<String>>();
+        // The moves were just played from the same seed
+        let fingerprint = fingerprint(seed, &moves).unwrap();
+        Ok(Fixture {
+            seed,
+            moves,
+            fingerprint,
+        })
+    }
+
+    /// Play the moves again, checking that they reach the same positions
+    pub fn check(&self) -> Result<bool, Error> {
+        Ok(fingerprint(self.seed, &self.moves)? == self.fingerprint)
+    }
+
+    /// Read a fixture from a line written by its `Display` impl
+    pub fn parse(line: &str) -> Option<Fixture> {
+        let mut words = line.split_whitespace();
+        let hex = words.next()?;
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+        let mut seed = [0; 32];
+        for (i, b) in seed.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        let fingerprint = u64::from_str_radix(words.next()?, 16).ok()?;
+        Some(Fixture {
+            seed,
+            moves: words.map(String::from).collect(),
+            fingerprint,
+        })
+    }
+}
+
+impl fmt::Display for Fixture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.seed {
+            write!(f, "{:02x}", b)?;
+        }
+        write!(f, " {:016x}", self.fingerprint)?;
+        for x in self.moves.iter() {
+            write!(f, " {}", x)?;
+        }
+        Ok(())
+    }
+}
+
+/// Hash every position reached by playing the moves from the given seed
+///
+/// This uses FNV-1a over the cards in play and the scores, so it stays the same across builds.
+pub fn fingerprint(seed: Seed, moves: &[String]) -> Result<u64, Error> {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut feed = |x: u8| {
+        hash ^= x as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    };
+    let mut g = Game::builder().seed(seed).build()?;
+    for x in moves {
+        g.apply_annotation(x)?;
+        g.tick();
+        let s = &g.state;
+        feed(s.turn as u8);
+        for p in [PlayerId::Opponent, PlayerId::Dealer] {
+            let seat = s.seat(p);
+            for x in seat.hand.iter().chain(s.floor.iter()) {
+                x.cards.iter().for_each(|&c| feed(u8::from(c)));
+                feed(u8::MAX);
+            }
+            feed(seat.pairs.len() as u8);
+        }
+        for score in g.scores.iter() {
+            feed(score.opponent_total());
+            feed(score.dealer_total());
+        }
+    }
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{GreedyBot, RandomBot};
+    use crate::rules::FloorOverflow;
+
+    #[test]
+    fn test_run_selfplay() {
+        let seeds: Vec<Seed> = (0..6).map(|i| [i; 32]).collect();
+        let run = || run_selfplay(&mut GreedyBot::new(), &mut RandomBot::new([9; 32]), &seeds);
+        let report = run();
+        assert_eq!(report.matches, 6);
+        assert_eq!(report.wins[0] + report.wins[1] + report.ties, 6);
+        assert!(report.win_rate(0) > report.win_rate(1), "{}", report);
+        assert!(report.average_score(0) > report.average_score(1));
+        assert_eq!(run(), report);
+
+        // Shorter matches score fewer points
+        let rules = Rules {
+            games: 1,
+            ..Rules::default()
+        };
+        let short = run_selfplay_with(
+            &mut GreedyBot::new(),
+            &mut RandomBot::new([9; 32]),
+            &seeds,
+            rules,
+        );
+        assert_eq!(short.matches, 6);
+        assert!(short.points[0] + short.points[1] < report.points[0] + report.points[1]);
+    }
+
+    #[test]
+    fn test_candidate_moves() {
+        let mut g = Game::default();
+        g.seed(Seed::default());
+        g.deal();
+        let moves = candidate_moves(&g.state);
+        assert!(moves.contains(&String::from("*C&3")));
+        assert!(moves.contains(&String::from("!1")));
+        assert!(!moves.contains(&String::from("*A&3")));
+    }
+
+    #[test]
+    fn test_audit_game() {
+        for i in 0..8 {
+            let res = audit_game([i; 32]);
+            assert!(res.is_ok(), "{}", res.unwrap_err());
+        }
+    }
+
+    #[test]
+    fn test_fixture() {
+        let x = Fixture::record([5; 32]).unwrap();
+        assert_eq!(x.check(), Ok(true));
+        assert_eq!(Fixture::parse(&x.to_string()), Some(x.clone()));
+
+        let mut y = x.clone();
+        y.moves.truncate(10);
+        assert_eq!(y.check(), Ok(false));
+        y.moves.push(String::from("*A&9"));
+        assert!(y.check().is_err());
+        assert!(Fixture::parse("00 0 !1").is_none());
+    }
+
+    #[test]
+    fn test_capture_all_matches() {
+        // Without unique values, a full floor may have nothing to capture
+        let rules = Rules {
+            unique_floor: false,
+            capture_all: true,
+            floor_overflow: FloorOverflow::AwardLastScorer,
+            ..Rules::default()
+        };
+        for i in 0..8 {
+            if let Err(e) = play_match_with([i; 32], rules.clone(), |g, _, _| g.state.audit()) {
+                panic!("{}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_simulate_with_audit() {
+        assert_eq!(simulate_with_audit(Seed::default(), 4), Ok(()));
+        // The same seed always plays the same matches
+        let x = seeds([3; 32], 4);
+        assert_eq!(x, seeds([3; 32], 4));
+        assert_ne!(x, seeds([4; 32], 4));
+        assert!(x.windows(2).all(|y| y[0] != y[1]));
+    }
+}
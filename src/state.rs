@@ -1,9 +1,24 @@
-use crate::action::{Address, Move, MoveError, Operation};
-use crate::card::{Card, Value};
+use crate::action::{Action, Address, Annotation, Move, MoveError, Operation};
+use crate::card::{Card, Suit, Value};
+use crate::consts::{
+    ACTION_SPACE_SIZE, DECK_SIZE, FLOOR_DEAL_SIZE, HAND_SIZE, MAX_BUILD_VALUE, VALUE_SLOTS,
+};
+use crate::locale::Locale;
 use crate::pile::{Mark, Pile, PileError};
 use crate::rng::{ChaCha20Rng, SliceRandom};
+use crate::rules::{FloorOverflow, Rules};
+use crate::score::Score;
+use std::borrow::Cow;
 use std::collections::{HashSet, VecDeque};
 use std::fmt;
+use std::sync::OnceLock;
+
+#[cfg(test)]
+thread_local! {
+    /// Makes the next call to `State::apply` on this thread panic, for testing that the engine
+    /// contains panics in every build profile
+    pub(crate) static INJECT_PANIC: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
 
 /// State manipulation errors
 #[derive(Debug, Eq, PartialEq)]
@@ -16,8 +31,14 @@ pub enum StateError {
     FloorIsFull,
     PileIsNotEmpty,
     OwnTooManyPiles,
-    UnpairablePileValue,
+    UnpairablePileValue(Orphan),
     DuplicateFloorValue,
+    MultipleBuildsNotAllowed,
+    NotYourTurn,
+    UncapturedPile(Address),
+    MoveLimitReached,
+    /// The engine hit a bug while applying a move, and the move was undone
+    Internal(String),
 }
 
 impl From<MoveError> for StateError {
@@ -33,21 +54,225 @@ impl From<PileError> for StateError {
 }
 
 impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Locale::English.state_message(self))
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// An owned floor pile that its owner no longer holds a matching card for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Orphan {
+    pub pile: Address,
+    pub value: u8,
+}
+
+impl fmt::Display for Orphan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Locale::English.orphan_message(self))
+    }
+}
+
+/// State invariant violations
+#[derive(Debug, Eq, PartialEq)]
+pub enum AuditError {
+    DuplicateCard(Card),
+    MissingCard(Card),
+    InvalidCard(Card),
+    InvalidFloorSize(usize),
+    InvalidFloorPile(usize),
+    InvalidHandPile(PlayerId, usize),
+    DuplicateFloorValue,
+    OwnTooManyPiles(PlayerId),
+    NoLegalMove,
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Locale::English.audit_message(self))
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+/// The way a hand card can be combined with a floor pile
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TargetKind {
+    Pair,
+    Build,
+    Group,
+}
+
+/// A legal way to play a hand card onto a floor pile
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CaptureOption {
+    pub target: Address,
+    /// Every floor pile the move uses, starting with the target
+    pub piles: Vec<Address>,
+    pub kind: TargetKind,
+    pub value: u8,
+    pub annotation: String,
+}
+
+/// A build a player could make toward one of their hand cards
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BuildOption {
+    /// Hand card played onto the floor
+    pub card: Address,
+    /// Floor piles built on
+    pub piles: Vec<Address>,
+    pub annotation: String,
+}
+
+/// A legal way to merge a hand card with floor piles of the same value
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MergeOption {
+    pub annotation: String,
+    /// Floor piles the card is merged with
+    pub piles: Vec<Address>,
+    /// Pile the move leaves on the floor, or `None` if the card and piles are captured
+    pub result: Option<Pile>,
+}
+
+/// What a player's hand can do on their turn
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HandSummary {
+    /// Floor piles the player could capture with one of their cards
+    pub captures: Vec<Address>,
+    /// Build values the player could make with one of their cards
+    pub builds: Vec<u8>,
+    /// Cards in the player's hand that score points on their own
+    pub point_cards: Vec<Card>,
+    /// Number of spades in the player's hand
+    pub spades: usize,
+}
+
+/// How likely a discard is to be captured on the other player's next turn
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiscardRisk {
+    /// Unseen cards that could capture the discard, alone or with one other floor pile
+    pub threats: Vec<Card>,
+    /// Chance the other player holds at least one of the threats
+    pub chance: f64,
+}
+
+impl DiscardRisk {
+    /// Check if the other player cannot possibly capture the discard
+    pub fn is_safe(&self) -> bool {
+        self.threats.is_empty()
+    }
+}
+
+/// Where a card is in the game
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Location {
+    Deck,
+    Floor(u16), // Id of the floor pile holding the card
+    Hand(PlayerId, u8),
+    Pairs(PlayerId),
+}
+
+/// A difference between two game states
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Change {
+    CardMoved {
+        card: Card,
+        from: Location,
+        to: Location,
+    },
+    PileAdded {
+        id: u16,
+        address: Address,
+    },
+    PileRemoved {
+        id: u16,
+    },
+    OwnerChanged {
+        id: u16,
+        owner: PlayerId,
+    },
+    ScoreChanged {
+        player: PlayerId,
+        before: u8,
+        after: u8,
+    },
+}
+
+/// Where a card ends up in a keyframe
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Destination {
+    Pile(Address),
+    /// The current player's captured cards
+    Captured,
+}
+
+/// A single card moving from one pile to another while a move is applied
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keyframe {
+    pub card: Card,
+    pub from: Address,
+    pub to: Destination,
+}
+
+/// A player seat
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlayerId {
+    #[default]
+    Opponent = 0,
+    Dealer = 1,
+}
+
+impl PlayerId {
+    /// Get the other player
+    pub fn other(self) -> PlayerId {
+        match self {
+            PlayerId::Opponent => PlayerId::Dealer,
+            PlayerId::Dealer => PlayerId::Opponent,
+        }
+    }
+}
+
+impl From<bool> for PlayerId {
+    fn from(dealer: bool) -> PlayerId {
+        if dealer {
+            PlayerId::Dealer
+        } else {
+            PlayerId::Opponent
+        }
+    }
+}
+
+impl TryFrom<u8> for PlayerId {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<PlayerId, u8> {
+        match value {
+            0 => Ok(PlayerId::Opponent),
+            1 => Ok(PlayerId::Dealer),
+            _ => Err(value),
+        }
+    }
+}
+
+impl From<PlayerId> for bool {
+    fn from(p: PlayerId) -> bool {
+        p == PlayerId::Dealer
+    }
+}
+
+impl fmt::Display for PlayerId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "State Error: {}",
+            "{}",
             match self {
-                StateError::InvalidAddress => "Invalid address".to_string(),
-                StateError::InvalidDiscard => "Invalid discard".to_string(),
-                StateError::InvalidInput => "Invalid input".to_string(),
-                StateError::InvalidMove(e) => format!("Invalid move - {}", e),
-                StateError::InvalidPile(e) => format!("Invalid pile - {}", e),
-                StateError::FloorIsFull => "Floor is full".to_string(),
-                StateError::PileIsNotEmpty => "Pile is not empty".to_string(),
-                StateError::OwnTooManyPiles => "Owning too may piles".to_string(),
-                StateError::UnpairablePileValue => "Un-pairable pile value".to_string(),
-                StateError::DuplicateFloorValue => "Duplicate floor card".to_string(),
+                PlayerId::Opponent => "Opponent",
+                PlayerId::Dealer => "Dealer",
             }
         )
     }
@@ -55,6 +280,7 @@ impl fmt::Display for StateError {
 
 /// The state of a player
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Player {
     pub hand: Vec<Pile>,
     pub pairs: Vec<Pile>,
@@ -62,7 +288,7 @@ pub struct Player {
 }
 
 impl Player {
-    /// Get a new player from 8 piles
+    /// Get a new player from a hand of piles
     pub fn new(hand: Vec<Pile>) -> Player {
         Player {
             hand,
@@ -82,22 +308,245 @@ impl Player {
     }
 }
 
+/// Simple moves worked out for a player, with the position they were worked out in
+#[derive(Clone, Debug, Default)]
+struct SimpleMoves {
+    turn: PlayerId,
+    hand: Vec<Pile>,
+    floor: Vec<Pile>,
+    rules: Rules,
+    moves: Vec<String>,
+}
+
+/// A search for every legal move in a state, keeping one move for each position they lead to
+struct MoveGen<'a> {
+    root: &'a State,
+    seen: HashSet<Vec<Vec<u8>>>,
+    moves: Vec<Move>,
+}
+
+impl MoveGen<'_> {
+    /// Try a move on the starting state, keeping it if it leads to a new position
+    ///
+    /// Returns whether the move is legal.
+    fn offer(&mut self, builds: &[Vec<Action>], actions: Vec<Action>) -> bool {
+        let mut m = Move::with_builds(builds.to_vec(), actions);
+        if self.root.rules.capture_all {
+            m = self.root.complete_capture(m);
+        }
+        let mut s = self.root.clone();
+        if s.apply(m.clone()).is_err() {
+            return false;
+        }
+        if self.seen.insert(s.position_key()) {
+            self.moves.push(m);
+        }
+        true
+    }
+
+    /// Look for moves in a state that the given floor builds have been made in
+    ///
+    /// `last` is the first pile of the latest floor build, so each set of floor builds is only
+    /// tried in one order.
+    fn search(&mut self, s: &State, builds: Vec<Vec<Action>>, last: Option<usize>) {
+        let floor = (0..s.floor.len())
+            .filter(|&j| !s.floor[j].is_empty())
+            .collect::<Vec<usize>>();
+        let buildable = |xs: &[Address]| {
+            xs.iter()
+                .all(|&a| s.get(a).is_some_and(|x| Pile::buildable(x).is_ok()))
+        };
+        // Piles a card has already captured or grouped, which other orders of chunks can skip
+        let mut done = HashSet::new();
+        for (i, h) in s.player().hand.iter().enumerate() {
+            if h.is_empty() {
+                continue;
+            }
+            let card = Address::Hand(i as u8);
+            self.offer(&builds, vec![Action::new(Operation::Passive, card)]);
+
+            // Capture floor piles, and builds of floor piles, with the card's value
+            let chunks = s.chunks(&floor, h.value);
+            let mut sets = vec![];
+            disjoint_sets(&chunks, 0, &mut vec![], &mut sets);
+            for (set, used) in sets {
+                if done.contains(&(i, 0, used)) {
+                    continue;
+                }
+                let mut xs = s.order_chunks(set.iter().map(|&k| chunks[k].clone()).collect());
+                xs.push(vec![card]);
+                if self.offer(&builds, chunk_actions(&xs, true)) {
+                    done.insert((i, 0, used));
+                }
+            }
+
+            // Builds and groups have to end up with a value the player has another card for
+            let mut values = (s.player().hand.iter().enumerate())
+                .filter(|&(k, x)| k != i && !x.is_empty())
+                .map(|(_, x)| x.value)
+                .collect::<Vec<u8>>();
+            values.sort_unstable();
+            values.dedup();
+
+            // Build the card onto floor piles
+            for &w in values
+                .iter()
+                .filter(|&&w| w <= MAX_BUILD_VALUE && w > h.value)
+            {
+                let mut sums = vec![];
+                s.floor_sums(&floor, w - h.value, &mut vec![], &mut sums);
+                for xs in sums.into_iter().filter(|xs| buildable(xs)) {
+                    if let Some(xs) = s.order_chunk([xs, vec![card]].concat()) {
+                        self.offer(&builds, chunk_actions(&[xs], false));
+                    }
+                }
+            }
+
+            // Group the card, on its own or built onto floor piles, with piles of the same value
+            for &w in values.iter() {
+                let mut own = vec![];
+                if h.value == w {
+                    own.push(vec![card]);
+                }
+                if w <= MAX_BUILD_VALUE && h.value < w {
+                    let mut sums = vec![];
+                    s.floor_sums(&floor, w - h.value, &mut vec![], &mut sums);
+                    own.extend(
+                        sums.into_iter()
+                            .filter(|xs| buildable(xs))
+                            .filter_map(|xs| s.order_chunk([xs, vec![card]].concat())),
+                    );
+                }
+                if own.is_empty() {
+                    continue;
+                }
+                let chunks = s.chunks(&floor, w);
+                for x in own {
+                    let mut sets = vec![];
+                    disjoint_sets(&chunks, floor_mask(&x), &mut vec![], &mut sets);
+                    for (set, used) in sets {
+                        if done.contains(&(i, w, used)) {
+                            continue;
+                        }
+                        let mut xs = set.iter().map(|&k| chunks[k].clone()).collect::<Vec<_>>();
+                        xs.push(x.clone());
+                        if self.offer(&builds, chunk_actions(&s.order_chunks(xs), false)) {
+                            done.insert((i, w, used));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Make another floor build first, with a value the player could capture it with
+        if !s.rules.multi_build {
+            return;
+        }
+        let mut values = (s.player().hand.iter())
+            .filter(|x| !x.is_empty())
+            .map(|x| x.value)
+            .collect::<Vec<u8>>();
+        values.sort_unstable();
+        values.dedup();
+        let mut subsets = vec![];
+        for &w in values.iter().filter(|&&w| w <= MAX_BUILD_VALUE) {
+            s.floor_sums(&floor, w, &mut vec![], &mut subsets);
+        }
+        for xs in subsets {
+            let Some(Address::Floor(first)) = xs.first().copied() else {
+                continue;
+            };
+            if xs.len() < 2 || last.is_some_and(|x| first as usize <= x) || !buildable(&xs) {
+                continue;
+            }
+            let Some(xs) = s.order_chunk(xs) else {
+                continue;
+            };
+            let mut t = s.clone();
+            if xs.windows(2).rev().any(|w| t.build(w[0], w[1]).is_err()) {
+                continue;
+            }
+            let mut next = builds.clone();
+            next.push(chunk_actions(&[xs], false));
+            self.search(&t, next, Some(first as usize));
+        }
+    }
+}
+
+/// Get the floor piles in a set of addresses as bits
+fn floor_mask(xs: &[Address]) -> u32 {
+    xs.iter().fold(0, |m, a| match a {
+        Address::Floor(j) => m | 1 << j,
+        Address::Hand(_) => m,
+    })
+}
+
+/// Write chunks of piles out as the actions of a move
+///
+/// The piles in each chunk are built together, then the chunks are grouped, or captured when
+/// the move is a capture.
+fn chunk_actions(chunks: &[Vec<Address>], capture: bool) -> Vec<Action> {
+    let mut actions = vec![];
+    for (i, chunk) in chunks.iter().enumerate() {
+        for (k, &a) in chunk.iter().enumerate() {
+            let operation = match (i, k) {
+                (0, 0) if capture => Operation::Active,
+                (_, 0) => Operation::Passive,
+                _ => Operation::Active,
+            };
+            actions.push(Action::new(operation, a));
+        }
+    }
+    actions
+}
+
+/// Find every set of chunks, taken in order, that don't share a floor pile with each other or
+/// the used piles, along with all the piles each set uses
+fn disjoint_sets(
+    chunks: &[Vec<Address>],
+    used: u32,
+    current: &mut Vec<usize>,
+    out: &mut Vec<(Vec<usize>, u32)>,
+) {
+    let from = current.last().map_or(0, |k| k + 1);
+    for (k, chunk) in chunks.iter().enumerate().skip(from) {
+        let m = floor_mask(chunk);
+        if m & used == 0 {
+            current.push(k);
+            out.push((current.clone(), used | m));
+            disjoint_sets(chunks, used | m, current, out);
+            current.pop();
+        }
+    }
+}
+
 /// The state of a game
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct State {
     pub deck: VecDeque<Card>,
     pub floor: Vec<Pile>,
     pub dealer: Player,
     pub opponent: Player,
-    pub turn: bool,
-    pub last_score: bool,
+    pub turn: PlayerId,
+    /// The last player to capture, and so the one the floor goes to
+    ///
+    /// Games start this at the dealer, who takes the floor if nobody captures.
+    pub last_score: PlayerId,
+    pub rules: Rules,
+    pub last_pile_id: u16,
+    /// Every card moved by the last move applied, in order, including intermediate steps
+    pub keyframes: Vec<Keyframe>,
+    /// Simple moves the current player could make, worked out the first time they are needed
+    #[cfg_attr(feature = "serde", serde(skip))]
+    simple: OnceLock<SimpleMoves>,
 }
 
 impl State {
-    /// Initialize the deck with all 52 cards
+    /// Initialize the deck with every card
     pub fn init_deck(&mut self) {
-        for i in 0..52 {
-            self.deck.push_back(Card::from(i));
+        for i in 0..DECK_SIZE {
+            self.deck.push_back(Card::from(i as u8));
         }
     }
 
@@ -114,11 +563,12 @@ impl State {
         }
     }
 
-    /// Deal eight cards to each player
+    /// Deal a full hand of cards to each player
     pub fn deal_hands(&mut self) {
+        self.simple = OnceLock::new();
         self.opponent.hand = vec![];
         self.dealer.hand = vec![];
-        for _ in 0..8 {
+        for _ in 0..HAND_SIZE {
             let a = self.deal_pile();
             let b = self.deal_pile();
             self.opponent.hand.push(a);
@@ -126,6 +576,17 @@ impl State {
         }
     }
 
+    /// Shuffle every captured card back into the bottom of the deck
+    pub fn reshuffle_captures(&mut self, rng: &mut ChaCha20Rng) {
+        let mut cards = [PlayerId::Opponent, PlayerId::Dealer]
+            .iter()
+            .flat_map(|&p| std::mem::take(&mut self.seat_mut(p).pairs))
+            .flat_map(|x| x.cards)
+            .collect::<Vec<Card>>();
+        cards.shuffle(rng);
+        self.deck.extend(cards);
+    }
+
     /// Check if the floor contains only unique values
     pub fn unique_floor(&self) -> bool {
         let mut unique = HashSet::new();
@@ -136,27 +597,74 @@ impl State {
             .all(|v| unique.insert(v))
     }
 
-    /// Deal four unique cards to the floor
+    /// Check if the floor satisfies the unique floor rule, when it is in play
+    fn floor_allowed(&self) -> bool {
+        !self.rules.unique_floor || self.unique_floor()
+    }
+
+    /// Deal the opening cards to the floor, unique unless the rules allow duplicates
     pub fn deal_floor(&mut self) {
         self.floor = vec![];
         self.collapse_floor();
-        for i in 0..4 {
-            while self.floor[i].is_empty() {
+        for i in 0..FLOOR_DEAL_SIZE {
+            while self.floor[i].is_empty() && !self.deck.is_empty() {
                 let x = self.deal_pile();
                 self.floor[i].replace(x);
-                if !self.unique_floor() {
+                if !self.floor_allowed() {
                     for c in self.floor[i].take().cards.iter().copied() {
                         self.deck.push_back(c);
                     }
                 }
             }
+            self.floor[i].id = self.next_pile_id();
+        }
+    }
+
+    /// Get a new id for a pile placed on the floor
+    ///
+    /// Ids wrap around once they run out, skipping 0 and any id still on the floor.
+    fn next_pile_id(&mut self) -> u16 {
+        loop {
+            self.last_pile_id = self.last_pile_id.wrapping_add(1);
+            let id = self.last_pile_id;
+            if id != 0 && !self.floor.iter().any(|x| !x.is_empty() && x.id == id) {
+                return id;
+            }
         }
     }
 
+    /// Get the current address of the floor pile with the given id
+    pub fn address_of(&self, id: u16) -> Option<Address> {
+        self.floor
+            .iter()
+            .position(|x| id != 0 && x.id == id)
+            .map(|i| Address::Floor(i as u8))
+    }
+
+    /// Get the keyframes for moving every card in a pile to the given destination
+    fn moving(&self, a: Address, to: Destination) -> Vec<Keyframe> {
+        self.get(a)
+            .into_iter()
+            .flat_map(|x| x.cards.iter())
+            .map(|&card| Keyframe { card, from: a, to })
+            .collect()
+    }
+
     /// Collapse all piles to the beginning of the floor array
     fn collapse_floor(&mut self) {
+        let mut slides = vec![];
+        for (i, j) in (0..self.floor.len())
+            .filter(|&i| !self.floor[i].is_empty())
+            .enumerate()
+        {
+            if i != j {
+                let to = Destination::Pile(Address::Floor(i as u8));
+                slides.append(&mut self.moving(Address::Floor(j as u8), to));
+            }
+        }
+        self.keyframes.append(&mut slides);
         self.floor.retain(|x| !x.is_empty());
-        while self.floor.len() < 13 {
+        while self.floor.len() < self.rules.floor_size {
             self.floor.push(Pile::empty());
         }
     }
@@ -170,11 +678,7 @@ impl State {
             .flat_map(|x| x.cards.clone())
             .collect::<Vec<Card>>();
         let last_pair = Pile::new(cards, Value::Invalid as u8, Mark::Pair);
-        if self.last_score {
-            self.dealer.pairs.push(last_pair);
-        } else {
-            self.opponent.pairs.push(last_pair);
-        }
+        self.seat_mut(self.last_score).pairs.push(last_pair);
     }
 
     /// Get the number of piles on the floor
@@ -182,22 +686,30 @@ impl State {
         self.floor.iter().filter(|x| !x.is_empty()).count()
     }
 
+    /// Get a reference to the player in the given seat
+    pub fn seat(&self, p: PlayerId) -> &Player {
+        match p {
+            PlayerId::Dealer => &self.dealer,
+            PlayerId::Opponent => &self.opponent,
+        }
+    }
+
+    /// Get a mutable reference to the player in the given seat
+    pub fn seat_mut(&mut self, p: PlayerId) -> &mut Player {
+        match p {
+            PlayerId::Dealer => &mut self.dealer,
+            PlayerId::Opponent => &mut self.opponent,
+        }
+    }
+
     /// Get a reference to the player for the current turn
     pub fn player(&self) -> &Player {
-        if self.turn {
-            &self.dealer
-        } else {
-            &self.opponent
-        }
+        self.seat(self.turn)
     }
 
     /// Get a mutable reference to the player for the current turn
     pub fn player_mut(&mut self) -> &mut Player {
-        if self.turn {
-            &mut self.dealer
-        } else {
-            &mut self.opponent
-        }
+        self.seat_mut(self.turn)
     }
 
     /// Get the context needed to access the given address
@@ -216,24 +728,34 @@ impl State {
         }
     }
 
+    /// Check if an address points at a pile in the current player's hand or on the floor
+    pub fn has_address(&self, a: Address) -> bool {
+        let (piles, i) = self.pile(a);
+        i < piles.len()
+    }
+
+    /// Get the pile at an address, if there is one
+    pub fn get(&self, a: Address) -> Option<&Pile> {
+        let (piles, i) = self.pile(a);
+        piles.get(i)
+    }
+
     /// Take the value out of a pile if it is not empty
     pub fn take(&mut self, a: Address) -> Option<Pile> {
         let (piles, i) = self.pile_mut(a);
-        if !piles[i].is_empty() {
-            Some(piles[i].take())
-        } else {
-            None
-        }
+        piles.get_mut(i).filter(|x| !x.is_empty()).map(|x| x.take())
     }
 
     /// Replace the value of an empty pile
     pub fn replace(&mut self, a: Address, p: Pile) -> Result<(), StateError> {
         let (piles, i) = self.pile_mut(a);
-        if piles[i].is_empty() {
-            piles[i].replace(p);
-            Ok(())
-        } else {
-            Err(StateError::PileIsNotEmpty)
+        match piles.get_mut(i) {
+            Some(x) if x.is_empty() => {
+                x.replace(p);
+                Ok(())
+            }
+            Some(_) => Err(StateError::PileIsNotEmpty),
+            None => Err(StateError::InvalidAddress),
         }
     }
 
@@ -247,13 +769,26 @@ impl State {
                     if let Some(pile) = self.take(a) {
                         if let Some(j) = self.floor.iter().position(|x| x.is_empty()) {
                             self.floor[j].replace(pile);
-                            if self.unique_floor() {
+                            if self.floor_allowed() {
+                                self.floor[j].id = self.next_pile_id();
+                                let to = Destination::Pile(Address::Floor(j as u8));
+                                let mut frames = self.moving(Address::Floor(j as u8), to);
+                                frames.iter_mut().for_each(|x| x.from = a);
+                                self.keyframes.append(&mut frames);
                                 Ok(())
                             } else {
                                 let v = self.floor[j].take();
                                 self.replace(a, v)?;
-                                Err(StateError::InvalidDiscard)
+                                Err(StateError::DuplicateFloorValue)
+                            }
+                        } else if self.rules.floor_overflow == FloorOverflow::AwardLastScorer {
+                            for &card in pile.cards.iter() {
+                                let to = Destination::Captured;
+                                self.keyframes.push(Keyframe { card, from: a, to });
                             }
+                            let x = Pile::new(pile.cards, Value::Invalid as u8, Mark::Pair);
+                            self.seat_mut(self.last_score).pairs.push(x);
+                            Ok(())
                         } else {
                             self.replace(a, pile)?;
                             Err(StateError::FloorIsFull)
@@ -282,13 +817,14 @@ impl State {
             match reduce(&mut x, &mut y) {
                 Ok(mut z) => {
                     z.owner = self.turn;
+                    // The combined pile keeps the identity of the floor pile it was built on
+                    z.id = if x.id != 0 { x.id } else { y.id };
                     save(self, z)
                 }
                 Err(e) => {
-                    let (xs, i) = self.pile_mut(p.0);
-                    xs[i].replace(x);
-                    let (ys, i) = self.pile_mut(p.1);
-                    ys[i].replace(y);
+                    // Both addresses held a pile, so they are in bounds
+                    self.replace(p.0, x)?;
+                    self.replace(p.1, y)?;
                     Err(e.into())
                 }
             }
@@ -299,18 +835,64 @@ impl State {
 
     /// Build a pile from two addresses
     pub fn build(&mut self, a: Address, b: Address) -> Result<(), StateError> {
-        self.combine(Pile::build, |g, z| g.replace(a, z), (a, b))
+        let mut frames = self.moving(b, Destination::Pile(a));
+        self.combine(Pile::build, |g, z| g.replace(a, z), (a, b))?;
+        self.keyframes.append(&mut frames);
+        Ok(())
     }
 
     /// Group two piles from two addresses
     pub fn group(&mut self, a: Address, b: Address) -> Result<(), StateError> {
-        self.combine(Pile::group, |g, z| g.replace(a, z), (a, b))
+        let mut frames = self.moving(b, Destination::Pile(a));
+        let faces = self.rules.face_pairs_only;
+        let reduce = |x: &mut Pile, y: &mut Pile| {
+            if faces {
+                Pile::face_groupable(x)?;
+            }
+            Pile::group(x, y)
+        };
+        self.combine(reduce, |g, z| g.replace(a, z), (a, b))?;
+        self.keyframes.append(&mut frames);
+        Ok(())
+    }
+
+    /// Gather a pile into another with the same value, to be captured together
+    pub fn gather(&mut self, a: Address, b: Address) -> Result<(), StateError> {
+        let mut frames = self.moving(b, Destination::Pile(a));
+        let faces = self.rules.face_pairs_only;
+        let reduce = |x: &mut Pile, y: &mut Pile| {
+            if faces {
+                Pile::face_groupable(x)?;
+            }
+            Pile::gather(x, y)
+        };
+        self.combine(reduce, |g, z| g.replace(a, z), (a, b))?;
+        self.keyframes.append(&mut frames);
+        Ok(())
     }
 
     /// Pair a pile with a capturing card
     pub fn pair(&mut self, a: Address, b: Address) -> Result<(), StateError> {
+        let value = self.get(a).ok_or(StateError::InvalidAddress)?.value;
+        if self.captures_all(value) {
+            if let Some(j) = (0..self.floor.len()).find(|&j| {
+                let x = &self.floor[j];
+                a != Address::Floor(j as u8) && !x.is_empty() && x.value == value
+            }) {
+                return Err(StateError::UncapturedPile(Address::Floor(j as u8)));
+            }
+        }
+        let mut frames = self.moving(a, Destination::Captured);
+        frames.append(&mut self.moving(b, Destination::Captured));
+        let faces = self.rules.face_pairs_only;
+        let reduce = |x: &mut Pile, y: &mut Pile| {
+            if faces {
+                Pile::face_pairable(x)?;
+            }
+            Pile::pair(x, y)
+        };
         let res = self.combine(
-            Pile::pair,
+            reduce,
             |g, z| {
                 g.player_mut().pairs.push(z);
                 Ok(())
@@ -319,6 +901,7 @@ impl State {
         );
         if res.is_ok() {
             self.last_score = self.turn;
+            self.keyframes.append(&mut frames);
         }
         res
     }
@@ -331,14 +914,51 @@ impl State {
             .count()
     }
 
+    /// Get the most stacked piles a player may own at once
+    ///
+    /// Under the multi-build variant every owned pile needs its own card in hand to capture it.
+    pub fn stack_limit(&self, id: PlayerId) -> usize {
+        if self.rules.multi_build {
+            self.seat(id).hand.iter().filter(|x| !x.is_empty()).count()
+        } else {
+            1
+        }
+    }
+
+    /// Get the owned floor piles the current player can no longer capture
+    pub fn orphan_risk(&self) -> Vec<Orphan> {
+        self.floor
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.cards.len() > 1 && x.owner == self.turn)
+            .filter(|(_, x)| !self.player().hand.iter().any(|y| y.value == x.value))
+            .map(|(i, x)| Orphan {
+                pile: Address::Floor(i as u8),
+                value: x.value,
+            })
+            .collect()
+    }
+
     /// Make sure a turn results in a valid game state
     pub fn validate_turn(&self, destination: Address, pair: bool) -> Result<(), StateError> {
-        let (piles, i) = self.pile(destination);
-        if self.stacks() > 1 {
+        let value = self
+            .get(destination)
+            .ok_or(StateError::InvalidAddress)?
+            .value;
+        let orphan = if !pair && !self.player().hand.iter().any(|x| x.value == value) {
+            Some(Orphan {
+                pile: destination,
+                value,
+            })
+        } else {
+            // A capture can't use up the last card an owned pile is waiting for either
+            self.orphan_risk().first().copied()
+        };
+        if self.stacks() > self.stack_limit(self.turn) {
             Err(StateError::OwnTooManyPiles)
-        } else if !pair && !self.player().hand.iter().any(|x| x.value == piles[i].value) {
-            Err(StateError::UnpairablePileValue)
-        } else if !self.unique_floor() {
+        } else if let Some(o) = orphan {
+            Err(StateError::UnpairablePileValue(o))
+        } else if !self.floor_allowed() {
             Err(StateError::DuplicateFloorValue)
         } else {
             Ok(())
@@ -347,7 +967,29 @@ impl State {
 
     /// Apply a move to the game state
     pub fn apply(&mut self, m: Move) -> Result<(), StateError> {
+        #[cfg(test)]
+        if INJECT_PANIC.with(|x| x.replace(false)) {
+            panic!("injected fault");
+        }
+        self.keyframes.clear();
+        self.simple = OnceLock::new();
         m.is_valid()?;
+        if !m
+            .actions
+            .iter()
+            .chain(m.builds.iter().flatten())
+            .all(|x| self.has_address(x.address))
+        {
+            return Err(StateError::InvalidAddress);
+        }
+        if !m.builds.is_empty() && !self.rules.multi_build {
+            return Err(StateError::MultipleBuildsNotAllowed);
+        }
+        for b in m.builds.iter() {
+            for w in b.windows(2).rev() {
+                self.build(w[0].address, w[1].address)?;
+            }
+        }
         let mut builds = vec![];
         for w in m.actions.windows(2).rev() {
             match w[1].operation {
@@ -364,90 +1006,845 @@ impl State {
         for (i, b) in builds.iter().rev().enumerate() {
             if i == builds.len() - 1 && pair {
                 self.pair(destination, b.to_owned())?;
+            } else if pair && self.rules.capture_all {
+                self.gather(destination, b.to_owned())?;
             } else {
                 self.group(destination, b.to_owned())?;
             }
         }
+        let mut target = destination;
         if let Address::Hand(_) = destination {
             if !pair {
+                let stacked = self.get(destination).is_some_and(|x| x.cards.len() > 1);
                 self.collapse_floor();
                 self.discard(destination)?;
+                // A build or group put together in the hand is checked where it lands
+                if stacked {
+                    target = self.address_of(self.last_pile_id).unwrap_or(destination);
+                }
             }
         }
-        self.validate_turn(destination, pair)?;
+        self.validate_turn(target, pair)?;
         self.collapse_floor();
+        // Clearing the floor with a capture is a suipi
+        if pair && self.floor_count() == 0 {
+            self.player_mut().suipi_count += 1;
+        }
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::action::{Action, Address, Operation};
-    use crate::card::{Suit, Value};
-    use crate::pile::Mark;
-    use crate::rng::Rng;
+    /// Check if capturing piles of the given value has to take every one of them
+    ///
+    /// Face cards are exempt when they may only be paired one for one.
+    fn captures_all(&self, value: u8) -> bool {
+        self.rules.capture_all && !(self.rules.face_pairs_only && value > MAX_BUILD_VALUE)
+    }
 
-    /// Setup an initial game state
-    fn setup() -> State {
-        let mut rng = Rng::from_seed([0; 32]);
-        let mut g = State::default();
-        g.init_deck();
-        g.shuffle_deck(rng.rng_borrow_mut());
-        g.deal_hands();
-        g.deal_floor();
-        g
+    /// Add every other floor pile with the captured value to a capture, as the capture all rule
+    /// requires
+    ///
+    /// Moves other than a capture of floor piles with a hand card are returned as they are.
+    pub fn complete_capture(&self, mut m: Move) -> Move {
+        let (Some(first), Some(last)) = (m.actions.first(), m.actions.last()) else {
+            return m;
+        };
+        let (Address::Floor(_), Address::Hand(h)) = (first.address, last.address) else {
+            return m;
+        };
+        if first.operation != Operation::Active
+            || m.actions[1..]
+                .iter()
+                .any(|x| x.operation != Operation::Passive)
+        {
+            return m;
+        }
+        let Some(value) = self.player().hand.get(h as usize).map(|x| x.value) else {
+            return m;
+        };
+        if !self.captures_all(value) {
+            return m;
+        }
+        let hand = m.actions.pop().unwrap();
+        for (j, x) in self.floor.iter().enumerate() {
+            let a = Address::Floor(j as u8);
+            if !x.is_empty() && x.value == value && !m.actions.iter().any(|y| y.address == a) {
+                m.actions.push(Action {
+                    operation: Operation::Passive,
+                    address: a,
+                });
+            }
+        }
+        m.actions.push(hand);
+        m
     }
 
-    /// Helper for populating a pile with a pair
-    fn pair(xs: Vec<Card>, v: Value) -> Pile {
-        Pile::new(xs, v as u8, Mark::Pair)
+    /// Apply a move on behalf of the given player, rejecting it if it is not their turn
+    pub fn apply_for(&mut self, p: PlayerId, m: Move) -> Result<(), StateError> {
+        if p != self.turn {
+            Err(StateError::NotYourTurn)
+        } else {
+            self.apply(m)
+        }
     }
 
-    /// Helper for populating a pile with a group
-    fn group(xs: Vec<Card>, v: Value) -> Pile {
-        Pile::new(xs, v as u8, Mark::Group)
+    /// Get which action ids are legal for the current player, indexed by id
+    pub fn action_mask(&self) -> Vec<bool> {
+        (0..ACTION_SPACE_SIZE)
+            .map(|id| Move::from_action_id(id, self).is_some())
+            .collect()
     }
 
-    /// Helper for populating a pile with a build
-    fn build(xs: Vec<Card>, v: Value) -> Pile {
-        Pile::new(xs, v as u8, Mark::Build)
+    /// Check if a move would be accepted in the current state
+    fn accepts(&self, annotation: &str) -> bool {
+        match Annotation::new(String::from(annotation)).to_move() {
+            Ok(m) => self.clone().apply(m).is_ok(),
+            Err(_) => false,
+        }
     }
 
-    /// Helper for populating a pile with a single
-    fn single(v: Value, s: Suit) -> Pile {
-        Pile::single(Card::create(v, s))
+    /// Work out every simple move the current player could make
+    fn find_simple_moves(&self) -> Vec<String> {
+        let mut moves = vec![];
+        for (i, h) in self.player().hand.iter().enumerate() {
+            if h.is_empty() {
+                continue;
+            }
+            let i = Address::Hand(i as u8);
+            moves.push(format!("!{}", i));
+            for (j, f) in self.floor.iter().enumerate() {
+                if f.is_empty() {
+                    continue;
+                }
+                let j = Address::Floor(j as u8);
+                moves.push(format!("*{}&{}", j, i));
+                moves.push(format!("{}+{}", j, i));
+                moves.push(format!("{}&{}", j, i));
+            }
+        }
+        moves
+            .into_iter()
+            .filter_map(|x| {
+                let m = Annotation::new(x.to_string()).to_move().ok()?;
+                if self.rules.capture_all {
+                    // Captures have to take every matching pile
+                    let m = self.complete_capture(m);
+                    self.clone().apply(m.clone()).ok()?;
+                    return Some(m.to_annotation());
+                }
+                self.clone().apply(m).ok()?;
+                Some(x)
+            })
+            .collect()
     }
 
-    /// Helper for getting an empty pile
-    fn empty() -> Pile {
-        Pile::empty()
+    /// Get the annotations for every simple move the current player could make
+    ///
+    /// The moves are worked out once and kept until a move is applied or hands are dealt. If the
+    /// state's fields were changed directly since then, they are worked out again each time,
+    /// until `forget_simple_moves` is called.
+    pub fn simple_moves(&self) -> Cow<'_, [String]> {
+        let x = self.simple.get_or_init(|| SimpleMoves {
+            turn: self.turn,
+            hand: self.player().hand.clone(),
+            floor: self.floor.clone(),
+            rules: self.rules.clone(),
+            moves: self.find_simple_moves(),
+        });
+        if x.turn == self.turn
+            && x.hand == self.player().hand
+            && x.floor == self.floor
+            && x.rules == self.rules
+        {
+            Cow::Borrowed(&x.moves)
+        } else {
+            Cow::Owned(self.find_simple_moves())
+        }
     }
 
-    #[test]
-    fn test_state_setup() {
-        let g = setup();
+    /// Check if a move would be accepted, looking in the simple moves first
+    pub fn is_legal(&self, annotation: &str) -> bool {
+        self.simple_moves().iter().any(|x| x == annotation) || self.accepts(annotation)
+    }
 
-        assert_eq!(
-            g.opponent,
-            Player::new(vec![
-                single(Value::Ace, Suit::Hearts),
-                single(Value::King, Suit::Clubs),
-                single(Value::Two, Suit::Diamonds),
-                single(Value::Ace, Suit::Clubs),
-                single(Value::Seven, Suit::Clubs),
-                single(Value::Eight, Suit::Spades),
-                single(Value::King, Suit::Hearts),
-                single(Value::Three, Suit::Spades),
-            ])
-        );
+    /// Drop the simple moves kept for the current player, so they are worked out again
+    pub fn forget_simple_moves(&mut self) {
+        self.simple = OnceLock::new();
+    }
 
-        assert_eq!(
-            g.dealer,
-            Player::new(vec![
-                single(Value::Ten, Suit::Diamonds),
-                single(Value::Four, Suit::Hearts),
-                single(Value::Ten, Suit::Spades),
+    /// Get every floor pile the given hand card could legally pair with, build on, or group into
+    pub fn targets_for(&self, hand_index: usize) -> impl Iterator<Item = CaptureOption> + '_ {
+        let card = self.player().hand.get(hand_index).filter(|x| !x.is_empty());
+        let h = Address::Hand(hand_index as u8);
+        self.floor
+            .iter()
+            .enumerate()
+            .filter(move |(_, f)| card.is_some() && !f.is_empty())
+            .flat_map(move |(j, f)| {
+                let t = Address::Floor(j as u8);
+                let v = card.map_or(0, |x| x.value);
+                [
+                    (TargetKind::Pair, f.value, format!("*{}&{}", t, h)),
+                    (TargetKind::Build, f.value + v, format!("{}+{}", t, h)),
+                    (TargetKind::Group, f.value, format!("{}&{}", t, h)),
+                ]
+                .into_iter()
+                .map(move |(kind, value, annotation)| CaptureOption {
+                    target: t,
+                    piles: vec![t],
+                    kind,
+                    value,
+                    annotation,
+                })
+            })
+            .filter(|x| self.is_legal(&x.annotation))
+    }
+
+    /// Get every legal capture the given hand card could make, of a single floor pile or of
+    /// several piles that share its value or add up to it
+    pub fn captures_for(&self, hand_index: usize) -> Vec<CaptureOption> {
+        let Some(card) = self.player().hand.get(hand_index).filter(|x| !x.is_empty()) else {
+            return vec![];
+        };
+        let h = Address::Hand(hand_index as u8);
+        self.legal_moves()
+            .into_iter()
+            .filter(|m| m.builds.is_empty() && m.actions.iter().any(|a| a.address == h))
+            .filter(|m| m.actions.first().map(|a| a.operation) == Some(Operation::Active))
+            .filter_map(|m| {
+                let piles = m
+                    .actions
+                    .iter()
+                    .map(|a| a.address)
+                    .filter(|&x| x != h)
+                    .collect::<Vec<Address>>();
+                Some(CaptureOption {
+                    target: *piles.first()?,
+                    piles,
+                    kind: TargetKind::Pair,
+                    value: card.value,
+                    annotation: m.to_annotation(),
+                })
+            })
+            .collect()
+    }
+
+    /// Suggest a legal move that merges a hand card into the floor pile with the same value
+    ///
+    /// Use this to offer a fix when discarding the card is rejected as a duplicate floor value.
+    /// Grouping is preferred, since it leaves the card on the floor like the discard would have.
+    pub fn merge_suggestion(&self, hand_index: usize) -> Option<String> {
+        let v = self.player().hand.get(hand_index)?.value;
+        let options = self
+            .targets_for(hand_index)
+            .filter(|x| x.kind != TargetKind::Build && x.value == v)
+            .collect::<Vec<CaptureOption>>();
+        options
+            .iter()
+            .find(|x| x.kind == TargetKind::Group)
+            .or(options.first())
+            .map(|x| x.annotation.clone())
+    }
+
+    /// Get every legal way to merge a hand card with the floor piles that have its value
+    ///
+    /// Where a duplicate floor value could be resolved more than one way, this lets the player
+    /// pick the grouping instead of taking `merge_suggestion`. Groups that leave a pile on the
+    /// floor come before captures.
+    pub fn merge_options(&self, hand_index: usize) -> Vec<MergeOption> {
+        let Some(card) = self.player().hand.get(hand_index).filter(|x| !x.is_empty()) else {
+            return vec![];
+        };
+        let h = Address::Hand(hand_index as u8);
+        let matching = (0..self.floor.len())
+            .filter(|&j| !self.floor[j].is_empty() && self.floor[j].value == card.value)
+            .map(|j| Address::Floor(j as u8))
+            .collect::<Vec<Address>>();
+        let mut options: Vec<MergeOption> = vec![];
+        for set in 1..1usize << matching.len() {
+            let piles = (0..matching.len())
+                .filter(|k| set & 1 << k != 0)
+                .map(|k| matching[k])
+                .collect::<Vec<Address>>();
+            let group = piles
+                .iter()
+                .chain([&h])
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join("&");
+            for x in [group.clone(), format!("*{}", group)] {
+                let Ok(mut m) = Annotation::new(x).to_move() else {
+                    continue;
+                };
+                if self.rules.capture_all {
+                    m = self.complete_capture(m);
+                }
+                let piles = m
+                    .actions
+                    .iter()
+                    .map(|a| a.address)
+                    .filter(|&x| x != h)
+                    .collect::<Vec<Address>>();
+                let mut next = self.clone();
+                let annotation = m.to_annotation();
+                if options.iter().any(|o| o.annotation == annotation) || next.apply(m).is_err() {
+                    continue;
+                }
+                let result = next
+                    .floor
+                    .iter()
+                    .find(|f| f.cards.contains(&card.cards[0]))
+                    .cloned();
+                options.push(MergeOption {
+                    annotation,
+                    piles,
+                    result,
+                });
+            }
+        }
+        options.sort_by_key(|x| x.result.is_none());
+        options
+    }
+
+    /// Summarize what the given player's hand could do if it were their turn
+    pub fn hand_summary(&self, p: PlayerId) -> HandSummary {
+        let mut s = self.clone();
+        s.turn = p;
+        s.forget_simple_moves();
+        let options = (0..s.player().hand.len())
+            .flat_map(|i| s.targets_for(i).collect::<Vec<CaptureOption>>())
+            .collect::<Vec<CaptureOption>>();
+        let mut summary = HandSummary::default();
+        for x in options {
+            match x.kind {
+                TargetKind::Pair if !summary.captures.contains(&x.target) => {
+                    summary.captures.push(x.target)
+                }
+                TargetKind::Build if !summary.builds.contains(&x.value) => {
+                    summary.builds.push(x.value)
+                }
+                _ => (),
+            }
+        }
+        summary.captures.sort_by_key(|x| x.to_string());
+        summary.builds.sort();
+        let cards = s.player().hand.iter().flat_map(|x| x.cards.first());
+        summary.point_cards = cards
+            .clone()
+            .filter(|c| c.is_point_card())
+            .copied()
+            .collect();
+        summary.spades = cards.filter(|c| c.suit == Suit::Spades as u8).count();
+        summary
+    }
+
+    /// For each of the given player's hand cards, get the builds they could make this turn
+    /// with their other cards that total its value
+    pub fn buildable_totals(&self, p: PlayerId) -> Vec<Vec<BuildOption>> {
+        let mut s = self.clone();
+        s.turn = p;
+        s.forget_simple_moves();
+        let floor = (0..s.floor.len())
+            .filter(|&j| !s.floor[j].is_empty())
+            .collect::<Vec<usize>>();
+        let hand = &s.player().hand;
+        hand.iter()
+            .enumerate()
+            .map(|(t, target)| {
+                let mut options = vec![];
+                if target.is_empty() {
+                    return options;
+                }
+                for (i, h) in hand.iter().enumerate() {
+                    if h.is_empty() || i == t || h.value >= target.value {
+                        continue;
+                    }
+                    let card = Address::Hand(i as u8);
+                    let mut combos = vec![];
+                    s.floor_sums(&floor, target.value - h.value, &mut vec![], &mut combos);
+                    for piles in combos {
+                        let annotation = piles
+                            .iter()
+                            .chain([&card])
+                            .map(|x| x.to_string())
+                            .collect::<Vec<String>>()
+                            .join("+");
+                        if s.accepts(&annotation) {
+                            options.push(BuildOption {
+                                card,
+                                piles,
+                                annotation,
+                            });
+                        }
+                    }
+                }
+                options
+            })
+            .collect()
+    }
+
+    /// Get every move the current player could make
+    ///
+    /// As well as the simple moves from `simple_moves`, this has captures of several floor piles
+    /// at once, builds and groups of several piles, and under the multiple builds rule, extra
+    /// floor builds made first. Moves are put together from the same steps `apply` takes, and
+    /// each one is checked by applying it to a copy of the state, so every move returned will
+    /// succeed. Moves that leave the game in the same position are only listed once.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut gen = MoveGen {
+            root: self,
+            seen: HashSet::new(),
+            moves: vec![],
+        };
+        for x in self.simple_moves().iter() {
+            if let Ok(m) = Annotation::new(x.clone()).to_move() {
+                gen.offer(&[], m.actions);
+            }
+        }
+        gen.search(self, vec![], None);
+        gen.moves
+    }
+
+    /// Get the move from `legal_moves` that leads to the same position as the given move
+    pub fn listed_move(&self, m: &Move) -> Option<Move> {
+        let after = |x: &Move| {
+            let mut s = self.clone();
+            s.apply(x.clone()).ok().map(|_| s.position_key())
+        };
+        let key = after(m)?;
+        self.legal_moves()
+            .into_iter()
+            .find(|x| after(x).as_ref() == Some(&key))
+    }
+
+    /// Get a key that is the same for two states exactly when they are the same position
+    ///
+    /// Floor order, pile ids, and the order of cards within a pile are left out.
+    fn position_key(&self) -> Vec<Vec<u8>> {
+        let pile = |x: &Pile| {
+            let mut ids = x.cards.iter().map(|&c| u8::from(c)).collect::<Vec<u8>>();
+            ids.sort_unstable();
+            [vec![x.mark as u8, x.value, x.owner as u8], ids].concat()
+        };
+        let mut floor = self
+            .floor
+            .iter()
+            .filter(|x| !x.is_empty())
+            .map(pile)
+            .collect::<Vec<Vec<u8>>>();
+        floor.sort();
+        let mut key = vec![vec![self.turn as u8, self.last_score as u8]];
+        key.append(&mut floor);
+        for p in [&self.dealer, &self.opponent] {
+            key.push(vec![u8::MAX, p.suipi_count]);
+            key.extend(p.hand.iter().chain(p.pairs.iter()).map(pile));
+        }
+        key
+    }
+
+    /// Order the piles of a build so the last two are not single cards of the same value
+    ///
+    /// Builds are put together from the end, so only the last two piles are checked against
+    /// each other. Fails if every pile is a single card of the same value.
+    fn order_chunk(&self, mut xs: Vec<Address>) -> Option<Vec<Address>> {
+        let n = xs.len();
+        let twins = |a: Address, b: Address| match (self.get(a), self.get(b)) {
+            (Some(x), Some(y)) => x.is_single() && y.is_single() && x.value == y.value,
+            _ => false,
+        };
+        if n > 1 && twins(xs[n - 2], xs[n - 1]) {
+            let k = (0..n - 2).find(|&k| !twins(xs[k], xs[n - 1]))?;
+            xs.swap(k, n - 2);
+        }
+        Some(xs)
+    }
+
+    /// Get every floor pile, or build of several floor piles, with the given value
+    fn chunks(&self, floor: &[usize], total: u8) -> Vec<Vec<Address>> {
+        let mut sums = vec![];
+        self.floor_sums(floor, total, &mut vec![], &mut sums);
+        sums.into_iter()
+            .filter(|xs| {
+                xs.len() == 1
+                    || total <= MAX_BUILD_VALUE
+                        && xs
+                            .iter()
+                            .all(|&a| self.get(a).is_some_and(|x| Pile::buildable(x).is_ok()))
+            })
+            .filter_map(|xs| self.order_chunk(xs))
+            .collect()
+    }
+
+    /// Order chunks that are grouped or captured together
+    ///
+    /// The first two chunks are grouped first, and two single cards can't be grouped, so a
+    /// chunk that isn't a single card is moved up to second when the first one is.
+    fn order_chunks(&self, mut chunks: Vec<Vec<Address>>) -> Vec<Vec<Address>> {
+        let single =
+            |c: &Vec<Address>| c.len() == 1 && self.get(c[0]).is_some_and(|x| x.is_single());
+        if chunks.len() > 2 && single(&chunks[0]) && single(&chunks[1]) {
+            if let Some(k) = (2..chunks.len()).find(|&k| !single(&chunks[k])) {
+                chunks.swap(1, k);
+            }
+        }
+        chunks
+    }
+
+    /// Get every set of two or more floor piles that could be built into a pile of the given
+    /// value, from 1 up to `MAX_BUILD_VALUE`
+    ///
+    /// Each set is listed once, with its piles in floor order. Groups can't be built on, and
+    /// neither can singles that all have the same value.
+    pub fn build_sets(&self, target: u8) -> Vec<Vec<Address>> {
+        if target > MAX_BUILD_VALUE {
+            return vec![];
+        }
+        let floor = (0..self.floor.len())
+            .filter(|&j| !self.floor[j].is_empty() && Pile::buildable(&self.floor[j]).is_ok())
+            .collect::<Vec<usize>>();
+        let mut sets = vec![];
+        self.floor_sums(&floor, target, &mut vec![], &mut sets);
+        sets.retain(|set| {
+            let piles = set
+                .iter()
+                .filter_map(|a| match a {
+                    Address::Floor(j) => self.floor.get(*j as usize),
+                    Address::Hand(_) => None,
+                })
+                .collect::<Vec<&Pile>>();
+            set.len() > 1
+                && !piles
+                    .iter()
+                    .all(|x| x.is_single() && x.value == piles[0].value)
+        });
+        sets
+    }
+
+    /// Find the sets of floor piles, taken in order, whose values add up to the given total
+    fn floor_sums(
+        &self,
+        floor: &[usize],
+        total: u8,
+        current: &mut Vec<Address>,
+        out: &mut Vec<Vec<Address>>,
+    ) {
+        for (k, &j) in floor.iter().enumerate() {
+            let v = self.floor[j].value;
+            if v > total {
+                continue;
+            }
+            current.push(Address::Floor(j as u8));
+            if v == total {
+                out.push(current.clone());
+            } else {
+                self.floor_sums(&floor[k + 1..], total - v, current, out);
+            }
+            current.pop();
+        }
+    }
+
+    /// Get the cards the given player has not seen, which are in the deck or the other hand
+    ///
+    /// These are the cards of a full deck that are not in the player's hand, on the floor, or
+    /// captured, so the player's redacted view gives the same cards as the full state.
+    pub fn unseen_by(&self, p: PlayerId) -> Vec<Card> {
+        let mut seen = [false; DECK_SIZE];
+        let piles = (self.seat(p).hand.iter())
+            .chain(self.floor.iter())
+            .chain(self.dealer.pairs.iter())
+            .chain(self.opponent.pairs.iter());
+        for c in piles.flat_map(|x| x.cards.iter()) {
+            if let Some(x) = seen.get_mut(u8::from(*c) as usize) {
+                *x = true;
+            }
+        }
+        (0..DECK_SIZE)
+            .filter(|&i| !seen[i])
+            .map(|i| Card::from(i as u8))
+            .collect()
+    }
+
+    /// Count the cards of each value the given player has not seen, indexed by value
+    pub fn unseen_values(&self, p: PlayerId) -> [u8; VALUE_SLOTS] {
+        let mut counts = [0; VALUE_SLOTS];
+        for c in self.unseen_by(p) {
+            counts[c.value as usize] += 1;
+        }
+        counts
+    }
+
+    /// Estimate how likely the other player is to capture a hand card if it is discarded
+    ///
+    /// Any unseen card could be in the other player's hand. A card threatens the discard if it
+    /// pairs with it, or with a build of it and one other floor pile. Returns nothing if the
+    /// card cannot be discarded.
+    pub fn discard_risk(&self, hand_index: usize) -> Option<DiscardRisk> {
+        let v = self.player().hand.get(hand_index)?.value;
+        if !self.accepts(&format!("!{}", hand_index + 1)) {
+            return None;
+        }
+        let mut values = vec![v];
+        for f in self.floor.iter().filter(|x| Pile::buildable(x).is_ok()) {
+            if v + f.value <= MAX_BUILD_VALUE {
+                values.push(v + f.value);
+            }
+        }
+        let unseen = self.unseen_by(self.turn);
+        let threats = unseen
+            .iter()
+            .filter(|c| values.contains(&c.value))
+            .copied()
+            .collect::<Vec<Card>>();
+        // Chance that none of the other player's cards are threats, drawing without replacement
+        let (n, t) = (unseen.len(), threats.len());
+        let safe = (0..self.seat(self.turn.other()).card_count().min(n))
+            .map(|i| (n - t).saturating_sub(i) as f64 / (n - i) as f64)
+            .product::<f64>();
+        Some(DiscardRisk {
+            threats,
+            chance: 1.0 - safe,
+        })
+    }
+
+    /// Check that a floor pile's cards are consistent with its mark and value
+    fn well_formed(p: &Pile) -> bool {
+        !p.is_pair() && p.verify().is_ok()
+    }
+
+    /// Get a copy of the state that only shows what the given player is allowed to see
+    ///
+    /// The other player's hand cards and the deck order are replaced by hidden cards, keeping
+    /// their counts.
+    pub fn redacted_for(&self, p: PlayerId) -> State {
+        let mut s = self.redacted();
+        s.seat_mut(p).hand = self.seat(p).hand.clone();
+        s
+    }
+
+    /// Get a copy of the state for spectators, with both hands and the deck hidden
+    pub fn redacted(&self) -> State {
+        let hide = |xs: &mut Vec<Pile>| {
+            xs.iter_mut()
+                .filter(|x| !x.is_empty())
+                .for_each(|x| *x = Pile::single(Card::invalid()));
+        };
+        let mut s = self.clone();
+        hide(&mut s.dealer.hand);
+        hide(&mut s.opponent.hand);
+        s.deck.iter_mut().for_each(|c| *c = Card::invalid());
+        // The simple moves kept for the current player were worked out from their real hand
+        s.forget_simple_moves();
+        s
+    }
+
+    /// Get the location of every card, indexed by card id
+    fn locations(&self) -> [Option<Location>; DECK_SIZE] {
+        let mut locations = [None; DECK_SIZE];
+        let mut set = |c: &Card, l: Location| {
+            if let Some(x) = locations.get_mut(u8::from(*c) as usize) {
+                *x = Some(l);
+            }
+        };
+        self.deck.iter().for_each(|c| set(c, Location::Deck));
+        for p in self.floor.iter() {
+            p.cards.iter().for_each(|c| set(c, Location::Floor(p.id)));
+        }
+        for id in [PlayerId::Opponent, PlayerId::Dealer] {
+            for (i, p) in self.seat(id).hand.iter().enumerate() {
+                p.cards
+                    .iter()
+                    .for_each(|c| set(c, Location::Hand(id, i as u8)));
+            }
+            for p in self.seat(id).pairs.iter() {
+                p.cards.iter().for_each(|c| set(c, Location::Pairs(id)));
+            }
+        }
+        locations
+    }
+
+    /// Describe what changed between an earlier state and this one
+    ///
+    /// Floor piles are matched by id, so this works across any number of missed moves.
+    pub fn diff(&self, before: &State) -> Vec<Change> {
+        let mut changes = vec![];
+        for (i, (from, to)) in before.locations().iter().zip(self.locations()).enumerate() {
+            if let (Some(from), Some(to)) = (*from, to) {
+                if from != to {
+                    changes.push(Change::CardMoved {
+                        card: Card::from(i as u8),
+                        from,
+                        to,
+                    });
+                }
+            }
+        }
+        let piles = |s: &State| {
+            s.floor
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| !p.is_empty())
+                .map(|(i, p)| (p.id, i, p.owner))
+                .collect::<Vec<(u16, usize, PlayerId)>>()
+        };
+        let (old, new) = (piles(before), piles(self));
+        for &(id, _, _) in old.iter() {
+            if !new.iter().any(|x| x.0 == id) {
+                changes.push(Change::PileRemoved { id });
+            }
+        }
+        for &(id, i, owner) in new.iter() {
+            match old.iter().find(|x| x.0 == id) {
+                None => changes.push(Change::PileAdded {
+                    id,
+                    address: Address::Floor(i as u8),
+                }),
+                Some(x) if x.2 != owner => changes.push(Change::OwnerChanged { id, owner }),
+                Some(_) => {}
+            }
+        }
+        let (a, b) = (Score::from(before), Score::from(self));
+        for player in [PlayerId::Opponent, PlayerId::Dealer] {
+            let (before, after) = (a.total(player), b.total(player));
+            if before != after {
+                changes.push(Change::ScoreChanged {
+                    player,
+                    before,
+                    after,
+                });
+            }
+        }
+        changes
+    }
+
+    /// Verify the state invariants that every legal sequence of moves must preserve
+    pub fn audit(&self) -> Result<(), AuditError> {
+        let mut seen = [false; DECK_SIZE];
+        let cards = self
+            .deck
+            .iter()
+            .chain(self.floor.iter().flat_map(|p| p.cards.iter()))
+            .chain([&self.dealer, &self.opponent].into_iter().flat_map(|p| {
+                p.hand
+                    .iter()
+                    .chain(p.pairs.iter())
+                    .flat_map(|x| x.cards.iter())
+            }));
+        for &c in cards {
+            let id = u8::from(c) as usize;
+            if id >= DECK_SIZE || c.suit > 3 {
+                return Err(AuditError::InvalidCard(c));
+            } else if seen[id] {
+                return Err(AuditError::DuplicateCard(c));
+            }
+            seen[id] = true;
+        }
+        if let Some(id) = seen.iter().position(|&x| !x) {
+            return Err(AuditError::MissingCard(Card::from(id as u8)));
+        }
+        if self.floor.len() != self.rules.floor_size {
+            return Err(AuditError::InvalidFloorSize(self.floor.len()));
+        }
+        if let Some(i) = self.floor.iter().position(|p| !State::well_formed(p)) {
+            return Err(AuditError::InvalidFloorPile(i));
+        }
+        let mut ids = HashSet::new();
+        if let Some(i) = self
+            .floor
+            .iter()
+            .position(|p| !p.is_empty() && p.id != 0 && !ids.insert(p.id))
+        {
+            return Err(AuditError::InvalidFloorPile(i));
+        }
+        for id in [PlayerId::Dealer, PlayerId::Opponent] {
+            let p = self.seat(id);
+            if let Some(i) = p.hand.iter().position(|x| !x.is_empty() && !x.is_single()) {
+                return Err(AuditError::InvalidHandPile(id, i));
+            }
+            let stacks = self
+                .floor
+                .iter()
+                .filter(|x| x.cards.len() > 1 && x.owner == id)
+                .count();
+            if stacks > self.stack_limit(id) {
+                return Err(AuditError::OwnTooManyPiles(id));
+            }
+        }
+        if !self.floor_allowed() {
+            return Err(AuditError::DuplicateFloorValue);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::{Action, Address, Operation};
+    use crate::card::{Suit, Value};
+    use crate::consts::FLOOR_SIZE;
+    use crate::pile::Mark;
+    use crate::rng::Rng;
+    use crate::scenario::Scenario;
+
+    /// Setup an initial game state
+    fn setup() -> State {
+        let mut rng = Rng::from_seed([0; 32]);
+        let mut g = State::default();
+        g.init_deck();
+        g.shuffle_deck(rng.rng_borrow_mut());
+        g.deal_hands();
+        g.deal_floor();
+        g
+    }
+
+    /// Helper for populating a pile with a pair
+    fn pair(xs: Vec<Card>, v: Value) -> Pile {
+        Pile::new(xs, v as u8, Mark::Pair)
+    }
+
+    /// Helper for populating a pile with a group
+    fn group(xs: Vec<Card>, v: Value) -> Pile {
+        Pile::new(xs, v as u8, Mark::Group)
+    }
+
+    /// Helper for populating a pile with a build
+    fn build(xs: Vec<Card>, v: Value) -> Pile {
+        Pile::new(xs, v as u8, Mark::Build)
+    }
+
+    /// Helper for populating a pile with a single
+    fn single(v: Value, s: Suit) -> Pile {
+        Pile::single(Card::create(v, s))
+    }
+
+    /// Helper for getting an empty pile
+    fn empty() -> Pile {
+        Pile::empty()
+    }
+
+    #[test]
+    fn test_state_setup() {
+        let g = setup();
+
+        assert_eq!(
+            g.opponent,
+            Player::new(vec![
+                single(Value::Ace, Suit::Hearts),
+                single(Value::King, Suit::Clubs),
+                single(Value::Two, Suit::Diamonds),
+                single(Value::Ace, Suit::Clubs),
+                single(Value::Seven, Suit::Clubs),
+                single(Value::Eight, Suit::Spades),
+                single(Value::King, Suit::Hearts),
+                single(Value::Three, Suit::Spades),
+            ])
+        );
+
+        assert_eq!(
+            g.dealer,
+            Player::new(vec![
+                single(Value::Ten, Suit::Diamonds),
+                single(Value::Four, Suit::Hearts),
+                single(Value::Ten, Suit::Spades),
                 single(Value::Five, Suit::Spades),
                 single(Value::Three, Suit::Diamonds),
                 single(Value::Five, Suit::Clubs),
@@ -532,6 +1929,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_address_bounds() {
+        let mut g = setup();
+        g.floor.truncate(4);
+        g.opponent.hand.truncate(2);
+        assert!(g.has_address(Address::Floor(3)));
+        assert!(!g.has_address(Address::Floor(4)));
+        assert!(!g.has_address(Address::Hand(2)));
+        assert_eq!(
+            g.get(Address::Hand(1)),
+            Some(&single(Value::King, Suit::Clubs))
+        );
+        assert_eq!(g.take(Address::Floor(12)), None);
+        assert_eq!(
+            g.replace(Address::Hand(7), empty()),
+            Err(StateError::InvalidAddress)
+        );
+
+        let floor = g.floor.clone();
+        for m in [
+            vec![
+                Action::new(Operation::Active, Address::Floor(12)),
+                Action::new(Operation::Passive, Address::Hand(0)),
+            ],
+            vec![
+                Action::new(Operation::Active, Address::Floor(0)),
+                Action::new(Operation::Passive, Address::Hand(7)),
+            ],
+            vec![Action::new(Operation::Passive, Address::Hand(7))],
+        ] {
+            assert_eq!(g.apply(Move::new(m)), Err(StateError::InvalidAddress));
+        }
+        assert_eq!(g.floor, floor);
+    }
+
     #[test]
     fn test_build_method() {
         let mut g = setup();
@@ -678,4 +2110,814 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_targets_for() {
+        let g = setup();
+
+        assert_eq!(
+            g.targets_for(2).collect::<Vec<CaptureOption>>(),
+            vec![CaptureOption {
+                target: Address::Floor(2),
+                piles: vec![Address::Floor(2)],
+                kind: TargetKind::Pair,
+                value: 2,
+                annotation: String::from("*C&3"),
+            }]
+        );
+
+        assert_eq!(
+            g.targets_for(0).collect::<Vec<CaptureOption>>(),
+            vec![CaptureOption {
+                target: Address::Floor(2),
+                piles: vec![Address::Floor(2)],
+                kind: TargetKind::Build,
+                value: 3,
+                annotation: String::from("C+1"),
+            }]
+        );
+
+        // The three of spades could build a seven, but there is already a seven on the floor
+        assert_eq!(g.targets_for(7).count(), 0);
+        assert_eq!(g.targets_for(8).count(), 0);
+    }
+
+    #[test]
+    fn test_audit() {
+        let mut g = setup();
+        assert_eq!(g.audit(), Ok(()));
+
+        g.apply(Move::new(vec![
+            Action::new(Operation::Active, Address::Floor(2)),
+            Action::new(Operation::Passive, Address::Hand(2)),
+        ]))
+        .unwrap();
+        assert_eq!(g.audit(), Ok(()));
+
+        let c = g.deck.pop_front().unwrap();
+        assert_eq!(g.audit(), Err(AuditError::MissingCard(c)));
+
+        g.deck.push_back(c);
+        g.deck.push_back(c);
+        assert_eq!(g.audit(), Err(AuditError::DuplicateCard(c)));
+
+        g.deck.pop_back();
+        g.floor[1].value = 3;
+        assert_eq!(g.audit(), Err(AuditError::InvalidFloorPile(1)));
+    }
+
+    #[test]
+    fn test_multi_build() {
+        let mut g = setup();
+        // Swap the seven of diamonds on the floor for the dealer's three of diamonds
+        g.floor[1] = single(Value::Three, Suit::Diamonds);
+        g.dealer.hand[4] = single(Value::Seven, Suit::Diamonds);
+        let m = || Annotation::new(String::from("A+B;C+1")).to_move().unwrap();
+
+        assert_eq!(
+            g.clone().apply(m()),
+            Err(StateError::MultipleBuildsNotAllowed)
+        );
+
+        g.rules.multi_build = true;
+        assert!(g.apply(m()).is_ok());
+        assert_eq!(
+            g.floor[..3],
+            [
+                build(
+                    vec![
+                        Card::create(Value::Four, Suit::Clubs),
+                        Card::create(Value::Three, Suit::Diamonds),
+                    ],
+                    Value::Seven
+                ),
+                build(
+                    vec![
+                        Card::create(Value::Two, Suit::Spades),
+                        Card::create(Value::Ace, Suit::Hearts),
+                    ],
+                    Value::Three
+                ),
+                single(Value::Eight, Suit::Clubs),
+            ]
+        );
+        assert_eq!(g.stacks(), 2);
+        assert_eq!(g.audit(), Ok(()));
+
+        // Every build must still be pairable from the player's hand
+        let mut g = setup();
+        g.rules.multi_build = true;
+        assert_eq!(
+            g.apply(Annotation::new(String::from("A+C;D+1")).to_move().unwrap()),
+            Err(StateError::UnpairablePileValue(Orphan {
+                pile: Address::Floor(3),
+                value: 9
+            }))
+        );
+
+        // Each build needs its own card in hand, even when the values match
+        let text = "rules: multi_build=true unique_floor=false\nfloor: 2C 5D 3H 6S\nhand: 7C 4D";
+        let m = || Annotation::new(String::from("A+B;C+2")).to_move().unwrap();
+        let s = Scenario::parse(text).unwrap().state;
+        assert_eq!(s.clone().apply(m()), Err(StateError::OwnTooManyPiles));
+        let mut s = Scenario::parse(&format!("{} 7H", text)).unwrap().state;
+        assert!(s.apply(m()).is_ok());
+        assert_eq!(s.stacks(), 2);
+        assert_eq!(s.audit(), Ok(()));
+    }
+
+    #[test]
+    fn test_pile_ids() {
+        let mut g = setup();
+        let ids = g.floor.iter().map(|x| x.id).collect::<Vec<u16>>();
+        assert_eq!(ids[..5], [1, 2, 3, 4, 0]);
+
+        // Capturing the two of spades shifts the eight of clubs down without changing its id
+        g.apply(Annotation::new(String::from("*C&3")).to_move().unwrap())
+            .unwrap();
+        assert_eq!(g.address_of(3), None);
+        assert_eq!(g.address_of(4), Some(Address::Floor(2)));
+
+        // Discarded cards get a new id
+        g.apply(Annotation::new(String::from("!8")).to_move().unwrap())
+            .unwrap();
+        assert_eq!(g.address_of(5), Some(Address::Floor(3)));
+        assert_eq!(g.address_of(0), None);
+
+        // Builds keep the id of the floor pile they were built on
+        g.turn = PlayerId::Dealer;
+        g.apply(Annotation::new(String::from("A+7")).to_move().unwrap())
+            .unwrap();
+        assert_eq!(g.address_of(1), Some(Address::Floor(0)));
+        assert_eq!(g.floor[0].value, 10);
+
+        assert_eq!(g.audit(), Ok(()));
+        g.floor[1].id = 1;
+        assert_eq!(g.audit(), Err(AuditError::InvalidFloorPile(1)));
+
+        // Ids wrap around once they run out, without reusing one still on the floor
+        let mut g = setup();
+        g.last_pile_id = u16::MAX;
+        g.apply(Annotation::new(String::from("!1")).to_move().unwrap())
+            .unwrap();
+        assert_eq!(g.address_of(5), Some(Address::Floor(4)));
+        assert_eq!(g.audit(), Ok(()));
+    }
+
+    #[test]
+    fn test_diff() {
+        let start = setup();
+        assert_eq!(start.diff(&start), vec![]);
+
+        let mut g = start.clone();
+        g.apply(Annotation::new(String::from("*C&3")).to_move().unwrap())
+            .unwrap();
+        g.turn = PlayerId::Dealer;
+        g.apply(Annotation::new(String::from("A+7")).to_move().unwrap())
+            .unwrap();
+        let score = Score::from(&g).total(PlayerId::Opponent);
+        assert_eq!(
+            g.diff(&start),
+            vec![
+                Change::CardMoved {
+                    card: Card::create(Value::Two, Suit::Diamonds),
+                    from: Location::Hand(PlayerId::Opponent, 2),
+                    to: Location::Pairs(PlayerId::Opponent),
+                },
+                Change::CardMoved {
+                    card: Card::create(Value::Two, Suit::Spades),
+                    from: Location::Floor(3),
+                    to: Location::Pairs(PlayerId::Opponent),
+                },
+                Change::CardMoved {
+                    card: Card::create(Value::Six, Suit::Spades),
+                    from: Location::Hand(PlayerId::Dealer, 6),
+                    to: Location::Floor(1),
+                },
+                Change::PileRemoved { id: 3 },
+                Change::OwnerChanged {
+                    id: 1,
+                    owner: PlayerId::Dealer
+                },
+                Change::ScoreChanged {
+                    player: PlayerId::Opponent,
+                    before: 0,
+                    after: score
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redacted() {
+        let mut g = setup();
+        g.apply(Annotation::new(String::from("*C&3")).to_move().unwrap())
+            .unwrap();
+
+        let r = g.redacted_for(PlayerId::Opponent);
+        assert_eq!(r.opponent, g.opponent);
+        assert_eq!(r.floor, g.floor);
+        assert_eq!(r.dealer.card_count(), 8);
+        assert_eq!(r.dealer.pairs, g.dealer.pairs);
+        assert!(r.dealer.hand.iter().all(|x| x.cards == [Card::invalid()]));
+        assert_eq!(r.deck.len(), g.deck.len());
+        assert!(r.deck.iter().all(|c| *c == Card::invalid()));
+
+        let r = g.redacted();
+        assert_eq!(r.opponent.card_count(), 7);
+        assert_eq!(r.opponent.pairs, g.opponent.pairs);
+        assert!(r
+            .opponent
+            .hand
+            .iter()
+            .all(|x| x.is_empty() || x.cards == [Card::invalid()]));
+    }
+
+    #[test]
+    fn test_redacted_forgets_simple_moves() {
+        let g = setup();
+        assert!(!g.simple_moves().is_empty());
+        let hidden = g
+            .opponent
+            .hand
+            .iter()
+            .flat_map(|x| x.cards.iter())
+            .chain(g.deck.iter())
+            .map(|c| format!("{:?}", c))
+            .collect::<Vec<String>>();
+        for r in [g.redacted_for(PlayerId::Dealer), g.redacted()] {
+            let text = format!("{:?}", r);
+            assert!(hidden.iter().all(|c| !text.contains(c)), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_orphan_risk() {
+        let mut g = setup();
+        assert!(g.orphan_risk().is_empty());
+
+        // Build the two of spades and the ace of hearts into a three
+        g.apply(Annotation::new(String::from("C+1")).to_move().unwrap())
+            .unwrap();
+        assert!(g.orphan_risk().is_empty());
+
+        // Losing the three of spades leaves the build without a matching card
+        g.opponent.hand[7] = empty();
+        assert_eq!(
+            g.orphan_risk(),
+            vec![Orphan {
+                pile: Address::Floor(2),
+                value: 3
+            }]
+        );
+        assert_eq!(
+            StateError::UnpairablePileValue(g.orphan_risk()[0]).to_string(),
+            "State Error: Un-pairable pile value - pile C needs a 3 in hand"
+        );
+    }
+
+    #[test]
+    fn test_floor_overflow() {
+        let mut g = setup();
+        // Fill the floor with one card of every value from the deck
+        while g.floor_count() < FLOOR_SIZE {
+            let i = g
+                .deck
+                .iter()
+                .position(|c| g.floor.iter().all(|x| x.value != c.value))
+                .unwrap();
+            let x = Pile::single(g.deck.remove(i).unwrap());
+            let j = g.floor_count();
+            g.floor[j] = x;
+        }
+        g.last_score = PlayerId::Dealer;
+        let m = || Annotation::new(String::from("!1")).to_move().unwrap();
+        let ace = Card::create(Value::Ace, Suit::Hearts);
+
+        let mut x = g.clone();
+        assert_eq!(x.apply(m()), Err(StateError::FloorIsFull));
+        assert_eq!(x.opponent.hand[0], Pile::single(ace));
+        // A full floor always has a pile to capture
+        let j = x.floor.iter().position(|p| p.value == ace.value).unwrap();
+        let capture = format!("*{}&1", Address::Floor(j as u8));
+        assert!(x.apply(Annotation::new(capture).to_move().unwrap()).is_ok());
+
+        g.rules.floor_overflow = FloorOverflow::AwardLastScorer;
+        assert!(g.apply(m()).is_ok());
+        assert!(g.opponent.hand[0].is_empty());
+        assert_eq!(g.floor_count(), FLOOR_SIZE);
+        assert_eq!(g.dealer.pairs.last().unwrap().cards, vec![ace]);
+        assert_eq!(g.audit(), Ok(()));
+    }
+
+    #[test]
+    fn test_simple_moves() {
+        let mut g = setup();
+        let moves = g.simple_moves().into_owned();
+        assert!(moves.contains(&String::from("*C&3")));
+        assert!(matches!(g.simple_moves(), Cow::Borrowed(_)));
+        assert!(g.is_legal("*C&3"));
+        assert!(!g.is_legal("*A&1"));
+        // Editing the state directly is noticed
+        g.opponent.hand[0] = Pile::empty();
+        assert!(matches!(g.simple_moves(), Cow::Owned(_)));
+        assert!(!g.is_legal("!1"));
+        g.forget_simple_moves();
+        assert!(matches!(g.simple_moves(), Cow::Borrowed(_)));
+
+        g.apply(Annotation::new(String::from("*C&3")).to_move().unwrap())
+            .unwrap();
+        assert!(!g.is_legal("*C&3"));
+        assert!(matches!(g.simple_moves(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_legal_moves() {
+        let mut g = setup();
+        let moves = g.legal_moves();
+        let annotations = moves
+            .iter()
+            .map(|x| x.to_annotation())
+            .collect::<Vec<String>>();
+        for x in g.simple_moves().iter() {
+            assert!(annotations.contains(x));
+        }
+        for m in moves {
+            assert!(g.clone().apply(m).is_ok());
+        }
+
+        // Take the eight of clubs, and the seven of diamonds with the ace of hearts, with the
+        // eight of spades
+        g.floor[0] = single(Value::Ace, Suit::Hearts);
+        let annotations = g
+            .legal_moves()
+            .iter()
+            .map(|x| x.to_annotation())
+            .collect::<Vec<String>>();
+        assert!(annotations.contains(&String::from("*A+B&6")));
+        assert!(annotations.contains(&String::from("*D&6")));
+        // Grouping the two eights means building the ace onto the seven first
+        assert!(!annotations.contains(&String::from("*A&D&6")));
+
+        // A build of eight can be taken together with the eight of clubs
+        g.floor[0] = Pile::new(
+            vec![
+                Card::create(Value::Three, Suit::Hearts),
+                Card::create(Value::Five, Suit::Hearts),
+            ],
+            8,
+            Mark::Build,
+        );
+        assert!(g
+            .legal_moves()
+            .iter()
+            .any(|x| x.to_annotation() == "*A&D&6"));
+    }
+
+    /// Find every legal move with up to `max` addresses by trying every annotation
+    fn brute_force_moves(s: &State, prefix: &str, max: usize) -> Vec<Move> {
+        fn orders(xs: &[String], max: usize, cur: &mut Vec<String>, out: &mut Vec<Vec<String>>) {
+            if !cur.is_empty() {
+                out.push(cur.clone());
+            }
+            for x in xs {
+                if cur.len() < max && !cur.contains(x) {
+                    cur.push(x.clone());
+                    orders(xs, max, cur, out);
+                    cur.pop();
+                }
+            }
+        }
+        let mut addresses = (0..s.floor.len())
+            .filter(|&j| !s.floor[j].is_empty())
+            .map(|j| Address::Floor(j as u8).to_string())
+            .collect::<Vec<String>>();
+        let hand = (0..HAND_SIZE).filter(|&i| !s.player().hand[i].is_empty());
+        addresses.extend(hand.map(|i| Address::Hand(i as u8).to_string()));
+        let mut sequences = vec![];
+        orders(&addresses, max, &mut vec![], &mut sequences);
+        let mut moves = vec![];
+        for xs in sequences {
+            if xs
+                .iter()
+                .filter(|x| x.as_bytes()[0].is_ascii_digit())
+                .count()
+                != 1
+            {
+                continue;
+            }
+            for ops in 0..2u32 << (xs.len() - 1) {
+                let mut x = format!("{}{}", prefix, if ops & 1 == 0 { "!" } else { "*" });
+                for (k, a) in xs.iter().enumerate() {
+                    if k > 0 {
+                        x.push(if ops & 1 << k == 0 { '&' } else { '+' });
+                    }
+                    x.push_str(a);
+                }
+                if let Ok(m) = Annotation::new(x).to_move() {
+                    if s.clone().apply(m.clone()).is_ok() {
+                        moves.push(m);
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    #[test]
+    fn test_legal_moves_exhaustive() {
+        let after = |s: &State, m: &Move| {
+            let mut s = s.clone();
+            s.apply(m.clone()).unwrap();
+            s.position_key()
+        };
+        for text in [
+            "floor: 3C 4D 7D 2H 5S\nhand: 7C",
+            "floor: 3C 4D 2H 6S\nhand: 7C 2D",
+            "floor: 2C 2D 4H KS {3S+5H}=8\nhand: 4C 8D",
+            "floor: 5C [5D 5H]=5 {2S+3S}=5* 9D\nhand: 5S 9H",
+            "rules: capture_all=true\nfloor: 4C 4D 2H 2S 6C\nhand: 4H 8S",
+            "rules: face_pairs_only=true unique_floor=false\nfloor: KC KD 3H 7S\nhand: KS 10D",
+            "rules: multi_build=true\nfloor: 2C 3D 4H 6S\nhand: 5C 7D 9H",
+        ] {
+            let s = Scenario::parse(text).unwrap().state;
+            let moves = s.legal_moves();
+            let found = moves.iter().map(|m| after(&s, m)).collect::<Vec<_>>();
+            let mut unique = found.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), found.len(), "{}", text);
+
+            let mut expected = brute_force_moves(&s, "", 6);
+            if s.rules.multi_build {
+                for x in ["A+B;", "A+C;", "B+C;", "A+B+C;"] {
+                    expected.extend(brute_force_moves(&s, x, 4));
+                }
+            }
+            for m in expected {
+                assert!(found.contains(&after(&s, &m)), "{}: {} is missing", text, m);
+            }
+        }
+
+        // Captures can take piles that add up to the card along with ones that match it
+        let s = Scenario::parse("floor: 3C 4D 7D 2H 5S\nhand: 7C")
+            .unwrap()
+            .state;
+        let moves = s.legal_moves();
+        for x in ["*A+B&C&1", "*A+B&D+E&1", "*A+B&D+E&C&1"] {
+            let m = Annotation::new(String::from(x)).to_move().unwrap();
+            assert!(moves.iter().any(|y| after(&s, y) == after(&s, &m)), "{}", x);
+        }
+    }
+
+    #[test]
+    fn test_capture_all() {
+        let mut g = setup();
+        g.rules.unique_floor = false;
+        g.rules.capture_all = true;
+        g.floor[4] = Pile::card(2, 2);
+        let m = |x: &str| Annotation::new(String::from(x)).to_move().unwrap();
+        assert_eq!(
+            g.clone().apply(m("*C&3")),
+            Err(StateError::UncapturedPile(Address::Floor(4)))
+        );
+        let full = g.complete_capture(m("*C&3"));
+        assert_eq!(full.to_annotation(), "*C&E&3");
+        assert_eq!(g.complete_capture(m("!1")), m("!1"));
+        assert_eq!(g.complete_capture(m("*C&E&3")), full);
+
+        let mut x = g.clone();
+        assert!(x.apply(full).is_ok());
+        assert_eq!(x.opponent.pairs[0].cards.len(), 3);
+        assert_eq!(x.floor_count(), 3);
+
+        // Only one of the twos has to be taken without the rule
+        g.rules.capture_all = false;
+        assert!(g.apply(m("*C&3")).is_ok());
+    }
+
+    #[test]
+    fn test_merge_suggestion() {
+        let mut g = setup();
+        let m = Annotation::new(String::from("!5")).to_move().unwrap();
+        assert_eq!(g.clone().apply(m), Err(StateError::DuplicateFloorValue));
+        // Two singles can't be grouped, so the seven of clubs has to pair the seven of diamonds
+        assert_eq!(g.merge_suggestion(4), Some(String::from("*B&5")));
+        assert_eq!(g.merge_suggestion(0), None);
+
+        // A build can take the card as a group instead, if another seven is left to pair it
+        g.floor[1] = build(
+            vec![
+                Card::create(Value::Five, Suit::Hearts),
+                Card::create(Value::Two, Suit::Hearts),
+            ],
+            Value::Seven,
+        );
+        g.floor[1].owner = PlayerId::Dealer;
+        assert_eq!(g.merge_suggestion(4), Some(String::from("*B&5")));
+        g.opponent.hand[0] = single(Value::Seven, Suit::Hearts);
+        assert_eq!(g.merge_suggestion(4), Some(String::from("B&5")));
+    }
+
+    #[test]
+    fn test_build_sets() {
+        let mut g = setup();
+        g.floor = vec![
+            single(Value::Three, Suit::Clubs),
+            single(Value::Four, Suit::Diamonds),
+            single(Value::Two, Suit::Hearts),
+            single(Value::Two, Suit::Spades),
+            group(
+                vec![
+                    Card::create(Value::Five, Suit::Clubs),
+                    Card::create(Value::Five, Suit::Hearts),
+                ],
+                Value::Five,
+            ),
+            build(
+                vec![
+                    Card::create(Value::Ace, Suit::Clubs),
+                    Card::create(Value::Ace, Suit::Hearts),
+                ],
+                Value::Two,
+            ),
+        ];
+        let (a, b, c, d, f) = (
+            Address::Floor(0),
+            Address::Floor(1),
+            Address::Floor(2),
+            Address::Floor(3),
+            Address::Floor(5),
+        );
+        assert_eq!(
+            g.build_sets(7),
+            vec![vec![a, b], vec![a, c, d], vec![a, c, f], vec![a, d, f]]
+        );
+        assert!(g.build_sets(6).contains(&vec![c, d, f]));
+        // The two singles with the same value can't be built together, but the build can join them
+        assert_eq!(g.build_sets(4), vec![vec![c, f], vec![d, f]]);
+        // A single pile already has its value, and the group can't be built on
+        assert!(g.build_sets(3).is_empty());
+        assert!(g
+            .build_sets(5)
+            .iter()
+            .all(|x| !x.contains(&Address::Floor(4))));
+        assert!(g.build_sets(11).is_empty());
+    }
+
+    #[test]
+    fn test_captures_for() {
+        let mut g = setup();
+        g.floor = vec![
+            single(Value::Three, Suit::Clubs),
+            single(Value::Four, Suit::Diamonds),
+            single(Value::Seven, Suit::Diamonds),
+            single(Value::Two, Suit::Hearts),
+            single(Value::Five, Suit::Spades),
+            empty(),
+        ];
+        g.opponent.hand[0] = single(Value::Seven, Suit::Clubs);
+        g.forget_simple_moves();
+        let options = g.captures_for(0);
+        let annotations = options.iter().map(|x| x.annotation.as_str());
+        // The seven can take the other seven, two piles that add up to seven, or any of these
+        // together
+        assert_eq!(
+            annotations.collect::<Vec<&str>>(),
+            vec![
+                "*C&1",
+                "*A+B&1",
+                "*A+B&C&1",
+                "*A+B&C&D+E&1",
+                "*A+B&D+E&1",
+                "*C&D+E&1",
+                "*D+E&1"
+            ]
+        );
+        assert_eq!(options[1].target, Address::Floor(0));
+        assert_eq!(options[1].piles, vec![Address::Floor(0), Address::Floor(1)]);
+        assert!(options
+            .iter()
+            .all(|x| x.kind == TargetKind::Pair && x.value == 7 && g.is_legal(&x.annotation)));
+        assert!(g.captures_for(8).is_empty());
+    }
+
+    #[test]
+    fn test_merge_options() {
+        let mut g = setup();
+        assert!(g.merge_options(0).is_empty());
+        let annotations = |g: &State| {
+            g.merge_options(4)
+                .into_iter()
+                .map(|x| x.annotation)
+                .collect::<Vec<String>>()
+        };
+        // Two singles can't be grouped, so pairing is the only way
+        assert_eq!(annotations(&g), vec!["*B&5"]);
+        assert_eq!(g.merge_options(4)[0].result, None);
+
+        // A build can be grouped with the card, or captured
+        g.floor[1] = build(
+            vec![
+                Card::create(Value::Five, Suit::Hearts),
+                Card::create(Value::Two, Suit::Hearts),
+            ],
+            Value::Seven,
+        );
+        g.floor[1].owner = PlayerId::Dealer;
+        g.opponent.hand[0] = single(Value::Seven, Suit::Hearts);
+        assert_eq!(annotations(&g), vec!["B&5", "*B&5"]);
+        let grouped = g.merge_options(4)[0].result.clone().unwrap();
+        assert_eq!(grouped.value, 7);
+        assert_eq!(grouped.cards.len(), 3);
+
+        // With a second seven on the floor, a group has to take both piles
+        g.floor[2] = single(Value::Seven, Suit::Spades);
+        assert_eq!(annotations(&g), vec!["B&C&5", "*B&5", "*C&5", "*B&C&5"]);
+        let options = g.merge_options(4);
+        assert_eq!(options[0].piles, vec![Address::Floor(1), Address::Floor(2)]);
+        assert_eq!(options[0].result.as_ref().unwrap().cards.len(), 4);
+        for x in options {
+            assert!(g.is_legal(&x.annotation), "{}", x.annotation);
+        }
+    }
+
+    #[test]
+    fn test_discard_risk() {
+        let mut g = setup();
+        // The opponent can see their own hand and the floor, but not the dealer's hand or deck
+        assert_eq!(g.unseen_by(PlayerId::Opponent).len(), 40);
+        let counts = g.unseen_values(PlayerId::Opponent);
+        assert_eq!(counts.iter().map(|&x| x as usize).sum::<usize>(), 40);
+        // Both red aces are in view
+        assert_eq!(counts[Value::Ace as usize], 2);
+        assert_eq!(counts[0], 0);
+        assert!(!g
+            .unseen_by(PlayerId::Opponent)
+            .contains(&Card::create(Value::Ace, Suit::Hearts)));
+
+        // A king can only be taken by one of the two unseen kings
+        let king = g.discard_risk(1).unwrap();
+        assert_eq!(
+            king.threats,
+            vec![
+                Card::create(Value::King, Suit::Diamonds),
+                Card::create(Value::King, Suit::Spades),
+            ]
+        );
+        assert!(king.chance > 0.0 && king.chance < 0.5);
+
+        // An ace can be paired, or built with any floor card into a three, five, eight, or nine
+        let ace = g.discard_risk(0).unwrap();
+        assert!(ace
+            .threats
+            .contains(&Card::create(Value::Nine, Suit::Hearts)));
+        assert!(ace
+            .threats
+            .contains(&Card::create(Value::Three, Suit::Hearts)));
+        assert!(ace.chance > king.chance);
+
+        // Nothing threatens a discard once every other card has been captured
+        let mut cards = g.deck.drain(..).collect::<Vec<Card>>();
+        for x in g.dealer.hand.iter_mut() {
+            cards.append(&mut x.take().cards);
+        }
+        g.dealer.pairs.push(pair(cards, Value::Invalid));
+        assert!(g.discard_risk(1).unwrap().is_safe());
+        assert_eq!(g.discard_risk(1).unwrap().chance, 0.0);
+        assert!(g.discard_risk(4).is_none());
+        assert!(g.discard_risk(8).is_none());
+    }
+
+    #[test]
+    fn test_discard_risk_redacted() {
+        let mut g = setup();
+        g.apply(Annotation::new(String::from("*C&3")).to_move().unwrap())
+            .unwrap();
+        g.turn = PlayerId::Dealer;
+        // A player's own view gives the same risks as the full state
+        let view = g.redacted_for(PlayerId::Dealer);
+        assert_eq!(
+            view.unseen_values(PlayerId::Dealer),
+            g.unseen_values(PlayerId::Dealer)
+        );
+        assert_eq!(view.unseen_by(PlayerId::Dealer).len(), 39);
+        for i in 0..HAND_SIZE {
+            let (x, y) = (g.discard_risk(i), view.discard_risk(i));
+            assert_eq!(x, y);
+            if let Some(x) = x {
+                assert!(x.chance > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hand_summary() {
+        let g = setup();
+        assert_eq!(
+            g.hand_summary(PlayerId::Opponent),
+            HandSummary {
+                captures: vec![Address::Floor(1), Address::Floor(2), Address::Floor(3)],
+                // Building onto the seven or the two would duplicate a floor value
+                builds: vec![3],
+                point_cards: vec![
+                    Card::create(Value::Ace, Suit::Hearts),
+                    Card::create(Value::Ace, Suit::Clubs),
+                ],
+                spades: 2,
+            }
+        );
+        // The summary works for the player waiting for their turn too
+        let dealer = g.hand_summary(PlayerId::Dealer);
+        assert_eq!(dealer.captures, vec![Address::Floor(0)]);
+        assert_eq!(dealer.builds, vec![5, 6, 10]);
+        assert_eq!(dealer.spades, 3);
+        assert_eq!(g.turn, PlayerId::Opponent);
+    }
+
+    #[test]
+    fn test_buildable_totals() {
+        let g = setup();
+        let options = g.buildable_totals(PlayerId::Opponent);
+        assert_eq!(options.len(), 8);
+        // Either ace can build the two of spades up to the three in hand
+        assert_eq!(
+            options[7]
+                .iter()
+                .map(|x| x.annotation.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["C+1", "C+4"]
+        );
+        assert_eq!(options[7][0].piles, vec![Address::Floor(2)]);
+        assert_eq!(options[7][0].card, Address::Hand(0));
+        // Building seven or eight would duplicate a floor value
+        assert!(options[4].is_empty());
+        assert!(options[5].is_empty());
+
+        // Several floor piles can go into one build
+        let mut s = g.clone();
+        s.rules.unique_floor = false;
+        let options = s.buildable_totals(PlayerId::Opponent);
+        assert!(options[4].iter().any(|x| x.annotation == "A+C+1"));
+        assert!(options[5].iter().any(|x| x.annotation == "B+1"));
+        assert_eq!(g.turn, PlayerId::Opponent);
+    }
+
+    #[test]
+    fn test_keyframes() {
+        let mut g = setup();
+        let card = |v, s| Card::create(v, s);
+        let frame = |card, from, to| Keyframe { card, from, to };
+        let m = |x: &str| Annotation::new(String::from(x)).to_move().unwrap();
+        g.apply(m("C+1")).unwrap();
+        assert_eq!(
+            g.keyframes,
+            vec![frame(
+                card(Value::Ace, Suit::Hearts),
+                Address::Hand(0),
+                Destination::Pile(Address::Floor(2))
+            )]
+        );
+
+        let mut g = setup();
+        g.apply(m("*B&5")).unwrap();
+        assert_eq!(
+            g.keyframes,
+            vec![
+                frame(
+                    card(Value::Seven, Suit::Diamonds),
+                    Address::Floor(1),
+                    Destination::Captured
+                ),
+                frame(
+                    card(Value::Seven, Suit::Clubs),
+                    Address::Hand(4),
+                    Destination::Captured
+                ),
+                frame(
+                    card(Value::Two, Suit::Spades),
+                    Address::Floor(2),
+                    Destination::Pile(Address::Floor(1))
+                ),
+                frame(
+                    card(Value::Eight, Suit::Clubs),
+                    Address::Floor(3),
+                    Destination::Pile(Address::Floor(2))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_for() {
+        let mut g = setup();
+        let m = || Annotation::new(String::from("!1")).to_move().unwrap();
+        assert_eq!(
+            g.apply_for(PlayerId::Dealer, m()),
+            Err(StateError::NotYourTurn)
+        );
+        assert_eq!(g.floor_count(), 4);
+        assert_eq!(g.apply_for(PlayerId::Opponent, m()), Ok(()));
+        assert_eq!(g.floor_count(), 5);
+    }
 }
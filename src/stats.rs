@@ -0,0 +1,296 @@
+use crate::card::Suit;
+use crate::game::Game;
+use crate::pile::Pile;
+use crate::rng::Seed;
+use crate::score::Score;
+use crate::sim::{play_match, seeds, Violation};
+use crate::state::PlayerId;
+use std::fmt;
+
+/// Average points earned per game in each scoring category
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CategoryStats {
+    pub aces: f64,
+    pub most_cards: f64,
+    pub most_spades: f64,
+    pub suipi_bonus: f64,
+    pub ten_of_diamonds: f64,
+    pub two_of_spades: f64,
+}
+
+impl CategoryStats {
+    /// Add up the points awarded in a single game
+    fn add(&mut self, aces: u8, points: [u8; 5]) {
+        self.aces += aces as f64;
+        self.most_cards += points[0] as f64;
+        self.most_spades += points[1] as f64;
+        self.suipi_bonus += points[2] as f64;
+        self.ten_of_diamonds += points[3] as f64;
+        self.two_of_spades += points[4] as f64;
+    }
+
+    /// Turn point totals into per game averages
+    fn average(&mut self, games: usize) {
+        let n = games.max(1) as f64;
+        self.aces /= n;
+        self.most_cards /= n;
+        self.most_spades /= n;
+        self.suipi_bonus /= n;
+        self.ten_of_diamonds /= n;
+        self.two_of_spades /= n;
+    }
+
+    /// Get the average total points per game
+    pub fn total(&self) -> f64 {
+        self.aces
+            + self.most_cards
+            + self.most_spades
+            + self.suipi_bonus
+            + self.ten_of_diamonds
+            + self.two_of_spades
+    }
+}
+
+/// Balance statistics collected over a batch of simulated games
+///
+/// The first player is the opponent, who always moves before the dealer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub games: usize,
+    pub first_player_wins: usize,
+    pub dealer_wins: usize,
+    pub ties: usize,
+    pub first_player: CategoryStats,
+    pub dealer: CategoryStats,
+    pub suipis: usize,
+    pub games_with_suipi: usize,
+}
+
+impl Report {
+    /// Get the fraction of games won by the first player
+    pub fn first_player_win_rate(&self) -> f64 {
+        self.first_player_wins as f64 / self.games.max(1) as f64
+    }
+
+    /// Get the fraction of games won by the dealer
+    pub fn dealer_win_rate(&self) -> f64 {
+        self.dealer_wins as f64 / self.games.max(1) as f64
+    }
+
+    /// Get the average number of suipis scored per game
+    pub fn suipis_per_game(&self) -> f64 {
+        self.suipis as f64 / self.games.max(1) as f64
+    }
+
+    /// Get the fraction of games with at least one suipi
+    pub fn suipi_frequency(&self) -> f64 {
+        self.games_with_suipi as f64 / self.games.max(1) as f64
+    }
+
+    /// Record the final score of a single game
+    fn record(&mut self, score: &Score, suipis: usize) {
+        self.games += 1;
+        match score.opponent_total().cmp(&score.dealer_total()) {
+            std::cmp::Ordering::Greater => self.first_player_wins += 1,
+            std::cmp::Ordering::Less => self.dealer_wins += 1,
+            std::cmp::Ordering::Equal => self.ties += 1,
+        }
+        self.first_player
+            .add(score.opponent_aces, score.opponent_points());
+        self.dealer.add(score.dealer_aces, score.dealer_points());
+        self.suipis += suipis;
+        if suipis > 0 {
+            self.games_with_suipi += 1;
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Games: {}", self.games)?;
+        writeln!(
+            f,
+            "Wins: first player {:.1}%, dealer {:.1}%, ties {:.1}%",
+            self.first_player_win_rate() * 100.0,
+            self.dealer_win_rate() * 100.0,
+            self.ties as f64 / self.games.max(1) as f64 * 100.0
+        )?;
+        writeln!(
+            f,
+            "Suipis: {:.2} per game, {:.1}% of games",
+            self.suipis_per_game(),
+            self.suipi_frequency() * 100.0
+        )?;
+        writeln!(
+            f,
+            "Player | Aces | Most Cards | Most Spades | 10♦  | 2♠   | Suipis | Total"
+        )?;
+        for (name, c) in [("First ", &self.first_player), ("Dealer", &self.dealer)] {
+            writeln!(
+                f,
+                "{} | {:.2} | {:.2}       | {:.2}        | {:.2} | {:.2} | {:.2}   | {:.2}",
+                name,
+                c.aces,
+                c.most_cards,
+                c.most_spades,
+                c.ten_of_diamonds,
+                c.two_of_spades,
+                c.suipi_bonus,
+                c.total()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Collect statistics from random matches played with each of the given seeds
+pub fn collect(seeds: &[Seed]) -> Result<Report, Violation> {
+    let mut report = Report::default();
+    for &seed in seeds {
+        let mut suipis = [0; 2];
+        let g = play_match(seed, |g, _, ended| {
+            if !ended && g.state.floor_count() == 0 {
+                suipis[g.game as usize] += 1;
+            }
+            Ok(())
+        })?;
+        for (score, n) in g.scores.iter().zip(suipis) {
+            report.record(score, n);
+        }
+    }
+    report.first_player.average(report.games);
+    report.dealer.average(report.games);
+    Ok(report)
+}
+
+/// Collect statistics from `n` random matches with seeds drawn from the given seed
+pub fn simulate(seed: Seed, n: usize) -> Result<Report, Violation> {
+    collect(&seeds(seed, n))
+}
+
+/// How much the cards dealt in a match's first game favored one player
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DealLuck {
+    /// Points the point cards dealt to the player's hands are worth
+    pub point_cards: u8,
+    /// Spades dealt to the player's hands
+    pub spades: u8,
+    /// Cards in the player's opening hand that can capture a pile on the opening floor
+    pub opening_captures: u8,
+}
+
+impl DealLuck {
+    /// Get a rough measure of how favorable the deal was, in points
+    pub fn score(&self) -> u8 {
+        self.point_cards + self.opening_captures
+    }
+}
+
+/// How favorable the deal from a seed was for each player, to normalize scores for luck
+///
+/// Only the first game is covered, since every card in it is dealt from a deck shuffled by the
+/// seed alone, and it is dealt the same way whatever the players do.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DealReport {
+    /// Luck of each player, indexed by player id
+    pub players: [DealLuck; 2],
+    /// Points the point cards dealt to the opening floor are worth
+    pub floor_points: u8,
+}
+
+impl DealReport {
+    /// Get how much more favorable the deal was for the given player than for the other one
+    pub fn advantage(&self, p: PlayerId) -> i16 {
+        self.players[p as usize].score() as i16 - self.players[p.other() as usize].score() as i16
+    }
+}
+
+/// Score how favorable the deal from the given seed was for each player
+pub fn deal_fairness(seed: Seed) -> DealReport {
+    let g = Game::builder().seed(seed).build().unwrap();
+    let mut report = DealReport {
+        floor_points: g.state.floor.iter().map(pile_points).sum(),
+        ..DealReport::default()
+    };
+    for p in [PlayerId::Opponent, PlayerId::Dealer] {
+        report.players[p as usize].opening_captures = g
+            .state
+            .seat(p)
+            .hand
+            .iter()
+            .filter(|h| {
+                g.state
+                    .floor
+                    .iter()
+                    .any(|f| !f.is_empty() && f.value == h.value)
+            })
+            .count() as u8;
+    }
+    let mut s = g.state.clone();
+    loop {
+        for p in [PlayerId::Opponent, PlayerId::Dealer] {
+            let luck = &mut report.players[p as usize];
+            for c in s.seat(p).hand.iter().flat_map(|x| x.cards.iter()) {
+                luck.point_cards += c.points();
+                luck.spades += (c.suit == Suit::Spades as u8) as u8;
+            }
+        }
+        if s.deck.is_empty() {
+            break;
+        }
+        s.deal_hands();
+    }
+    report
+}
+
+/// Get the points the point cards in a pile are worth
+fn pile_points(pile: &Pile) -> u8 {
+    pile.cards.iter().map(|c| c.points()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect() {
+        let report = collect(&[[0; 32], [1; 32], [2; 32]]).unwrap();
+        assert_eq!(report.games, 6);
+        assert_eq!(
+            report.first_player_wins + report.dealer_wins + report.ties,
+            6
+        );
+        assert!(report.suipis >= report.games_with_suipi);
+        // Every ace is always awarded to one of the players
+        assert_eq!(report.first_player.aces + report.dealer.aces, 4.0);
+        // The ten of diamonds is always worth two points to somebody
+        assert_eq!(
+            report.first_player.ten_of_diamonds + report.dealer.ten_of_diamonds,
+            2.0
+        );
+        assert_eq!(collect(&[[0; 32], [1; 32], [2; 32]]), Ok(report));
+
+        let report = simulate([5; 32], 2).unwrap();
+        assert_eq!(report.games, 4);
+        assert_eq!(simulate([5; 32], 2), Ok(report));
+    }
+
+    #[test]
+    fn test_deal_fairness() {
+        let report = deal_fairness(Seed::default());
+        // The two of spades starts on the floor
+        assert_eq!(report.floor_points, 1);
+        let [opponent, dealer] = report.players;
+        // The opponent can take the 2, 7, and 8 on the opening floor
+        assert_eq!(opponent.opening_captures, 3);
+        // Every other point card and spade is dealt to one of the hands
+        assert_eq!(opponent.point_cards + dealer.point_cards, 6);
+        assert_eq!(opponent.spades + dealer.spades, 12);
+        assert_eq!(
+            report.advantage(PlayerId::Opponent),
+            -report.advantage(PlayerId::Dealer)
+        );
+        assert_eq!(deal_fairness(Seed::default()), report);
+        assert_ne!(deal_fairness([1; 32]), report);
+    }
+}
@@ -3,23 +3,9 @@ use playsuipi_core::api::Scorecard;
 use playsuipi_core::card::{Card, Suit, Value};
 use playsuipi_core::game::Game;
 use playsuipi_core::pile::{Mark, Pile};
+use playsuipi_core::state::PlayerId;
 use std::ffi::{CStr, CString};
 
-/// A pile owner
-pub enum Owner {
-    Opponent,
-    Dealer,
-}
-
-impl Into<bool> for Owner {
-    fn into(self) -> bool {
-        match self {
-            Owner::Opponent => false,
-            Owner::Dealer => true,
-        }
-    }
-}
-
 /// Setup an initial game state
 pub fn setup_default() -> Box<Game> {
     setup([0; 32])
@@ -27,20 +13,25 @@ pub fn setup_default() -> Box<Game> {
 
 /// Setup an initial game state for the given seed
 pub fn setup(seed: [u8; 32]) -> Box<Game> {
-    unsafe { api::new_game(&seed) }
+    unsafe { api::new_game(&seed) }.unwrap()
 }
 
 /// Read the current floor state
+#[allow(clippy::borrowed_box)]
 pub fn read_floor(g: &Box<Game>) -> Vec<Pile> {
-    api::read_floor(g).iter().map(|&c| c.into()).collect()
+    let mut piles = vec![api::Pile::default(); api::floor_size(g)];
+    let n = unsafe { api::read_floor(g, piles.as_mut_ptr(), piles.len()) };
+    piles.iter().take(n).map(|&c| c.into()).collect()
 }
 
 /// Read the current player hand states
+#[allow(clippy::borrowed_box)]
 pub fn read_hands(g: &Box<Game>) -> Vec<Card> {
     api::read_hands(g).iter().map(|&c| Card::from(c)).collect()
 }
 
 /// Read the game scorecards
+#[allow(clippy::borrowed_box)]
 pub fn get_scores(g: &Box<Game>) -> Box<[Scorecard; 4]> {
     api::get_scores(g)
 }
@@ -95,9 +86,9 @@ pub fn apply_moves(g: &mut Box<Game>, xs: Vec<&str>) {
 }
 
 /// Helper for populating a pile with a pair
-pub fn pair(xs: Vec<Card>, v: Value, o: Owner) -> Pile {
+pub fn pair(xs: Vec<Card>, v: Value, o: PlayerId) -> Pile {
     let mut p = Pile::new(xs, v as u8, Mark::Pair);
-    p.owner = o.into();
+    p.owner = o;
     p
 }
 
@@ -1,4 +1,5 @@
 use playsuipi_core::card::{Suit, Value};
+use playsuipi_core::state::PlayerId;
 
 mod common;
 use common::*;
@@ -60,7 +61,7 @@ fn test_pair_two_cards() {
                 card(Value::Two, Suit::Diamonds),
             ],
             Value::Two,
-            Owner::Opponent,
+            PlayerId::Opponent,
         )]
     );
 }
@@ -424,7 +425,7 @@ fn test_build_and_pair() {
                 card(Value::Nine, Suit::Spades),
             ],
             Value::Nine,
-            Owner::Opponent,
+            PlayerId::Opponent,
         )]
     );
 }
@@ -492,7 +493,7 @@ fn test_build_and_group_then_pair() {
                 card(Value::Seven, Suit::Spades),
             ],
             Value::Seven,
-            Owner::Opponent,
+            PlayerId::Opponent,
         )]
     );
 }
@@ -561,7 +562,7 @@ fn test_first_round() {
                     card(Value::Eight, Suit::Spades),
                 ],
                 Value::Eight,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -569,7 +570,7 @@ fn test_first_round() {
                     card(Value::Seven, Suit::Clubs),
                 ],
                 Value::Seven,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -577,7 +578,7 @@ fn test_first_round() {
                     card(Value::King, Suit::Clubs),
                 ],
                 Value::King,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -586,7 +587,7 @@ fn test_first_round() {
                     card(Value::Three, Suit::Spades),
                 ],
                 Value::Three,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
         ]
     );
@@ -601,7 +602,7 @@ fn test_first_round() {
                     card(Value::Six, Suit::Spades),
                 ],
                 Value::Six,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -609,7 +610,7 @@ fn test_first_round() {
                     card(Value::Five, Suit::Clubs),
                 ],
                 Value::Five,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -618,7 +619,7 @@ fn test_first_round() {
                     card(Value::Four, Suit::Hearts),
                 ],
                 Value::Four,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -626,7 +627,7 @@ fn test_first_round() {
                     card(Value::Ten, Suit::Diamonds),
                 ],
                 Value::Ten,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
         ]
     );
@@ -701,7 +702,7 @@ fn test_another_round() {
                     card(Value::Six, Suit::Spades),
                 ],
                 Value::Six,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -709,7 +710,7 @@ fn test_another_round() {
                     card(Value::Nine, Suit::Spades),
                 ],
                 Value::Nine,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -718,7 +719,7 @@ fn test_another_round() {
                     card(Value::Ten, Suit::Spades),
                 ],
                 Value::Ten,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -726,7 +727,7 @@ fn test_another_round() {
                     card(Value::Eight, Suit::Spades),
                 ],
                 Value::Eight,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
         ]
     );
@@ -740,7 +741,7 @@ fn test_another_round() {
                     card(Value::Seven, Suit::Spades),
                 ],
                 Value::Seven,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -749,7 +750,7 @@ fn test_another_round() {
                     card(Value::Four, Suit::Spades),
                 ],
                 Value::Four,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
         ]
     );
@@ -782,7 +783,7 @@ fn test_first_game() {
                     card(Value::Two, Suit::Hearts),
                 ],
                 Value::Two,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -790,7 +791,7 @@ fn test_first_game() {
                     card(Value::Eight, Suit::Diamonds),
                 ],
                 Value::Eight,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -798,7 +799,7 @@ fn test_first_game() {
                     card(Value::Four, Suit::Hearts),
                 ],
                 Value::Four,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -806,7 +807,7 @@ fn test_first_game() {
                     card(Value::Three, Suit::Hearts),
                 ],
                 Value::Three,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -815,7 +816,7 @@ fn test_first_game() {
                     card(Value::Ten, Suit::Hearts),
                 ],
                 Value::Ten,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -823,7 +824,7 @@ fn test_first_game() {
                     card(Value::Five, Suit::Spades),
                 ],
                 Value::Five,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -832,7 +833,7 @@ fn test_first_game() {
                     card(Value::Nine, Suit::Diamonds),
                 ],
                 Value::Nine,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -840,7 +841,7 @@ fn test_first_game() {
                     card(Value::King, Suit::Hearts),
                 ],
                 Value::King,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -848,7 +849,7 @@ fn test_first_game() {
                     card(Value::Queen, Suit::Clubs),
                 ],
                 Value::Queen,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
             pair(
                 vec![
@@ -856,7 +857,7 @@ fn test_first_game() {
                     card(Value::Six, Suit::Spades),
                 ],
                 Value::Six,
-                Owner::Opponent,
+                PlayerId::Opponent,
             ),
         ]
     );
@@ -870,7 +871,7 @@ fn test_first_game() {
                     card(Value::Jack, Suit::Hearts),
                 ],
                 Value::Jack,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -878,7 +879,7 @@ fn test_first_game() {
                     card(Value::Nine, Suit::Spades),
                 ],
                 Value::Nine,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -886,7 +887,7 @@ fn test_first_game() {
                     card(Value::King, Suit::Clubs),
                 ],
                 Value::King,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -894,7 +895,7 @@ fn test_first_game() {
                     card(Value::Queen, Suit::Spades),
                 ],
                 Value::Queen,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -902,7 +903,7 @@ fn test_first_game() {
                     card(Value::Jack, Suit::Diamonds),
                 ],
                 Value::Jack,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -916,7 +917,7 @@ fn test_first_game() {
                     card(Value::Seven, Suit::Spades),
                 ],
                 Value::Seven,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -924,7 +925,7 @@ fn test_first_game() {
                     card(Value::Ten, Suit::Spades),
                 ],
                 Value::Ten,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -933,7 +934,7 @@ fn test_first_game() {
                     card(Value::Six, Suit::Hearts),
                 ],
                 Value::Six,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
             pair(
                 vec![
@@ -943,7 +944,7 @@ fn test_first_game() {
                     card(Value::Eight, Suit::Hearts),
                 ],
                 Value::Eight,
-                Owner::Dealer,
+                PlayerId::Dealer,
             ),
         ]
     );
@@ -1005,3 +1006,429 @@ fn test_first_game() {
     assert_eq!(get_scores(&g)[2], blank_scorecard());
     assert_eq!(get_scores(&g)[3], blank_scorecard());
 }
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_status_serde() {
+    let g = setup_default();
+    let status = playsuipi_core::api::status(&g);
+    let json = serde_json::to_string(&*status).unwrap();
+    assert!(json.contains("\"turn\":\"Opponent\""));
+    assert!(json.contains("\"games\":2"));
+    let back: playsuipi_core::api::Status = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, *status);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_match_result_serde() {
+    let mut g = setup_default();
+    let result = g.resign(PlayerId::Opponent).unwrap().clone();
+    let json = serde_json::to_string(&result).unwrap();
+    assert!(json.contains("\"winner\":\"Dealer\""));
+    assert!(json.contains("\"termination\":\"Resigned\""));
+    let back: playsuipi_core::result::MatchResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, result);
+}
+
+#[test]
+fn test_undo_reports_reason() {
+    let mut g = setup_default();
+    let undo = |g: &mut Box<playsuipi_core::game::Game>| unsafe {
+        std::ffi::CStr::from_ptr(playsuipi_core::api::undo(g))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(
+        undo(&mut g),
+        playsuipi_core::game::GameError::NothingToUndo.to_string()
+    );
+    assert!(apply(&mut g, "*C&3").is_ok());
+    assert_eq!(undo(&mut g), "");
+    assert_eq!(read_floor(&g)[2], single(Value::Two, Suit::Spades));
+}
+
+#[test]
+fn test_redo_reports_reason() {
+    let mut g = setup_default();
+    let read = |x: *const std::ffi::c_char| unsafe {
+        std::ffi::CStr::from_ptr(x).to_str().unwrap().to_string()
+    };
+
+    assert_eq!(
+        read(playsuipi_core::api::redo(&mut g)),
+        playsuipi_core::game::GameError::NothingToRedo.to_string()
+    );
+    assert!(apply(&mut g, "*C&3").is_ok());
+    let floor = read_floor(&g);
+    assert_eq!(read(playsuipi_core::api::undo(&mut g)), "");
+    assert_eq!(read(playsuipi_core::api::redo(&mut g)), "");
+    assert_eq!(read_floor(&g), floor);
+}
+
+#[test]
+fn test_get_history() {
+    let mut g = setup_default();
+    let history = |g: &mut Box<playsuipi_core::game::Game>| unsafe {
+        std::ffi::CStr::from_ptr(playsuipi_core::api::get_history(g))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(history(&mut g), "");
+    assert!(apply(&mut g, "*C&3").is_ok());
+    playsuipi_core::api::next_turn(&mut g);
+    assert!(apply(&mut g, "1").is_ok());
+    assert_eq!(history(&mut g), "1. Opponent *C&3\n2. Dealer !1\n");
+}
+
+#[test]
+fn test_captures_for() {
+    let g = setup_default();
+    let captures = |i| unsafe {
+        std::ffi::CStr::from_ptr(playsuipi_core::api::captures_for(&g, i))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(captures(2), "*C&3");
+    assert_eq!(captures(5), "*D&6");
+    assert_eq!(captures(1), "");
+}
+
+#[test]
+fn test_build_sets() {
+    let g = setup_default();
+    let sets = |x| unsafe {
+        std::ffi::CStr::from_ptr(playsuipi_core::api::build_sets(&g, x))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    // The floor is the four of clubs, seven of diamonds, two of spades, and eight of clubs
+    assert_eq!(sets(6), "A+C");
+    assert_eq!(sets(9), "B+C");
+    assert_eq!(sets(10), "C+D");
+    assert_eq!(sets(5), "");
+}
+
+#[test]
+fn test_merge_options() {
+    let g = setup_default();
+    let options = |i| unsafe {
+        std::ffi::CStr::from_ptr(playsuipi_core::api::merge_options(&g, i))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    // Two singles can't be grouped, so the seven of clubs can only pair the seven of diamonds
+    assert_eq!(options(4), "*B&5");
+    assert_eq!(options(1), "");
+}
+
+#[test]
+fn test_get_hint() {
+    let mut g = setup_default();
+    let hint = |g: &mut Box<playsuipi_core::game::Game>| unsafe {
+        std::ffi::CStr::from_ptr(playsuipi_core::api::get_hint(g))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(hint(&mut g), "*C&3 point-card");
+    assert!(g.resign(playsuipi_core::state::PlayerId::Opponent).is_ok());
+    assert_eq!(hint(&mut g), "");
+}
+
+#[test]
+fn test_get_bot_move() {
+    let mut g = setup_default();
+    let bot_move = |g: &mut Box<playsuipi_core::game::Game>, d| unsafe {
+        std::ffi::CStr::from_ptr(playsuipi_core::api::get_bot_move(g, d))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    for d in 0..=2 {
+        let x = bot_move(&mut g, d);
+        assert!(g.state.is_legal(&x), "{}", x);
+    }
+    // The greedy bot takes the two of spades
+    let x = bot_move(&mut g, 1);
+    assert_eq!(x, "*C&3");
+    assert!(apply(&mut g, &x).is_ok());
+    assert!(g.resign(playsuipi_core::state::PlayerId::Dealer).is_ok());
+    assert_eq!(bot_move(&mut g, 1), "");
+}
+
+#[test]
+fn test_get_move_grade() {
+    let mut g = setup_default();
+    let grade = |g: &mut Box<playsuipi_core::game::Game>, turn| unsafe {
+        std::ffi::CStr::from_ptr(playsuipi_core::api::get_move_grade(g, turn, 1))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(grade(&mut g, 0), "");
+    assert!(apply(&mut g, "!8").is_ok());
+    assert_eq!(grade(&mut g, 0), "blunder");
+    assert_eq!(grade(&mut g, 1), "");
+}
+
+#[test]
+fn test_read_win_probabilities() {
+    let mut g = setup_default();
+    assert!(apply(&mut g, "*C&3").is_ok());
+    assert!(apply(&mut g, "!1").is_ok());
+    let mut out = [-1.0f32; 3];
+    let n = unsafe { playsuipi_core::api::read_win_probabilities(&g, 1, out.as_mut_ptr(), 3) };
+    assert_eq!(n, 2);
+    assert!(out[..2].iter().all(|x| (0.0..=1.0).contains(x)));
+    assert_eq!(out[2], -1.0);
+}
+
+static SCORED: std::sync::Mutex<Vec<playsuipi_core::api::ScoreEvent>> =
+    std::sync::Mutex::new(vec![]);
+
+extern "C" fn on_score(e: playsuipi_core::api::ScoreEvent) {
+    SCORED.lock().unwrap().push(e);
+}
+
+#[test]
+fn test_score_callback() {
+    use playsuipi_core::api::{set_score_callback, ScoreKind};
+    let mut g = setup_default();
+    set_score_callback(&mut g, Some(on_score));
+    assert!(apply(&mut g, "*C&3").is_ok());
+    let scored = std::mem::take(&mut *SCORED.lock().unwrap());
+    assert_eq!(
+        scored
+            .iter()
+            .map(|e| (e.kind, e.player, e.points))
+            .collect::<Vec<_>>(),
+        vec![
+            (ScoreKind::Point, PlayerId::Opponent, 1),
+            (ScoreKind::MostCards, PlayerId::Opponent, 3),
+            (ScoreKind::MostSpades, PlayerId::Opponent, 1),
+        ]
+    );
+    assert_eq!(scored[0].card, u8::from(card(Value::Two, Suit::Spades)));
+
+    set_score_callback(&mut g, None);
+    playsuipi_core::api::next_turn(&mut g);
+    assert!(apply(&mut g, "*A&2").is_ok());
+    assert!(SCORED.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_deck_histogram() {
+    let mut g = setup_default();
+    let counts = *playsuipi_core::api::deck_histogram(&g);
+    assert_eq!(counts.iter().map(|&x| x as usize).sum::<usize>(), 40);
+    // The opponent holds two kings, and none are on the floor
+    assert_eq!(counts[Value::King as usize], 2);
+
+    // After the sevens are paired, the dealer counts what they have not seen
+    assert!(apply(&mut g, "*B&5").is_ok());
+    playsuipi_core::api::next_turn(&mut g);
+    let counts = *playsuipi_core::api::deck_histogram(&g);
+    assert_eq!(counts.iter().map(|&x| x as usize).sum::<usize>(), 40 - 1);
+    assert_eq!(counts[Value::Seven as usize], 2);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_journal_serde() {
+    let mut g = setup_default();
+    assert!(apply(&mut g, "*C&3").is_ok());
+    playsuipi_core::api::next_turn(&mut g);
+    let journal = g.journal();
+    let json = serde_json::to_string(&journal).unwrap();
+    assert!(json.contains("{\"Move\":\"*C&3\"},\"EndTurn\""));
+    let back: playsuipi_core::journal::Journal = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, journal);
+    let h = back.project().ok().unwrap();
+    assert_eq!(read_floor(&Box::new(h)), read_floor(&g));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_snapshot_serde() {
+    use playsuipi_core::action::Annotation;
+    use playsuipi_core::game::Snapshot;
+    let mut g = setup_default();
+    assert!(apply(&mut g, "*C&3").is_ok());
+    playsuipi_core::api::next_turn(&mut g);
+
+    let snapshot = g.export();
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let back: Snapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.status, snapshot.status);
+    assert_eq!(back.state.floor, snapshot.state.floor);
+    assert_eq!(back.state.deck, snapshot.state.deck);
+    assert_eq!(back.state.opponent, snapshot.state.opponent);
+    assert_eq!(back.state.rules, snapshot.state.rules);
+    assert_eq!(back.scores, snapshot.scores);
+    assert_eq!(back.moves, snapshot.moves);
+    // The restored state plays on like the original
+    let m = Annotation::new(String::from("!1")).to_move().unwrap();
+    let mut state = back.state;
+    assert!(state.apply(m).is_ok());
+
+    let m = Annotation::new(String::from("A+B;C+1")).to_move().unwrap();
+    let json = serde_json::to_string(&m).unwrap();
+    assert_eq!(
+        serde_json::from_str::<playsuipi_core::action::Move>(&json).unwrap(),
+        m
+    );
+    let c = card(Value::Ten, Suit::Diamonds);
+    assert_eq!(
+        serde_json::to_string(&c).unwrap(),
+        "{\"value\":10,\"suit\":1}"
+    );
+
+    // Players only see their own hand
+    let hidden = serde_json::to_string(&g.export_for(PlayerId::Dealer)).unwrap();
+    let back: Snapshot = serde_json::from_str(&hidden).unwrap();
+    assert_eq!(back.state.dealer.hand, g.state.dealer.hand);
+    assert_ne!(back.state.opponent.hand, g.state.opponent.hand);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_checks_pile_values() {
+    use playsuipi_core::game::Snapshot;
+    use playsuipi_core::pile::Pile;
+    let g = setup_default();
+    let pile = &g.state.floor[0];
+    let json = serde_json::to_string(pile).unwrap();
+    let back: Pile = serde_json::from_str(&json).unwrap();
+    assert_eq!(&back, pile);
+
+    // A pile whose value doesn't match its cards is rejected, on its own or in a snapshot
+    let value = format!("],\"value\":{},", pile.value);
+    let bad = json.replace(&value, &format!("],\"value\":{},", pile.value % 9 + 1));
+    assert_ne!(bad, json);
+    let err = serde_json::from_str::<Pile>(&bad).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("The pile value does not match its cards"));
+    let snapshot = serde_json::to_string(&g.export()).unwrap();
+    assert!(serde_json::from_str::<Snapshot>(&snapshot.replacen(&json, &bad, 1)).is_err());
+}
+
+#[test]
+fn test_state_mirror() {
+    use playsuipi_core::consts::{
+        INVALID_CARD_ID, MIRROR_FLOOR_OFFSET, MIRROR_HANDS_OFFSET, MIRROR_PILE_SIZE, MIRROR_SIZE,
+    };
+
+    let mut g = setup_default();
+    let ptr = playsuipi_core::api::enable_mirror(&mut g, PlayerId::Opponent as u8);
+    let mirror = || unsafe { std::slice::from_raw_parts(ptr, MIRROR_SIZE).to_vec() };
+    let version = |m: &[u8]| u32::from_le_bytes(m[..4].try_into().unwrap());
+
+    let m = mirror();
+    assert_eq!(version(&m), 1);
+    // Game, round, turn, next, dealer, games, over, floor size, deck size, last scorer
+    assert_eq!(&m[4..14], &[0, 0, 0, 1, 1, 2, 0, 13, 32, 1]);
+    let pile = |m: &[u8], i: usize| {
+        let x = MIRROR_FLOOR_OFFSET + i * MIRROR_PILE_SIZE;
+        m[x..x + MIRROR_PILE_SIZE].to_vec()
+    };
+    assert_eq!(pile(&m, 2)[0], u8::from(card(Value::Two, Suit::Spades)));
+    assert_eq!(pile(&m, 2)[1], INVALID_CARD_ID);
+    assert_eq!(
+        m[MIRROR_HANDS_OFFSET + 2],
+        u8::from(card(Value::Two, Suit::Diamonds))
+    );
+    // The dealer's hand is hidden from the opponent
+    assert!(m[MIRROR_HANDS_OFFSET + 8..]
+        .iter()
+        .all(|&x| x == INVALID_CARD_ID));
+
+    // The same pointer sees every change to the game
+    assert!(apply(&mut g, "*C&3").is_ok());
+    let m = mirror();
+    assert_eq!(version(&m), 2);
+    assert_eq!(pile(&m, 2)[0], u8::from(card(Value::Eight, Suit::Clubs)));
+    assert_eq!(m[MIRROR_HANDS_OFFSET + 2], INVALID_CARD_ID);
+
+    playsuipi_core::api::next_turn(&mut g);
+    let m = mirror();
+    assert_eq!(version(&m), 3);
+    assert_eq!(m[6], 1);
+    assert_eq!(g.mirror().map(|x| x.to_vec()), Some(m));
+
+    // Switching the viewer shows the dealer's hand in the same buffer instead
+    assert_eq!(
+        playsuipi_core::api::enable_mirror(&mut g, PlayerId::Dealer as u8),
+        ptr
+    );
+    let m = mirror();
+    assert_eq!(version(&m), 4);
+    // A viewer that isn't a seat is refused and the mirror is left alone
+    assert!(playsuipi_core::api::enable_mirror(&mut g, 2).is_null());
+    assert_eq!(version(&mirror()), 4);
+    assert!(m[MIRROR_HANDS_OFFSET..MIRROR_HANDS_OFFSET + 8]
+        .iter()
+        .all(|&x| x == INVALID_CARD_ID));
+    assert!(m[MIRROR_HANDS_OFFSET + 8..]
+        .iter()
+        .any(|&x| x != INVALID_CARD_ID));
+}
+
+#[test]
+fn test_apply_move_with_code() {
+    use playsuipi_core::error::ErrorCode;
+    let mut g = setup_default();
+    let mut code = ErrorCode::Success;
+    let mut apply = |g: &mut Box<playsuipi_core::game::Game>, a: &str| unsafe {
+        let a = std::ffi::CString::new(a).unwrap();
+        let message = std::ffi::CStr::from_ptr(playsuipi_core::api::apply_move_with_code(
+            g,
+            a.as_ptr(),
+            &mut code,
+        ))
+        .to_str()
+        .unwrap()
+        .to_string();
+        (code, message)
+    };
+    let (x, message) = apply(&mut g, "*A&3");
+    assert_eq!(x, ErrorCode::PilePairDifferentValues);
+    assert_eq!(
+        message,
+        "State Error: Invalid pile - You may not pair a card with a pile that has a different value"
+    );
+    assert_eq!(apply(&mut g, "").0, ErrorCode::ParsingBlankAnnotation);
+    assert_eq!(apply(&mut g, "*C&3"), (ErrorCode::Success, String::new()));
+}
+
+#[test]
+fn test_redacted_for_hides_cards() {
+    let g = setup_default();
+    // Ask for the moves first, so they are kept on the state
+    assert!(!g.state.simple_moves().is_empty());
+    let view = g.state.redacted_for(PlayerId::Dealer);
+    let hidden = g
+        .state
+        .opponent
+        .hand
+        .iter()
+        .flat_map(|x| x.cards.iter())
+        .chain(g.state.deck.iter())
+        .collect::<Vec<_>>();
+    let text = format!("{:?}", view);
+    assert!(hidden.iter().all(|c| !text.contains(&format!("{:?}", c))));
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&view).unwrap();
+        assert!(hidden
+            .iter()
+            .all(|c| !json.contains(&serde_json::to_string(c).unwrap())));
+    }
+}
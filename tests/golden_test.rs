@@ -0,0 +1,16 @@
+use playsuipi_core::sim::Fixture;
+
+/// Recorded with `cargo run --bin suipi-golden -- 32 > tests/fixtures/golden.txt`
+const GOLDEN: &str = include_str!("fixtures/golden.txt");
+
+#[test]
+fn test_golden_fixtures() {
+    let fixtures = GOLDEN
+        .lines()
+        .map(|x| Fixture::parse(x).expect("malformed fixture"))
+        .collect::<Vec<Fixture>>();
+    assert_eq!(fixtures.len(), 32);
+    for (i, x) in fixtures.iter().enumerate() {
+        assert_eq!(x.check(), Ok(true), "fixture {} no longer matches", i + 1);
+    }
+}
@@ -1,6 +1,9 @@
-use playsuipi_core::action::MoveError;
+use playsuipi_core::action::{Address, Annotation, MoveError};
+use playsuipi_core::game::Game;
 use playsuipi_core::pile::PileError;
-use playsuipi_core::state::StateError;
+use playsuipi_core::rules::{FloorOverflow, Rules};
+use playsuipi_core::scenario::Scenario;
+use playsuipi_core::state::{Orphan, StateError};
 
 #[allow(dead_code)]
 mod common;
@@ -64,6 +67,20 @@ fn test_cannot_pair_without_a_single() {
     );
 }
 
+#[test]
+fn test_capture_must_pair_the_hand_card() {
+    let s = Scenario::parse(
+        "floor: 4C 3D\n\
+         hand: 7S 2H\n\
+         expect: *1 -> err A capture must pair your hand card with the floor\n\
+         expect: *A+2 -> err A capture must pair your hand card with the floor\n\
+         expect: *A+B&1 -> ok\n",
+    )
+    .unwrap();
+    let failed = s.run();
+    assert!(failed.is_empty(), "{}", failed[0]);
+}
+
 #[test]
 fn test_cannot_pair_different_values() {
     let mut g = setup_default();
@@ -135,10 +152,40 @@ fn test_cannot_build_piles_you_cannot_pair() {
     assert!(res.is_err());
     assert_eq!(
         res.err().unwrap(),
-        StateError::UnpairablePileValue.to_string()
+        StateError::UnpairablePileValue(Orphan {
+            pile: Address::Floor(2),
+            value: 5
+        })
+        .to_string()
     );
 }
 
+#[test]
+fn test_cannot_capture_with_the_card_your_build_needs() {
+    let s = Scenario::parse(
+        "floor: 3C 5D {2S+6H}=8*\n\
+         hand: 8C 7D\n\
+         expect: *A+B&1 -> err pile C needs a 8 in hand\n\
+         expect: *C&1 -> ok\n",
+    )
+    .unwrap();
+    let failed = s.run();
+    assert!(failed.is_empty(), "{}", failed[0]);
+}
+
+#[test]
+fn test_cannot_build_in_hand_without_a_card_for_it() {
+    let s = Scenario::parse(
+        "floor: 3C\n\
+         hand: 5D 9H\n\
+         expect: !1+A -> err pile A needs a 8 in hand\n\
+         expect: A+1 -> err pile A needs a 8 in hand\n",
+    )
+    .unwrap();
+    let failed = s.run();
+    assert!(failed.is_empty(), "{}", failed[0]);
+}
+
 #[test]
 fn test_floor_must_be_unique() {
     let mut g = setup_default();
@@ -149,3 +196,99 @@ fn test_floor_must_be_unique() {
         StateError::DuplicateFloorValue.to_string()
     );
 }
+
+#[test]
+fn test_duplicate_floor_variant() {
+    let rules = Rules {
+        unique_floor: false,
+        floor_overflow: FloorOverflow::AwardLastScorer,
+        ..Rules::default()
+    };
+    let setup = || {
+        Box::new(
+            Game::builder()
+                .seed([0; 32])
+                .rules(rules.clone())
+                .build()
+                .unwrap(),
+        )
+    };
+    let mut g = setup();
+    assert!(apply(&mut g, "A+C+1").is_ok());
+    let floor = read_floor(&g);
+    assert_eq!(floor[0].value, 7);
+    assert_eq!(floor[1].value, 7);
+    assert_eq!(g.state.audit(), Ok(()));
+    // Discarding a card that matches a floor pile is allowed too
+    let mut g = setup();
+    assert!(apply(&mut g, "!5").is_ok());
+    assert_eq!(read_floor(&g)[4].value, 7);
+    assert_eq!(g.state.audit(), Ok(()));
+}
+
+#[test]
+fn test_larger_floor_variant() {
+    let rules = Rules {
+        floor_size: 20,
+        ..Rules::default()
+    };
+    let g = Box::new(Game::builder().seed([0; 32]).rules(rules).build().unwrap());
+    assert_eq!(read_floor(&g).len(), 20);
+    assert_eq!(g.state.audit(), Ok(()));
+    // Piles past `M` can be addressed
+    assert_eq!(
+        Annotation::new(String::from("*T&1"))
+            .to_move()
+            .map(|x| x.actions[0].address),
+        Ok(Address::Floor(19))
+    );
+    // but not past the end of a default floor
+    assert_eq!(
+        apply(&mut setup_default(), "*N&1"),
+        Err(StateError::InvalidAddress.to_string())
+    );
+}
+
+#[test]
+fn test_capture_all_scenario() {
+    let s = Scenario::parse(
+        "rules: unique_floor=false capture_all=true\n\
+         floor: 7C 4D 7H\n\
+         hand: 7S 3S\n\
+         expect: *A&1 -> err Every pile with the captured value must be taken (C)\n\
+         expect: *A&C&1 -> ok\n\
+         expect: B+2 -> ok\n",
+    )
+    .unwrap();
+    let failed = s.run();
+    assert!(failed.is_empty(), "{}", failed[0]);
+}
+
+#[test]
+fn test_face_pairs_only_scenario() {
+    let s = Scenario::parse(
+        "rules: unique_floor=false face_pairs_only=true\n\
+         floor: KC 5D KH [QC QD]=12\n\
+         hand: KS QS 5S\n\
+         expect: *A&1 -> ok\n\
+         expect: *A&C&1 -> err You may not group face cards\n\
+         expect: A&1 -> err You may not group face cards\n\
+         expect: *D&2 -> err Face cards may only be paired one for one\n\
+         expect: *B&3 -> ok\n",
+    )
+    .unwrap();
+    let failed = s.run();
+    assert!(failed.is_empty(), "{}", failed[0]);
+
+    // Face cards are exempt from having to capture every matching pile
+    let s = Scenario::parse(
+        "rules: unique_floor=false face_pairs_only=true capture_all=true\n\
+         floor: KC 5D KH 5C\n\
+         hand: KS 5S\n\
+         expect: *A&1 -> ok\n\
+         expect: *B&2 -> err Every pile with the captured value must be taken\n",
+    )
+    .unwrap();
+    let failed = s.run();
+    assert!(failed.is_empty(), "{}", failed[0]);
+}